@@ -0,0 +1,253 @@
+/// The agent registry: every agent named on a queued entry, with when it was first and last
+/// active, how many messages it sent, the voice and rate of its latest one, and whether it is
+/// muted. A muted agent's messages join the timeline already "suppressed" instead of waiting
+/// to be spoken. The timeline starts empty each run, but the registry is kept in agents.json
+/// in the config folder, written every SAVE_INTERVAL while it changes and at quit, so agents
+/// keep their history and their mute across restarts.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::config::{config_dir, load_json, save_json};
+use crate::error::report_error;
+use crate::events;
+use crate::state::{AppState, VoiceEntry};
+
+pub const AGENTS_FILE: &str = "agents.json";
+
+/// How often a changed registry is written
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Agents kept; past it the one active longest ago is forgotten, muted ones only after the rest
+pub const AGENTS_MAX: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Agent {
+    pub name: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_active: DateTime<Utc>,
+    /// Messages queued under this name, the ones held back included
+    pub messages: u64,
+    /// Messages held back because the agent was muted
+    pub suppressed: u64,
+    /// Voice and rate of the latest message: its per-agent voice, or the default it got
+    pub voice: String,
+    pub rate: u32,
+    pub muted: bool,
+}
+
+/// The agents by name, and whether they changed since agents.json was written
+#[derive(Debug, Default)]
+pub struct Registry {
+    agents: BTreeMap<String, Agent>,
+    dirty: bool,
+}
+
+impl Registry {
+    fn of(agents: Vec<Agent>) -> Self {
+        let agents = agents.into_iter().map(|agent| (agent.name.clone(), agent)).collect();
+        Self { agents, dirty: false }
+    }
+
+    /// Record a message from `entry`'s agent, adding the agent when new. Returns whether the
+    /// agent is muted; false for entries without one.
+    pub fn seen(&mut self, entry: &VoiceEntry) -> bool {
+        let Some(name) = entry.agent.as_deref().filter(|name| !name.trim().is_empty()) else {
+            return false;
+        };
+        if !self.agents.contains_key(name) && self.agents.len() >= AGENTS_MAX {
+            self.forget_one();
+        }
+        let agent = self.agents.entry(name.to_string()).or_insert_with(|| Agent {
+            name: name.to_string(),
+            first_seen: entry.timestamp,
+            last_active: entry.timestamp,
+            messages: 0,
+            suppressed: 0,
+            voice: String::new(),
+            rate: 0,
+            muted: false,
+        });
+        agent.last_active = entry.timestamp;
+        agent.messages += 1;
+        if agent.muted {
+            agent.suppressed += 1;
+        }
+        agent.voice = entry.voice.clone();
+        agent.rate = entry.rate;
+        self.dirty = true;
+        agent.muted
+    }
+
+    /// Forget the agent active longest ago, an unmuted one when there is one
+    fn forget_one(&mut self) {
+        let oldest = self.agents.values()
+            .min_by_key(|agent| (agent.muted, agent.last_active))
+            .map(|agent| agent.name.clone());
+        if let Some(name) = oldest {
+            self.agents.remove(&name);
+        }
+    }
+
+    /// By name
+    pub fn list(&self) -> Vec<Agent> {
+        self.agents.values().cloned().collect()
+    }
+
+    /// Mute or unmute an agent already seen; returns it as it is now
+    pub fn set_muted(&mut self, name: &str, muted: bool) -> Option<Agent> {
+        let agent = self.agents.get_mut(name)?;
+        if agent.muted != muted {
+            agent.muted = muted;
+            self.dirty = true;
+        }
+        Some(agent.clone())
+    }
+}
+
+fn path() -> PathBuf {
+    config_dir().join(AGENTS_FILE)
+}
+
+/// Read agents.json into the registry; a missing or unreadable file leaves it empty
+pub fn load(state: &AppState) {
+    load_from(state, &path());
+}
+
+fn load_from(state: &AppState, path: &Path) {
+    let agents: Vec<Agent> = load_json(path);
+    if !agents.is_empty() {
+        info!("Loaded {} agent(s) from {}", agents.len(), path.display());
+    }
+    *state.agents.lock() = Registry::of(agents);
+}
+
+/// Write the registry to agents.json if it changed since the last write
+pub fn save(state: &AppState) {
+    save_to(state, &path());
+}
+
+fn save_to(state: &AppState, path: &Path) {
+    let agents = {
+        let mut registry = state.agents.lock();
+        if !registry.dirty {
+            return;
+        }
+        registry.dirty = false;
+        registry.list()
+    };
+    if let Err(e) = save_json(path, &agents) {
+        report_error(state, "Saving the agents", &e);
+        state.agents.lock().dirty = true;
+    }
+}
+
+pub fn list(state: &AppState) -> Vec<Agent> {
+    state.agents.lock().list()
+}
+
+/// Mute or unmute agent `name` for get_agents' mute toggles and PATCH /agents/:name; the
+/// change is saved at once
+pub fn set_muted(state: &AppState, name: &str, muted: bool) -> Result<Agent, String> {
+    let agent = state.agents.lock()
+        .set_muted(name, muted)
+        .ok_or_else(|| format!("No agent named \"{}\" has sent a message", name))?;
+    info!("Agent {} {}", name, if muted { "muted" } else { "unmuted" });
+    save(state);
+    events::emit(state, events::AGENT_MUTED, agent.clone());
+    Ok(agent)
+}
+
+/// Write the registry every SAVE_INTERVAL while it changes; runs as the "agents" task
+pub async fn keep_saved(state: Arc<AppState>) {
+    loop {
+        tokio::time::sleep(SAVE_INTERVAL).await;
+        let state = state.clone();
+        if let Err(e) = tokio::task::spawn_blocking(move || save(&state)).await {
+            warn!("Saving the agents did not finish: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::SpeakRequest;
+    use tempfile::TempDir;
+
+    fn entry(agent: &str) -> VoiceEntry {
+        VoiceEntry {
+            id: 1,
+            timestamp: Utc::now(),
+            text: "Hi".to_string(),
+            voice: "Samantha".to_string(),
+            rate: 200,
+            agent: Some(agent.to_string()),
+            status: "queued".to_string(),
+            priority: 0,
+            detail: None,
+            replay_of: None,
+            relayed_from: None,
+        }
+    }
+
+    fn request(agent: Option<&str>, text: &str) -> SpeakRequest {
+        SpeakRequest { text: text.to_string(), voice: Some("Daniel".to_string()), rate: Some(180), agent: agent.map(str::to_string) }
+    }
+
+    #[test]
+    fn test_muted_agents_are_suppressed() {
+        let state = AppState::default();
+        let first = state.speak(request(Some("ci"), "Build passed")).id;
+        state.speak(request(None, "Hello"));
+        assert!(set_muted(&state, "deploy", true).is_err(), "only agents seen can be muted");
+        assert!(state.agents.lock().set_muted("ci", true).is_some_and(|ci| ci.muted));
+        let second = state.speak(request(Some("ci"), "Build failed")).id;
+
+        let timeline = state.timeline.lock();
+        assert_eq!(timeline.get(first).map(|e| e.status.as_str()), Some("queued"));
+        let held = timeline.get(second).expect("entry");
+        assert_eq!((held.status.as_str(), held.detail.as_deref()), ("suppressed", Some("Agent ci is muted")));
+        drop(timeline);
+
+        let agents = list(&state);
+        assert_eq!(agents.len(), 1, "entries without an agent are not registered");
+        let ci = &agents[0];
+        assert_eq!((ci.messages, ci.suppressed, ci.voice.as_str(), ci.rate, ci.muted), (2, 1, "Daniel", 180, true));
+        assert!(ci.first_seen <= ci.last_active);
+    }
+
+    #[test]
+    fn test_registry_survives_restart() {
+        let dir = TempDir::new().expect("create temp dir");
+        let file = dir.path().join(AGENTS_FILE);
+        let state = AppState::default();
+        state.speak(request(Some("ci"), "Build passed"));
+        state.agents.lock().set_muted("ci", true);
+        save_to(&state, &file);
+
+        let restarted = AppState::default();
+        load_from(&restarted, &file);
+        assert_eq!(list(&restarted), list(&state));
+        restarted.speak(request(Some("ci"), "Build failed"));
+        assert_eq!(list(&restarted)[0].suppressed, 1, "the mute carries over");
+    }
+
+    #[test]
+    fn test_registry_forgets_idle_agents_first() {
+        let mut registry = Registry::default();
+        registry.seen(&entry("agent-0"));
+        registry.seen(&entry("agent-1"));
+        registry.set_muted("agent-0", true);
+        for i in 2..=AGENTS_MAX {
+            registry.seen(&entry(&format!("agent-{}", i)));
+        }
+        let names: Vec<String> = registry.list().into_iter().map(|agent| agent.name).collect();
+        assert_eq!(names.len(), AGENTS_MAX);
+        assert!(names.contains(&"agent-0".to_string()) && !names.contains(&"agent-1".to_string()), "the muted agent outlives an idle one");
+    }
+}
@@ -0,0 +1,42 @@
+/// Start at Login through tauri-plugin-autostart: a LaunchAgent on macOS, a value under the
+/// HKCU Run key on Windows and an XDG autostart entry on Linux, each named after the app and
+/// pointing at the running executable (or the AppImage).
+use tauri::plugin::TauriPlugin;
+use tauri::Wry;
+use tauri_plugin_autostart::ManagerExt;
+use tracing::warn;
+
+use crate::state::AppState;
+
+/// The autostart plugin; its default on macOS is a LaunchAgent
+pub fn plugin() -> TauriPlugin<Wry> {
+    tauri_plugin_autostart::Builder::new().build()
+}
+
+/// Whether the app is registered to start at login, read from the system each time
+/// so changes made in system settings show up. False until the app is up.
+pub fn is_enabled(state: &AppState) -> bool {
+    let Some(app) = state.app_handle.read().clone() else {
+        return false;
+    };
+    app.autolaunch().is_enabled().unwrap_or_else(|e| {
+        warn!("Failed to read Start at Login: {}", e);
+        false
+    })
+}
+
+/// Register or unregister the app as a login item
+pub fn set_enabled(state: &AppState, enabled: bool) -> Result<(), String> {
+    let Some(app) = state.app_handle.read().clone() else {
+        return Err("The app is still starting".to_string());
+    };
+    let manager = app.autolaunch();
+    let result = if enabled {
+        manager.enable()
+    } else if manager.is_enabled().unwrap_or(true) {
+        manager.disable()
+    } else {
+        return Ok(());
+    };
+    result.map_err(|e| e.to_string())
+}
@@ -0,0 +1,110 @@
+/// Queue-count badge for the tray icon
+/// Draws a filled circle with the pending count into a copy of the base icon's RGBA pixels.
+/// No font is bundled, so digits come from a 3×5 bitmap scaled to the badge size.
+use image::{Rgba, RgbaImage};
+
+const BADGE_COLOR: Rgba<u8> = Rgba([224, 49, 49, 255]);
+const TEXT_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// 3×5 glyphs, one row per byte, bit 2 = left column
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        _ => [0; 5],
+    }
+}
+
+/// Badge text for a queue length: none when empty, "9+" for ten or more
+pub fn badge_label(count: usize) -> Option<String> {
+    match count {
+        0 => None,
+        1..=9 => Some(count.to_string()),
+        _ => Some("9+".to_string()),
+    }
+}
+
+/// Copy of `rgba` (width × height) with the badge in the top-right corner.
+/// For a template image the badge is black with the digits cut out, since only alpha survives.
+/// None if the buffer does not match the size.
+pub fn composite_badge(rgba: &[u8], width: u32, height: u32, label: &str, template: bool) -> Option<Vec<u8>> {
+    let (badge_color, text_color) = if template {
+        (Rgba([0, 0, 0, 255]), Rgba([0, 0, 0, 0]))
+    } else {
+        (BADGE_COLOR, TEXT_COLOR)
+    };
+    if rgba.len() != (width * height * 4) as usize {
+        return None;
+    }
+    let mut img = RgbaImage::from_raw(width, height, rgba.to_vec())?;
+    let radius = (width.min(height) as f32 * 0.3).max(4.0);
+    let (cx, cy) = (width as f32 - radius, radius);
+    for y in 0..height {
+        for x in 0..width {
+            let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+            if dx * dx + dy * dy <= radius * radius {
+                img.put_pixel(x, y, badge_color);
+            }
+        }
+    }
+
+    // Glyphs 3 wide with a 1-pixel gap, scaled so the text is about 1.2 radii tall
+    let chars: Vec<char> = label.chars().collect();
+    let columns = chars.len() as u32 * 4 - 1;
+    let scale = ((radius * 1.2 / 5.0) as u32).min((radius * 1.6) as u32 / columns).max(1);
+    let left = (cx - (columns * scale) as f32 / 2.0).round().max(0.0) as u32;
+    let top = (cy - (5 * scale) as f32 / 2.0).round().max(0.0) as u32;
+    for (i, c) in chars.iter().enumerate() {
+        for (row, bits) in glyph(*c).iter().enumerate() {
+            for col in 0..3u32 {
+                if bits & (0b100 >> col) == 0 {
+                    continue;
+                }
+                let x0 = left + (i as u32 * 4 + col) * scale;
+                let y0 = top + row as u32 * scale;
+                for y in y0..(y0 + scale).min(height) {
+                    for x in x0..(x0 + scale).min(width) {
+                        img.put_pixel(x, y, text_color);
+                    }
+                }
+            }
+        }
+    }
+    Some(img.into_raw())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_badge_label() {
+        assert_eq!(badge_label(0), None);
+        assert_eq!(badge_label(3).as_deref(), Some("3"));
+        assert_eq!(badge_label(10).as_deref(), Some("9+"));
+    }
+
+    #[test]
+    fn test_composite_badge() {
+        let base = vec![0u8; 32 * 32 * 4];
+        let badged = RgbaImage::from_raw(32, 32, composite_badge(&base, 32, 32, "1", false).expect("badge")).expect("image");
+        // Bottom-left untouched, badge edge red, stem of the "1" white
+        assert_eq!(badged.get_pixel(2, 29), &Rgba([0, 0, 0, 0]));
+        assert_eq!(badged.get_pixel(31, 9), &BADGE_COLOR);
+        assert_eq!(badged.get_pixel(22, 9), &TEXT_COLOR);
+        assert_eq!(composite_badge(&base, 16, 16, "1", false), None);
+
+        let template = RgbaImage::from_raw(32, 32, composite_badge(&base, 32, 32, "1", true).expect("badge")).expect("image");
+        assert_eq!(template.get_pixel(31, 9), &Rgba([0, 0, 0, 255]));
+        assert_eq!(template.get_pixel(22, 9), &Rgba([0, 0, 0, 0]));
+    }
+}
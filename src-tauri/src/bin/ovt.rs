@@ -0,0 +1,480 @@
+/// ovt: the command line client for the running app's HTTP API, for scripts and CI.
+///
+///   ovt say "Build passed" --voice Samantha --rate 200 --agent ci
+///   ovt say --file notes.md --split
+///   ovt status | timeline --agent ci --limit 10 | stop | mute on|off
+///
+/// The port comes from --port or OVT_PORT, else from server.json (written by the app while it
+/// listens), else from http.port in the config, else the default; a port that refuses the
+/// connection falls through to the next. The API has no token yet: it only listens on
+/// 127.0.0.1 unless http.bind says otherwise, so ovt sends no Authorization header. Exits 0 on
+/// success, 1 when the request fails and 2 on a usage error.
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+use voice_tray_v2_lib::{saved_http_port, server_file_path, FileResponse, ServerFile, SpeakResponse, VoiceEntry, VOICE_SERVER_PORT};
+
+const USAGE: &str = "Usage: ovt [--json] [--port PORT] <command>
+
+Commands:
+  say TEXT [--voice NAME] [--rate WPM] [--agent NAME]   Queue TEXT; TEXT - reads it from stdin
+  say --file PATH [--split] [--voice ...]             Queue a text file, one entry per paragraph with --split
+                                                      (the app reads it; its folder must be in files.allowed_dirs)
+  status                                              What the app is doing
+  timeline [--agent NAME] [--limit N]                 Recent messages, oldest first
+  stop                                                Stop the message being spoken
+  mute on|off                                         Mute or unmute speech
+
+Options:
+  --json         Print the server's JSON response instead of a summary
+  --port PORT    Talk to this port only (also OVT_PORT)
+
+The API takes no token yet, so there is nothing to pass for authentication.";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Say { text: String, voice: Option<String>, rate: Option<u32>, agent: Option<String> },
+    SayFile { path: String, split: bool, voice: Option<String>, rate: Option<u32>, agent: Option<String> },
+    Status,
+    Timeline { agent: Option<String>, limit: Option<usize> },
+    Stop,
+    Mute(bool),
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    command: Command,
+    json: bool,
+    port: Option<u16>,
+}
+
+/// Parse the arguments after the program name. `say`'s text is every word that is not an
+/// option, so it need not be quoted.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut json = false;
+    let mut split = false;
+    let mut port = None;
+    let mut words = Vec::new();
+    let mut named: Vec<(String, String)> = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--split" => split = true,
+            "-h" | "--help" => return Ok(Options { command: Command::Help, json, port }),
+            "--port" | "--voice" | "--rate" | "--agent" | "--limit" | "--file" => {
+                let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+                if arg == "--port" {
+                    port = Some(parse_number(arg, value)?);
+                } else {
+                    named.push((arg.clone(), value.clone()));
+                }
+            }
+            // A lone "-" is say's stdin marker, not an option
+            option if option.starts_with("--") => return Err(format!("Unknown option {}", option)),
+            _ => words.push(arg.clone()),
+        }
+    }
+    let Some((name, rest)) = words.split_first() else {
+        return Err("No command given".to_string());
+    };
+    let allow = |allowed: &[&str]| match named.iter().find(|(option, _)| !allowed.contains(&option.as_str())) {
+        Some((option, _)) => Err(format!("{} does not apply to {}", option, name)),
+        None => Ok(()),
+    };
+    let value = |option: &str| named.iter().rev().find(|(o, _)| o == option).map(|(_, v)| v.clone());
+    let no_words = || match rest.first() {
+        Some(word) => Err(format!("Unexpected argument \"{}\" for {}", word, name)),
+        None => Ok(()),
+    };
+    let command = match name.as_str() {
+        "say" if value("--file").is_some() || split => {
+            allow(&["--voice", "--rate", "--agent", "--file"])?;
+            no_words()?;
+            Command::SayFile {
+                path: value("--file").ok_or("--split only applies to say --file")?,
+                split,
+                voice: value("--voice"),
+                rate: value("--rate").map(|rate| parse_number("--rate", &rate)).transpose()?,
+                agent: value("--agent"),
+            }
+        }
+        "say" => {
+            allow(&["--voice", "--rate", "--agent"])?;
+            if rest.is_empty() {
+                return Err("say needs the text to speak, or - to read it from stdin".to_string());
+            }
+            Command::Say {
+                text: rest.join(" "),
+                voice: value("--voice"),
+                rate: value("--rate").map(|rate| parse_number("--rate", &rate)).transpose()?,
+                agent: value("--agent"),
+            }
+        }
+        "status" | "stop" => {
+            allow(&[])?;
+            no_words()?;
+            if name == "status" { Command::Status } else { Command::Stop }
+        }
+        "timeline" => {
+            allow(&["--agent", "--limit"])?;
+            no_words()?;
+            Command::Timeline {
+                agent: value("--agent"),
+                limit: value("--limit").map(|limit| parse_number("--limit", &limit)).transpose()?,
+            }
+        }
+        "mute" => {
+            allow(&[])?;
+            match rest {
+                [state] if state == "on" => Command::Mute(true),
+                [state] if state == "off" => Command::Mute(false),
+                _ => return Err("mute takes on or off".to_string()),
+            }
+        }
+        other => return Err(format!("Unknown command \"{}\"", other)),
+    };
+    if split && !matches!(command, Command::SayFile { .. }) {
+        return Err(format!("--split does not apply to {}", name));
+    }
+    Ok(Options { command, json, port })
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} takes a number, not \"{}\"", option, value))
+}
+
+/// Ports to try, in order: the one asked for, or else where the app says it listens, the
+/// configured one and the default
+fn candidate_ports(explicit: Option<u16>) -> Vec<u16> {
+    if let Some(port) = explicit {
+        return vec![port];
+    }
+    let advertised = std::fs::read_to_string(server_file_path()).ok()
+        .and_then(|content| serde_json::from_str::<ServerFile>(&content).ok())
+        .map(|file| file.port);
+    let mut ports = Vec::new();
+    for port in advertised.into_iter().chain([saved_http_port(), VOICE_SERVER_PORT]) {
+        if !ports.contains(&port) {
+            ports.push(port);
+        }
+    }
+    ports
+}
+
+/// Send one request to the first port that takes the connection. Returns the port, the
+/// status code and the JSON body.
+fn request(ports: &[u16], method: &str, path: &str, body: Option<&Value>) -> Result<(u16, u16, Value), String> {
+    let mut refused = Vec::new();
+    for &port in ports {
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let mut stream = match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+            Ok(stream) => stream,
+            Err(e) => {
+                refused.push(format!("{} ({})", port, e));
+                continue;
+            }
+        };
+        let (status, body) = exchange(&mut stream, port, method, path, body)
+            .map_err(|e| format!("Request to port {} failed: {}", port, e))?;
+        return Ok((port, status, body));
+    }
+    Err(format!("Voice Tray is not running or not reachable; tried port {}", refused.join(", ")))
+}
+
+fn exchange(stream: &mut TcpStream, port: u16, method: &str, path: &str, body: Option<&Value>) -> std::io::Result<(u16, Value)> {
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    let body = body.map(Value::to_string).unwrap_or_default();
+    let content_type = if body.is_empty() { "" } else { "Content-Type: application/json\r\n" };
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: 127.0.0.1:{}\r\nConnection: close\r\n{}Content-Length: {}\r\n\r\n{}",
+        method, path, port, content_type, body.len(), body
+    )?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    parse_response(&String::from_utf8_lossy(&response))
+}
+
+/// The status code and body of a whole HTTP/1.1 response. The server always sends a
+/// Content-Length, so a chunked body is refused rather than decoded.
+fn parse_response(response: &str) -> std::io::Result<(u16, Value)> {
+    let invalid = |what: String| std::io::Error::new(std::io::ErrorKind::InvalidData, what);
+    let (head, body) = response.split_once("\r\n\r\n").ok_or_else(|| invalid("incomplete response".to_string()))?;
+    let mut lines = head.lines();
+    let status = lines.next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| invalid("no status line".to_string()))?;
+    let mut length = None;
+    for (name, value) in lines.filter_map(|line| line.split_once(':')) {
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("transfer-encoding") && !value.eq_ignore_ascii_case("identity") {
+            return Err(invalid(format!("unsupported Transfer-Encoding: {}", value)));
+        }
+        if name.eq_ignore_ascii_case("content-length") {
+            length = Some(value.parse::<usize>().map_err(|_| invalid(format!("bad Content-Length: {}", value)))?);
+        }
+    }
+    let body = match length {
+        Some(length) => body.get(..length).ok_or_else(|| invalid(format!("response cut short: {} of {} bytes", body.len(), length)))?,
+        None => body,
+    };
+    let body = serde_json::from_str(body).unwrap_or_else(|_| Value::String(body.trim().to_string()));
+    Ok((status, body))
+}
+
+/// The message of an error response: its "error", its first diagnostic, or the body itself
+fn error_message(body: &Value) -> String {
+    if let Some(error) = body.get("error").and_then(Value::as_str) {
+        return error.to_string();
+    }
+    if let Some(message) = body.pointer("/diagnostics/0/message").and_then(Value::as_str) {
+        return message.to_string();
+    }
+    match body {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn query_string(pairs: &[(&str, Option<String>)]) -> String {
+    let pairs: Vec<String> = pairs.iter()
+        .filter_map(|(key, value)| value.as_ref().map(|value| format!("{}={}", key, encode(value))))
+        .collect();
+    if pairs.is_empty() { String::new() } else { format!("?{}", pairs.join("&")) }
+}
+
+/// Percent-encode everything but unreserved characters
+fn encode(value: &str) -> String {
+    value.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+        other => format!("%{:02X}", other),
+    }).collect()
+}
+
+fn yes_no(value: &Value) -> &'static str {
+    if value.as_bool().unwrap_or(false) { "yes" } else { "no" }
+}
+
+fn print_status(port: u16, status: &Value) {
+    println!("Listening: 127.0.0.1:{}", port);
+    println!("Speaking: {}", yes_no(&status["is_speaking"]));
+    println!("Muted: {}", yes_no(&status["muted"]));
+    if let Some(minutes) = status["snooze_minutes_left"].as_i64() {
+        println!("Snoozed: {} min left", minutes);
+    }
+    println!("Queue: {} queued, {} in the timeline", status["queued"], status["total"]);
+    println!(
+        "MQTT: {} ({})",
+        status["mqtt_status"].as_str().unwrap_or("unknown"),
+        status["mqtt_broker"].as_str().unwrap_or("?")
+    );
+    if let Some(profile) = status["profile"].as_str() {
+        println!("Profile: {}", profile);
+    }
+    if let Some(warning) = status["config_warning"].as_str() {
+        println!("Config warning: {}", warning);
+    }
+}
+
+fn print_timeline(entries: Vec<VoiceEntry>) {
+    if entries.is_empty() {
+        println!("No messages");
+    }
+    for entry in entries {
+        println!(
+            "#{:<5} {}  {:<9} {:<12} {}",
+            entry.id,
+            entry.timestamp.with_timezone(&chrono::Local).format("%H:%M:%S"),
+            entry.status,
+            entry.agent.as_deref().unwrap_or("-"),
+            entry.text
+        );
+    }
+}
+
+/// Run the parsed command; Err is the message to print before exiting with 1
+fn run(options: Options) -> Result<(), String> {
+    let (method, path, body) = match &options.command {
+        Command::Help => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Command::Say { text, voice, rate, agent } => {
+            let text = if text == "-" {
+                let mut input = String::new();
+                std::io::stdin().read_to_string(&mut input).map_err(|e| format!("Failed to read stdin: {}", e))?;
+                input.trim().to_string()
+            } else {
+                text.clone()
+            };
+            let body = serde_json::json!({ "text": text, "voice": voice, "rate": rate, "agent": agent });
+            ("POST", "/speak".to_string(), Some(body))
+        }
+        Command::SayFile { path, split, voice, rate, agent } => {
+            // The app resolves the path, so it goes as an absolute one
+            let path = std::path::absolute(path).map_err(|e| format!("{}: {}", path, e))?;
+            let body = serde_json::json!({ "path": path, "split": split, "voice": voice, "rate": rate, "agent": agent });
+            ("POST", "/speak/file".to_string(), Some(body))
+        }
+        Command::Status => ("GET", "/status".to_string(), None),
+        Command::Timeline { agent, limit } => {
+            let query = query_string(&[("agent", agent.clone()), ("limit", limit.map(|l| l.to_string()))]);
+            ("GET", format!("/timeline{}", query), None)
+        }
+        Command::Stop => ("POST", "/stop".to_string(), None),
+        Command::Mute(muted) => ("POST", "/mute".to_string(), Some(serde_json::json!({ "muted": muted }))),
+    };
+    let (port, status, response) = request(&candidate_ports(options.port), method, &path, body.as_ref())?;
+    if !(200..300).contains(&status) {
+        return Err(format!("HTTP {}: {}", status, error_message(&response)));
+    }
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&response).unwrap_or_else(|_| response.to_string()));
+        return Ok(());
+    }
+    let unexpected = |e: serde_json::Error| format!("Unexpected response: {}", e);
+    match options.command {
+        Command::Say { .. } => {
+            let queued: SpeakResponse = serde_json::from_value(response).map_err(unexpected)?;
+            println!("Queued #{}", queued.id);
+        }
+        Command::SayFile { .. } => {
+            let queued: FileResponse = serde_json::from_value(response).map_err(unexpected)?;
+            let ids: Vec<String> = queued.ids.iter().map(|id| format!("#{}", id)).collect();
+            println!("Queued {} ({} characters)", ids.join(", "), queued.characters);
+        }
+        Command::Status => print_status(port, &response),
+        Command::Timeline { .. } => print_timeline(serde_json::from_value(response).map_err(unexpected)?),
+        Command::Stop if response["stopped"].as_bool() == Some(true) => println!("Stopped"),
+        Command::Stop => println!("Nothing was speaking"),
+        Command::Mute(true) => println!("Muted"),
+        Command::Mute(false) => println!("Unmuted"),
+        Command::Help => {}
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("ovt: {}\n\n{}", error, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if options.port.is_none() {
+        if let Ok(port) = std::env::var("OVT_PORT") {
+            match parse_number("OVT_PORT", &port) {
+                Ok(port) => options.port = Some(port),
+                Err(error) => {
+                    eprintln!("ovt: {}", error);
+                    std::process::exit(2);
+                }
+            }
+        }
+    }
+    if let Err(error) = run(options) {
+        eprintln!("ovt: {}", error);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+
+    fn parse(line: &str) -> Result<Options, String> {
+        parse_args(&line.split_whitespace().map(String::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse("--json say Build passed --rate 200 --agent ci").expect("say");
+        assert!(options.json);
+        assert_eq!(options.command, Command::Say {
+            text: "Build passed".to_string(),
+            voice: None,
+            rate: Some(200),
+            agent: Some("ci".to_string()),
+        });
+        assert_eq!(parse("say -").map(|o| o.command), Ok(Command::Say { text: "-".to_string(), voice: None, rate: None, agent: None }));
+        assert_eq!(
+            parse("timeline --agent ci --limit 10 --port 4000").map(|o| (o.command, o.port)),
+            Ok((Command::Timeline { agent: Some("ci".to_string()), limit: Some(10) }, Some(4000)))
+        );
+        assert_eq!(parse("mute off").map(|o| o.command), Ok(Command::Mute(false)));
+        assert_eq!(
+            parse("say --file notes.md --split --agent notes").map(|o| o.command),
+            Ok(Command::SayFile { path: "notes.md".to_string(), split: true, voice: None, rate: None, agent: Some("notes".to_string()) })
+        );
+
+        assert!(parse("").is_err());
+        assert!(parse("say").is_err());
+        assert!(parse("mute maybe").is_err());
+        assert!(parse("stop --voice Alex").is_err());
+        assert!(parse("say --file notes.md extra words").is_err());
+        assert!(parse("say Hello --split").is_err());
+        assert!(parse("status --split").is_err());
+        assert!(parse("timeline --limit ten").is_err());
+        assert!(parse("status --verbose").is_err());
+        assert!(parse("dance").is_err());
+    }
+
+    #[test]
+    fn test_query_string() {
+        assert_eq!(query_string(&[("agent", None), ("limit", None)]), "");
+        assert_eq!(query_string(&[("agent", Some("my agent/1".to_string())), ("limit", Some("5".to_string()))]), "?agent=my%20agent%2F1&limit=5");
+    }
+
+    /// A refused port falls through to the next one, and error bodies come back with their status
+    #[test]
+    fn test_request_falls_back_to_next_port() {
+        let closed = TcpListener::bind("127.0.0.1:0").expect("bind").local_addr().expect("addr").port();
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let live = listener.local_addr().expect("addr").port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("accept");
+            let mut reader = std::io::BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).expect("read");
+            let mut line = String::new();
+            while reader.read_line(&mut line).map(|n| n > 2).unwrap_or(false) {
+                line.clear();
+            }
+            let body = r#"{"error":"text must not be empty"}"#;
+            write!(reader.get_mut(), "HTTP/1.1 400 Bad Request\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).expect("write");
+            request_line
+        });
+
+        let (port, status, body) = request(&[closed, live], "POST", "/speak", Some(&serde_json::json!({ "text": "" }))).expect("response");
+        assert_eq!((port, status), (live, 400));
+        assert_eq!(error_message(&body), "text must not be empty");
+        assert!(server.join().expect("server").starts_with("POST /speak HTTP/1.1"));
+
+        let refused = request(&[closed], "GET", "/status", None).expect_err("nothing listens");
+        assert!(refused.contains(&closed.to_string()), "{}", refused);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let (status, body) = parse_response("HTTP/1.1 200 OK\r\ncontent-length: 9\r\n\r\n{\"id\":7}\n").expect("response");
+        assert_eq!((status, body), (200, serde_json::json!({ "id": 7 })));
+
+        let chunked = parse_response("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n8\r\n{\"id\":7}\r\n0\r\n\r\n").expect_err("chunked");
+        assert_eq!(chunked.to_string(), "unsupported Transfer-Encoding: chunked");
+        let short = parse_response("HTTP/1.1 200 OK\r\nContent-Length: 20\r\n\r\n{\"id\":7}").expect_err("short");
+        assert_eq!(short.to_string(), "response cut short: 8 of 20 bytes");
+        assert!(parse_response("HTTP/1.1 200 OK\r\n").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+/// Config bundles: the active profile's settings as one JSON file for setting up another machine,
+/// for the export_config and import_config commands and PUT /config?import=true. Bundles never
+/// carry the MQTT password, ntfy tokens, relay token, GitHub secret or webhook URLs; an import keeps the ones this machine has.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::AppHandle;
+use tauri_plugin_dialog::DialogExt;
+use tracing::info;
+
+use crate::config::{
+    backup_config, load_saved_config, upgrade_settings, validate_config, AppConfig, ConfigView, Diagnostic, SaveOutcome, CONFIG_VERSION,
+    SECTIONS,
+};
+use crate::export::ExportOutcome;
+use crate::reload;
+use crate::state::AppState;
+
+/// `kind` of every bundle, so other JSON files are not taken for one
+pub const BUNDLE_KIND: &str = "oracle-voice-tray-config";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bundle {
+    pub kind: String,
+    pub schema_version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub app_version: String,
+    /// Sections of config.json; an import may leave some out
+    pub config: Map<String, Value>,
+}
+
+impl Bundle {
+    /// `config` as a bundle, without the MQTT password or its placeholder
+    pub fn of(config: &AppConfig) -> Self {
+        let mut sections = match serde_json::to_value(ConfigView::of(config.clone())) {
+            Ok(Value::Object(sections)) => sections,
+            _ => Map::new(),
+        };
+        sections.remove("schema_version");
+        if let Some(Value::Object(mqtt)) = sections.get_mut("mqtt") {
+            mqtt.remove("password");
+            mqtt.remove("has_password");
+        }
+        Self {
+            kind: BUNDLE_KIND.to_string(),
+            schema_version: CONFIG_VERSION,
+            exported_at: Utc::now(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            config: sections,
+        }
+    }
+
+    /// Read a bundle, upgrading the settings of an older one. Refuses other files, bundles from
+    /// a newer version and unknown sections.
+    pub fn parse(value: Value) -> Result<Self, String> {
+        if value.get("kind").and_then(Value::as_str) != Some(BUNDLE_KIND) {
+            return Err("Not a Voice Tray config bundle".to_string());
+        }
+        let mut bundle: Bundle = serde_json::from_value(value).map_err(|e| format!("Invalid bundle: {}", e))?;
+        let upgraded = upgrade_settings(Value::Object(bundle.config), bundle.schema_version)?;
+        let Value::Object(sections) = upgraded else {
+            return Err("Invalid bundle: config is not an object".to_string());
+        };
+        if let Some(unknown) = sections.keys().find(|key| !SECTIONS.contains(&key.as_str())) {
+            return Err(format!("Invalid bundle: unknown section \"{}\"", unknown));
+        }
+        bundle.config = sections;
+        bundle.schema_version = CONFIG_VERSION;
+        Ok(bundle)
+    }
+}
+
+/// How an import treats the sections a bundle leaves out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// They keep their current settings
+    #[default]
+    Merge,
+    /// They go back to the defaults
+    Replace,
+}
+
+/// A setting an import changes
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub field_path: String,
+    pub current: Value,
+    pub imported: Value,
+}
+
+/// What import_config did. A preview lists the changes and diagnostics without saving; import
+/// again with `apply` to save.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ImportOutcome {
+    Cancelled,
+    Preview {
+        path: Option<PathBuf>,
+        mode: ImportMode,
+        changes: Vec<Change>,
+        diagnostics: Vec<Diagnostic>,
+    },
+    /// Errors in the imported settings; nothing was saved
+    Rejected {
+        path: Option<PathBuf>,
+        diagnostics: Vec<Diagnostic>,
+    },
+    Applied {
+        path: Option<PathBuf>,
+        changes: Vec<Change>,
+        /// Copy of the settings file from before the import
+        backup: Option<PathBuf>,
+        saved: SaveOutcome,
+    },
+}
+
+/// The settings `current` becomes with `bundle` imported. The MQTT password stays as it is.
+pub fn imported_config(current: &AppConfig, bundle: &Bundle, mode: ImportMode) -> Result<AppConfig, String> {
+    let base = match mode {
+        ImportMode::Merge => current.clone(),
+        ImportMode::Replace => AppConfig::default(),
+    };
+    let Ok(Value::Object(mut sections)) = serde_json::to_value(&base) else {
+        return Err("Failed to read the current settings".to_string());
+    };
+    for (name, section) in &bundle.config {
+        sections.insert(name.clone(), section.clone());
+    }
+    let mut config: AppConfig = serde_json::from_value(Value::Object(sections)).map_err(|e| format!("Invalid bundle: {}", e))?;
+    config.mqtt.password = current.mqtt.password.clone();
+    Ok(config)
+}
+
+/// Every setting that differs, by dotted path in name order; lists are compared whole
+pub fn diff(current: &AppConfig, imported: &AppConfig) -> Vec<Change> {
+    fn walk(path: &str, current: &Value, imported: &Value, changes: &mut Vec<Change>) {
+        match (current, imported) {
+            (Value::Object(a), Value::Object(b)) => {
+                let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+                for key in keys {
+                    let field = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                    walk(&field, a.get(key).unwrap_or(&Value::Null), b.get(key).unwrap_or(&Value::Null), changes);
+                }
+            }
+            (a, b) if a != b => changes.push(Change { field_path: path.to_string(), current: a.clone(), imported: b.clone() }),
+            _ => {}
+        }
+    }
+    let mut changes = Vec::new();
+    if let (Ok(a), Ok(b)) = (serde_json::to_value(current), serde_json::to_value(imported)) {
+        walk("", &a, &b, &mut changes);
+    }
+    changes
+}
+
+/// Preview or apply `bundle` over the settings the settings window shows. Applying backs the
+/// settings file up first, then saves like the settings window.
+pub fn import(state: &Arc<AppState>, bundle: &Bundle, path: Option<PathBuf>, mode: ImportMode, apply: bool) -> Result<ImportOutcome, String> {
+    let current = AppConfig::load_redacted(state.tray_config(), state.voice_config());
+    let config = imported_config(&current, bundle, mode)?;
+    let changes = diff(&current, &config);
+    let diagnostics = validate_config(&config);
+    if !apply {
+        return Ok(ImportOutcome::Preview { path, mode, changes, diagnostics });
+    }
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        return Ok(ImportOutcome::Rejected { path, diagnostics });
+    }
+    let backup = backup_config("import")?;
+    let saved = match reload::save_settings(state, config) {
+        Ok(saved) => SaveOutcome { warnings: diagnostics, ..saved },
+        Err(diagnostics) => return Ok(ImportOutcome::Rejected { path, diagnostics }),
+    };
+    info!("Imported {} settings ({:?})", changes.len(), mode);
+    Ok(ImportOutcome::Applied { path, changes, backup, saved })
+}
+
+/// Ask where to save, then write the active profile's settings as a bundle. Blocks on the
+/// dialog, so call it off the main thread.
+pub fn export(app: &AppHandle) -> Result<ExportOutcome, String> {
+    let Some(chosen) = app.dialog().file()
+        .set_title("Export Settings")
+        .set_file_name("voice-tray-settings.json")
+        .add_filter("JSON", &["json"])
+        .blocking_save_file()
+    else {
+        return Ok(ExportOutcome::Cancelled);
+    };
+    let path = chosen.into_path().map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(&Bundle::of(&load_saved_config())).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("{}: {}", path.display(), e))?;
+    info!("Exported settings to {}", path.display());
+    Ok(ExportOutcome::Saved { path })
+}
+
+/// Import the bundle at `path`, asking for the file when there is none. Blocks on the dialog,
+/// so call it off the main thread.
+pub fn import_file(app: &AppHandle, state: &Arc<AppState>, path: Option<PathBuf>, mode: ImportMode, apply: bool) -> Result<ImportOutcome, String> {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            let Some(chosen) = app.dialog().file()
+                .set_title("Import Settings")
+                .add_filter("JSON", &["json"])
+                .blocking_pick_file()
+            else {
+                return Ok(ImportOutcome::Cancelled);
+            };
+            chosen.into_path().map_err(|e| e.to_string())?
+        }
+    };
+    let content = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let value: Value = serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    import(state, &Bundle::parse(value)?, Some(path), mode, apply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_leaves_out_password() {
+        let mut config = AppConfig::default();
+        config.mqtt.password = Some("hunter2".to_string());
+        config.voice.default_voice = "Karen".to_string();
+        let bundle = Bundle::of(&config);
+        let json = serde_json::to_string(&bundle).expect("serialize");
+        assert!(!json.contains("hunter2") && !json.contains("\"password\""));
+
+        let parsed = Bundle::parse(serde_json::from_str(&json).expect("parse")).expect("bundle");
+        let imported = imported_config(&AppConfig::default(), &parsed, ImportMode::Replace).expect("import");
+        assert_eq!(imported.voice.default_voice, "Karen");
+        assert_eq!(imported.mqtt.password, None, "the password is not imported");
+    }
+
+    #[test]
+    fn test_parse_checks_kind_version_and_sections() {
+        let bundle = |version: u32, config: Value| {
+            serde_json::json!({ "kind": BUNDLE_KIND, "schema_version": version, "exported_at": "2026-01-05T09:30:00Z", "app_version": "0.1.0", "config": config })
+        };
+        assert_eq!(Bundle::parse(serde_json::json!({ "mqtt": {} })).unwrap_err(), "Not a Voice Tray config bundle");
+        assert!(Bundle::parse(bundle(CONFIG_VERSION + 1, serde_json::json!({}))).unwrap_err().contains("newer version"));
+        assert_eq!(Bundle::parse(bundle(1, serde_json::json!({ "speech": {} }))).unwrap_err(), "Invalid bundle: unknown section \"speech\"");
+        // A version 0 config (flat MQTT settings) is upgraded
+        let old = Bundle::parse(bundle(0, serde_json::json!({ "broker": "pi.local" }))).expect("upgrade");
+        assert_eq!(old.config["mqtt"]["broker"], "pi.local");
+    }
+
+    #[test]
+    fn test_merge_and_replace() {
+        let mut current = AppConfig::default();
+        current.mqtt.broker = "home.local".to_string();
+        current.mqtt.password = Some("********".to_string());
+        current.ui.muted = true;
+        let mut exported = AppConfig::default();
+        exported.voice.default_rate = 180;
+        let mut bundle = Bundle::of(&exported);
+        bundle.config.retain(|name, _| name == "voice");
+
+        let merged = imported_config(&current, &bundle, ImportMode::Merge).expect("merge");
+        assert_eq!((merged.mqtt.broker.as_str(), merged.ui.muted, merged.voice.default_rate), ("home.local", true, 180));
+        let changes: Vec<String> = diff(&current, &merged).into_iter().map(|c| c.field_path).collect();
+        assert_eq!(changes, ["voice.default_rate"]);
+
+        let replaced = imported_config(&current, &bundle, ImportMode::Replace).expect("replace");
+        assert_eq!((replaced.mqtt.broker.as_str(), replaced.ui.muted), ("127.0.0.1", false), "left-out sections reset");
+        assert_eq!(replaced.mqtt.password.as_deref(), Some("********"), "password kept");
+        let changes = diff(&current, &replaced);
+        assert_eq!(changes.iter().map(|c| c.field_path.as_str()).collect::<Vec<_>>(), ["mqtt.broker", "ui.muted", "voice.default_rate"]);
+        assert_eq!(changes[0].imported, Value::from("127.0.0.1"));
+    }
+}
@@ -0,0 +1,112 @@
+/// Timeline changes broadcast inside the app. The timeline mutations in state.rs and the queue
+/// publish; each outbound surface (window events, stats) subscribes on its own. A consumer
+/// that falls behind misses events, as broadcast channels do, without holding up the others.
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+use crate::state::{AppState, VoiceEntry};
+use crate::tray::TraySnapshot;
+
+/// Events a consumer may fall behind by before it misses some
+pub const BUS_CAPACITY: usize = 512;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TimelineEvent {
+    /// A new entry, already in the timeline
+    Queued { entry: VoiceEntry },
+    /// An entry's status (and detail) changed at `at`
+    StatusChanged { entry: VoiceEntry, at: DateTime<Utc> },
+    /// An entry left the timeline: deleted, cleared, or pushed out past TIMELINE_MAX
+    Removed { entry: VoiceEntry },
+    /// What the tray shows changed (queue length, speaking, MQTT, mute, ...)
+    StatusSnapshot { snapshot: TraySnapshot },
+}
+
+pub fn channel() -> broadcast::Sender<TimelineEvent> {
+    broadcast::channel(BUS_CAPACITY).0
+}
+
+pub fn publish(state: &AppState, event: TimelineEvent) {
+    // Fails only while nothing is subscribed, and then nobody needs the event
+    let _ = state.bus.send(event);
+}
+
+/// Publish an entry's new status as of now
+pub fn status_changed(state: &AppState, entry: &VoiceEntry) {
+    publish(state, TimelineEvent::StatusChanged { entry: entry.clone(), at: Utc::now() });
+}
+
+pub fn subscribe(state: &AppState) -> broadcast::Receiver<TimelineEvent> {
+    state.bus.subscribe()
+}
+
+/// Pass every event from now on to `handle`; `name` says who fell behind in the log
+pub async fn consume(state: &AppState, name: &str, mut handle: impl FnMut(TimelineEvent)) {
+    let mut events = subscribe(state);
+    loop {
+        match events.recv().await {
+            Ok(event) => handle(event),
+            Err(RecvError::Lagged(missed)) => warn!("{} fell behind and missed {} timeline events", name, missed),
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    fn drain(events: &mut broadcast::Receiver<TimelineEvent>) -> Vec<(&'static str, u64, String)> {
+        let mut seen = Vec::new();
+        loop {
+            match events.try_recv() {
+                Ok(TimelineEvent::Queued { entry }) => seen.push(("queued", entry.id, entry.status)),
+                Ok(TimelineEvent::StatusChanged { entry, .. }) => seen.push(("status_changed", entry.id, entry.status)),
+                Ok(TimelineEvent::Removed { entry }) => seen.push(("removed", entry.id, entry.status)),
+                Ok(TimelineEvent::StatusSnapshot { .. }) => {}
+                Err(TryRecvError::Lagged(_)) => seen.push(("lagged", 0, String::new())),
+                Err(_) => return seen,
+            }
+        }
+    }
+
+    #[test]
+    fn test_entry_lifecycle_events() {
+        let state = AppState::default();
+        let mut events = subscribe(&state);
+        let id = state.enqueue("Build passed".to_string(), "Samantha".to_string(), 220, Some("ci".to_string()), 0, None);
+        let mut entry = state.start_next().expect("queued entry");
+        assert_eq!(entry.id, id);
+        entry.status = "done".to_string();
+        state.finish_entry(&entry);
+
+        assert_eq!(drain(&mut events), vec![
+            ("queued", id, "queued".to_string()),
+            ("status_changed", id, "speaking".to_string()),
+            ("status_changed", id, "done".to_string()),
+        ]);
+        assert!(state.start_next().is_none());
+    }
+
+    #[test]
+    fn test_lagging_subscriber_misses_events() {
+        let state = AppState::default();
+        let mut slow = subscribe(&state);
+        for _ in 0..BUS_CAPACITY + 1 {
+            state.enqueue("tick".to_string(), "Samantha".to_string(), 220, None, 0, None);
+        }
+        let mut fresh = subscribe(&state);
+        let id = state.enqueue("after".to_string(), "Samantha".to_string(), 220, None, 0, None);
+
+        // Past TIMELINE_MAX each enqueue also pushes the oldest entry out
+        let seen = drain(&mut fresh);
+        assert_eq!((seen[0].clone(), seen[1].0, seen.len()), (("queued", id, "queued".to_string()), "removed", 2));
+        let seen = drain(&mut slow);
+        assert_eq!(seen[0].0, "lagged");
+        assert_eq!(seen.len(), BUS_CAPACITY + 1);
+    }
+}
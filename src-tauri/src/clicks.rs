@@ -0,0 +1,139 @@
+/// Tray icon clicks, mapped to the actions in config.json's ui section. Double-clicks are detected here
+/// because only Windows reports them; the other buttons act on release.
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconEvent};
+use tauri::{AppHandle, Manager};
+use tracing::{info, warn};
+
+use crate::config::TrayAction;
+use crate::lock::Lock;
+use crate::state::AppState;
+use crate::tray;
+
+/// A second left click within this is a double-click; same as the popup toggle debounce
+const DOUBLE_CLICK_WINDOW: Duration = Duration::from_millis(300);
+
+static TRACKER: Lock<ClickTracker> = Lock::new(ClickTracker::new());
+
+/// What a left-button release turned out to be
+#[derive(Debug, PartialEq)]
+enum LeftUp {
+    Double,
+    /// Maybe a single click: decide once the window is over (see ClickTracker::expire)
+    Pending(u64),
+}
+
+/// The left click waiting to find out whether a second one follows
+struct ClickTracker {
+    pending: Option<(u64, Instant)>,
+    next: u64,
+}
+
+impl ClickTracker {
+    const fn new() -> Self {
+        Self { pending: None, next: 0 }
+    }
+
+    fn left_up(&mut self, now: Instant) -> LeftUp {
+        if let Some((_, at)) = self.pending.take() {
+            if now.duration_since(at) < DOUBLE_CLICK_WINDOW {
+                return LeftUp::Double;
+            }
+        }
+        self.next += 1;
+        self.pending = Some((self.next, now));
+        LeftUp::Pending(self.next)
+    }
+
+    /// Called when click `id`'s window is over: true if no second click claimed it
+    fn expire(&mut self, id: u64) -> bool {
+        if self.pending.is_some_and(|(pending, _)| pending == id) {
+            self.pending = None;
+            return true;
+        }
+        false
+    }
+}
+
+/// Dispatch a tray icon event. Without a double-click action a left click fires at once,
+/// exactly as before actions were configurable.
+pub fn handle_tray_event(app: &AppHandle, event: TrayIconEvent) {
+    let TrayIconEvent::Click { button, button_state: MouseButtonState::Up, position, .. } = event else {
+        return;
+    };
+    let actions = app.state::<Arc<AppState>>().tray_config().actions;
+    let (x, y) = (position.x, position.y);
+    match button {
+        MouseButton::Left if actions.double_click == TrayAction::Nothing => run(app, actions.left_click, x, y),
+        MouseButton::Left => {
+            let click = TRACKER.lock().left_up(Instant::now());
+            match click {
+                LeftUp::Double => run(app, actions.double_click, x, y),
+                LeftUp::Pending(id) => {
+                    let app = app.clone();
+                    std::thread::spawn(move || {
+                        std::thread::sleep(DOUBLE_CLICK_WINDOW);
+                        if TRACKER.lock().expire(id) {
+                            run(&app, actions.left_click, x, y);
+                        }
+                    });
+                }
+            }
+        }
+        MouseButton::Middle => run(app, actions.middle_click, x, y),
+        MouseButton::Right => {}
+    }
+}
+
+/// Run a click action through the same paths as the menu items and hotkeys
+fn run(app: &AppHandle, action: TrayAction, x: f64, y: f64) {
+    let state = app.state::<Arc<AppState>>();
+    match action {
+        TrayAction::TogglePopup => crate::toggle_popup(app, x, y),
+        TrayAction::ToggleMute => {
+            let muted = state.is_muted();
+            if let Err(e) = tray::set_muted(&state, !muted) {
+                warn!("Failed to save mute setting: {}", e);
+            }
+        }
+        TrayAction::StopSpeaking => {
+            state.stop_speaking();
+        }
+        TrayAction::RepeatLast => {
+            if let Err(e) = state.replay_last() {
+                info!("Nothing replayed: {}", e);
+            }
+        }
+        TrayAction::Nothing => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_double_click_claims_pending_single() {
+        let mut tracker = ClickTracker::new();
+        let start = Instant::now();
+        assert_eq!(tracker.left_up(start), LeftUp::Pending(1));
+        assert_eq!(tracker.left_up(start + Duration::from_millis(150)), LeftUp::Double);
+        assert!(!tracker.expire(1));
+
+        // A third click starts over
+        assert_eq!(tracker.left_up(start + Duration::from_millis(250)), LeftUp::Pending(2));
+        assert!(tracker.expire(2));
+    }
+
+    #[test]
+    fn test_slow_clicks_are_singles() {
+        let mut tracker = ClickTracker::new();
+        let start = Instant::now();
+        assert_eq!(tracker.left_up(start), LeftUp::Pending(1));
+        assert!(tracker.expire(1));
+        assert_eq!(tracker.left_up(start + Duration::from_millis(400)), LeftUp::Pending(2));
+        assert!(tracker.expire(2));
+    }
+}
@@ -0,0 +1,34 @@
+/// The HTTP client for the requests this app makes: ntfy streams, entries relayed to a peer and
+/// mirror posts. One reqwest client is shared so connections to a server are kept alive between
+/// requests. It speaks TLS with rustls against the system's certificates, follows redirects and
+/// goes through the system's proxy settings.
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Longest connecting, including the TLS handshake, may take
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The shared client, built on first use; an error when TLS could not be set up
+pub fn client() -> Result<reqwest::Client, String> {
+    static CLIENT: OnceLock<Result<reqwest::Client, String>> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .user_agent(concat!("oracle-voice-tray/", env!("CARGO_PKG_VERSION")))
+            .connect_timeout(CONNECT_TIMEOUT)
+            .build()
+            .map_err(|e| format!("cannot set up HTTP requests: {}", describe(&e)))
+    }).clone()
+}
+
+/// What went wrong with a request. reqwest's own message only names the URL, so the innermost
+/// cause is given instead.
+pub fn describe(error: &reqwest::Error) -> String {
+    if error.is_timeout() {
+        return "the server did not answer in time".to_string();
+    }
+    let mut cause: &dyn std::error::Error = error;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    if error.is_connect() { format!("cannot connect: {}", cause) } else { cause.to_string() }
+}
@@ -1,124 +1,940 @@
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tracing::{info, warn};
 
-/// MQTT Configuration
+use crate::error::{self, VoiceTrayError};
+use crate::github;
+use crate::hotkeys::parse_accelerator;
+use crate::i18n;
+use crate::keychain::{self, KEYRING_MARKER};
+use crate::lock::Lock;
+use crate::logging;
+use crate::mirror;
+use crate::watcher;
+
+mod migrations;
+pub mod overrides;
+mod profiles;
+mod sections;
+mod watcher_config;
+
+use migrations::{adopt_legacy_dir, declared_version, file_version, migrate};
+pub use migrations::{backup_config, upgrade_settings};
+use profiles::{active_profile_in, load_profile_in, profile_path, set_active_profile};
+pub use profiles::{
+    active_profile, create_profile, delete_profile, get_config_path, list_profiles, switch_profile,
+    DEFAULT_PROFILE,
+};
+pub use sections::*;
+pub use watcher_config::*;
+
+/// Serializes reads and read-modify-writes of config.json, so sections saved from different
+/// threads do not overwrite each other and a migration runs once
+static CONFIG_LOCK: Lock<()> = Lock::new(());
+
+/// config.json as the app runs with it: loaded once, then replaced by saves and by reloads
+/// that parse and validate. An invalid edit on disk leaves it as it was.
+static ACTIVE: Lock<Option<AppConfig>> = Lock::new(None);
+
+/// Bumped whenever ACTIVE changes, so long-running threads know to read their section again
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// The active profile's name, read from config.json's active_profile on first use
+static PROFILE: Lock<Option<String>> = Lock::new(None);
+
+/// Set at startup when config.json is from a newer version; see config_warning
+static NEWER_FILE: Lock<Option<String>> = Lock::new(None);
+
+/// Layout of config.json written by this version. Older files are upgraded by MIGRATIONS;
+/// newer ones are read as defaults and never overwritten.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Top-level keys of config.json, in file order
+pub const SECTIONS: [&str; 13] = ["mqtt", "http", "voice", "watcher", "ui", "ntfy", "pipe", "files", "transcripts", "relay", "github", "mirror", "log"];
+
+/// Everything in config.json, one section per subsystem. Every section and field has a
+/// default, so partially written files load.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MqttConfig {
-    pub broker: String,
-    pub port: u16,
-    pub topic_speak: String,
-    pub topic_status: String,
-    #[serde(default)]
-    pub username: Option<String>,
-    #[serde(default)]
-    pub password: Option<String>,
-}
-
-impl Default for MqttConfig {
+#[serde(default)]
+pub struct AppConfig {
+    /// Layout version, CONFIG_VERSION when saved by this build
+    pub schema_version: u32,
+    pub mqtt: MqttConfig,
+    pub http: HttpConfig,
+    pub voice: VoiceConfig,
+    pub watcher: WatcherConfig,
+    pub ui: TrayConfig,
+    pub ntfy: NtfyConfig,
+    pub pipe: PipeConfig,
+    pub files: FilesConfig,
+    pub transcripts: TranscriptConfig,
+    pub relay: RelayConfig,
+    pub github: GithubConfig,
+    pub mirror: MirrorConfig,
+    pub log: LogConfig,
+}
+
+impl Default for AppConfig {
     fn default() -> Self {
         Self {
-            broker: "127.0.0.1".to_string(),
-            port: 1883,
-            topic_speak: "voice/speak".to_string(),
-            topic_status: "voice/status".to_string(),
-            username: None,
-            password: None,
+            schema_version: CONFIG_VERSION,
+            mqtt: MqttConfig::default(),
+            http: HttpConfig::default(),
+            voice: VoiceConfig::default(),
+            watcher: WatcherConfig::default(),
+            ui: TrayConfig::default(),
+            ntfy: NtfyConfig::default(),
+            pipe: PipeConfig::default(),
+            files: FilesConfig::default(),
+            transcripts: TranscriptConfig::default(),
+            relay: RelayConfig::default(),
+            github: GithubConfig::default(),
+            mirror: MirrorConfig::default(),
+            log: LogConfig::default(),
         }
     }
 }
 
-/// Get config file path
-pub fn get_config_path() -> PathBuf {
-    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-    PathBuf::from(home).join(".oracle-voice-tray").join("config.json")
+/// Stands in for a saved MQTT password, ntfy token, relay token, GitHub secret or webhook URL path in get_config; saving it back keeps the stored one
+pub const REDACTED_PASSWORD: &str = "********";
+
+/// The mqtt section as reads return it: a stored password becomes REDACTED_PASSWORD and
+/// has_password says whether there is one
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MqttView {
+    #[serde(flatten)]
+    settings: MqttConfig,
+    has_password: bool,
 }
 
-/// Load MQTT config from file or return defaults
-pub fn load_mqtt_config() -> MqttConfig {
-    let path = get_config_path();
+impl MqttView {
+    pub fn of(config: MqttConfig) -> Self {
+        Self { has_password: config.has_password(), settings: config.redacted() }
+    }
+}
+
+/// Settings as every read path returns them (get_config, GET /config, bundles). Secrets are
+/// write-only: the fields are private, so a view only comes from `of`, which redacts them.
+/// Sending a view back to save_config keeps the stored secrets.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigView {
+    schema_version: u32,
+    mqtt: MqttView,
+    http: HttpConfig,
+    voice: VoiceConfig,
+    watcher: WatcherConfig,
+    ui: TrayConfig,
+    ntfy: NtfyConfig,
+    pipe: PipeConfig,
+    files: FilesConfig,
+    transcripts: TranscriptConfig,
+    relay: RelayConfig,
+    github: GithubConfig,
+    mirror: MirrorConfig,
+    log: LogConfig,
+}
+
+impl ConfigView {
+    pub fn of(config: AppConfig) -> Self {
+        Self {
+            schema_version: config.schema_version,
+            mqtt: MqttView::of(config.mqtt),
+            http: config.http,
+            voice: config.voice,
+            watcher: config.watcher,
+            ui: config.ui,
+            ntfy: config.ntfy.redacted(),
+            pipe: config.pipe,
+            files: config.files,
+            transcripts: config.transcripts,
+            relay: config.relay.redacted(),
+            github: config.github.redacted(),
+            mirror: config.mirror.redacted(),
+            log: config.log,
+        }
+    }
+
+    /// config.json with the running ui and voice sections, for the settings window and GET /config
+    pub fn load(ui: TrayConfig, voice: VoiceConfig) -> Self {
+        Self::of(AppConfig::load_redacted(ui, voice))
+    }
+}
+
+/// What save_config did: the message for the UI, the subsystems that were restarted
+/// ("mqtt", "http", "watcher", "ntfy", "pipe") and the warnings the saved settings still have
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SaveOutcome {
+    pub message: String,
+    pub restarted: Vec<String>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Whether a diagnostic blocks saving
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem with a settings field, e.g. `mqtt.port`, with a message the UI shows next to it.
+/// `field_path` is empty when it is not about one field, e.g. when saving itself failed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub field_path: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(field_path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { field_path: field_path.into(), severity: Severity::Error, message: message.into() }
+    }
+
+    pub fn warning(field_path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { field_path: field_path.into(), severity: Severity::Warning, message: message.into() }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// Longest a broker lookup may hold up validate_config
+const BROKER_LOOKUP_TIMEOUT: Duration = Duration::from_millis(500);
+
+impl AppConfig {
+    /// config.json as the settings window edits it, with the running ui and voice sections and
+    /// the MQTT password, ntfy tokens, relay token, GitHub secret and webhook URLs redacted: the base PUT /config and imports apply
+    /// changes to. Reads return a ConfigView instead.
+    pub fn load_redacted(ui: TrayConfig, voice: VoiceConfig) -> Self {
+        let config = Self { ui, voice, ..load_config() };
+        Self {
+            mqtt: config.mqtt.redacted(),
+            ntfy: config.ntfy.redacted(),
+            relay: config.relay.redacted(),
+            github: config.github.redacted(),
+            mirror: config.mirror.redacted(),
+            ..config
+        }
+    }
+
+    /// Put the stored password back where the form returned the redacted one unchanged
+    pub fn restore_password(&mut self, stored: &MqttConfig) {
+        self.mqtt.restore_password(stored);
+    }
+
+    /// Errors that would stop the settings from working, then warnings about settings that work
+    /// but probably not as meant. The settings can be saved when there are no errors. Checks
+    /// only the values themselves; see validate_config for the checks that look outside.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut errors = Vec::new();
+        let mut check = |ok: bool, field: &str, message: &str| {
+            if !ok {
+                errors.push(Diagnostic::error(field, message));
+            }
+        };
+        check(!self.mqtt.broker.trim().is_empty(), "mqtt.broker", "Broker is required");
+        check(self.mqtt.port != 0, "mqtt.port", "Port must be 1-65535");
+        check(!self.mqtt.topic_speak.trim().is_empty(), "mqtt.topic_speak", "Speak topic is required");
+        check(
+            valid_subscription(&self.mqtt.topic_speak),
+            "mqtt.topic_speak",
+            "Speak topic can use + only as a whole level and # only as the last level",
+        );
+        check(
+            !self.mqtt.topic_status.trim().is_empty() && !self.mqtt.topic_status.contains(['+', '#']),
+            "mqtt.topic_status",
+            "Status topic is required and cannot contain + or #",
+        );
+        check(self.http.port != 0, "http.port", "Port must be 1-65535");
+        check(
+            self.http.bind.trim().parse::<IpAddr>().is_ok(),
+            "http.bind",
+            "Bind address must be an IP address such as 127.0.0.1 or 0.0.0.0",
+        );
+        check(!self.voice.default_voice.trim().is_empty(), "voice.default_voice", "Default voice is required");
+        check((80..=500).contains(&self.voice.default_rate), "voice.default_rate", "Rate must be 80-500 words per minute");
+        check(!self.voice.test_voice_text.trim().is_empty(), "voice.test_voice_text", "Test text is required");
+        check(i18n::valid_language(&self.voice.language), "voice.language", "Language must be a code such as en, th or es");
+        check(
+            self.voice.favorite_voices.iter().all(|v| !v.trim().is_empty()),
+            "voice.favorite_voices",
+            "Favorite voice names must not be empty",
+        );
+        check(self.ui.copy_format.contains("{text}"), "ui.copy_format", "Copy format must include {text}");
+        check(
+            ["auto", "light", "dark"].contains(&self.ui.icon_theme.as_str()),
+            "ui.icon_theme",
+            "Icon theme must be auto, light or dark",
+        );
+        check(
+            self.ui.snooze_minutes.iter().all(|m| (1..=1440).contains(m)),
+            "ui.snooze_minutes",
+            "Snooze lengths must be 1-1440 minutes",
+        );
+        check(
+            self.files.allowed_dirs.iter().all(|dir| dir.trim().starts_with('~') || Path::new(dir.trim()).is_absolute()),
+            "files.allowed_dirs",
+            "Folders must be absolute paths or start with ~",
+        );
+        check((1..=1024 * 1024).contains(&self.files.max_bytes), "files.max_bytes", "Largest file must be 1 byte to 1 MB");
+        check(
+            self.transcripts.dir.as_deref().is_none_or(|dir| !dir.trim().is_empty()),
+            "transcripts.dir",
+            "Folder must not be blank; leave it out for the default",
+        );
+        check(self.transcripts.keep_days <= 3650, "transcripts.keep_days", "Keep 0-3650 days; 0 keeps every file");
+        let mut parsed = Vec::new();
+        let hotkeys = [
+            ("ui.stop_hotkey", &self.ui.stop_hotkey),
+            ("ui.mute_hotkey", &self.ui.mute_hotkey),
+            ("ui.clipboard_hotkey", &self.ui.clipboard_hotkey),
+        ];
+        for (field, binding) in hotkeys {
+            if binding.trim().is_empty() {
+                continue;
+            }
+            match parse_accelerator(binding) {
+                Ok(accelerator) if parsed.contains(&accelerator) => errors.push(Diagnostic::error(field, "Already used by another hotkey")),
+                Ok(accelerator) => parsed.push(accelerator),
+                Err(e) => errors.push(Diagnostic::error(field, format!("Invalid hotkey: {}", e))),
+            }
+        }
+        errors.extend(self.watcher.validate());
+        for (i, topic) in self.ntfy.topics.iter().enumerate() {
+            let server = tauri::Url::parse(&topic.server).ok().filter(|url| matches!(url.scheme(), "http" | "https") && url.has_host());
+            if server.is_none() {
+                errors.push(Diagnostic::error(format!("ntfy.topics[{}].server", i), "Server must be an http:// or https:// URL"));
+            }
+            let field = format!("ntfy.topics[{}].topic", i);
+            if !(1..=64).contains(&topic.topic.len()) || !topic.topic.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_')) {
+                errors.push(Diagnostic::error(field, "Topic must be 1-64 letters, digits, - or _"));
+            } else if self.ntfy.topics[..i].iter().any(|t| t.server == topic.server && t.topic == topic.topic) {
+                errors.push(Diagnostic::error(field, "Already subscribed to this topic"));
+            }
+        }
+        if self.relay.enabled || !self.relay.url.trim().is_empty() {
+            let peer = tauri::Url::parse(self.relay.url.trim()).ok().filter(|url| matches!(url.scheme(), "http" | "https") && url.has_host());
+            match peer {
+                None => errors.push(Diagnostic::error("relay.url", "Peer must be an http:// or https:// URL")),
+                Some(url) if is_this_app(&url, self.http.port) => {
+                    errors.push(Diagnostic::error("relay.url", "Peer must be another Voice Tray, not this one"));
+                }
+                Some(_) => {}
+            }
+        }
+        if self.github.enabled && self.github.secret.as_deref().is_none_or(|s| s.trim().is_empty()) {
+            errors.push(Diagnostic::error("github.secret", "Secret is required to check deliveries"));
+        }
+        if let Some(event) = self.github.events.iter().find(|e| !github::EVENTS.contains(&e.as_str())) {
+            errors.push(Diagnostic::error("github.events", format!("Unknown event \"{}\"; use {}", event, github::EVENTS.join(", "))));
+        }
+        for (i, destination) in self.mirror.destinations.iter().enumerate() {
+            let url = tauri::Url::parse(destination.url.trim()).ok().filter(|url| matches!(url.scheme(), "http" | "https") && url.has_host());
+            if url.is_none() {
+                errors.push(Diagnostic::error(format!("mirror.destinations[{}].url", i), "Webhook must be an http:// or https:// URL"));
+            } else if !destination.url.ends_with(REDACTED_PASSWORD) && self.mirror.destinations[..i].iter().any(|d| d.url.trim() == destination.url.trim()) {
+                errors.push(Diagnostic::error(format!("mirror.destinations[{}].url", i), "Already mirrored to this webhook"));
+            }
+            if !mirror::FORMATS.contains(&destination.format.as_str()) {
+                errors.push(Diagnostic::error(format!("mirror.destinations[{}].format", i), format!("Format must be one of {}", mirror::FORMATS.join(", "))));
+            }
+        }
+        if let Err(e) = logging::parse_filter(&self.log.level) {
+            errors.push(Diagnostic::error("log.level", e));
+        }
+
+        let mut warnings = Vec::new();
+        if (1..1024).contains(&self.http.port) {
+            warnings.push(Diagnostic::warning("http.port", "Ports below 1024 need administrator rights on macOS and Linux"));
+        }
+        if !self.http.bind_ip().is_loopback() {
+            warnings.push(Diagnostic::warning("http.bind", "The HTTP API has no token; anyone on the network can queue messages"));
+        }
+        let password_set = self.mqtt.has_password();
+        if password_set && !self.mqtt.use_keyring {
+            warnings.push(Diagnostic::warning("mqtt.use_keyring", "The broker password is saved in plain text in config.json"));
+        }
+        if self.mqtt.enabled && self.mqtt.username.is_none() && password_set {
+            warnings.push(Diagnostic::warning("mqtt.username", "A password without a username is not sent to the broker"));
+        }
+        errors.extend(warnings);
+        errors
+    }
+}
+
+/// Folder under the platform's config directory
+const APP_DIR: &str = "oracle-voice-tray";
+
+/// Folder in the home directory that held the config before it moved to the platform's
+/// config directory
+const LEGACY_DIR: &str = ".oracle-voice-tray";
+
+/// ~/Library/Application Support on macOS, %APPDATA% on Windows, $XDG_CONFIG_HOME or ~/.config
+/// on Linux. Without one the legacy home folder is used, and without a home directory the
+/// executable's folder, so the config never depends on the launch directory.
+fn resolve_config_dir(platform_config: Option<PathBuf>, home: Option<PathBuf>, exe_dir: Option<PathBuf>) -> PathBuf {
+    platform_config.map(|dir| dir.join(APP_DIR))
+        .or_else(|| home.map(|dir| dir.join(LEGACY_DIR)))
+        .or(exe_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Directory holding config.json and the profiles folder
+pub fn config_dir() -> PathBuf {
+    let exe_dir = std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf));
+    resolve_config_dir(dirs::config_dir(), dirs::home_dir(), exe_dir)
+}
+
+fn legacy_config_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(LEGACY_DIR))
+}
+
+pub const CONFIG_FILE: &str = "config.json";
+
+/// Load a JSON config file or return defaults
+pub(crate) fn load_json<T: DeserializeOwned + Default>(path: &Path) -> T {
     if path.exists() {
-        match fs::read_to_string(&path) {
+        match fs::read_to_string(path) {
             Ok(content) => {
                 match serde_json::from_str(&content) {
                     Ok(config) => return config,
-                    Err(e) => eprintln!("Failed to parse config: {}", e),
+                    Err(e) => warn!("Failed to parse config {}: {}", path.display(), e),
                 }
             }
-            Err(e) => eprintln!("Failed to read config: {}", e),
+            Err(e) => warn!("Failed to read config {}: {}", path.display(), e),
         }
     }
-    MqttConfig::default()
+    T::default()
 }
 
-/// Save MQTT config to file
-pub fn save_mqtt_config_to_file(config: &MqttConfig) -> Result<(), String> {
-    let path = get_config_path();
+/// Write a config struct as pretty JSON, creating the config dir if needed
+pub(crate) fn save_json<T: Serialize>(path: &Path, config: &T) -> error::Result<()> {
     if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(config).map_err(|e| VoiceTrayError::Config(e.to_string()))?;
+    // Written next to the file and renamed over it, so a crash never leaves half a file
+    // for load_json to replace with defaults
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, json)?;
+    fs::rename(&tmp, path).map_err(|e| {
+        // Best effort: the rename error is the one worth returning
+        let _ = fs::remove_file(&tmp);
+        e.into()
+    })
+}
+
+/// config.json from the platform's config directory, adopting the legacy folder first
+fn load_from_disk() -> AppConfig {
+    let dir = config_dir();
+    if let Some(legacy) = legacy_config_dir() {
+        adopt_legacy_dir(&dir, &legacy);
+    }
+    let profile = active_profile_in(&dir);
+    let config = load_profile_in(&dir, &profile);
+    set_active_profile(&profile);
+    let warning = newer_file_error(&profile_path(&dir, &profile));
+    if let Some(warning) = &warning {
+        warn!("{}, running with defaults", warning);
+    }
+    *NEWER_FILE.lock() = warning;
+    config
+}
+
+/// Loads config.json from `dir`, upgrading older versions. A file from a newer version gives
+/// the defaults.
+fn load_config_in(dir: &Path) -> AppConfig {
+    let _guard = CONFIG_LOCK.lock();
+    let path = dir.join(CONFIG_FILE);
+    let value: Option<serde_json::Value> = fs::read_to_string(&path).ok()
+        .and_then(|content| serde_json::from_str(&content).ok());
+    let version = file_version(dir, value.as_ref());
+    match value {
+        _ if version > CONFIG_VERSION => AppConfig::default(),
+        _ if version == CONFIG_VERSION => load_json(&path),
+        Some(value) => migrate(dir, value, version),
+        None => migrate(dir, serde_json::json!({}), version),
+    }
+}
+
+/// Why the config file at `path` must not be overwritten: it is from a newer version
+fn newer_file_error(path: &Path) -> Option<String> {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    declared_version(path)
+        .filter(|version| *version > CONFIG_VERSION)
+        .map(|version| format!("{} is from a newer version of the app (schema {}, this one reads {})", name, version, CONFIG_VERSION))
+}
+
+/// Set when config.json is from a newer version: settings are the defaults and are not saved
+pub fn config_warning() -> Option<String> {
+    NEWER_FILE.lock().clone()
+}
+
+fn update_config_in(path: &Path, current: AppConfig, change: impl FnOnce(&mut AppConfig)) -> Result<AppConfig, String> {
+    if let Some(e) = newer_file_error(path) {
+        return Err(format!("{}; not overwriting it", e));
     }
-    let json = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
-    fs::write(&path, json).map_err(|e| e.to_string())?;
-    Ok(())
+    let mut config = current.clone();
+    change(&mut config);
+    let mut config = overrides::unapply(config, &current);
+    config.schema_version = CONFIG_VERSION;
+    save_json(path, &config)?;
+    Ok(config)
+}
+
+/// Strict read for reloads: where load_json falls back to defaults, a file that does not
+/// parse or validate is an error
+fn read_config(path: &Path) -> Result<AppConfig, String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let value: serde_json::Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    match file_version(dir, Some(&value)) {
+        version if version > CONFIG_VERSION => {
+            return Err(format!("schema_version {} is from a newer version of the app (this one reads {})", version, CONFIG_VERSION));
+        }
+        version if version < CONFIG_VERSION => {
+            return Err(format!("schema_version {} is an older layout; restart the app to upgrade it", version));
+        }
+        _ => {}
+    }
+    let config: AppConfig = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    let errors: Vec<Diagnostic> = config.validate().into_iter().filter(Diagnostic::is_error).collect();
+    match errors.first() {
+        None => Ok(config),
+        Some(first) if errors.len() == 1 => Err(format!("{}: {}", first.field_path, first.message)),
+        Some(first) => Err(format!("{}: {} (and {} more)", first.field_path, first.message, errors.len() - 1)),
+    }
+}
+
+/// Strict read of the active profile's file, for the doctor. None when there is no file yet
+/// and the defaults are in use.
+pub fn check_active_file() -> Result<Option<(PathBuf, AppConfig)>, String> {
+    let dir = config_dir();
+    let path = profile_path(&dir, &active_profile_in(&dir));
+    if !path.exists() {
+        return Ok(None);
+    }
+    match read_config(&path) {
+        Ok(config) => Ok(Some((path, config))),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// http.port as the running app would use it: the active profile's file with its override,
+/// read without migrating or saving anything. The default when the file is missing or does
+/// not read. For ovt, which runs next to the app.
+pub fn saved_http_port() -> u16 {
+    let config = check_active_file().ok().flatten().map(|(_, config)| config).unwrap_or_default();
+    overrides::apply(config).http.port
+}
+
+/// Whether `url` is this machine's own API on `port`, which relaying to would only loop
+fn is_this_app(url: &tauri::Url, port: u16) -> bool {
+    let host = url.host_str().unwrap_or_default().trim_matches(['[', ']']);
+    let local = host.eq_ignore_ascii_case("localhost") || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback());
+    local && url.port_or_known_default() == Some(port)
+}
+
+/// An MQTT subscription filter: `+` only as a whole level, `#` only as the whole last level
+fn valid_subscription(topic: &str) -> bool {
+    let levels: Vec<&str> = topic.split('/').collect();
+    levels.iter().enumerate().all(|(i, level)| match *level {
+        "+" => true,
+        "#" => i == levels.len() - 1,
+        level => !level.contains(['+', '#']),
+    })
+}
+
+/// Hints about what the settings point at, for validate_config: an enabled watcher profile
+/// whose directory is missing, and a broker name that does not resolve. Warnings only, and the
+/// broker lookup gives up after BROKER_LOOKUP_TIMEOUT.
+pub fn environment_hints(config: &AppConfig, home: &Path) -> Vec<Diagnostic> {
+    let mut hints = Vec::new();
+    if config.watcher.enabled {
+        for profile in config.watcher.active_profiles() {
+            let root = watcher::glob_root(&watcher::expand_home(&profile.path, home));
+            if root.is_dir() {
+                continue;
+            }
+            let field = match config.watcher.profiles.iter().position(|p| p.name == profile.name) {
+                Some(i) => format!("watcher.profiles.{}.path", i),
+                None => "watcher.enabled".to_string(),
+            };
+            hints.push(Diagnostic::warning(field, format!("{} does not exist yet; {} is not watched until it does", root.display(), profile.name)));
+        }
+    }
+    let broker = config.mqtt.broker.trim();
+    if config.mqtt.enabled && !broker.is_empty() && broker.parse::<std::net::IpAddr>().is_err() {
+        match resolve_host(broker, config.mqtt.port) {
+            Some(Ok(())) => {}
+            Some(Err(e)) => hints.push(Diagnostic::warning("mqtt.broker", format!("{} does not resolve: {}", broker, e))),
+            None => hints.push(Diagnostic::warning("mqtt.broker", format!("Looking up {} is taking long; the broker may be unreachable", broker))),
+        }
+    }
+    hints
+}
+
+/// Look `host` up on a helper thread; None when that takes longer than BROKER_LOOKUP_TIMEOUT
+fn resolve_host(host: &str, port: u16) -> Option<Result<(), String>> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let host = host.to_string();
+    std::thread::spawn(move || {
+        let resolved = (host.as_str(), port).to_socket_addrs().map(|_| ()).map_err(|e| e.to_string());
+        // Nobody listens after BROKER_LOOKUP_TIMEOUT, and then the answer is not needed
+        let _ = tx.send(resolved);
+    });
+    rx.recv_timeout(BROKER_LOOKUP_TIMEOUT).ok()
+}
+
+/// `base` with the fields present in `patch` replaced, object by object, for PUT /config:
+/// fields a client leaves out keep their current values
+pub fn merge_settings(base: &AppConfig, patch: serde_json::Value) -> Result<AppConfig, String> {
+    fn merge(target: &mut serde_json::Value, patch: serde_json::Value) {
+        match (target, patch) {
+            (serde_json::Value::Object(target), serde_json::Value::Object(patch)) => {
+                for (key, value) in patch {
+                    merge(target.entry(key).or_insert(serde_json::Value::Null), value);
+                }
+            }
+            (target, patch) => *target = patch,
+        }
+    }
+    if !patch.is_object() {
+        return Err("Expected a JSON object of settings sections".to_string());
+    }
+    let mut value = serde_json::to_value(base).map_err(|e| e.to_string())?;
+    merge(&mut value, patch);
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+/// Everything validate_config reports: the checks of AppConfig::validate, then environment_hints
+pub fn validate_config(config: &AppConfig) -> Vec<Diagnostic> {
+    let mut diagnostics = config.validate();
+    if let Some(home) = dirs::home_dir() {
+        diagnostics.extend(environment_hints(config, &home));
+    }
+    diagnostics
+}
+
+/// Sections that differ between two configs
+pub fn changed_sections(previous: &AppConfig, config: &AppConfig) -> Vec<&'static str> {
+    let (Ok(serde_json::Value::Object(before)), Ok(serde_json::Value::Object(after))) =
+        (serde_json::to_value(previous), serde_json::to_value(config))
+    else {
+        return Vec::new();
+    };
+    SECTIONS.into_iter().filter(|section| before.get(*section) != after.get(*section)).collect()
+}
+
+/// Load config.json, migrating files from older versions first; defaults where it is missing.
+/// Read from disk once, then kept in memory. ORACLE_VOICE_* overrides are laid over it.
+pub fn load_config() -> AppConfig {
+    overrides::apply(load_saved_config())
+}
+
+/// config.json as saved, without the environment overrides
+pub fn load_saved_config() -> AppConfig {
+    ACTIVE.lock().get_or_insert_with(load_from_disk).clone()
+}
+
+/// Change some of the active profile's settings and save them; sections saved from elsewhere
+/// meanwhile are kept.
+/// Values that are only there because of an environment override are not written.
+pub fn update_config(change: impl FnOnce(&mut AppConfig)) -> Result<AppConfig, String> {
+    let mut active = ACTIVE.lock();
+    let current = active.clone().unwrap_or_else(load_from_disk);
+    let config = update_config_in(&get_config_path(), current, change)?;
+    *active = Some(config.clone());
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    Ok(config)
+}
+
+/// Read the active profile's file again after a config file changed on disk; an edit of
+/// config.json's active_profile switches profiles. Returns the previous and the new config,
+/// or None when nothing changed, as after one of our own saves. An edit that does not parse or
+/// validate is returned as an error and the active config stays. Both configs carry the
+/// environment overrides, so an edit they cover changes nothing.
+pub fn reload_config() -> Result<Option<(AppConfig, AppConfig)>, String> {
+    let mut active = ACTIVE.lock();
+    let dir = config_dir();
+    let profile = active_profile_in(&dir);
+    let config = read_config(&profile_path(&dir, &profile))?;
+    let previous = active.clone().unwrap_or_default();
+    let switched = profile != active_profile();
+    if !switched && changed_sections(&previous, &config).is_empty() {
+        return Ok(None);
+    }
+    if switched {
+        info!("Switched to profile \"{}\"", profile);
+        set_active_profile(&profile);
+    }
+    *active = Some(config.clone());
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    Ok(Some((overrides::apply(previous), overrides::apply(config))))
+}
+
+/// Changes whenever the active config does (see GENERATION)
+pub fn config_generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Load the mqtt section, with the password read from the keychain when it is kept there
+pub fn load_mqtt_config() -> MqttConfig {
+    let config = load_config().mqtt;
+    MqttConfig { password: keychain::resolve(config.password.clone()), ..config }
+}
+
+/// Save the mqtt section; with use_keyring the password goes to the keychain instead
+pub fn save_mqtt_config_to_file(config: &MqttConfig) -> Result<(), String> {
+    update_config(|c| {
+        let was_in_keyring = c.mqtt.password.as_deref() == Some(KEYRING_MARKER);
+        // An overridden password must not reach the keychain either
+        let edited = AppConfig { mqtt: config.clone(), ..c.clone() };
+        let config = overrides::unapply(edited, c).mqtt;
+        let password = keychain::store(config.password.clone(), config.use_keyring, was_in_keyring);
+        c.mqtt = MqttConfig { password, ..config };
+    })
+    .map(|_| ())
+}
+
+/// Load the watcher section
+pub fn load_watcher_config() -> WatcherConfig {
+    load_config().watcher
+}
+
+/// Save the watcher section
+pub fn save_watcher_config_to_file(config: &WatcherConfig) -> Result<(), String> {
+    update_config(|c| c.watcher = config.clone()).map(|_| ())
+}
+
+/// Save the ui section
+pub fn save_tray_config_to_file(config: &TrayConfig) -> Result<(), String> {
+    update_config(|c| c.ui = config.clone()).map(|_| ())
+}
+
+/// Save the voice section
+pub fn save_voice_config_to_file(config: &VoiceConfig) -> Result<(), String> {
+    update_config(|c| c.voice = config.clone()).map(|_| ())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::http::VOICE_SERVER_PORT;
     use tempfile::TempDir;
 
     #[test]
-    fn test_mqtt_config_default() {
-        let config = MqttConfig::default();
-        assert_eq!(config.broker, "127.0.0.1");
-        assert_eq!(config.port, 1883);
-        assert_eq!(config.topic_speak, "voice/speak");
-        assert_eq!(config.topic_status, "voice/status");
+    fn test_settings_validation() {
+        assert_eq!(AppConfig::default().validate(), vec![]);
+        let mut settings = AppConfig::default();
+        settings.mqtt.port = 0;
+        settings.mqtt.topic_status = "voice/#".to_string();
+        settings.http.port = 0;
+        settings.http.bind = "localhost".to_string();
+        settings.voice.default_rate = 20;
+        settings.voice.favorite_voices = vec!["Daniel".to_string(), " ".to_string()];
+        settings.ui.copy_format = "{agent}: {message}".to_string();
+        settings.ui.snooze_minutes = vec![30, 0];
+        settings.ui.mute_hotkey = "Shift+Ctrl+S".to_string();
+        settings.ui.stop_hotkey = "Ctrl+Shift+S".to_string();
+        let fields: Vec<String> = settings.validate().into_iter().map(|e| e.field_path).collect();
+        assert_eq!(fields, vec![
+            "mqtt.port", "mqtt.topic_status", "http.port", "http.bind", "voice.default_rate", "voice.favorite_voices",
+            "ui.copy_format", "ui.snooze_minutes", "ui.mute_hotkey",
+        ]);
+        settings.ui.mute_hotkey = "Ctrl+Shift+Pause".to_string();
+        let errors = settings.validate();
+        assert_eq!(errors.last().map(|e| e.message.as_str()), Some("Invalid hotkey: unknown key \"Pause\""));
+
+        let settings = AppConfig { watcher: serde_json::from_str(r#"{
+            "exclude": ["oss-[*"],
+            "quiet_hours": {"start": "22:00", "end": "7am"},
+            "profiles": [{"name": "codex"}, {"name": "claude", "enabled": false}]
+        }"#).expect("parse"), ..AppConfig::default() };
+        let fields: Vec<String> = settings.validate().into_iter().map(|e| e.field_path).collect();
+        assert_eq!(fields, vec!["watcher.exclude", "watcher.quiet_hours", "watcher.profiles.0.path"]);
     }
 
     #[test]
-    fn test_mqtt_config_serialization() {
-        let config = MqttConfig {
-            broker: "mqtt.example.com".to_string(),
-            port: 8883,
-            topic_speak: "custom/speak".to_string(),
-            topic_status: "custom/status".to_string(),
-            username: Some("user".to_string()),
-            password: Some("pass".to_string()),
-        };
+    fn test_diagnostics() {
+        for (topic, valid) in [("voice/speak", true), ("agents/+/speak", true), ("voice/#", true), ("#", true), ("voice/#/x", false), ("voice#", false), ("a+/b", false)] {
+            assert_eq!(valid_subscription(topic), valid, "{}", topic);
+        }
+        let mut settings = AppConfig::default();
+        settings.http.port = 80;
+        settings.http.bind = "0.0.0.0".to_string();
+        settings.mqtt.password = Some("hunter2".to_string());
+        settings.mqtt.use_keyring = false;
+        settings.mqtt.topic_speak = "voice/#/speak".to_string();
+        let diagnostics: Vec<(String, Severity)> = settings.validate().into_iter().map(|d| (d.field_path, d.severity)).collect();
+        assert_eq!(diagnostics, vec![
+            ("mqtt.topic_speak".to_string(), Severity::Error),
+            ("http.port".to_string(), Severity::Warning),
+            ("http.bind".to_string(), Severity::Warning),
+            ("mqtt.use_keyring".to_string(), Severity::Warning),
+            ("mqtt.username".to_string(), Severity::Warning),
+        ]);
+        let json = serde_json::to_value(&settings.validate()[1]).expect("serialize");
+        assert_eq!(json["severity"], "warning");
 
-        let json = serde_json::to_string(&config).expect("serialize");
-        let parsed: MqttConfig = serde_json::from_str(&json).expect("deserialize");
+        // A missing log directory is a hint; an IP broker is not looked up
+        let temp_dir = TempDir::new().expect("create temp dir");
+        let mut settings = AppConfig::default();
+        settings.watcher.profiles = vec![WatcherProfile { name: "codex".to_string(), path: "~/.codex/sessions/**/*.jsonl".to_string(), ..WatcherProfile::default() }];
+        let fields: Vec<String> = environment_hints(&settings, temp_dir.path()).into_iter().map(|d| d.field_path).collect();
+        assert_eq!(fields, vec!["watcher.enabled", "watcher.profiles.0.path"]);
+        fs::create_dir_all(temp_dir.path().join(".codex/sessions")).expect("create dir");
+        fs::create_dir_all(temp_dir.path().join(".claude/projects")).expect("create dir");
+        assert!(environment_hints(&settings, temp_dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_merge_settings() {
+        let mut base = AppConfig::default();
+        base.mqtt.broker = "pi.local".to_string();
+        let merged = merge_settings(&base, serde_json::json!({"voice": {"default_rate": 180}, "mqtt": {"port": 8883}})).expect("merge");
+        assert_eq!(merged.voice.default_rate, 180);
+        assert_eq!((merged.mqtt.broker.as_str(), merged.mqtt.port), ("pi.local", 8883), "fields left out keep their values");
+        assert_eq!(merged.voice.default_voice, base.voice.default_voice);
+        assert!(merge_settings(&base, serde_json::json!({"http": {"port": "high"}})).is_err());
+        assert!(merge_settings(&base, serde_json::json!([1])).is_err());
+    }
+
+    #[test]
+    fn test_password_redaction() {
+        let stored = MqttConfig { password: Some("hunter2".to_string()), ..MqttConfig::default() };
+        let mut settings = AppConfig::default();
+        settings.mqtt.password = Some(REDACTED_PASSWORD.to_string());
+        settings.restore_password(&stored);
+        assert_eq!(settings.mqtt, stored, "unchanged form saves the stored password");
+
+        settings.mqtt.password = Some("changed".to_string());
+        settings.restore_password(&stored);
+        assert_eq!(settings.mqtt.password.as_deref(), Some("changed"));
 
-        assert_eq!(parsed.broker, config.broker);
-        assert_eq!(parsed.port, config.port);
-        assert_eq!(parsed.topic_speak, config.topic_speak);
-        assert_eq!(parsed.topic_status, config.topic_status);
+        settings.mqtt.password = Some(String::new());
+        settings.restore_password(&stored);
+        assert_eq!(settings.mqtt.password, None, "an empty password clears the stored one");
     }
 
     #[test]
-    fn test_config_persistence() {
+    fn test_config_view_never_holds_secrets() {
+        let mut config = AppConfig::default();
+        config.mqtt.password = Some("hunter2".to_string());
+        let json = serde_json::to_string(&ConfigView::of(config.clone())).expect("serialize");
+        assert!(!json.contains("hunter2"));
+        let mqtt = &serde_json::from_str::<serde_json::Value>(&json).expect("parse")["mqtt"];
+        assert_eq!((mqtt["password"].as_str(), mqtt["has_password"].as_bool()), (Some(REDACTED_PASSWORD), Some(true)));
+        let popup = serde_json::to_string(&MqttView::of(config.mqtt.clone())).expect("serialize");
+        assert!(!popup.contains("hunter2") && popup.contains("\"has_password\":true"));
+
+        // What a client reads, sent back unchanged, saves the stored password
+        let mut returned: AppConfig = serde_json::from_str(&json).expect("view reads as settings");
+        returned.restore_password(&config.mqtt);
+        assert_eq!(returned.mqtt, config.mqtt);
+
+        let json = serde_json::to_string(&ConfigView::of(AppConfig::default())).expect("serialize");
+        assert!(json.contains("\"password\":null,") && json.contains("\"has_password\":false"));
+    }
+
+    #[test]
+    fn test_newer_file_is_not_overwritten() {
         let temp_dir = TempDir::new().expect("create temp dir");
-        let config_dir = temp_dir.path().join(".oracle-voice-tray");
-        let config_path = config_dir.join("config.json");
-
-        fs::create_dir_all(&config_dir).expect("create dir");
-
-        let config = MqttConfig {
-            broker: "test.broker.com".to_string(),
-            port: 9999,
-            topic_speak: "test/speak".to_string(),
-            topic_status: "test/status".to_string(),
-            username: None,
-            password: None,
-        };
-        let json = serde_json::to_string_pretty(&config).expect("serialize");
-        fs::write(&config_path, &json).expect("write config");
+        let dir = temp_dir.path();
+        let newer = include_str!("../tests/fixtures/config/newer.json");
+        let path = dir.join("config.json");
+        fs::write(&path, newer).expect("write config");
+
+        let config = load_config_in(dir);
+        assert_eq!(config.mqtt.broker, MqttConfig::default().broker, "runs with defaults");
+        assert!(newer_file_error(&path).expect("warning").contains("schema 99"));
+        let saved = update_config_in(&path, config, |c| c.ui.muted = true);
+        assert!(saved.unwrap_err().ends_with("not overwriting it"));
+        assert_eq!(fs::read_to_string(&path).expect("read"), newer);
+        assert!(read_config(&path).unwrap_err().contains("newer version"), "reload refuses it too");
+    }
+
+    #[test]
+    fn test_config_dir_resolution() {
+        let roaming = PathBuf::from(r"C:\Users\nat\AppData\Roaming");
+        // Windows: HOME is usually unset, and the config still lands in %APPDATA%
+        assert_eq!(resolve_config_dir(Some(roaming.clone()), None, None), roaming.join("oracle-voice-tray"));
+        assert_eq!(
+            resolve_config_dir(None, Some(PathBuf::from("/home/nat")), None),
+            PathBuf::from("/home/nat/.oracle-voice-tray"),
+        );
+        let exe_dir = PathBuf::from(r"C:\Program Files\Oracle Voice Tray");
+        assert_eq!(resolve_config_dir(None, None, Some(exe_dir.clone())), exe_dir);
+    }
+
+    #[test]
+    fn test_partial_config_file() {
+        let temp_dir = TempDir::new().expect("create temp dir");
+        let dir = temp_dir.path();
+        assert_eq!(load_config_in(dir).mqtt, MqttConfig::default(), "missing file");
+
+        fs::write(dir.join("config.json"), r#"{"mqtt": {"broker": "pi.local"}, "ui": {"muted": true}}"#).expect("write");
+        let config = load_config_in(dir);
+        assert_eq!(config.mqtt.broker, "pi.local");
+        assert_eq!(config.mqtt.port, 1883);
+        assert!(config.ui.muted);
+        assert_eq!(config.http.port, VOICE_SERVER_PORT);
+        assert_eq!(config.voice, VoiceConfig::default());
+        assert_eq!(fs::read_dir(dir).expect("list").count(), 1, "sectioned files are not migrated or backed up");
+    }
 
-        let content = fs::read_to_string(&config_path).expect("read");
-        let loaded: MqttConfig = serde_json::from_str(&content).expect("parse");
+    #[test]
+    fn test_config_round_trip() {
+        let temp_dir = TempDir::new().expect("create temp dir");
+        let dir = temp_dir.path();
+        let path = dir.join("config.json");
+        update_config_in(&path, load_config_in(dir), |c| c.voice.default_voice = "Karen".to_string()).expect("save voice");
+        update_config_in(&path, load_config_in(dir), |c| c.mqtt.username = Some("tray".to_string())).expect("save mqtt");
+
+        let config = load_config_in(dir);
+        assert_eq!(config.voice.default_voice, "Karen", "other sections survive a section save");
+        assert_eq!(config.mqtt.username.as_deref(), Some("tray"));
+        let saved = serde_json::to_value(&config).expect("serialize");
+        let reloaded = serde_json::to_value(load_config_in(dir)).expect("serialize");
+        assert_eq!(saved, reloaded);
+    }
+
+    #[test]
+    fn test_reload_rejects_invalid_edits() {
+        let temp_dir = TempDir::new().expect("create temp dir");
+        let path = temp_dir.path().join("config.json");
+        fs::write(&path, r#"{"mqtt": {"broker": "pi.local"#).expect("write");
+        assert!(read_config(&path).is_err(), "half-saved file");
+        fs::write(&path, r#"{"voice": {"default_rate": 5}, "http": {"port": 0}}"#).expect("write");
+        assert_eq!(read_config(&path).unwrap_err(), "http.port: Port must be 1-65535 (and 1 more)");
+
+        fs::write(&path, r#"{"voice": {"default_voice": "Karen"}, "http": {"port": 8080}}"#).expect("write");
+        let config = read_config(&path).expect("valid edit");
+        assert_eq!(changed_sections(&AppConfig::default(), &config), vec!["http", "voice"]);
+        assert!(changed_sections(&config, &config.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_save_json_replaces_file() {
+        let temp_dir = TempDir::new().expect("create temp dir");
+        let path = temp_dir.path().join("nested").join("tray.json");
+        save_json(&path, &VoiceConfig::default()).expect("first save");
+        let config = VoiceConfig { default_voice: "Daniel".to_string(), ..VoiceConfig::default() };
+        save_json(&path, &config).expect("second save");
 
-        assert_eq!(loaded.broker, "test.broker.com");
-        assert_eq!(loaded.port, 9999);
+        let loaded: VoiceConfig = load_json(&path);
+        assert_eq!(loaded.default_voice, "Daniel");
+        let names: Vec<_> = fs::read_dir(path.parent().expect("parent")).expect("list")
+            .map(|e| e.expect("entry").file_name())
+            .collect();
+        assert_eq!(names, vec!["tray.json"], "no temp file left behind");
     }
 }
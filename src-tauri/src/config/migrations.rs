@@ -0,0 +1,232 @@
+/// Upgrades of config.json from older layouts, one MIGRATIONS step per schema version, with
+/// the timestamped backups taken before a file is rewritten, and the one-time adoption of
+/// the legacy ~/.oracle-voice-tray folder.
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use super::{get_config_path, load_json, save_json, AppConfig, CONFIG_FILE, CONFIG_VERSION};
+
+/// MIGRATIONS[n] upgrades a version n file to version n + 1. Each step gets the config dir, for
+/// settings older versions kept in other files; None for settings that did not come from it.
+const MIGRATIONS: [fn(Option<&Path>, serde_json::Value) -> serde_json::Value; CONFIG_VERSION as usize] = [migrate_v0];
+
+/// Files from before config.json had sections, folded into it by migrate
+const LEGACY_TRAY_FILE: &str = "tray.json";
+const LEGACY_WATCHER_FILE: &str = "watcher.json";
+
+/// config.json as the popup wrote it before sections: the MQTT settings at the top level
+fn is_legacy_mqtt(value: &serde_json::Value) -> bool {
+    value.get("broker").is_some() && value.get("mqtt").is_none()
+}
+
+/// `path` with `.bak` appended: tray.json.bak
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    PathBuf::from(backup)
+}
+
+/// Where a config file is copied before it is rewritten, e.g. config.json.v0-20260105-093000.bak
+/// before a migration or config.json.import-20260105-093000.bak before an import
+fn timestamped_backup_path(path: &Path, label: &str) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".{}-{}.bak", label, chrono::Local::now().format("%Y%m%d-%H%M%S")));
+    PathBuf::from(backup)
+}
+
+/// Copy the active profile's file to a timestamped backup labelled `label`. None when there is
+/// no file yet.
+pub fn backup_config(label: &str) -> Result<Option<PathBuf>, String> {
+    let path = get_config_path();
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let backup = timestamped_backup_path(&path, label);
+    fs::copy(&path, &backup).map_err(|e| format!("{}: {}", backup.display(), e))?;
+    Ok(Some(backup))
+}
+
+/// Settings of layout `version` from outside config.json (an imported bundle) brought up to
+/// CONFIG_VERSION. Newer layouts are refused.
+pub fn upgrade_settings(value: serde_json::Value, version: u32) -> Result<serde_json::Value, String> {
+    if version > CONFIG_VERSION {
+        return Err(format!("schema_version {} is from a newer version of the app (this one reads {})", version, CONFIG_VERSION));
+    }
+    let mut value = value;
+    for step in &MIGRATIONS[version as usize..] {
+        value = step(None, value);
+    }
+    Ok(value)
+}
+
+/// The version of a parsed config.json. Files from before schema_version are version 0 when
+/// flat or joined by tray.json / watcher.json, else version 1 (sections, no version field).
+pub(super) fn file_version(dir: &Path, value: Option<&serde_json::Value>) -> u32 {
+    let declared = value.and_then(|v| v.get("schema_version")).and_then(serde_json::Value::as_u64);
+    match declared {
+        Some(version) => u32::try_from(version).unwrap_or(u32::MAX),
+        None if value.is_some_and(is_legacy_mqtt) => 0,
+        None if dir.join(LEGACY_TRAY_FILE).exists() || dir.join(LEGACY_WATCHER_FILE).exists() => 0,
+        None => 1,
+    }
+}
+
+/// The schema_version config.json declares, when it parses and declares one
+pub(super) fn declared_version(path: &Path) -> Option<u32> {
+    let content = fs::read_to_string(path).ok()?;
+    let value: serde_json::Value = serde_json::from_str(&content).ok()?;
+    value.get("schema_version")?.as_u64().map(|v| u32::try_from(v).unwrap_or(u32::MAX))
+}
+
+/// Version 0 to 1: the flat MQTT config.json becomes the mqtt section, tray.json the ui and
+/// voice sections, watcher.json the watcher section
+fn migrate_v0(dir: Option<&Path>, value: serde_json::Value) -> serde_json::Value {
+    let mut config = if is_legacy_mqtt(&value) {
+        serde_json::json!({ "mqtt": value })
+    } else {
+        value
+    };
+    let Some(dir) = dir else {
+        return config;
+    };
+    let tray_path = dir.join(LEGACY_TRAY_FILE);
+    if tray_path.exists() {
+        // The old tray.json held both sections' fields; each takes its own
+        let tray: serde_json::Value = load_json(&tray_path);
+        config["ui"] = tray.clone();
+        config["voice"] = tray;
+    }
+    let watcher_path = dir.join(LEGACY_WATCHER_FILE);
+    if watcher_path.exists() {
+        config["watcher"] = load_json(&watcher_path);
+    }
+    config
+}
+
+/// Upgrade config.json from `version` one MIGRATIONS step at a time and write it back, after
+/// copying the original to a timestamped backup. tray.json and watcher.json, once folded in,
+/// are renamed to `.bak`. A failed write is only logged; the upgraded settings are still used,
+/// and the migration is tried again next time.
+pub(super) fn migrate(dir: &Path, value: serde_json::Value, version: u32) -> AppConfig {
+    let path = dir.join(CONFIG_FILE);
+    let mut value = value;
+    for step in &MIGRATIONS[version as usize..] {
+        value = step(Some(dir), value);
+    }
+    value["schema_version"] = serde_json::Value::from(CONFIG_VERSION);
+    let config: AppConfig = serde_json::from_value(value).unwrap_or_else(|e| {
+        warn!("Failed to upgrade {}, using defaults: {}", path.display(), e);
+        AppConfig::default()
+    });
+
+    let written = (|| {
+        if path.exists() {
+            fs::copy(&path, timestamped_backup_path(&path, &format!("v{}", version))).map_err(|e| e.to_string())?;
+        }
+        save_json(&path, &config)?;
+        for legacy in [dir.join(LEGACY_TRAY_FILE), dir.join(LEGACY_WATCHER_FILE)] {
+            if legacy.exists() {
+                fs::rename(&legacy, backup_path(&legacy)).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok::<(), String>(())
+    })();
+    match written {
+        Ok(()) => info!("Upgraded {} from version {} to {} (original kept as a backup)", path.display(), version, CONFIG_VERSION),
+        Err(e) => warn!("Failed to upgrade {}: {}", path.display(), e),
+    }
+    config
+}
+
+/// Copy the config files of versions that kept them in ~/.oracle-voice-tray into `dir`, once:
+/// only while `dir` has no config.json. The originals stay where they were. Returns true
+/// when anything was copied.
+pub(super) fn adopt_legacy_dir(dir: &Path, legacy: &Path) -> bool {
+    if dir == legacy || dir.join(CONFIG_FILE).exists() {
+        return false;
+    }
+    let files: Vec<&str> = [CONFIG_FILE, LEGACY_TRAY_FILE, LEGACY_WATCHER_FILE].into_iter()
+        .filter(|name| legacy.join(name).is_file())
+        .collect();
+    if files.is_empty() {
+        return false;
+    }
+    let copied = fs::create_dir_all(dir).map_err(|e| e.to_string()).and_then(|_| {
+        files.iter().try_for_each(|name| fs::copy(legacy.join(name), dir.join(name)).map(|_| ()).map_err(|e| format!("{}: {}", name, e)))
+    });
+    match copied {
+        Ok(()) => {
+            info!("Copied {} from {} to {}", files.join(", "), legacy.display(), dir.display());
+            true
+        }
+        Err(e) => {
+            warn!("Failed to copy settings from {}: {}", legacy.display(), e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_config_in;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_migrate_v0() {
+        let temp_dir = TempDir::new().expect("create temp dir");
+        let dir = temp_dir.path();
+        let legacy = include_str!("../../tests/fixtures/config/v0/config.json");
+        fs::write(dir.join("config.json"), legacy).expect("write config");
+        fs::write(dir.join("tray.json"), include_str!("../../tests/fixtures/config/v0/tray.json")).expect("write tray");
+        fs::write(dir.join("watcher.json"), include_str!("../../tests/fixtures/config/v0/watcher.json")).expect("write watcher");
+        assert_eq!(file_version(dir, Some(&serde_json::from_str(legacy).expect("parse"))), 0);
+
+        let config = load_config_in(dir);
+        assert_eq!(config.schema_version, CONFIG_VERSION);
+        assert_eq!((config.mqtt.broker.as_str(), config.mqtt.port), ("test.broker.com", 9999));
+        assert!(config.mqtt.enabled, "files from before the enabled flag keep connecting");
+        assert!(config.ui.muted);
+        assert_eq!(config.ui.copy_format, "[{agent}] {text}");
+        assert_eq!(config.voice.default_voice, "Daniel");
+        assert_eq!(config.voice.default_rate, 220);
+        assert_eq!(config.watcher.reminder_max, 1);
+
+        // Original backed up with its version and a timestamp, the upgraded file written back
+        let backups: Vec<String> = fs::read_dir(dir).expect("list")
+            .map(|e| e.expect("entry").file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("config.json.v0-") && name.ends_with(".bak"))
+            .collect();
+        assert_eq!(backups.len(), 1, "{:?}", backups);
+        assert_eq!(fs::read_to_string(dir.join(&backups[0])).expect("backup"), legacy);
+        assert!(dir.join("tray.json.bak").exists() && !dir.join("tray.json").exists());
+        assert!(dir.join("watcher.json.bak").exists() && !dir.join("watcher.json").exists());
+        let written: serde_json::Value = load_json(&dir.join("config.json"));
+        assert_eq!(written["schema_version"], CONFIG_VERSION);
+        assert_eq!(written["mqtt"]["broker"], "test.broker.com");
+        assert_eq!(written["voice"]["default_voice"], "Daniel");
+        assert_eq!(file_version(dir, Some(&written)), CONFIG_VERSION, "migrates once");
+    }
+
+    #[test]
+    fn test_legacy_dir_adopted_once() {
+        let temp_dir = TempDir::new().expect("create temp dir");
+        let legacy = temp_dir.path().join(".oracle-voice-tray");
+        let dir = temp_dir.path().join("config").join("oracle-voice-tray");
+        assert!(!adopt_legacy_dir(&dir, &legacy), "nothing to adopt");
+
+        fs::create_dir_all(&legacy).expect("create legacy dir");
+        fs::write(legacy.join("config.json"), r#"{"broker":"pi.local"}"#).expect("write config");
+        fs::write(legacy.join("tray.json"), r#"{"muted": true}"#).expect("write tray");
+        assert!(adopt_legacy_dir(&dir, &legacy));
+        assert!(legacy.join("config.json").exists(), "originals stay");
+        let config = load_config_in(&dir);
+        assert_eq!(config.mqtt.broker, "pi.local");
+        assert!(config.ui.muted);
+
+        fs::write(legacy.join("config.json"), r#"{"broker":"other.local"}"#).expect("write config");
+        assert!(!adopt_legacy_dir(&dir, &legacy), "copied once");
+        assert_eq!(load_config_in(&dir).mqtt.broker, "pi.local");
+    }
+}
@@ -0,0 +1,233 @@
+/// ORACLE_VOICE_* environment variables, laid over config.json for containers and quick tests.
+/// They are read once at startup and never saved: a save puts the file's own value back
+/// wherever a section still carries the override.
+use std::sync::OnceLock;
+
+use serde_json::{Map, Value};
+use tracing::{info, warn};
+
+use super::{AppConfig, REDACTED_PASSWORD};
+
+/// How a variable's text becomes a config value
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    /// Empty clears it (null)
+    OptionalText,
+    Port,
+    Number,
+    Flag,
+}
+
+/// Variable, the setting it overrides, its kind and whether /status shows its value
+const VARS: [(&str, &str, Kind, bool); 12] = [
+    ("ORACLE_VOICE_MQTT_ENABLED", "mqtt.enabled", Kind::Flag, false),
+    ("ORACLE_VOICE_MQTT_BROKER", "mqtt.broker", Kind::Text, false),
+    ("ORACLE_VOICE_MQTT_PORT", "mqtt.port", Kind::Port, false),
+    ("ORACLE_VOICE_MQTT_TOPIC_SPEAK", "mqtt.topic_speak", Kind::Text, false),
+    ("ORACLE_VOICE_MQTT_TOPIC_STATUS", "mqtt.topic_status", Kind::Text, false),
+    ("ORACLE_VOICE_MQTT_USERNAME", "mqtt.username", Kind::OptionalText, false),
+    ("ORACLE_VOICE_MQTT_PASSWORD", "mqtt.password", Kind::OptionalText, true),
+    ("ORACLE_VOICE_HTTP_PORT", "http.port", Kind::Port, false),
+    ("ORACLE_VOICE_DEFAULT_VOICE", "voice.default_voice", Kind::Text, false),
+    ("ORACLE_VOICE_DEFAULT_RATE", "voice.default_rate", Kind::Number, false),
+    ("ORACLE_VOICE_WATCHER_ENABLED", "watcher.enabled", Kind::Flag, false),
+    ("ORACLE_VOICE_MUTED", "ui.muted", Kind::Flag, false),
+];
+
+/// One variable that was set and parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Override {
+    pub var: &'static str,
+    /// Dotted setting name, e.g. "mqtt.port"
+    pub key: &'static str,
+    value: Value,
+    secret: bool,
+}
+
+impl Override {
+    fn pointer(&self) -> String {
+        format!("/{}", self.key.replace('.', "/"))
+    }
+
+    /// The value for logs and /status; secrets are redacted
+    pub fn shown(&self) -> Value {
+        if self.secret && !self.value.is_null() {
+            Value::String(REDACTED_PASSWORD.to_string())
+        } else {
+            self.value.clone()
+        }
+    }
+}
+
+static OVERRIDES: OnceLock<Vec<Override>> = OnceLock::new();
+
+fn parse_value(kind: Kind, raw: &str) -> Result<Value, String> {
+    let raw = raw.trim();
+    let invalid = |expected: &str| format!("\"{}\" is not {}", raw, expected);
+    match kind {
+        Kind::Text if raw.is_empty() => Err(invalid("a value")),
+        Kind::Text => Ok(Value::from(raw)),
+        Kind::OptionalText if raw.is_empty() => Ok(Value::Null),
+        Kind::OptionalText => Ok(Value::from(raw)),
+        Kind::Port => match raw.parse::<u16>() {
+            Ok(port) if port != 0 => Ok(Value::from(port)),
+            _ => Err(invalid("a port (1-65535)")),
+        },
+        Kind::Number => raw.parse::<u32>().map(Value::from).map_err(|_| invalid("a whole number")),
+        Kind::Flag => match raw.to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok(Value::Bool(true)),
+            "0" | "false" | "no" | "off" => Ok(Value::Bool(false)),
+            _ => Err(invalid("true or false")),
+        },
+    }
+}
+
+/// Overrides from `vars`, plus a message per variable that could not be parsed
+fn parse(vars: impl Iterator<Item = (String, String)>) -> (Vec<Override>, Vec<String>) {
+    let mut overrides = Vec::new();
+    let mut errors = Vec::new();
+    for (name, raw) in vars {
+        let Some(&(var, key, kind, secret)) = VARS.iter().find(|(var, ..)| *var == name) else {
+            if name.starts_with("ORACLE_VOICE_") {
+                errors.push(format!("{}: unknown setting", name));
+            }
+            continue;
+        };
+        match parse_value(kind, &raw) {
+            Ok(value) => overrides.push(Override { var, key, value, secret }),
+            Err(e) => errors.push(format!("{}: {}", var, e)),
+        }
+    }
+    overrides.sort_by_key(|o| VARS.iter().position(|(var, ..)| *var == o.var));
+    (overrides, errors)
+}
+
+/// The overrides in effect, read from the environment the first time
+pub fn active() -> &'static [Override] {
+    OVERRIDES.get_or_init(|| {
+        let (overrides, errors) = parse(std::env::vars());
+        for o in &overrides {
+            info!("{} overrides {} = {}", o.var, o.key, o.shown());
+        }
+        for e in &errors {
+            warn!("Ignored {}", e);
+        }
+        overrides
+    })
+}
+
+/// Overridden settings and the values shown for them, for /status
+pub fn shown() -> Map<String, Value> {
+    active().iter().map(|o| (o.key.to_string(), o.shown())).collect()
+}
+
+fn apply_in(config: AppConfig, overrides: &[Override]) -> AppConfig {
+    if overrides.is_empty() {
+        return config;
+    }
+    let Ok(mut value) = serde_json::to_value(&config) else {
+        return config;
+    };
+    for o in overrides {
+        if let Some(slot) = value.pointer_mut(&o.pointer()) {
+            *slot = o.value.clone();
+        }
+    }
+    serde_json::from_value(value).unwrap_or(config)
+}
+
+/// Where `config`, about to be saved, still carries an override, put back `file`'s value
+fn unapply_in(config: AppConfig, file: &AppConfig, overrides: &[Override]) -> AppConfig {
+    if overrides.is_empty() {
+        return config;
+    }
+    let (Ok(mut value), Ok(file)) = (serde_json::to_value(&config), serde_json::to_value(file)) else {
+        return config;
+    };
+    for o in overrides {
+        let pointer = o.pointer();
+        let original = file.pointer(&pointer).cloned();
+        if let (Some(slot), Some(original)) = (value.pointer_mut(&pointer), original) {
+            if *slot == o.value {
+                *slot = original;
+            }
+        }
+    }
+    serde_json::from_value(value).unwrap_or(config)
+}
+
+/// `config` as read from config.json, with the overrides laid over it
+pub fn apply(config: AppConfig) -> AppConfig {
+    apply_in(config, active())
+}
+
+/// `config` ready to be written to config.json: overridden values replaced by `file`'s own
+pub fn unapply(config: AppConfig, file: &AppConfig) -> AppConfig {
+    unapply_in(config, file, active())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_one(var: &str, raw: &str) -> Result<Value, String> {
+        let (overrides, errors) = parse(std::iter::once((var.to_string(), raw.to_string())));
+        match (overrides.first(), errors.first()) {
+            (Some(o), _) => Ok(o.value.clone()),
+            (None, Some(e)) => Err(e.clone()),
+            (None, None) => Err("ignored".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let cases: [(&str, &str, Result<Value, &str>); 13] = [
+            ("ORACLE_VOICE_MQTT_BROKER", " pi.local ", Ok(Value::from("pi.local"))),
+            ("ORACLE_VOICE_MQTT_BROKER", "", Err("ORACLE_VOICE_MQTT_BROKER: \"\" is not a value")),
+            ("ORACLE_VOICE_MQTT_PORT", "8883", Ok(Value::from(8883))),
+            ("ORACLE_VOICE_MQTT_PORT", "0", Err("ORACLE_VOICE_MQTT_PORT: \"0\" is not a port (1-65535)")),
+            ("ORACLE_VOICE_HTTP_PORT", "70000", Err("ORACLE_VOICE_HTTP_PORT: \"70000\" is not a port (1-65535)")),
+            ("ORACLE_VOICE_DEFAULT_RATE", "180", Ok(Value::from(180))),
+            ("ORACLE_VOICE_DEFAULT_RATE", "fast", Err("ORACLE_VOICE_DEFAULT_RATE: \"fast\" is not a whole number")),
+            ("ORACLE_VOICE_WATCHER_ENABLED", "Off", Ok(Value::Bool(false))),
+            ("ORACLE_VOICE_MUTED", "1", Ok(Value::Bool(true))),
+            ("ORACLE_VOICE_MUTED", "maybe", Err("ORACLE_VOICE_MUTED: \"maybe\" is not true or false")),
+            ("ORACLE_VOICE_MQTT_USERNAME", "", Ok(Value::Null)),
+            ("ORACLE_VOICE_VOLUME", "11", Err("ORACLE_VOICE_VOLUME: unknown setting")),
+            ("HOME", "/root", Err("ignored")),
+        ];
+        for (var, raw, expected) in cases {
+            assert_eq!(parse_one(var, raw), expected.map_err(str::to_string), "{}={}", var, raw);
+        }
+    }
+
+    #[test]
+    fn test_overrides_win_but_are_not_saved() {
+        let vars = [
+            ("ORACLE_VOICE_HTTP_PORT", "8080"),
+            ("ORACLE_VOICE_MQTT_PASSWORD", "s3cret"),
+            ("ORACLE_VOICE_DEFAULT_VOICE", "Karen"),
+        ];
+        let (overrides, errors) = parse(vars.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+        assert!(errors.is_empty());
+        assert_eq!(overrides.iter().map(|o| o.key).collect::<Vec<_>>(), ["mqtt.password", "http.port", "voice.default_voice"]);
+        assert_eq!(overrides[0].shown(), Value::from(REDACTED_PASSWORD));
+
+        let mut file = AppConfig::default();
+        file.voice.default_voice = "Daniel".to_string();
+        let running = apply_in(file.clone(), &overrides);
+        assert_eq!(running.http.port, 8080);
+        assert_eq!(running.voice.default_voice, "Karen", "the environment beats the file");
+        assert_eq!(running.mqtt.password.as_deref(), Some("s3cret"));
+
+        // The settings window sends back whole sections, overrides included, plus one real change
+        let mut edited = running.clone();
+        edited.voice.default_rate = 180;
+        let saved = unapply_in(edited, &file, &overrides);
+        assert_eq!(saved.http.port, file.http.port);
+        assert_eq!(saved.voice.default_voice, "Daniel");
+        assert_eq!(saved.mqtt.password, None);
+        assert_eq!(saved.voice.default_rate, 180);
+    }
+}
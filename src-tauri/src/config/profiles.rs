@@ -0,0 +1,261 @@
+/// Settings profiles: config.json is the default profile, and each other profile is its own
+/// file in the profiles folder. config.json's active_profile says which one is in use.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use tracing::{info, warn};
+
+use super::{
+    config_dir, load_config_in, load_json, load_saved_config, newer_file_error, read_config, save_json, AppConfig, ACTIVE,
+    CONFIG_FILE, CONFIG_VERSION, GENERATION, NEWER_FILE, PROFILE,
+};
+use super::overrides;
+use crate::keychain::{self, KEYRING_MARKER};
+
+/// The profile kept in config.json itself; other profiles are profiles/<name>.json
+pub const DEFAULT_PROFILE: &str = "default";
+const PROFILES_DIR: &str = "profiles";
+const PROFILE_NAME_MAX: usize = 32;
+
+/// Names Windows will not use for a file, whatever the extension
+const RESERVED_NAMES: [&str; 4] = ["con", "prn", "aux", "nul"];
+
+/// The active profile's file: config.json, or profiles/<name>.json
+pub fn get_config_path() -> PathBuf {
+    profile_path(&config_dir(), &active_profile())
+}
+
+pub(super) fn profile_path(dir: &Path, name: &str) -> PathBuf {
+    if name == DEFAULT_PROFILE {
+        dir.join(CONFIG_FILE)
+    } else {
+        dir.join(PROFILES_DIR).join(format!("{}.json", name))
+    }
+}
+
+/// A profile name safe to use as a file name: spaces become dashes, and only ASCII letters,
+/// digits, `-` and `_` are allowed. "default" is config.json's own profile.
+pub fn sanitize_profile_name(name: &str) -> Result<String, String> {
+    let name = name.split_whitespace().collect::<Vec<_>>().join("-");
+    if name.is_empty() {
+        return Err("Profile name is empty".to_string());
+    }
+    if name.chars().count() > PROFILE_NAME_MAX {
+        return Err(format!("Profile name is longer than {} characters", PROFILE_NAME_MAX));
+    }
+    if let Some(c) = name.chars().find(|c| !c.is_ascii_alphanumeric() && *c != '-' && *c != '_') {
+        return Err(format!("Profile names can only use letters, digits, - and _ (not '{}')", c));
+    }
+    let lower = name.to_ascii_lowercase();
+    let reserved = RESERVED_NAMES.contains(&lower.as_str())
+        || ((lower.starts_with("com") || lower.starts_with("lpt")) && lower.len() == 4 && lower.ends_with(|c: char| c.is_ascii_digit()));
+    if reserved {
+        return Err(format!("\"{}\" cannot be a file name on Windows", name));
+    }
+    if lower == DEFAULT_PROFILE {
+        return Err(format!("\"{}\" is the profile kept in config.json", DEFAULT_PROFILE));
+    }
+    Ok(name)
+}
+
+/// `name` checked for switching or deleting: the default profile or a sanitized name
+fn profile_name(name: &str) -> Result<String, String> {
+    if name.trim().eq_ignore_ascii_case(DEFAULT_PROFILE) {
+        Ok(DEFAULT_PROFILE.to_string())
+    } else {
+        sanitize_profile_name(name)
+    }
+}
+
+/// The profile config.json points at; the default one when it names a profile that is gone
+pub(super) fn active_profile_in(dir: &Path) -> String {
+    let value: Option<serde_json::Value> = fs::read_to_string(dir.join(CONFIG_FILE)).ok()
+        .and_then(|content| serde_json::from_str(&content).ok());
+    let Some(name) = value.as_ref().and_then(|v| v.get("active_profile")).and_then(serde_json::Value::as_str) else {
+        return DEFAULT_PROFILE.to_string();
+    };
+    match profile_name(name) {
+        Ok(name) if profile_path(dir, &name).is_file() => name,
+        _ => {
+            warn!("Profile \"{}\" not found, using the default profile", name);
+            DEFAULT_PROFILE.to_string()
+        }
+    }
+}
+
+/// Name of the profile in use
+pub fn active_profile() -> String {
+    PROFILE.lock().get_or_insert_with(|| active_profile_in(&config_dir())).clone()
+}
+
+pub(super) fn set_active_profile(name: &str) {
+    *PROFILE.lock() = Some(name.to_string());
+}
+
+/// The default profile first, then the others by name
+fn list_profiles_in(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir.join(PROFILES_DIR)).into_iter().flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string))
+        .filter(|name| sanitize_profile_name(name).as_deref() == Ok(name.as_str()))
+        .collect();
+    names.sort_by_key(|name| name.to_ascii_lowercase());
+    names.insert(0, DEFAULT_PROFILE.to_string());
+    names
+}
+
+/// Every profile, the default one first
+pub fn list_profiles() -> Vec<String> {
+    list_profiles_in(&config_dir())
+}
+
+/// Point config.json at `name`, keeping the rest of the file as it is
+fn write_active_profile(dir: &Path, name: &str) -> Result<(), String> {
+    let path = dir.join(CONFIG_FILE);
+    if let Some(e) = newer_file_error(&path) {
+        return Err(format!("{}; not overwriting it", e));
+    }
+    let mut value: serde_json::Value = fs::read_to_string(&path).ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_else(|| serde_json::to_value(AppConfig::default()).unwrap_or_default());
+    let Some(fields) = value.as_object_mut() else {
+        return Err(format!("{} is not a JSON object", path.display()));
+    };
+    if name == DEFAULT_PROFILE {
+        fields.remove("active_profile");
+    } else {
+        fields.insert("active_profile".to_string(), serde_json::Value::from(name));
+    }
+    Ok(save_json(&path, &value)?)
+}
+
+/// A profile's settings; a file from a newer version gives the defaults
+pub(super) fn load_profile_in(dir: &Path, name: &str) -> AppConfig {
+    if name == DEFAULT_PROFILE {
+        return load_config_in(dir);
+    }
+    let path = profile_path(dir, name);
+    if newer_file_error(&path).is_some() {
+        return AppConfig::default();
+    }
+    load_json(&path)
+}
+
+fn create_profile_in(dir: &Path, name: &str, from: &AppConfig) -> Result<String, String> {
+    let name = sanitize_profile_name(name)?;
+    if list_profiles_in(dir).iter().any(|existing| existing.eq_ignore_ascii_case(&name)) {
+        return Err(format!("A profile named \"{}\" already exists", name));
+    }
+    let config = AppConfig { schema_version: CONFIG_VERSION, ..from.clone() };
+    save_json(&profile_path(dir, &name), &config)?;
+    Ok(name)
+}
+
+/// Add a profile with a copy of the active profile's saved settings. Returns the sanitized name.
+pub fn create_profile(name: &str) -> Result<String, String> {
+    let from = load_saved_config();
+    let name = create_profile_in(&config_dir(), name, &from)?;
+    if from.mqtt.password.as_deref() == Some(KEYRING_MARKER) {
+        keychain::copy_password(&active_profile(), &name);
+    }
+    info!("Created profile \"{}\"", name);
+    Ok(name)
+}
+
+/// Make `name` the active profile and remember it in config.json. Returns the previous and the
+/// new settings, with the environment overrides, for the caller to apply.
+pub fn switch_profile(name: &str) -> Result<(AppConfig, AppConfig), String> {
+    let dir = config_dir();
+    let name = profile_name(name)?;
+    let path = profile_path(&dir, &name);
+    if name != DEFAULT_PROFILE && !path.is_file() {
+        return Err(format!("No profile named \"{}\"", name));
+    }
+    let mut active = ACTIVE.lock();
+    let config = if name == DEFAULT_PROFILE { load_config_in(&dir) } else { read_config(&path)? };
+    write_active_profile(&dir, &name)?;
+    let previous = active.clone().unwrap_or_default();
+    *active = Some(config.clone());
+    set_active_profile(&name);
+    *NEWER_FILE.lock() = newer_file_error(&path);
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    info!("Switched to profile \"{}\"", name);
+    Ok((overrides::apply(previous), overrides::apply(config)))
+}
+
+/// Remove a profile and its keychain password. The active profile is switched to the default
+/// one first, and that switch is returned for the caller to apply; the file stays if it fails.
+pub fn delete_profile(name: &str) -> Result<Option<(AppConfig, AppConfig)>, String> {
+    let name = profile_name(name)?;
+    if name == DEFAULT_PROFILE {
+        return Err("The default profile cannot be deleted".to_string());
+    }
+    let path = profile_path(&config_dir(), &name);
+    if !path.is_file() {
+        return Err(format!("No profile named \"{}\"", name));
+    }
+    let switched = if active_profile() == name { Some(switch_profile(DEFAULT_PROFILE)?) } else { None };
+    fs::remove_file(&path).map_err(|e| e.to_string())?;
+    keychain::forget_password(&name);
+    info!("Deleted profile \"{}\"", name);
+    Ok(switched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::update_config_in;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_profile_names() {
+        let cases = [
+            ("office", Ok("office")),
+            ("  Home Office ", Ok("Home-Office")),
+            ("lab_2", Ok("lab_2")),
+            ("", Err("Profile name is empty")),
+            ("../etc/passwd", Err("Profile names can only use letters, digits, - and _ (not '.')")),
+            ("café", Err("Profile names can only use letters, digits, - and _ (not 'é')")),
+            ("Default", Err("\"default\" is the profile kept in config.json")),
+            ("NUL", Err("\"NUL\" cannot be a file name on Windows")),
+            ("com1", Err("\"com1\" cannot be a file name on Windows")),
+            ("computer", Ok("computer")),
+            ("a-very-long-profile-name-for-work", Err("Profile name is longer than 32 characters")),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(sanitize_profile_name(name), expected.map(str::to_string).map_err(str::to_string), "{:?}", name);
+        }
+        assert_eq!(profile_name(" DEFAULT").as_deref(), Ok(DEFAULT_PROFILE));
+    }
+
+    #[test]
+    fn test_profiles_in_dir() {
+        let temp_dir = TempDir::new().expect("create temp dir");
+        let dir = temp_dir.path();
+        fs::write(dir.join("config.json"), r#"{"schema_version": 1, "mqtt": {"broker": "home.local"}}"#).expect("write");
+        assert_eq!(list_profiles_in(dir), ["default"]);
+        assert_eq!(active_profile_in(dir), DEFAULT_PROFILE);
+
+        let home = load_profile_in(dir, DEFAULT_PROFILE);
+        assert_eq!(create_profile_in(dir, "Work laptop", &home), Ok("Work-laptop".to_string()));
+        assert!(create_profile_in(dir, "work-LAPTOP", &home).unwrap_err().contains("already exists"));
+        update_config_in(&profile_path(dir, "Work-laptop"), load_profile_in(dir, "Work-laptop"), |c| c.mqtt.broker = "office.local".to_string())
+            .expect("save profile");
+        create_profile_in(dir, "away", &home).expect("create");
+        assert_eq!(list_profiles_in(dir), ["default", "away", "Work-laptop"]);
+
+        write_active_profile(dir, "Work-laptop").expect("switch");
+        assert_eq!(active_profile_in(dir), "Work-laptop");
+        assert_eq!(load_profile_in(dir, "Work-laptop").mqtt.broker, "office.local");
+        assert_eq!(load_config_in(dir).mqtt.broker, "home.local", "config.json keeps the default profile");
+
+        // A pointer to a profile that is gone falls back to the default one
+        fs::remove_file(profile_path(dir, "Work-laptop")).expect("delete");
+        assert_eq!(active_profile_in(dir), DEFAULT_PROFILE);
+        write_active_profile(dir, DEFAULT_PROFILE).expect("switch back");
+        let written: serde_json::Value = load_json(&dir.join("config.json"));
+        assert!(written.get("active_profile").is_none());
+        assert_eq!(written["mqtt"]["broker"], "home.local");
+    }
+}
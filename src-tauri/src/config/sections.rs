@@ -0,0 +1,649 @@
+/// The per-subsystem sections of config.json other than the watcher's: MQTT, the HTTP API,
+/// voice defaults, the ui section, and the ntfy, pipe, files, transcripts, relay, GitHub,
+/// mirror and log sections. Each has defaults for every field, and the ones holding a secret
+/// can redact it for reads and restore it from what is saved.
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr};
+
+use super::REDACTED_PASSWORD;
+use crate::github;
+use crate::http::VOICE_SERVER_PORT;
+use crate::i18n::{self, Phrase};
+
+/// MQTT Configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    pub broker: String,
+    pub port: u16,
+    pub topic_speak: String,
+    pub topic_status: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Off: no broker connection, and the tray menu hides Reconnect MQTT
+    pub enabled: bool,
+    /// Keep the password in the OS keychain, with KEYRING_MARKER in its place here. Off for
+    /// headless setups without a keychain.
+    pub use_keyring: bool,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            broker: "127.0.0.1".to_string(),
+            port: 1883,
+            topic_speak: "voice/speak".to_string(),
+            topic_status: "voice/status".to_string(),
+            username: None,
+            password: None,
+            enabled: true,
+            use_keyring: true,
+        }
+    }
+}
+
+impl MqttConfig {
+    /// This config with a saved password replaced by REDACTED_PASSWORD, for the UI
+    pub fn redacted(mut self) -> Self {
+        if self.has_password() {
+            self.password = Some(REDACTED_PASSWORD.to_string());
+        }
+        self
+    }
+
+    /// Put the stored password back where a form returned the redacted one unchanged. An empty
+    /// password clears the stored one.
+    pub fn restore_password(&mut self, stored: &MqttConfig) {
+        match self.password.as_deref() {
+            Some(REDACTED_PASSWORD) => self.password = stored.password.clone(),
+            Some("") => self.password = None,
+            _ => {}
+        }
+    }
+
+    pub(super) fn has_password(&self) -> bool {
+        self.password.as_deref().is_some_and(|p| !p.is_empty())
+    }
+}
+
+/// The local HTTP API
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Installed Claude Code hooks carry it in their URL
+    pub port: u16,
+    /// Address to listen on. 0.0.0.0 or a LAN address opens the API (which has no token) to
+    /// other machines; hooks and ovt reach it on 127.0.0.1 only with 0.0.0.0.
+    pub bind: String,
+    /// Announce the API over mDNS (see mdns.rs) while it listens on a non-loopback address
+    pub advertise: bool,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self { port: VOICE_SERVER_PORT, bind: "127.0.0.1".to_string(), advertise: true }
+    }
+}
+
+impl HttpConfig {
+    /// `bind` as an address; loopback when it does not parse (validate reports it)
+    pub fn bind_ip(&self) -> IpAddr {
+        self.bind.trim().parse().unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+    }
+}
+
+/// The voice pipe (see pipe.rs)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PipeConfig {
+    /// Speak the lines written to voice.pipe in the config folder (a named pipe on Windows)
+    pub enabled: bool,
+}
+
+/// ntfy topics whose messages are spoken (see ntfy.rs); none by default
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NtfyConfig {
+    pub topics: Vec<NtfyTopic>,
+}
+
+/// One subscription; its messages are queued with the topic name as the agent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NtfyTopic {
+    /// Base URL of the server, e.g. "https://ntfy.sh" or a self-hosted one
+    pub server: String,
+    pub topic: String,
+    /// Access token for a protected topic, sent as a Bearer token
+    pub token: Option<String>,
+}
+
+impl Default for NtfyTopic {
+    fn default() -> Self {
+        Self { server: "https://ntfy.sh".to_string(), topic: String::new(), token: None }
+    }
+}
+
+impl NtfyConfig {
+    /// This config with saved tokens replaced by REDACTED_PASSWORD, for reads
+    pub fn redacted(mut self) -> Self {
+        for topic in &mut self.topics {
+            if topic.token.as_deref().is_some_and(|t| !t.is_empty()) {
+                topic.token = Some(REDACTED_PASSWORD.to_string());
+            }
+        }
+        self
+    }
+
+    /// Put back the stored token of each topic a form returned redacted; an empty token
+    /// clears it. A topic with no stored counterpart gets none.
+    pub fn restore_tokens(&mut self, stored: &NtfyConfig) {
+        for topic in &mut self.topics {
+            match topic.token.as_deref() {
+                Some(REDACTED_PASSWORD) => {
+                    topic.token = stored.topics.iter()
+                        .find(|s| s.server == topic.server && s.topic == topic.topic)
+                        .and_then(|s| s.token.clone());
+                }
+                Some("") => topic.token = None,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Text files POST /speak/file may read aloud (see speak_file.rs)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilesConfig {
+    /// Folders whose files, and their subfolders' files, may be read; none by default, which
+    /// refuses every file. `~` is the home folder.
+    pub allowed_dirs: Vec<String>,
+    /// Largest file read
+    pub max_bytes: u64,
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        Self { allowed_dirs: Vec::new(), max_bytes: 64 * 1024 }
+    }
+}
+
+/// Daily files of what was spoken (see transcript.rs)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranscriptConfig {
+    pub enabled: bool,
+    /// Folder for the files; "transcripts" in the config folder when unset. `~` is the home folder.
+    pub dir: Option<String>,
+    /// Also write entries a Focus held back, marked "(suppressed)"
+    pub include_suppressed: bool,
+    /// Also write entries that failed to speak, marked "(failed)"
+    pub include_failed: bool,
+    /// Days of files kept, today's included; 0 keeps them all
+    pub keep_days: u32,
+}
+
+impl Default for TranscriptConfig {
+    fn default() -> Self {
+        Self { enabled: false, dir: None, include_suppressed: false, include_failed: false, keep_days: 30 }
+    }
+}
+
+/// Another Voice Tray that speaks the messages this one cannot (see relay.rs)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelayConfig {
+    /// Forward while muted, snoozed, in quiet hours or a muting Focus, or without TTS
+    pub enabled: bool,
+    /// The peer's HTTP API, e.g. "http://laptop.local:37779"
+    pub url: String,
+    /// Sent as a Bearer token, for a peer behind a proxy that checks one
+    pub token: Option<String>,
+}
+
+impl RelayConfig {
+    /// This config with a saved token replaced by REDACTED_PASSWORD, for reads
+    pub fn redacted(mut self) -> Self {
+        if self.token.as_deref().is_some_and(|t| !t.is_empty()) {
+            self.token = Some(REDACTED_PASSWORD.to_string());
+        }
+        self
+    }
+
+    /// Put back the stored token where a form returned it redacted; an empty token clears it
+    pub fn restore_token(&mut self, stored: &RelayConfig) {
+        match self.token.as_deref() {
+            Some(REDACTED_PASSWORD) => self.token = stored.token.clone(),
+            Some("") => self.token = None,
+            _ => {}
+        }
+    }
+}
+
+/// GitHub webhooks at POST /github (see github.rs)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GithubConfig {
+    /// Off: POST /github answers 404
+    pub enabled: bool,
+    /// The webhook's secret; deliveries without its X-Hub-Signature-256 are refused
+    pub secret: Option<String>,
+    /// Event types spoken, of github::EVENTS
+    pub events: Vec<String>,
+    /// Workflow run conclusions spoken; add "success" to hear passing runs too
+    pub workflow_conclusions: Vec<String>,
+    /// `{sender}`, `{commits}`, `{branch}` and `{repo}` are filled in
+    pub push_phrase: String,
+    /// `{workflow}`, `{result}`, `{branch}`, `{repo}` and `{sender}`
+    pub workflow_phrase: String,
+    /// `{sender}`, `{reviewer}`, `{number}`, `{title}` and `{repo}`
+    pub review_phrase: String,
+    /// `{sender}`, `{number}`, `{title}` and `{repo}`
+    pub issue_phrase: String,
+    /// Priority of failed and timed out workflow runs; other deliveries queue at 0
+    pub failure_priority: u8,
+}
+
+impl Default for GithubConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            secret: None,
+            events: github::EVENTS.iter().map(|e| e.to_string()).collect(),
+            workflow_conclusions: vec!["failure".to_string(), "timed_out".to_string(), "startup_failure".to_string()],
+            push_phrase: Phrase::GithubPush.english().to_string(),
+            workflow_phrase: Phrase::GithubWorkflow.english().to_string(),
+            review_phrase: Phrase::GithubReview.english().to_string(),
+            issue_phrase: Phrase::GithubIssue.english().to_string(),
+            failure_priority: 10,
+        }
+    }
+}
+
+impl GithubConfig {
+    /// This config with a saved secret replaced by REDACTED_PASSWORD, for reads
+    pub fn redacted(mut self) -> Self {
+        if self.secret.as_deref().is_some_and(|s| !s.is_empty()) {
+            self.secret = Some(REDACTED_PASSWORD.to_string());
+        }
+        self
+    }
+
+    /// Put back the stored secret where a form returned it redacted; an empty secret clears it
+    pub fn restore_secret(&mut self, stored: &GithubConfig) {
+        match self.secret.as_deref() {
+            Some(REDACTED_PASSWORD) => self.secret = stored.secret.clone(),
+            Some("") => self.secret = None,
+            _ => {}
+        }
+    }
+}
+
+/// Chat webhooks that get a copy of what was spoken (see mirror.rs); none by default
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MirrorConfig {
+    pub destinations: Vec<MirrorDestination>,
+    /// Also post entries held back while muted, snoozed, in quiet hours or a Focus, marked "(suppressed)"
+    pub include_suppressed: bool,
+    /// Agents whose entries are posted; empty posts every entry, those without an agent too
+    pub agents: Vec<String>,
+}
+
+/// One incoming webhook
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MirrorDestination {
+    /// The webhook URL. It is the credential, so reads show only its host.
+    pub url: String,
+    /// The payload posted, of mirror::FORMATS: "slack" (also Mattermost and Rocket.Chat) or "discord"
+    pub format: String,
+}
+
+impl Default for MirrorDestination {
+    fn default() -> Self {
+        Self { url: String::new(), format: "slack".to_string() }
+    }
+}
+
+impl MirrorDestination {
+    /// The URL as reads show it, e.g. "https://hooks.slack.com/********"
+    fn redacted_url(&self) -> String {
+        match tauri::Url::parse(self.url.trim()) {
+            Ok(url) if url.has_host() => {
+                let port = url.port().map(|port| format!(":{}", port)).unwrap_or_default();
+                format!("{}://{}{}/{}", url.scheme(), url.host_str().unwrap_or_default(), port, REDACTED_PASSWORD)
+            }
+            _ if self.url.is_empty() => String::new(),
+            _ => REDACTED_PASSWORD.to_string(),
+        }
+    }
+}
+
+impl MirrorConfig {
+    /// This config with the webhook URLs' paths replaced by REDACTED_PASSWORD, for reads
+    pub fn redacted(mut self) -> Self {
+        for destination in &mut self.destinations {
+            destination.url = destination.redacted_url();
+        }
+        self
+    }
+
+    /// Put back the stored URL of each destination a form returned redacted: the one at the same
+    /// position when it redacts the same, else the first that does. A redacted destination with
+    /// no stored counterpart, as from another machine's bundle, is left out.
+    pub fn restore_urls(&mut self, stored: &MirrorConfig) {
+        self.destinations = std::mem::take(&mut self.destinations).into_iter().enumerate().filter_map(|(i, mut destination)| {
+            if !destination.url.ends_with(REDACTED_PASSWORD) {
+                return Some(destination);
+            }
+            let matches = |s: &&MirrorDestination| s.redacted_url() == destination.url;
+            let original = stored.destinations.get(i).filter(matches).or_else(|| stored.destinations.iter().find(matches))?;
+            destination.url = original.url.clone();
+            Some(destination)
+        }).collect();
+    }
+}
+
+/// The log file and console output (see logging.rs)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// RUST_LOG-style filter, e.g. "info" or "debug,mqtt=warn"; RUST_LOG wins when set
+    pub level: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self { level: "info".to_string() }
+    }
+}
+
+/// Defaults for speech that names no voice or rate, and the tray's Voice submenu
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceConfig {
+    /// Voice for HTTP/MQTT requests that name none, and for Test Voice
+    pub default_voice: String,
+    /// Rate (words per minute) for requests that set none, and for Test Voice
+    pub default_rate: u32,
+    /// Spoken by Test Voice
+    pub test_voice_text: String,
+    /// Voices listed in the tray's Voice submenu, next to the default voice
+    pub favorite_voices: Vec<String>,
+    /// Say the voice's name when it is picked from the Voice submenu
+    pub announce_voice_switch: bool,
+    /// Language of the phrases the app speaks of its own, e.g. "en", "th" or "es"
+    pub language: String,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        Self {
+            default_voice: i18n::ENGLISH_VOICE.to_string(),
+            default_rate: 220,
+            test_voice_text: Phrase::TestVoice.english().to_string(),
+            favorite_voices: Vec::new(),
+            announce_voice_switch: true,
+            language: "en".to_string(),
+        }
+    }
+}
+
+impl VoiceConfig {
+    /// Voice for speech that names none: default_voice, or the language's voice while
+    /// default_voice is left at the English default
+    pub fn voice(&self) -> String {
+        if self.default_voice == i18n::ENGLISH_VOICE {
+            i18n::voice(&self.language)
+        } else {
+            self.default_voice.clone()
+        }
+    }
+}
+
+/// The ui section: tray icon, menu, hotkeys and popup behaviour
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrayConfig {
+    /// Hold the voice queue and turn watcher alerts into notifications
+    pub muted: bool,
+    /// macOS: show the queued count as the menu bar title next to the icon
+    pub show_queue_count: bool,
+    /// Launch at login; synced from the system at startup in case it was changed there
+    pub start_at_login: bool,
+    /// Global hotkey that stops the message being spoken, e.g. "CmdOrCtrl+Shift+S"; empty disables it
+    pub stop_hotkey: String,
+    /// Global hotkey that toggles mute; empty disables it
+    pub mute_hotkey: String,
+    /// Global hotkey that reads the clipboard aloud; empty (the default) disables it
+    pub clipboard_hotkey: String,
+    /// macOS: while Focus is on, mark queued messages suppressed instead of speaking them
+    pub mute_during_focus: bool,
+    /// Say how many messages Focus suppressed when it ends
+    pub focus_summary: bool,
+    /// Raise a (rate-limited) system notification for messages not spoken, per reason
+    pub notify_suppressed: SuppressedNotifications,
+    /// What Copy Last Message puts on the clipboard: `{text}`, `{agent}` and `{time}` are filled in
+    pub copy_format: String,
+    /// Windows/Linux tray icon colors: "auto" follows the system theme, or "light" / "dark"
+    /// for the taskbar color. macOS always uses template icons.
+    pub icon_theme: String,
+    /// What clicking the tray icon does; right-click always opens the menu
+    pub actions: TrayActions,
+    /// Lengths offered in the tray's Snooze submenu, in minutes
+    pub snooze_minutes: Vec<u32>,
+}
+
+/// Something a tray icon click can do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrayAction {
+    TogglePopup,
+    ToggleMute,
+    StopSpeaking,
+    RepeatLast,
+    #[serde(rename = "none")]
+    Nothing,
+}
+
+/// Tray click bindings. With a double-click action set, a single click waits out the
+/// double-click interval before it fires, so it does not run on the way to a double-click.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrayActions {
+    pub left_click: TrayAction,
+    pub double_click: TrayAction,
+    pub middle_click: TrayAction,
+}
+
+impl Default for TrayActions {
+    fn default() -> Self {
+        Self {
+            left_click: TrayAction::TogglePopup,
+            double_click: TrayAction::Nothing,
+            middle_click: TrayAction::Nothing,
+        }
+    }
+}
+
+/// Which suppressions notify; both off keeps messages silent as before
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SuppressedNotifications {
+    /// Messages queued while muted (held until unmuted)
+    pub muted: bool,
+    /// Watcher alerts during quiet hours, and messages suppressed by Focus, instead of one
+    /// plain notification each
+    pub quiet_hours: bool,
+}
+
+impl Default for TrayConfig {
+    fn default() -> Self {
+        Self {
+            muted: false,
+            show_queue_count: true,
+            start_at_login: false,
+            stop_hotkey: "CmdOrCtrl+Shift+S".to_string(),
+            mute_hotkey: "CmdOrCtrl+Shift+M".to_string(),
+            clipboard_hotkey: String::new(),
+            mute_during_focus: false,
+            focus_summary: true,
+            notify_suppressed: SuppressedNotifications::default(),
+            copy_format: "{text}".to_string(),
+            icon_theme: "auto".to_string(),
+            actions: TrayActions::default(),
+            snooze_minutes: vec![15, 30, 60],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AppConfig, ConfigView, Diagnostic};
+
+    #[test]
+    fn test_mqtt_config_default() {
+        let config = MqttConfig::default();
+        assert_eq!(config.broker, "127.0.0.1");
+        assert_eq!(config.port, 1883);
+        assert_eq!(config.topic_speak, "voice/speak");
+        assert_eq!(config.topic_status, "voice/status");
+    }
+
+    #[test]
+    fn test_mqtt_config_serialization() {
+        let config = MqttConfig {
+            broker: "mqtt.example.com".to_string(),
+            port: 8883,
+            topic_speak: "custom/speak".to_string(),
+            topic_status: "custom/status".to_string(),
+            username: Some("user".to_string()),
+            password: Some("pass".to_string()),
+            enabled: false,
+            use_keyring: false,
+        };
+
+        let json = serde_json::to_string(&config).expect("serialize");
+        let parsed: MqttConfig = serde_json::from_str(&json).expect("deserialize");
+
+        assert_eq!(parsed.broker, config.broker);
+        assert_eq!(parsed.port, config.port);
+        assert_eq!(parsed.topic_speak, config.topic_speak);
+        assert_eq!(parsed.topic_status, config.topic_status);
+        assert!(!parsed.enabled);
+
+        // Files written before the enabled flag keep connecting
+        let legacy: MqttConfig = serde_json::from_str(
+            r#"{"broker":"b","port":1883,"topic_speak":"s","topic_status":"t"}"#,
+        ).expect("deserialize legacy");
+        assert!(legacy.enabled);
+    }
+
+    #[test]
+    fn test_tray_config_partial_file() {
+        let config: TrayConfig = serde_json::from_str(r#"{"muted": true}"#).expect("parse");
+        assert!(config.muted);
+        assert!(config.show_queue_count);
+        assert_eq!(config.actions, TrayActions::default());
+
+        let config: TrayConfig = serde_json::from_str(r#"{"actions": {"double_click": "repeat_last"}}"#).expect("parse");
+        assert_eq!(config.actions.left_click, TrayAction::TogglePopup);
+        assert_eq!(config.actions.double_click, TrayAction::RepeatLast);
+        assert_eq!(config.actions.middle_click, TrayAction::Nothing);
+    }
+
+    #[test]
+    fn test_ntfy_topics() {
+        let topic = |server: &str, name: &str| NtfyTopic { server: server.to_string(), topic: name.to_string(), token: None };
+        let mut config = AppConfig::default();
+        config.ntfy.topics = vec![
+            NtfyTopic { token: Some("tk_secret".to_string()), ..topic("https://ntfy.sh", "backups") },
+            topic("https://ntfy.sh", "backups"),
+            topic("ftp://files.local", "ci"),
+            topic("https://ntfy.sh", "no spaces"),
+        ];
+        let fields: Vec<_> = config.validate().into_iter().filter(Diagnostic::is_error).map(|d| d.field_path).collect();
+        assert_eq!(fields, ["ntfy.topics[1].topic", "ntfy.topics[2].server", "ntfy.topics[3].topic"]);
+
+        // Tokens are redacted like the MQTT password, and a redacted one saves the stored token
+        let stored = config.ntfy.clone();
+        let json = serde_json::to_string(&ConfigView::of(config)).expect("serialize");
+        assert!(!json.contains("tk_secret"));
+        let mut returned: AppConfig = serde_json::from_str(&json).expect("view reads as settings");
+        assert_eq!(returned.ntfy.topics[0].token.as_deref(), Some(REDACTED_PASSWORD));
+        returned.ntfy.restore_tokens(&stored);
+        assert_eq!(returned.ntfy, stored);
+        returned.ntfy.topics[0].token = Some(String::new());
+        returned.ntfy.restore_tokens(&stored);
+        assert_eq!(returned.ntfy.topics[0].token, None, "an empty token clears the stored one");
+    }
+
+    #[test]
+    fn test_relay_settings() {
+        let relay_errors = |url: &str| {
+            let config = AppConfig { relay: RelayConfig { enabled: true, url: url.to_string(), token: None }, ..AppConfig::default() };
+            config.validate().into_iter().filter(|d| d.is_error()).map(|d| d.message).collect::<Vec<_>>()
+        };
+        assert!(relay_errors("http://laptop.local:37779").is_empty());
+        assert_eq!(relay_errors(""), ["Peer must be an http:// or https:// URL"]);
+        // Relaying to itself would only loop; another port on this machine is another instance
+        assert_eq!(relay_errors("http://127.0.0.1:37779"), ["Peer must be another Voice Tray, not this one"]);
+        assert_eq!(relay_errors("http://localhost:37779/"), ["Peer must be another Voice Tray, not this one"]);
+        assert!(relay_errors("http://[::1]:37780").is_empty());
+
+        let mut config = AppConfig::default();
+        config.relay.token = Some("tk_relay".to_string());
+        let stored = config.relay.clone();
+        let json = serde_json::to_string(&ConfigView::of(config)).expect("serialize");
+        assert!(!json.contains("tk_relay"));
+        let mut returned: AppConfig = serde_json::from_str(&json).expect("view reads as settings");
+        returned.relay.restore_token(&stored);
+        assert_eq!(returned.relay, stored);
+    }
+
+    #[test]
+    fn test_mirror_destinations() {
+        let destination = |url: &str, format: &str| MirrorDestination { url: url.to_string(), format: format.to_string() };
+        let mut config = AppConfig::default();
+        config.mirror.destinations = vec![
+            destination("https://hooks.slack.com/services/T0/B1/one", "slack"),
+            destination("https://hooks.slack.com/services/T0/B2/two", "slack"),
+            destination("https://hooks.slack.com/services/T0/B2/two", "slack"),
+            destination("ftp://example.com/hook", "discord"),
+            destination("https://chat.example.com:8065/hooks/x", "teams"),
+        ];
+        let fields: Vec<String> = config.validate().into_iter().filter(|d| d.is_error()).map(|d| d.field_path).collect();
+        assert_eq!(fields, ["mirror.destinations[2].url", "mirror.destinations[3].url", "mirror.destinations[4].format"]);
+
+        config.mirror.destinations.truncate(2);
+        config.mirror.destinations.push(destination("https://chat.example.com:8065/hooks/x", "slack"));
+        let stored = config.mirror.clone();
+        let json = serde_json::to_string(&ConfigView::of(config)).expect("serialize");
+        assert!(!json.contains("services") && !json.contains("hooks/x"));
+        let mut returned: AppConfig = serde_json::from_str(&json).expect("view reads as settings");
+        assert_eq!(returned.mirror.destinations[2].url, "https://chat.example.com:8065/********");
+        // Two webhooks on one host redact the same, and are told apart by position
+        assert!(returned.validate().iter().all(|d| !d.is_error()));
+        returned.mirror.restore_urls(&stored);
+        assert_eq!(returned.mirror, stored);
+
+        let mut imported = stored.clone().redacted();
+        imported.destinations.push(destination("https://discord.com/********", "discord"));
+        imported.restore_urls(&stored);
+        assert_eq!(imported, stored, "a redacted webhook this machine does not have is left out");
+    }
+
+    #[test]
+    fn test_voice_follows_language() {
+        let thai = VoiceConfig { language: "th".to_string(), ..VoiceConfig::default() };
+        assert_eq!(thai.voice(), "Kanya");
+        let pinned = VoiceConfig { default_voice: "Daniel".to_string(), ..thai.clone() };
+        assert_eq!(pinned.voice(), "Daniel");
+
+        let config = AppConfig { voice: VoiceConfig { language: "thai!".to_string(), ..thai }, ..AppConfig::default() };
+        assert!(config.validate().iter().any(|d| d.field_path == "voice.language"));
+    }
+}
@@ -0,0 +1,490 @@
+/// The watcher section: the coding CLIs whose session logs are followed, what their events
+/// say, and how and when they are spoken.
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::Diagnostic;
+use crate::i18n::{self, Phrase, REMINDERS};
+
+/// Session watcher configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatcherConfig {
+    /// Announce session events (false = paused; offsets are still tracked)
+    pub enabled: bool,
+    /// Seconds between repeated approval reminders
+    pub reminder_interval_secs: u64,
+    /// Reminders after the first approval alert (0 disables repeats)
+    pub reminder_max: u32,
+    /// First alert, then escalating reminders; the last phrase repeats
+    pub reminder_phrases: Vec<String>,
+    /// Voice for reminders (first alert keeps the default voice)
+    pub reminder_voice: Option<String>,
+    /// Spoken when Claude asks a question; `{project}` is the session's project name
+    pub question_phrase: String,
+    /// Spoken when Claude presents a plan for approval
+    pub plan_phrase: String,
+    /// Treat a turn ending with '?' as a question rather than a plain completion
+    pub detect_trailing_questions: bool,
+    /// Repeated completions of one session within this window are announced once
+    pub completion_window_ms: u64,
+    /// What to say when several sessions complete close together
+    pub completion_grouping: CompletionGrouping,
+    /// With `aggregate`, completions this soon after an announced one are collected into one follow-up
+    pub aggregate_window_ms: u64,
+    /// Follow-up for the collected completions; `{count}` and `{projects}` are filled in
+    pub aggregate_phrase: String,
+    /// Announce when Claude Code compacts a session's context
+    pub announce_compaction: bool,
+    pub compaction_phrase: String,
+    /// Announce context-limit warnings and usage limits
+    pub announce_limits: bool,
+    pub context_limit_phrase: String,
+    pub usage_limit_phrase: String,
+    /// At most one compaction/limit announcement of each kind per session within this many seconds
+    pub notice_cooldown_secs: u64,
+    /// Tools whose runs are timed for "still running" alerts
+    pub long_running_tools: Vec<String>,
+    /// First "still running" alert after this many seconds without a tool result
+    pub long_running_after_secs: u64,
+    /// Seconds between repeated "still running" alerts
+    pub long_running_interval_secs: u64,
+    /// Repeats after the first "still running" alert
+    pub long_running_max: u32,
+    /// `{project}` and `{duration}` ("five minutes") are filled in
+    pub long_running_phrase: String,
+    /// Give up on a command once its session log has been untouched this long
+    pub long_running_inactive_secs: u64,
+    /// Announce failed tool results and API errors
+    pub announce_errors: bool,
+    /// Spoken when a tool result comes back with is_error
+    pub error_phrase: String,
+    /// Spoken when Claude Code records an API error (overloaded, rate limited, ...)
+    pub api_error_phrase: String,
+    /// At most one error announcement per session within this many seconds
+    pub error_cooldown_secs: u64,
+    /// Session log lines longer than this are skipped instead of parsed
+    pub max_line_bytes: usize,
+    /// Append the turn's token count (or cost, with `usage_prices`) to completion announcements
+    pub announce_usage: bool,
+    /// USD per million tokens, keyed by a substring of the model id (e.g. "opus")
+    pub usage_prices: HashMap<String, TokenPrices>,
+    /// Stop tracking session files untouched for this long; they are picked up again at EOF on the next write
+    pub idle_untrack_secs: u64,
+    /// Only announce these projects (globs over project names); beats `exclude`
+    pub include: Vec<String>,
+    /// Never announce these projects; events are still tracked
+    pub exclude: Vec<String>,
+    /// Voice, rate and priority per event kind (as in the event log: "completion", "approval_alert", ...)
+    pub voices: HashMap<String, VoiceStyle>,
+    /// Per-project voice settings; these win over `voices`
+    pub project_voices: HashMap<String, VoiceStyle>,
+    /// Voice, system notification, or both, per event kind (default voice)
+    pub delivery: HashMap<String, Delivery>,
+    /// Local "HH:MM" range in which alerts are shown as notifications only
+    pub quiet_hours: Option<QuietHours>,
+    /// Extra CLIs to watch; a profile named "claude" replaces the built-in one
+    pub profiles: Vec<WatcherProfile>,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            reminder_interval_secs: 60,
+            reminder_max: 3,
+            reminder_phrases: REMINDERS.iter().map(|p| p.english().to_string()).collect(),
+            reminder_voice: None,
+            question_phrase: Phrase::Question.english().to_string(),
+            plan_phrase: Phrase::Plan.english().to_string(),
+            detect_trailing_questions: true,
+            completion_window_ms: 2000,
+            completion_grouping: CompletionGrouping::Each,
+            aggregate_window_ms: 20_000,
+            aggregate_phrase: Phrase::Aggregate.english().to_string(),
+            announce_compaction: true,
+            compaction_phrase: Phrase::Compaction.english().to_string(),
+            announce_limits: true,
+            context_limit_phrase: Phrase::ContextLimit.english().to_string(),
+            usage_limit_phrase: Phrase::UsageLimit.english().to_string(),
+            notice_cooldown_secs: 300,
+            long_running_tools: vec!["Bash".to_string()],
+            long_running_after_secs: 300,
+            long_running_interval_secs: 600,
+            long_running_max: 2,
+            long_running_phrase: Phrase::LongRunning.english().to_string(),
+            long_running_inactive_secs: 3600,
+            announce_errors: true,
+            error_phrase: Phrase::CommandFailed.english().to_string(),
+            api_error_phrase: Phrase::ApiError.english().to_string(),
+            error_cooldown_secs: 120,
+            max_line_bytes: 16 * 1024 * 1024,
+            announce_usage: false,
+            usage_prices: HashMap::new(),
+            idle_untrack_secs: 1800,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            voices: HashMap::new(),
+            project_voices: HashMap::new(),
+            delivery: HashMap::new(),
+            quiet_hours: None,
+            profiles: Vec::new(),
+        }
+    }
+}
+
+impl WatcherConfig {
+    /// Field errors for the settings window, named like `watcher.profiles.0.path`
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut errors = Vec::new();
+        let mut check = |ok: bool, field: String, message: &str| {
+            if !ok {
+                errors.push(Diagnostic::error(field, message));
+            }
+        };
+        check(self.reminder_interval_secs > 0, "watcher.reminder_interval_secs".to_string(), "Reminder interval must be at least 1 second");
+        for (field, patterns) in [("watcher.include", &self.include), ("watcher.exclude", &self.exclude)] {
+            check(
+                patterns.iter().all(|p| glob::Pattern::new(p).is_ok()),
+                field.to_string(),
+                "Project patterns must be valid globs",
+            );
+        }
+        if let Some(quiet) = &self.quiet_hours {
+            let valid = |s: &str| NaiveTime::parse_from_str(s, "%H:%M").is_ok();
+            check(valid(&quiet.start) && valid(&quiet.end), "watcher.quiet_hours".to_string(), "Quiet hours must be HH:MM");
+        }
+        for (i, profile) in self.profiles.iter().enumerate() {
+            check(!profile.name.trim().is_empty(), format!("watcher.profiles.{}.name", i), "Profile name is required");
+            // A disabled profile named "claude" only switches the built-in one off and needs no path
+            if profile.enabled {
+                check(
+                    !profile.path.trim().is_empty() && glob::Pattern::new(&profile.path).is_ok(),
+                    format!("watcher.profiles.{}.path", i),
+                    "Path must be a valid glob of log files",
+                );
+            }
+        }
+        errors
+    }
+
+    /// Phrase for the nth alert of an approval (0 = first alert), in voice.language unless changed
+    pub fn reminder_phrase(&self, n: u32) -> String {
+        let i = (n as usize).min(self.reminder_phrases.len().saturating_sub(1));
+        match self.reminder_phrases.get(i) {
+            Some(configured) => i18n::phrase_or(REMINDERS[i.min(REMINDERS.len() - 1)], configured),
+            None => i18n::phrase(Phrase::ApprovalNeeded),
+        }
+    }
+
+    /// Whether announcements for a project may be spoken. A project matching `include` is
+    /// always allowed; otherwise a non-empty `include` rejects it, and `exclude` is checked last.
+    pub fn project_allowed(&self, project: &str) -> bool {
+        let matches = |patterns: &[String]| patterns.iter().any(|p| {
+            glob::Pattern::new(p).map(|g| g.matches(project)).unwrap_or(p == project)
+        });
+        if matches(&self.include) {
+            return true;
+        }
+        self.include.is_empty() && !matches(&self.exclude)
+    }
+
+    /// Voice for an announcement of `kind`: built-in rate and priority, then the profile's voice,
+    /// then `voices[kind]`, then `project_voices[project]`
+    pub fn voice_style(&self, kind: &str, project: &str, profile_voice: Option<&str>) -> SpeechStyle {
+        let (rate, priority) = match kind {
+            "question" => (220, 1),
+            "subagent_spawn" | "tool_error" | "api_error" => (230, 0),
+            "approval_alert" => (240, 0),
+            _ => (220, 0),
+        };
+        let mut style = SpeechStyle { voice: profile_voice.map(str::to_string), rate, priority };
+        for layer in [self.voices.get(kind), self.project_voices.get(project)].into_iter().flatten() {
+            if let Some(voice) = &layer.voice {
+                style.voice = Some(voice.clone());
+            }
+            style.rate = layer.rate.unwrap_or(style.rate);
+            style.priority = layer.priority.unwrap_or(style.priority);
+        }
+        style
+    }
+
+    /// How an alert of `kind` is delivered when not muted or in quiet hours
+    pub fn delivery_for(&self, kind: &str) -> Delivery {
+        self.delivery.get(kind).copied().unwrap_or_default()
+    }
+
+    /// Price table for a model id; the longest matching key wins
+    pub fn prices_for(&self, model: &str) -> Option<&TokenPrices> {
+        self.usage_prices.iter()
+            .filter(|(key, _)| model.contains(key.as_str()))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, prices)| prices)
+    }
+
+    /// Enabled profiles: the built-in Claude profile (unless overridden) followed by user profiles
+    pub fn active_profiles(&self) -> Vec<WatcherProfile> {
+        let mut profiles = Vec::new();
+        if !self.profiles.iter().any(|p| p.name == "claude") {
+            profiles.push(WatcherProfile::claude());
+        }
+        profiles.extend(self.profiles.iter().cloned());
+        profiles.retain(|p| p.enabled && !p.path.is_empty());
+        for profile in &mut profiles {
+            if profile.agent.is_empty() {
+                profile.agent = profile.name.clone();
+            }
+        }
+        profiles
+    }
+}
+
+/// How near-simultaneous completions from different sessions are announced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompletionGrouping {
+    #[default]
+    Each,      // one announcement per session, naming the project when another just finished
+    Aggregate, // first one spoken at once, the rest of the window summed up in one follow-up
+}
+
+/// Voice overrides for an event kind or project; unset fields fall through
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceStyle {
+    pub voice: Option<String>,
+    pub rate: Option<u32>,
+    pub priority: Option<u8>,
+}
+
+/// Voice settings resolved for one announcement
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeechStyle {
+    pub voice: Option<String>, // None = default voice
+    pub rate: u32,
+    pub priority: u8,
+}
+
+/// Where a watcher alert goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Delivery {
+    #[default]
+    Voice,
+    Notification,
+    Both,
+}
+
+/// Daily quiet period; `end` before `start` wraps past midnight
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: String, // "22:00"
+    pub end: String,   // "07:00"
+}
+
+impl QuietHours {
+    /// Whether `time` falls in the range. Unparsable times never match.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        let parse = |s: &str| NaiveTime::parse_from_str(s, "%H:%M").ok();
+        match (parse(&self.start), parse(&self.end)) {
+            (Some(start), Some(end)) if start <= end => time >= start && time < end,
+            (Some(start), Some(end)) => time >= start || time < end,
+            _ => false,
+        }
+    }
+}
+
+/// USD per million tokens for one model family
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokenPrices {
+    pub input: f64,
+    pub output: f64,
+    pub cache_write: f64,
+    pub cache_read: f64,
+}
+
+/// How a profile's session logs are parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    #[default]
+    ClaudeJsonl, // Claude Code transcript schema
+    Mapped,      // any JSON-lines log, events matched by `mapping`
+}
+
+/// Matches a log line when the value at `pointer` exists and, if set, equals `equals`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonMatch {
+    pub pointer: String,
+    #[serde(default)]
+    pub equals: Option<String>,
+}
+
+/// JSON pointers that turn log lines into watcher events (for `mapped` profiles)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventMapping {
+    pub completion: Vec<JsonMatch>,
+    pub tool_use: Vec<JsonMatch>,
+    pub tool_result: Vec<JsonMatch>,
+    pub error: Vec<JsonMatch>,
+    /// Pointer to the tool name on tool_use lines
+    pub tool_name: Option<String>,
+    /// Pointer to the error message on error lines
+    pub error_text: Option<String>,
+    /// Pointer to the session's working directory
+    pub cwd: Option<String>,
+}
+
+/// One coding CLI whose session logs the watcher follows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatcherProfile {
+    pub name: String,
+    pub enabled: bool,
+    /// Glob of log files; a leading `~` is the home directory
+    pub path: String,
+    pub format: LogFormat,
+    /// Agent shown in the timeline (defaults to the profile name)
+    pub agent: String,
+    pub voice: Option<String>,
+    /// Spoken when a turn completes; `{project}` is the session's project name
+    pub completion_phrase: String,
+    /// Overrides `error_phrase` for this profile
+    pub error_phrase: Option<String>,
+    pub mapping: EventMapping,
+}
+
+impl Default for WatcherProfile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            path: String::new(),
+            format: LogFormat::Mapped,
+            agent: String::new(),
+            voice: None,
+            completion_phrase: Phrase::Done.english().to_string(),
+            error_phrase: None,
+            mapping: EventMapping::default(),
+        }
+    }
+}
+
+impl WatcherProfile {
+    /// Built-in profile for Claude Code transcripts
+    pub fn claude() -> Self {
+        Self {
+            name: "claude".to_string(),
+            path: "~/.claude/projects/**/*.jsonl".to_string(),
+            format: LogFormat::ClaudeJsonl,
+            agent: "claude".to_string(),
+            completion_phrase: Phrase::ClaudeStop.english().to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// completion_phrase, in voice.language unless changed
+    pub fn completion_template(&self) -> String {
+        match self.completion_phrase.as_str() {
+            phrase if phrase == Phrase::ClaudeStop.english() => i18n::phrase(Phrase::ClaudeStop),
+            phrase => i18n::phrase_or(Phrase::Done, phrase),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watcher_config_partial_file() {
+        let config: WatcherConfig = serde_json::from_str(r#"{"reminder_max":1}"#).expect("parse");
+        assert_eq!(config.reminder_max, 1);
+        assert_eq!(config.reminder_interval_secs, 60);
+        assert_eq!(config.reminder_phrase(0), "Action needed, please approve");
+        assert_eq!(config.reminder_phrase(9), config.reminder_phrases[2]);
+
+        let empty = WatcherConfig { reminder_phrases: vec![], ..WatcherConfig::default() };
+        assert_eq!(empty.reminder_phrase(2), "Action needed, please approve");
+    }
+
+    #[test]
+    fn test_active_profiles() {
+        let config = WatcherConfig::default();
+        let profiles = config.active_profiles();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].format, LogFormat::ClaudeJsonl);
+
+        let config: WatcherConfig = serde_json::from_str(r#"{"profiles":[
+            {"name":"codex","path":"~/.codex/sessions/**/*.jsonl",
+             "mapping":{"completion":[{"pointer":"/payload/type","equals":"task_complete"}]}},
+            {"name":"claude","enabled":false}
+        ]}"#).expect("parse");
+        let profiles = config.active_profiles();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].name, "codex");
+        assert_eq!(profiles[0].agent, "codex");
+        assert_eq!(profiles[0].format, LogFormat::Mapped);
+        assert_eq!(profiles[0].mapping.completion[0].equals.as_deref(), Some("task_complete"));
+    }
+
+    #[test]
+    fn test_project_allowed() {
+        let config = WatcherConfig::default();
+        assert!(config.project_allowed("anything"));
+
+        let config = WatcherConfig { exclude: vec!["oss-*".to_string(), "tokio".to_string()], ..WatcherConfig::default() };
+        assert!(!config.project_allowed("oss-serde"));
+        assert!(!config.project_allowed("tokio"));
+        assert!(config.project_allowed("oracle"));
+
+        let config = WatcherConfig {
+            include: vec!["oracle".to_string(), "oss-mine".to_string()],
+            exclude: vec!["oss-*".to_string()],
+            ..WatcherConfig::default()
+        };
+        assert!(config.project_allowed("oss-mine"), "include beats exclude");
+        assert!(config.project_allowed("oracle"));
+        assert!(!config.project_allowed("dashboard"));
+    }
+
+    #[test]
+    fn test_voice_style_layers() {
+        let config = WatcherConfig::default();
+        assert_eq!(config.voice_style("completion", "oracle", None), SpeechStyle { voice: None, rate: 220, priority: 0 });
+        assert_eq!(config.voice_style("question", "oracle", None).priority, 1);
+        assert_eq!(config.voice_style("approval_alert", "oracle", Some("Daniel")).voice.as_deref(), Some("Daniel"));
+
+        let config: WatcherConfig = serde_json::from_str(r#"{
+            "voices": {"approval_alert": {"voice": "Fred", "rate": 170, "priority": 2}},
+            "project_voices": {"infra": {"voice": "Karen"}}
+        }"#).expect("parse");
+        let style = config.voice_style("approval_alert", "oracle", Some("Daniel"));
+        assert_eq!(style, SpeechStyle { voice: Some("Fred".to_string()), rate: 170, priority: 2 });
+        let style = config.voice_style("approval_alert", "infra", None);
+        assert_eq!(style, SpeechStyle { voice: Some("Karen".to_string()), rate: 170, priority: 2 }, "project wins");
+        assert_eq!(config.voice_style("completion", "infra", None).rate, 220);
+    }
+
+    #[test]
+    fn test_quiet_hours_and_delivery() {
+        let at = |s| NaiveTime::parse_from_str(s, "%H:%M").expect("time");
+        let night = QuietHours { start: "22:00".to_string(), end: "07:00".to_string() };
+        assert!(night.contains(at("23:30")));
+        assert!(night.contains(at("06:59")));
+        assert!(!night.contains(at("07:00")));
+        assert!(!night.contains(at("12:00")));
+        let lunch = QuietHours { start: "12:00".to_string(), end: "13:00".to_string() };
+        assert!(lunch.contains(at("12:30")));
+        assert!(!lunch.contains(at("21:00")));
+        let broken = QuietHours { start: "late".to_string(), end: "07:00".to_string() };
+        assert!(!broken.contains(at("23:00")));
+
+        let config: WatcherConfig = serde_json::from_str(r#"{"delivery":{"approval_alert":"both"}}"#).expect("parse");
+        assert_eq!(config.delivery_for("approval_alert"), Delivery::Both);
+        assert_eq!(config.delivery_for("completion"), Delivery::Voice);
+    }
+}
@@ -0,0 +1,164 @@
+/// oraclevoice:// links, for Shortcuts, Raycast and bookmarklets that do not know the HTTP port:
+/// `speak?text=..&voice=..&rate=..&agent=..`, `mute` (toggles) and `show` (the popup).
+///
+/// The scheme is declared in tauri.conf.json for tauri-plugin-deep-link: the bundle puts it in
+/// Info.plist on macOS, and on Windows and Linux register_all() points it at this executable at
+/// each start, so it follows the app when it moves. Links reach on_open_url whether they open
+/// the running app or, on Windows and Linux, a second launch that the single-instance plugin
+/// hands over; the link this launch was started with is read with get_current().
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tracing::{info, warn};
+
+use crate::notifications;
+use crate::state::{AppState, SpeakRequest};
+use crate::tray;
+
+pub const SCHEME: &str = "oraclevoice";
+
+#[derive(Debug)]
+pub enum Action {
+    Speak(SpeakRequest),
+    Mute,
+    Show,
+}
+
+impl Action {
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Speak(_) => "speak",
+            Action::Mute => "mute",
+            Action::Show => "show",
+        }
+    }
+}
+
+/// The action of an oraclevoice:// link. Speak requests get the same checks as POST /speak.
+pub fn parse(link: &str) -> Result<Action, String> {
+    let url = Url::parse(link).map_err(|e| format!("not a valid link: {}", e))?;
+    if url.scheme() != SCHEME {
+        return Err(format!("not an {}:// link", SCHEME));
+    }
+    // oraclevoice://speak has the action as the host; oraclevoice:speak as the path
+    let action = url.host_str().unwrap_or_else(|| url.path()).trim_matches('/').to_ascii_lowercase();
+    let param = |name: &str| url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned());
+    match action.as_str() {
+        "speak" => {
+            let rate = match param("rate") {
+                Some(rate) => Some(rate.parse().map_err(|_| format!("rate must be a number, not \"{}\"", rate))?),
+                None => None,
+            };
+            let req = SpeakRequest {
+                text: param("text").unwrap_or_default(),
+                voice: param("voice").filter(|v| !v.is_empty()),
+                agent: param("agent").filter(|a| !a.is_empty()),
+                rate,
+            };
+            req.validate()?;
+            Ok(Action::Speak(req))
+        }
+        "mute" => Ok(Action::Mute),
+        "show" => Ok(Action::Show),
+        "" => Err("the link has no action; use speak, mute or show".to_string()),
+        other => Err(format!("unknown action \"{}\"; use speak, mute or show", other)),
+    }
+}
+
+/// The oraclevoice:// links among a launch's arguments
+pub fn links_in(args: &[String]) -> Vec<&str> {
+    let prefix = format!("{}:", SCHEME);
+    args.iter()
+        .map(String::as_str)
+        .filter(|arg| arg.get(..prefix.len()).is_some_and(|start| start.eq_ignore_ascii_case(&prefix)))
+        .collect()
+}
+
+/// Carry out a link; one that does not parse is shown as a notification
+pub fn handle(app: &AppHandle, link: &str) {
+    let state = app.state::<Arc<AppState>>();
+    let action = match parse(link) {
+        Ok(action) => action,
+        Err(error) => {
+            warn!("Ignoring link {}: {}", link, error);
+            notifications::notify(&state, "Link not understood", &format!("{}: {}", link, error));
+            return;
+        }
+    };
+    info!("Link: {}", action.name());
+    match action {
+        Action::Speak(req) => {
+            state.speak(req);
+        }
+        Action::Mute => {
+            if let Err(e) = tray::set_muted(&state, !state.is_muted()) {
+                warn!("Failed to save mute from a link: {}", e);
+            }
+        }
+        Action::Show => crate::show_popup_at_tray(app),
+    }
+}
+
+/// Carry out links from now on, starting with the one this launch was started with
+pub fn listen(app: &AppHandle) {
+    // Registering runs xdg-mime on Linux; the links themselves do not wait for it
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    {
+        let app = app.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = app.deep_link().register_all() {
+                warn!("Failed to register {}:// links: {}", SCHEME, e);
+            }
+        });
+    }
+    let handler = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            handle(&handler, url.as_str());
+        }
+    });
+    match app.deep_link().get_current() {
+        Ok(urls) => {
+            for url in urls.unwrap_or_default() {
+                handle(app, url.as_str());
+            }
+        }
+        Err(e) => warn!("Cannot read the link this launch was started with: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_speak() {
+        let Ok(Action::Speak(req)) = parse("oraclevoice://speak?text=Build%20passed+today&voice=Daniel&rate=200&agent=ci") else {
+            panic!("speak link not parsed");
+        };
+        assert_eq!(req.text, "Build passed today");
+        assert_eq!((req.voice.as_deref(), req.rate, req.agent.as_deref()), (Some("Daniel"), Some(200), Some("ci")));
+
+        let Ok(Action::Speak(req)) = parse("oraclevoice:speak?text=Hi&voice=") else {
+            panic!("path form not parsed");
+        };
+        assert_eq!((req.text.as_str(), req.voice), ("Hi", None));
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_links() {
+        assert!(matches!(parse("oraclevoice://MUTE"), Ok(Action::Mute)));
+        assert!(matches!(parse("oraclevoice://show/"), Ok(Action::Show)));
+        assert_eq!(parse("oraclevoice://speak?text=%20%20").map(|a| a.name()), Err("text must not be empty".to_string()));
+        assert!(parse("oraclevoice://speak?text=Hi&rate=fast").is_err_and(|e| e.contains("rate")));
+        assert!(parse("oraclevoice://dance").is_err_and(|e| e.contains("unknown action \"dance\"")));
+        assert!(parse("https://speak?text=Hi").is_err());
+        assert!(parse("not a link").is_err());
+    }
+
+    #[test]
+    fn test_links_in_args() {
+        let args = ["/usr/bin/voice-tray".to_string(), "--flag".to_string(), "OracleVoice://show".to_string()];
+        assert_eq!(links_in(&args), vec!["OracleVoice://show"]);
+    }
+}
@@ -0,0 +1,412 @@
+/// Self-test ("doctor"): is it the speech command, the HTTP port, the broker, the watched
+/// directories, config.json or the icons? Each check runs on its own thread with its own time
+/// limit, so one that hangs is reported as failed without holding up the rest. Runs shortly
+/// after startup, from the settings window (run_diagnostics) and as `voice-tray-v2 --doctor`;
+/// GET /health shows the last result.
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::{self, config_warning, load_config, load_mqtt_config, MqttConfig, VoiceConfig, WatcherConfig};
+use crate::i18n::{self, Phrase};
+use crate::mqtt;
+use crate::state::AppState;
+use crate::tasks;
+use crate::theme;
+use crate::tray;
+use crate::voices;
+use crate::watcher::{expand_home, glob_root};
+
+/// Time for the services to start before the startup run, so it sees them as they settle
+const STARTUP_DELAY: Duration = Duration::from_secs(3);
+/// Limit for a check that only looks at local things
+const LOCAL_TIMEOUT: Duration = Duration::from_secs(5);
+/// Limit for the broker connection test
+const MQTT_TIMEOUT: Duration = Duration::from_secs(8);
+/// Longest the spoken sample may take, on top of LOCAL_TIMEOUT
+const SAMPLE_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[cfg(target_os = "macos")]
+const TTS_HINT: &str = "say ships with macOS; check that /usr/bin/say exists and runs in Terminal";
+#[cfg(target_os = "linux")]
+const TTS_HINT: &str = "Install espeak, e.g. sudo apt install espeak";
+#[cfg(target_os = "windows")]
+const TTS_HINT: &str = "Speech goes through PowerShell; check that powershell.exe starts";
+
+/// Ordered by severity: a report is as bad as its worst check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub message: String,
+    /// What to do about a warning or failure
+    pub hint: Option<String>,
+    pub took_ms: u64,
+}
+
+impl Check {
+    fn new(outcome: Outcome, message: String, hint: Option<String>) -> Self {
+        Self { name: "", outcome, message, hint, took_ms: 0 }
+    }
+
+    fn pass(message: impl Into<String>) -> Self {
+        Self::new(Outcome::Pass, message.into(), None)
+    }
+
+    fn warn(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self::new(Outcome::Warn, message.into(), Some(hint.into()))
+    }
+
+    fn fail(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self::new(Outcome::Fail, message.into(), Some(hint.into()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub at: DateTime<Utc>,
+    /// The worst outcome of the checks
+    pub outcome: Outcome,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    fn of(checks: Vec<Check>) -> Self {
+        let outcome = checks.iter().map(|c| c.outcome).max().unwrap_or(Outcome::Pass);
+        Self { at: Utc::now(), outcome, checks }
+    }
+
+    /// One line per check, with its hint under it, as `--doctor` prints it
+    pub fn to_text(&self) -> String {
+        let mut lines = Vec::new();
+        for check in &self.checks {
+            let label = match check.outcome {
+                Outcome::Pass => "PASS",
+                Outcome::Warn => "WARN",
+                Outcome::Fail => "FAIL",
+            };
+            lines.push(format!("{}  {:<8} {}", label, check.name, check.message));
+            if let Some(hint) = &check.hint {
+                lines.push(format!("      {:<8} {}", "", hint));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// What the checks look at, from the running app or from config.json alone
+struct Setup {
+    voice: VoiceConfig,
+    mqtt: MqttConfig,
+    watcher: WatcherConfig,
+    http_port: u16,
+    /// The app's HTTP server is running: the port is expected to be taken, by us
+    http_serving: bool,
+    http_error: Option<String>,
+    mqtt_connected: bool,
+}
+
+impl Setup {
+    fn of_app(state: &AppState) -> Self {
+        Self {
+            voice: state.voice_config(),
+            mqtt: load_mqtt_config(),
+            watcher: load_config().watcher,
+            http_port: *state.http_port.lock(),
+            http_serving: tasks::is_running(state, "http"),
+            http_error: state.http_error.lock().clone(),
+            mqtt_connected: *state.mqtt_status.read() == "connected",
+        }
+    }
+
+    fn of_config() -> Self {
+        let config = load_config();
+        Self {
+            voice: config.voice,
+            mqtt: load_mqtt_config(),
+            watcher: config.watcher,
+            http_port: config.http.port,
+            http_serving: false,
+            http_error: None,
+            mqtt_connected: false,
+        }
+    }
+}
+
+type Probe = Box<dyn FnOnce() -> Check + Send>;
+
+/// Run the probes side by side; a probe that has not answered within its limit fails
+fn run_all(probes: Vec<(&'static str, Duration, Probe)>) -> Vec<Check> {
+    let started = Instant::now();
+    let pending: Vec<_> = probes.into_iter()
+        .map(|(name, limit, probe)| {
+            let (tx, rx) = mpsc::channel();
+            std::thread::spawn(move || {
+                let began = Instant::now();
+                let check = probe();
+                // Nobody listens once the limit has passed, and then the answer is not needed
+                let _ = tx.send(Check { took_ms: began.elapsed().as_millis() as u64, ..check });
+            });
+            (name, limit, rx)
+        })
+        .collect();
+    pending.into_iter()
+        .map(|(name, limit, rx)| {
+            let check = match rx.recv_timeout(limit.saturating_sub(started.elapsed())) {
+                Ok(check) => check,
+                Err(RecvTimeoutError::Timeout) => Check {
+                    took_ms: limit.as_millis() as u64,
+                    ..Check::fail(format!("No answer within {:?}", limit), "Something it depends on hangs; see the log")
+                },
+                Err(RecvTimeoutError::Disconnected) => Check::fail("The check crashed", "See the log for the panic"),
+            };
+            Check { name, ..check }
+        })
+        .collect()
+}
+
+fn run_checks(setup: Setup, speak_sample: bool) -> Report {
+    let Setup { voice, mqtt, watcher, http_port, http_serving, http_error, mqtt_connected } = setup;
+    let tts_limit = if speak_sample { LOCAL_TIMEOUT + SAMPLE_TIMEOUT } else { LOCAL_TIMEOUT };
+    let probes: Vec<(&'static str, Duration, Probe)> = vec![
+        ("speech", tts_limit, Box::new(move || check_tts(&voice, speak_sample))),
+        ("http", LOCAL_TIMEOUT, Box::new(move || check_http(http_port, http_serving, http_error))),
+        ("mqtt", MQTT_TIMEOUT, Box::new(move || check_mqtt(&mqtt, mqtt_connected))),
+        ("watcher", LOCAL_TIMEOUT, Box::new(move || match dirs::home_dir() {
+            Some(home) => check_watcher(&watcher, &home),
+            None => Check::fail("Home directory not found", "Set HOME for the app"),
+        })),
+        ("config", LOCAL_TIMEOUT, Box::new(check_config)),
+        ("icons", LOCAL_TIMEOUT, Box::new(check_icons)),
+    ];
+    Report::of(run_all(probes))
+}
+
+fn log(report: &Report) {
+    for check in &report.checks {
+        match (check.outcome, &check.hint) {
+            (Outcome::Pass, _) | (_, None) => info!("{}: {}", check.name, check.message),
+            (_, Some(hint)) => warn!("{}: {} ({})", check.name, check.message, hint),
+        }
+    }
+}
+
+/// Run every check against the running app, log the results and keep them for GET /health.
+/// Blocks for as long as the slowest check.
+pub fn run(state: &Arc<AppState>, speak_sample: bool) -> Report {
+    let report = run_checks(Setup::of_app(state), speak_sample);
+    log(&report);
+    *state.doctor.lock() = Some(report.clone());
+    report
+}
+
+/// The startup run, once the services have had STARTUP_DELAY to start
+pub fn start(state: Arc<AppState>) {
+    std::thread::spawn(move || {
+        std::thread::sleep(STARTUP_DELAY);
+        let report = run(&state, false);
+        info!("Self-test: {:?}", report.outcome);
+    });
+}
+
+/// `--doctor [--speak]`: the checks without starting the app, printed to stdout. The exit
+/// code is 1 if any check failed.
+pub fn run_cli(speak_sample: bool) -> i32 {
+    let report = run_checks(Setup::of_config(), speak_sample);
+    println!("Oracle Voice Tray {} diagnostics\n{}", env!("CARGO_PKG_VERSION"), report.to_text());
+    i32::from(report.outcome == Outcome::Fail)
+}
+
+fn check_tts(voice: &VoiceConfig, speak_sample: bool) -> Check {
+    if !tray::tts_available() {
+        return Check::fail("The speech command could not be started", TTS_HINT);
+    }
+    let default = voice.voice();
+    let mut message = match voices::list(&default, true) {
+        Ok(list) if !tray::voice_installed(&default) => {
+            return Check::warn(
+                format!("Default voice {} is not installed ({} voices are)", default, list.len()),
+                "Pick an installed voice under Voice in the tray menu or in Settings",
+            );
+        }
+        Ok(list) => format!("{} voices, default {}", list.len(), default),
+        Err(e) => {
+            return Check::warn(format!("Speech starts but the voice list could not be read: {}", e), "Voice names may not be recognized");
+        }
+    };
+    if speak_sample {
+        if let Err(e) = tray::speak_sample(&i18n::phrase(Phrase::Diagnostics), &default, voice.default_rate, SAMPLE_TIMEOUT) {
+            return Check::fail(e.to_string(), "Try the voice and rate in Settings; the log has the command's error");
+        }
+        message.push_str("; spoke a sample");
+    }
+    Check::pass(message)
+}
+
+fn check_http(port: u16, serving: bool, error: Option<String>) -> Check {
+    if serving {
+        return match error {
+            None => Check::pass(format!("Listening on http://127.0.0.1:{}", port)),
+            Some(e) => Check::fail(e, "Another program holds the port; pick a free one under HTTP API in Settings"),
+        };
+    }
+    match std::net::TcpListener::bind(("127.0.0.1", port)) {
+        Ok(_) => Check::pass(format!("Port {} is free; the app will listen on http://127.0.0.1:{}", port, port)),
+        Err(e) => Check::fail(
+            format!("Port {} is not available: {}", port, e),
+            "Expected while the app is running; otherwise another program holds it, so change http.port",
+        ),
+    }
+}
+
+fn check_mqtt(config: &MqttConfig, connected: bool) -> Check {
+    let broker = format!("{}:{}", config.broker, config.port);
+    if !config.enabled {
+        return Check::pass("Turned off in config.json");
+    }
+    if connected {
+        return Check::pass(format!("Connected to {}", broker));
+    }
+    let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(e) => return Check::fail(format!("Could not start the test: {}", e), "See the log"),
+    };
+    match runtime.block_on(mqtt::test_connection(config)) {
+        Ok(()) => Check::pass(format!("Connected to {} and disconnected", broker)),
+        Err(rumqttc::ConnectionError::ConnectionRefused(code)) => Check::fail(
+            format!("{} refused the connection: {:?}", broker, code),
+            "Check the username and password under MQTT in Settings",
+        ),
+        Err(e) => Check::fail(
+            format!("Could not connect to {}: {}", broker, e),
+            "Check that the broker is running and reachable, or turn MQTT off",
+        ),
+    }
+}
+
+fn check_watcher(config: &WatcherConfig, home: &Path) -> Check {
+    if !config.enabled {
+        return Check::pass("Paused in config.json");
+    }
+    let profiles = config.active_profiles();
+    if profiles.is_empty() {
+        return Check::warn("No watcher profile is turned on", "Turn one on in config.json's watcher.profiles");
+    }
+    let mut watched = Vec::new();
+    let mut missing = Vec::new();
+    for profile in profiles {
+        let root = glob_root(&expand_home(&profile.path, home));
+        match std::fs::read_dir(&root) {
+            Ok(_) => watched.push(root.display().to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => missing.push(format!("{} ({})", root.display(), profile.name)),
+            Err(e) => return Check::fail(format!("{} cannot be read: {}", root.display(), e), "Give the app permission to read it"),
+        }
+    }
+    match (watched.is_empty(), missing.is_empty()) {
+        (_, true) => Check::pass(format!("Watching {}", watched.join(", "))),
+        (false, false) => Check::warn(
+            format!("Watching {}; {} does not exist yet", watched.join(", "), missing.join(", ")),
+            "It is watched once the CLI creates it",
+        ),
+        (true, false) => Check::warn(
+            format!("{} does not exist yet", missing.join(", ")),
+            "Nothing is announced until a coding CLI creates it; check the profile's path",
+        ),
+    }
+}
+
+fn check_config() -> Check {
+    if let Some(warning) = config_warning() {
+        return Check::warn(warning, "Settings are the defaults and are not saved; update the app");
+    }
+    match config::check_active_file() {
+        Ok(None) => Check::pass("No config file yet; using the defaults"),
+        Ok(Some((path, config))) => {
+            let warnings: Vec<String> = config.validate().into_iter().map(|d| format!("{}: {}", d.field_path, d.message)).collect();
+            match warnings.first() {
+                None => Check::pass(format!("{} is valid", path.display())),
+                Some(first) => Check::warn(first.clone(), format!("{} warning(s) in {}; Settings shows them by their fields", warnings.len(), path.display())),
+            }
+        }
+        Err(e) => Check::fail(e, "Fix the file or restore a backup from the config folder; the app keeps its last good settings meanwhile"),
+    }
+}
+
+fn check_icons() -> Check {
+    let broken = theme::broken_icons();
+    if broken.is_empty() {
+        Check::pass("Tray icons load")
+    } else {
+        Check::fail(format!("Could not draw the {} icon(s)", broken.join(", ")), "The app bundle is damaged; reinstall it")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hung_probe_times_out_alone() {
+        let probes: Vec<(&'static str, Duration, Probe)> = vec![
+            ("hangs", Duration::from_millis(100), Box::new(|| {
+                std::thread::sleep(Duration::from_secs(5));
+                Check::pass("too late")
+            })),
+            ("quick", Duration::from_secs(5), Box::new(|| Check::warn("slow disk", "wait"))),
+            ("crashes", Duration::from_secs(5), Box::new(|| panic!("probe exploded"))),
+        ];
+        let started = Instant::now();
+        let report = Report::of(run_all(probes));
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let outcomes: Vec<_> = report.checks.iter().map(|c| (c.name, c.outcome)).collect();
+        assert_eq!(outcomes, vec![("hangs", Outcome::Fail), ("quick", Outcome::Warn), ("crashes", Outcome::Fail)]);
+        assert_eq!(report.checks[0].message, "No answer within 100ms");
+        assert_eq!(report.outcome, Outcome::Fail);
+        assert!(report.to_text().starts_with("FAIL  hangs    No answer"));
+    }
+
+    #[test]
+    fn test_http_port_check() {
+        let taken = std::net::TcpListener::bind(("127.0.0.1", 0)).expect("bind");
+        let port = taken.local_addr().expect("addr").port();
+        assert_eq!(check_http(port, false, None).outcome, Outcome::Fail);
+        assert_eq!(check_http(port, true, None).outcome, Outcome::Pass);
+        let held = check_http(port, true, Some(format!("port {} unavailable", port)));
+        assert_eq!((held.outcome, held.message), (Outcome::Fail, format!("port {} unavailable", port)));
+        drop(taken);
+        assert_eq!(check_http(port, false, None).outcome, Outcome::Pass);
+    }
+
+    #[test]
+    fn test_watcher_directories() {
+        let home = tempfile::TempDir::new().expect("tempdir");
+        let config = WatcherConfig::default();
+        let missing = check_watcher(&config, home.path());
+        assert_eq!(missing.outcome, Outcome::Warn);
+
+        for profile in config.active_profiles() {
+            std::fs::create_dir_all(glob_root(&expand_home(&profile.path, home.path()))).expect("mkdir");
+        }
+        assert_eq!(check_watcher(&config, home.path()).outcome, Outcome::Pass);
+        let paused = WatcherConfig { enabled: false, ..WatcherConfig::default() };
+        assert_eq!(check_watcher(&paused, home.path()).message, "Paused in config.json");
+    }
+
+    #[test]
+    fn test_embedded_icons_draw() {
+        assert_eq!(check_icons().outcome, Outcome::Pass);
+    }
+}
@@ -0,0 +1,88 @@
+/// The app's error type. Fallible internals return it; Tauri commands and HTTP handlers turn it
+/// into a message, and background work with no caller to answer passes it to `report`, which
+/// logs it and counts it in GET /stats.
+use thiserror::Error;
+use tracing::error;
+
+use crate::state::AppState;
+use crate::stats;
+
+#[derive(Debug, Error)]
+pub enum VoiceTrayError {
+    /// The speech command could not be started or exited with an error
+    #[error("{0}")]
+    Tts(String),
+    #[error("MQTT: {0}")]
+    Mqtt(#[from] rumqttc::ClientError),
+    #[error("{0}")]
+    Http(String),
+    #[error("{0}")]
+    Config(String),
+    #[error("{0}")]
+    Watcher(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// A tray, menu or window call
+    #[error(transparent)]
+    Ui(#[from] tauri::Error),
+}
+
+pub type Result<T> = std::result::Result<T, VoiceTrayError>;
+
+impl VoiceTrayError {
+    /// Key of the error counters in GET /stats
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Tts(_) => "tts",
+            Self::Mqtt(_) => "mqtt",
+            Self::Http(_) => "http",
+            Self::Config(_) => "config",
+            Self::Watcher(_) => "watcher",
+            Self::Io(_) => "io",
+            Self::Ui(_) => "ui",
+        }
+    }
+}
+
+/// Commands keep answering with a plain message
+impl From<VoiceTrayError> for String {
+    fn from(error: VoiceTrayError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Log and count a failure of `what` that has no caller to return to
+pub fn report_error(state: &AppState, what: &str, error: &VoiceTrayError) {
+    error!("{} failed: {}", what, error);
+    stats::record_error(state, error.kind());
+}
+
+/// `result`'s value, or None after reporting its error
+pub fn report<T, E: Into<VoiceTrayError>>(state: &AppState, what: &str, result: std::result::Result<T, E>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            report_error(state, what, &e.into());
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_counts_by_kind() {
+        let state = AppState::default();
+        assert_eq!(report(&state, "Saving", Ok::<_, VoiceTrayError>(3)), Some(3));
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "gone");
+        assert_eq!(report(&state, "Saving", Err::<(), _>(missing)), None);
+        report_error(&state, "Speaking", &VoiceTrayError::Tts("exited with 1".to_string()));
+        report_error(&state, "Speaking", &VoiceTrayError::Tts("exited with 1".to_string()));
+
+        let errors = stats::snapshot(&state).errors;
+        assert_eq!((errors["io"], errors["tts"], errors.len()), (1, 2, 2));
+        assert_eq!(String::from(VoiceTrayError::Config("bad port".to_string())), "bad port");
+    }
+}
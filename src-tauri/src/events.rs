@@ -0,0 +1,56 @@
+/// Tauri events pushed to the webview windows
+/// Entry events and voice://status are forwarded from the timeline bus (bus.rs) by the "events" task.
+/// A window takes its first snapshot from get_timeline/get_status and applies events from there.
+use serde::Serialize;
+use std::sync::Arc;
+use tauri::{Emitter, Manager};
+use tracing::warn;
+
+use crate::bus::{self, TimelineEvent};
+use crate::state::{AppState, VoiceEntry};
+
+/// A new entry was queued; payload is the VoiceEntry
+pub const ENTRY_QUEUED: &str = "voice://entry-queued";
+
+/// An entry changed status ("speaking", "done", "cancelled", "failed", "suppressed", "relaying", "relayed", or "removed" when it left the timeline)
+pub const ENTRY_STATUS: &str = "voice://entry-status";
+
+/// Queue length, speaking, MQTT, mute or watcher pause changed; payload is the tray snapshot
+pub const STATUS: &str = "voice://status";
+
+/// Settings were applied from outside the settings window: a config.json edit or a profile
+/// switch; payload is the active profile's name
+pub const CONFIG_CHANGED: &str = "voice://config-changed";
+
+/// An agent was muted or unmuted; payload is the agents::Agent
+pub const AGENT_MUTED: &str = "voice://agent-muted";
+
+/// The popup was pinned or unpinned; payload is the new pinned state
+pub const POPUP_PINNED: &str = "voice://popup-pinned";
+
+/// The popup was opened from a notification and should show the timeline, not its settings view
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))] // only macOS reports notification clicks (RunEvent::Reopen)
+pub const SHOW_TIMELINE: &str = "voice://show-timeline";
+
+/// Emit to all windows, skipped while none is open
+pub fn emit<S: Serialize + Clone>(state: &AppState, event: &str, payload: S) {
+    let Some(app) = state.app_handle.read().clone() else {
+        return;
+    };
+    if app.webview_windows().is_empty() {
+        return;
+    }
+    if let Err(e) = app.emit(event, payload) {
+        warn!("Failed to emit {}: {}", event, e);
+    }
+}
+
+/// Emit timeline events to the windows; runs as the "events" task
+pub async fn forward(state: Arc<AppState>) {
+    bus::consume(&state, "Window events", |event| match event {
+        TimelineEvent::Queued { entry } => emit(&state, ENTRY_QUEUED, entry),
+        TimelineEvent::StatusChanged { entry, .. } => emit(&state, ENTRY_STATUS, entry),
+        TimelineEvent::Removed { entry } => emit(&state, ENTRY_STATUS, VoiceEntry { status: "removed".to_string(), ..entry }),
+        TimelineEvent::StatusSnapshot { snapshot } => emit(&state, STATUS, snapshot),
+    }).await
+}
@@ -0,0 +1,198 @@
+/// Timeline export to CSV, JSON or Markdown through the system save dialog, for the
+/// export_timeline command and Export Timeline… in the tray menu.
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+use tauri_plugin_dialog::DialogExt;
+use tracing::info;
+
+use crate::state::{AppState, VoiceEntry};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Markdown,
+}
+
+impl ExportFormat {
+    const ALL: [ExportFormat; 3] = [ExportFormat::Csv, ExportFormat::Json, ExportFormat::Markdown];
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Markdown => "md",
+        }
+    }
+
+    fn filter_name(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Json => "JSON",
+            ExportFormat::Markdown => "Markdown",
+        }
+    }
+
+    /// Format for a file the user named in the dialog; CSV unless the extension says otherwise
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("json") => ExportFormat::Json,
+            Some("md" | "markdown") => ExportFormat::Markdown,
+            _ => ExportFormat::Csv,
+        }
+    }
+}
+
+/// What export_timeline did
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ExportOutcome {
+    Saved { path: PathBuf },
+    Cancelled,
+}
+
+/// Ask where to save, then write the timeline there. Blocks on the dialog, so call it off the
+/// main thread. Without `format` every format is offered and the file's extension picks one.
+pub fn export(app: &AppHandle, state: &AppState, format: Option<ExportFormat>) -> Result<ExportOutcome, String> {
+    let default = format.unwrap_or(ExportFormat::Csv);
+    let mut dialog = app.dialog().file()
+        .set_title("Export Timeline")
+        .set_file_name(format!("voice-timeline.{}", default.extension()));
+    for f in format.map(|f| vec![f]).unwrap_or(ExportFormat::ALL.to_vec()) {
+        dialog = dialog.add_filter(f.filter_name(), &[f.extension()]);
+    }
+    let Some(chosen) = dialog.blocking_save_file() else {
+        return Ok(ExportOutcome::Cancelled);
+    };
+    let path = chosen.into_path().map_err(|e| e.to_string())?;
+    let format = format.unwrap_or_else(|| ExportFormat::from_path(&path));
+
+    let entries: Vec<VoiceEntry> = state.timeline.lock().iter().cloned().collect();
+    let file = File::create(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut out = BufWriter::new(file);
+    write_timeline(&mut out, &entries, format)
+        .and_then(|_| out.flush())
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    info!("Wrote {} entries to {}", entries.len(), path.display());
+    Ok(ExportOutcome::Saved { path })
+}
+
+/// Write `entries`, oldest first
+pub fn write_timeline<W: Write>(out: &mut W, entries: &[VoiceEntry], format: ExportFormat) -> io::Result<()> {
+    match format {
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, entries)?;
+            writeln!(out)
+        }
+        ExportFormat::Csv => {
+            writeln!(out, "id,time,agent,voice,rate,status,text,detail,replay_of")?;
+            for e in entries {
+                let fields = row(e);
+                let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+                writeln!(out, "{}", line.join(","))?;
+            }
+            Ok(())
+        }
+        ExportFormat::Markdown => {
+            writeln!(out, "| id | time | agent | voice | rate | status | text | detail | replay_of |")?;
+            writeln!(out, "|---|---|---|---|---|---|---|---|---|")?;
+            for e in entries {
+                let fields = row(e);
+                let cells: Vec<String> = fields.iter().map(|f| markdown_cell(f)).collect();
+                writeln!(out, "| {} |", cells.join(" | "))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn row(e: &VoiceEntry) -> [String; 9] {
+    [
+        e.id.to_string(),
+        e.timestamp.to_rfc3339(),
+        e.agent.clone().unwrap_or_default(),
+        e.voice.clone(),
+        e.rate.to_string(),
+        e.status.clone(),
+        e.text.clone(),
+        e.detail.clone().unwrap_or_default(),
+        e.replay_of.map(|id| id.to_string()).unwrap_or_default(),
+    ]
+}
+
+/// RFC 4180 quoting for fields with commas, quotes or line breaks
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Table cell text: pipes escaped, line breaks as <br>
+fn markdown_cell(value: &str) -> String {
+    value.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace(['\n', '\r'], "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn entry(id: u64, text: &str) -> VoiceEntry {
+        VoiceEntry {
+            id,
+            timestamp: Utc.with_ymd_and_hms(2026, 1, 5, 9, 30, 0).unwrap(),
+            text: text.to_string(),
+            voice: "Samantha".to_string(),
+            rate: 220,
+            agent: Some("ci".to_string()),
+            status: "done".to_string(),
+            priority: 0,
+            detail: None,
+            replay_of: None,
+            relayed_from: None,
+        }
+    }
+
+    fn export_string(entries: &[VoiceEntry], format: ExportFormat) -> String {
+        let mut out = Vec::new();
+        write_timeline(&mut out, entries, format).expect("write");
+        String::from_utf8(out).expect("utf8")
+    }
+
+    #[test]
+    fn test_csv_escaping() {
+        let entries = [entry(1, "plain"), VoiceEntry { replay_of: Some(1), ..entry(2, "said \"hi\", then\nleft") }];
+        let csv = export_string(&entries, ExportFormat::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "id,time,agent,voice,rate,status,text,detail,replay_of");
+        assert_eq!(lines[1], "1,2026-01-05T09:30:00+00:00,ci,Samantha,220,done,plain,,");
+        assert_eq!(&csv[csv.find("\n2,").expect("row 2") + 1..], "2,2026-01-05T09:30:00+00:00,ci,Samantha,220,done,\"said \"\"hi\"\", then\nleft\",,1\n");
+    }
+
+    #[test]
+    fn test_markdown_and_json() {
+        let entries = [entry(1, "a | b\nc")];
+        let md = export_string(&entries, ExportFormat::Markdown);
+        assert!(md.lines().nth(2).expect("row").contains("| a \\| b<br>c |"));
+
+        let json = export_string(&entries, ExportFormat::Json);
+        let parsed: Vec<VoiceEntry> = serde_json::from_str(&json).expect("parse");
+        assert_eq!(parsed[0].text, "a | b\nc");
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ExportFormat::from_path(Path::new("/tmp/t.JSON")), ExportFormat::Json);
+        assert_eq!(ExportFormat::from_path(Path::new("/tmp/t.md")), ExportFormat::Markdown);
+        assert_eq!(ExportFormat::from_path(Path::new("/tmp/t")), ExportFormat::Csv);
+    }
+}
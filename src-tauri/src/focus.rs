@@ -0,0 +1,248 @@
+/// macOS Focus (Do Not Disturb): read every POLL and, with ui.mute_during_focus, treated like
+/// quiet hours. Queued messages are marked "suppressed" instead of spoken and watcher alerts
+/// become notifications; when Focus ends, one announcement says how many were suppressed.
+/// Other platforms report "unsupported" and never mute.
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+use crate::i18n::{self, Phrase};
+use crate::notifications::{notify_suppressed, Suppression};
+use crate::state::AppState;
+use crate::stats;
+
+/// How often Focus is read; there is no change notification to wait on
+const POLL: Duration = Duration::from_secs(30);
+
+/// Detail of the entries not spoken because of Focus
+const SUPPRESSED_DETAIL: &str = "Not spoken: Focus was on";
+
+/// The "Focus ended" announcement is spoken before anything queued after it
+const ENDED_PRIORITY: u8 = u8::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusState {
+    On,
+    Off,
+    /// Not read yet, or the last read failed (see `detail`)
+    Unknown,
+    Unsupported,
+}
+
+/// The last reading, for GET /status and get_status
+#[derive(Debug, Clone, Serialize)]
+pub struct FocusStatus {
+    pub state: FocusState,
+    /// Why the state is unknown
+    pub detail: Option<String>,
+    /// ui.mute_during_focus as of the last reading
+    pub mute: bool,
+    /// Entries suppressed since Focus came on
+    pub suppressed: usize,
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+impl Default for FocusStatus {
+    fn default() -> Self {
+        Self {
+            state: if cfg!(target_os = "macos") { FocusState::Unknown } else { FocusState::Unsupported },
+            detail: None,
+            mute: false,
+            suppressed: 0,
+            checked_at: None,
+        }
+    }
+}
+
+/// What a reading changed
+#[derive(Debug, PartialEq)]
+enum Transition {
+    None,
+    Began,
+    /// With how many entries were suppressed while it was on
+    Ended(usize),
+}
+
+impl FocusStatus {
+    /// Record `reading` (Ok(on), or why it could not be read). A failed read keeps the muting
+    /// as it was, so a flaky read does not toggle it.
+    fn apply(&mut self, reading: Result<bool, String>, mute: bool, now: DateTime<Utc>) -> Transition {
+        let was_on = self.state == FocusState::On;
+        self.mute = mute;
+        self.checked_at = Some(now);
+        let on = match reading {
+            Ok(on) => on,
+            Err(detail) => {
+                self.detail = Some(detail);
+                if !was_on {
+                    self.state = FocusState::Unknown;
+                }
+                return Transition::None;
+            }
+        };
+        self.detail = None;
+        self.state = if on { FocusState::On } else { FocusState::Off };
+        match (was_on, on) {
+            (false, true) => {
+                self.suppressed = 0;
+                Transition::Began
+            }
+            (true, false) => Transition::Ended(std::mem::take(&mut self.suppressed)),
+            _ => Transition::None,
+        }
+    }
+}
+
+/// Whether Focus is on and ui.mute_during_focus asks to stay quiet for it
+pub fn muting(state: &AppState) -> bool {
+    let on = state.focus.lock().state == FocusState::On;
+    on && state.tray_config().mute_during_focus
+}
+
+/// Mark every queued entry suppressed, counting them for the summary and in the stats
+pub fn suppress_queued(state: &AppState) {
+    let suppressed = state.finish_queued("suppressed", SUPPRESSED_DETAIL);
+    if suppressed.is_empty() {
+        return;
+    }
+    state.focus.lock().suppressed += suppressed.len();
+    for entry in &suppressed {
+        stats::record(state, entry.agent.as_deref(), |c| c.suppressed += 1);
+        notify_suppressed(state, Suppression::Focus, entry.agent.as_deref(), &entry.text);
+    }
+}
+
+/// "Focus ended, 3 messages suppressed"; None when nothing was
+pub fn ended_phrase(suppressed: usize) -> Option<String> {
+    match suppressed {
+        0 => None,
+        1 => Some(i18n::phrase(Phrase::FocusEndedOne)),
+        n => Some(i18n::phrase(Phrase::FocusEnded).replace("{count}", &n.to_string())),
+    }
+}
+
+/// Read Focus now and act on a change; the poll loop's step
+fn check(state: &AppState) {
+    let config = state.tray_config();
+    let reading = read();
+    let transition = state.focus.lock().apply(reading, config.mute_during_focus, Utc::now());
+    match transition {
+        Transition::None => return,
+        Transition::Began => {
+            info!("Focus on{}", if config.mute_during_focus { ": muting" } else { "" });
+            // Like quiet hours: the message being spoken is cut off too
+            if config.mute_during_focus {
+                state.stop_speaking();
+            }
+        }
+        Transition::Ended(suppressed) => {
+            info!("Focus off, {} suppressed", suppressed);
+            if let Some(phrase) = ended_phrase(suppressed).filter(|_| config.focus_summary) {
+                let defaults = state.voice_config();
+                state.enqueue(phrase, defaults.voice(), defaults.default_rate, Some("focus".to_string()), ENDED_PRIORITY, None);
+            }
+        }
+    }
+    state.notify_changed();
+}
+
+/// Poll Focus for the life of the app; nothing to do where it cannot be read
+pub fn start_monitor(state: Arc<AppState>) {
+    if !cfg!(target_os = "macos") {
+        return;
+    }
+    std::thread::spawn(move || loop {
+        check(&state);
+        std::thread::sleep(POLL);
+    });
+}
+
+/// Monterey and later keep the active Focus in ~/Library/DoNotDisturb/DB/Assertions.json (read
+/// with Full Disk Access only); before that, Do Not Disturb was a defaults key
+#[cfg(target_os = "macos")]
+fn read() -> Result<bool, String> {
+    let home = dirs::home_dir().ok_or("no home folder")?;
+    let path = home.join("Library/DoNotDisturb/DB/Assertions.json");
+    match std::fs::read_to_string(&path) {
+        Ok(content) => assertions_active(&content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let output = std::process::Command::new("defaults")
+                .args(["-currentHost", "read", "com.apple.notificationcenterui", "doNotDisturb"])
+                .output()
+                .map_err(|e| format!("Failed to run defaults: {}", e))?;
+            // The key is missing until Do Not Disturb is first used
+            Ok(output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "1")
+        }
+        Err(e) => Err(format!("Cannot read {}: {} (give the app Full Disk Access)", path.display(), e)),
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn read() -> Result<bool, String> {
+    Err("Focus is only read on macOS".to_string())
+}
+
+/// Whether Assertions.json holds an active Focus assertion
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn assertions_active(content: &str) -> Result<bool, String> {
+    let value: serde_json::Value = serde_json::from_str(content).map_err(|e| format!("Unreadable Focus assertions: {}", e))?;
+    let data = value.get("data").and_then(|d| d.as_array()).map(Vec::as_slice).unwrap_or_default();
+    Ok(data.iter().any(|store| {
+        store.get("storeAssertionRecords").and_then(|r| r.as_array()).is_some_and(|records| !records.is_empty())
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assertions_active() {
+        let on = r#"{"data":[{"storeAssertionRecords":[{"assertionDetails":{"assertionDetailsModeIdentifier":"com.apple.focus.work"}}]}],"header":{}}"#;
+        assert_eq!(assertions_active(on), Ok(true));
+        assert_eq!(assertions_active(r#"{"data":[{"storeAssertionRecords":[]}]}"#), Ok(false));
+        assert_eq!(assertions_active(r#"{"data":[{}]}"#), Ok(false));
+        assert!(assertions_active("not json").is_err());
+    }
+
+    #[test]
+    fn test_transitions() {
+        let now = Utc::now();
+        let mut status = FocusStatus { state: FocusState::Unknown, ..FocusStatus::default() };
+        assert_eq!(status.apply(Ok(false), true, now), Transition::None);
+        assert_eq!(status.apply(Ok(true), true, now), Transition::Began);
+        status.suppressed = 3;
+        // A failed read keeps Focus on rather than unmuting
+        assert_eq!(status.apply(Err("denied".to_string()), true, now), Transition::None);
+        assert_eq!((status.state, status.detail.as_deref()), (FocusState::On, Some("denied")));
+        assert_eq!(status.apply(Ok(false), true, now), Transition::Ended(3));
+        assert_eq!((status.state, status.suppressed, status.detail.as_deref()), (FocusState::Off, 0, None));
+        assert_eq!(status.apply(Err("denied".to_string()), true, now), Transition::None);
+        assert_eq!(status.state, FocusState::Unknown);
+
+        assert_eq!(ended_phrase(0), None);
+        assert_eq!(ended_phrase(1).as_deref(), Some("Focus ended, 1 message suppressed"));
+    }
+
+    #[test]
+    fn test_suppress_queued_while_muting() {
+        let state = AppState::default();
+        state.enqueue("Build passed".to_string(), "Samantha".to_string(), 220, Some("ci".to_string()), 0, None);
+        state.enqueue("Deploy done".to_string(), "Samantha".to_string(), 220, None, 0, None);
+        assert!(!muting(&state));
+        state.tray_config.write().mute_during_focus = true;
+        state.focus.lock().apply(Ok(true), true, Utc::now());
+        assert!(muting(&state));
+
+        suppress_queued(&state);
+        let timeline = state.timeline.lock();
+        assert!(timeline.iter().all(|e| e.status == "suppressed" && e.detail.as_deref() == Some(SUPPRESSED_DETAIL)));
+        assert!(timeline.iter().all(|e| e.is_finished()));
+        drop(timeline);
+        assert_eq!(state.focus.lock().suppressed, 2);
+        assert_eq!(stats::snapshot(&state).agents["ci"].suppressed, 1);
+    }
+}
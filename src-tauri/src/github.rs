@@ -0,0 +1,356 @@
+/// GitHub webhooks, for POST /github: pushes, finished workflow runs, review requests and new
+/// issues are spoken with agent "github". A delivery is only read once its X-Hub-Signature-256
+/// matches the HMAC of the body under github.secret, compared in constant time. Deliveries
+/// seen before (by X-GitHub-Delivery) are not spoken again, and event types not spoken here
+/// are accepted and counted, so GitHub does not mark the hook as failing.
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::{BTreeMap, VecDeque};
+use tracing::{info, warn};
+
+use crate::config::GithubConfig;
+use crate::i18n::{self, Phrase};
+use crate::prepare;
+use crate::state::{AppState, SpeakRequest};
+
+pub const AGENT: &str = "github";
+pub const SIGNATURE_HEADER: &str = "X-Hub-Signature-256";
+pub const EVENT_HEADER: &str = "X-GitHub-Event";
+pub const DELIVERY_HEADER: &str = "X-GitHub-Delivery";
+
+/// Event types with a phrase, as github.events names them
+pub const EVENTS: [&str; 4] = ["push", "workflow_run", "pull_request", "issues"];
+
+/// Workflow run conclusions spoken with github.failure_priority
+const FAILURES: [&str; 3] = ["failure", "timed_out", "startup_failure"];
+
+/// Delivery ids remembered for spotting redeliveries
+const DELIVERIES_KEPT: usize = 500;
+
+/// Deliveries since the app started, for GET /status
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GithubStatus {
+    /// Deliveries with a valid signature
+    pub received: u64,
+    pub queued: u64,
+    /// Refused for a missing or wrong signature
+    pub rejected: u64,
+    /// Redeliveries of an id already received
+    pub duplicates: u64,
+    /// Deliveries of event types not spoken here, by type
+    pub unhandled: BTreeMap<String, u64>,
+    #[serde(skip)]
+    deliveries: VecDeque<String>,
+}
+
+/// The headers GitHub sends with a delivery
+#[derive(Debug)]
+pub struct Delivery<'a> {
+    pub event: &'a str,
+    pub id: Option<&'a str>,
+    pub signature: Option<&'a str>,
+}
+
+/// What became of a delivery
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Queued(u64),
+    /// A spoken event type this delivery had nothing to say for, e.g. a passing run
+    Skipped(String),
+    Duplicate,
+    /// An event type not spoken here (202)
+    Unhandled(String),
+}
+
+/// Why a delivery was refused; each maps to an HTTP status
+#[derive(Debug, PartialEq)]
+pub enum Refusal {
+    /// github.enabled is off (404)
+    Disabled,
+    /// Missing or wrong X-Hub-Signature-256 (401)
+    Signature,
+    Invalid(String),
+}
+
+/// What an event says, before it is queued
+#[derive(Debug, PartialEq)]
+enum Speech {
+    Say { text: String, failure: bool },
+    Skip(String),
+    Unhandled,
+}
+
+/// Check, read and queue one delivery
+pub fn receive(state: &AppState, config: &GithubConfig, delivery: Delivery, body: &[u8]) -> Result<Outcome, Refusal> {
+    let secret = config.secret.as_deref().filter(|s| !s.is_empty());
+    let Some(secret) = secret.filter(|_| config.enabled) else {
+        return Err(Refusal::Disabled);
+    };
+    if !verify(secret, body, delivery.signature) {
+        warn!("Refused a GitHub delivery with a bad signature");
+        record(state, |s| s.rejected += 1);
+        return Err(Refusal::Signature);
+    }
+    // Counted as seen only once signed, so unsigned requests cannot block real deliveries
+    let first = {
+        let mut status = state.github.lock();
+        status.received += 1;
+        match delivery.id {
+            Some(id) => {
+                let first = !status.deliveries.iter().any(|seen| seen == id);
+                if first {
+                    status.deliveries.push_back(id.to_string());
+                    if status.deliveries.len() > DELIVERIES_KEPT {
+                        status.deliveries.pop_front();
+                    }
+                } else {
+                    status.duplicates += 1;
+                }
+                first
+            }
+            None => true,
+        }
+    };
+    if !first {
+        info!("GitHub delivery {} was already received", delivery.id.unwrap_or_default());
+        return Ok(Outcome::Duplicate);
+    }
+    let payload: Value = serde_json::from_slice(body).map_err(|e| Refusal::Invalid(format!("Invalid payload: {}", e)))?;
+    match speech(config, delivery.event, &payload) {
+        Speech::Say { text, failure } => {
+            let req = SpeakRequest { text, voice: None, rate: None, agent: Some(AGENT.to_string()) };
+            req.validate().map_err(Refusal::Invalid)?;
+            let defaults = state.voice_config();
+            let priority = if failure { config.failure_priority } else { 0 };
+            let id = state.enqueue(req.text, defaults.voice(), defaults.default_rate, req.agent, priority, None);
+            record(state, |s| s.queued += 1);
+            Ok(Outcome::Queued(id))
+        }
+        Speech::Skip(reason) => Ok(Outcome::Skipped(reason)),
+        Speech::Unhandled => {
+            record(state, |s| *s.unhandled.entry(delivery.event.to_string()).or_default() += 1);
+            Ok(Outcome::Unhandled(delivery.event.to_string()))
+        }
+    }
+}
+
+fn record(state: &AppState, change: impl FnOnce(&mut GithubStatus)) {
+    let mut status = state.github.lock();
+    change(&mut status);
+}
+
+/// Whether `signature` ("sha256=<hex>") is the HMAC-SHA256 of `body` under `secret`.
+/// Mac::verify_slice compares in constant time.
+fn verify(secret: &str, body: &[u8], signature: Option<&str>) -> bool {
+    let Some(expected) = signature.and_then(|s| s.trim().strip_prefix("sha256=")).and_then(|hex| hex::decode(hex).ok()) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// The string at `path` in `payload`, cleaned for speech
+fn field(payload: &Value, path: &str) -> String {
+    let text = payload.pointer(path).map(|v| match v {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        v => v.to_string(),
+    });
+    prepare::clean(&text.unwrap_or_default()).trim_end_matches('.').to_string()
+}
+
+/// What to say for `event`
+fn speech(config: &GithubConfig, event: &str, payload: &Value) -> Speech {
+    if !EVENTS.contains(&event) {
+        return if event == "ping" { Speech::Skip("ping".to_string()) } else { Speech::Unhandled };
+    }
+    if !config.events.iter().any(|e| e == event) {
+        return Speech::Skip(format!("{} is not in github.events", event));
+    }
+    let action = payload.get("action").and_then(Value::as_str).unwrap_or_default();
+    let (repo, sender) = (field(payload, "/repository/name"), field(payload, "/sender/login"));
+    let say = |template: String, values: &[(&str, &str)], failure: bool| {
+        let values = [values, &[("repo", repo.as_str()), ("sender", sender.as_str())]].concat();
+        Speech::Say { text: render(&template, &values), failure }
+    };
+    match event {
+        "push" => {
+            if payload.get("deleted").and_then(Value::as_bool).unwrap_or(false) {
+                return Speech::Skip("branch deleted".to_string());
+            }
+            let git_ref = field(payload, "/ref");
+            let branch = git_ref.strip_prefix("refs/heads/").or_else(|| git_ref.strip_prefix("refs/tags/")).unwrap_or(&git_ref);
+            let commits = match payload.get("commits").and_then(Value::as_array).map_or(0, Vec::len) {
+                1 => i18n::phrase(Phrase::Commit),
+                n => i18n::phrase(Phrase::Commits).replace("{n}", &n.to_string()),
+            };
+            let template = i18n::phrase_or(Phrase::GithubPush, &config.push_phrase);
+            say(template, &[("branch", branch), ("commits", &commits)], false)
+        }
+        "workflow_run" => {
+            let conclusion = payload.pointer("/workflow_run/conclusion").and_then(Value::as_str).unwrap_or_default();
+            if action != "completed" {
+                return Speech::Skip(format!("action {}", action));
+            }
+            if !config.workflow_conclusions.iter().any(|c| c == conclusion) {
+                return Speech::Skip(format!("conclusion {} is not in github.workflow_conclusions", conclusion));
+            }
+            let result = match conclusion {
+                "success" => i18n::phrase(Phrase::Passed),
+                "failure" | "startup_failure" => i18n::phrase(Phrase::Failed),
+                "timed_out" => i18n::phrase(Phrase::TimedOut),
+                "cancelled" => i18n::phrase(Phrase::Cancelled),
+                other => other.replace('_', " "),
+            };
+            let (workflow, branch) = (field(payload, "/workflow_run/name"), field(payload, "/workflow_run/head_branch"));
+            let template = i18n::phrase_or(Phrase::GithubWorkflow, &config.workflow_phrase);
+            say(template, &[("workflow", &workflow), ("branch", &branch), ("result", &result)], FAILURES.contains(&conclusion))
+        }
+        "pull_request" if action == "review_requested" => {
+            let reviewer = match field(payload, "/requested_reviewer/login") {
+                login if login.is_empty() => field(payload, "/requested_team/name"),
+                login => login,
+            };
+            let (number, title) = (field(payload, "/pull_request/number"), field(payload, "/pull_request/title"));
+            let template = i18n::phrase_or(Phrase::GithubReview, &config.review_phrase);
+            say(template, &[("number", &number), ("title", &title), ("reviewer", &reviewer)], false)
+        }
+        "issues" if action == "opened" => {
+            let (number, title) = (field(payload, "/issue/number"), field(payload, "/issue/title"));
+            let template = i18n::phrase_or(Phrase::GithubIssue, &config.issue_phrase);
+            say(template, &[("number", &number), ("title", &title)], false)
+        }
+        _ => Speech::Skip(format!("action {}", action)),
+    }
+}
+
+/// `template` with each `{name}` in `values` filled in, in one pass: a value that itself holds
+/// `{repo}` or `{title}` is left as it is. Other braces stay.
+fn render(template: &str, values: &[(&str, &str)]) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        text.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let value = after.find('}').and_then(|close| {
+            let name = &after[..close];
+            values.iter().find(|(n, _)| *n == name).map(|(_, value)| (*value, close))
+        });
+        match value {
+            Some((value, close)) => {
+                text.push_str(value);
+                rest = &after[close + 1..];
+            }
+            None => {
+                text.push('{');
+                rest = after;
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(secret: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("key");
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    fn config() -> GithubConfig {
+        GithubConfig { enabled: true, secret: Some("s3cret".to_string()), ..GithubConfig::default() }
+    }
+
+    #[test]
+    fn test_verify() {
+        let body = br#"{"zen":"Keep it simple."}"#;
+        let signature = sign("s3cret", body);
+        assert!(verify("s3cret", body, Some(&signature)));
+        assert!(!verify("other", body, Some(&signature)));
+        assert!(!verify("s3cret", b"{}", Some(&signature)));
+        assert!(!verify("s3cret", body, Some(signature.trim_start_matches("sha256="))));
+        assert!(!verify("s3cret", body, Some("sha256=zz")));
+        assert!(!verify("s3cret", body, None));
+    }
+
+    #[test]
+    fn test_speech_per_event() {
+        let config = config();
+        let say = |event: &str, payload: Value| match speech(&config, event, &payload) {
+            Speech::Say { text, failure } => Ok((text, failure)),
+            other => Err(other),
+        };
+        let repo = serde_json::json!({"name": "oracle"});
+        let sender = serde_json::json!({"login": "nat"});
+
+        let failed = serde_json::json!({"action": "completed", "repository": repo, "sender": sender,
+            "workflow_run": {"name": "CI", "head_branch": "main", "conclusion": "failure"}});
+        assert_eq!(say("workflow_run", failed), Ok(("CI failed on main in oracle".to_string(), true)));
+        let passed = serde_json::json!({"action": "completed", "workflow_run": {"conclusion": "success"}});
+        assert!(matches!(say("workflow_run", passed), Err(Speech::Skip(_))), "successes are not in the default conclusions");
+
+        let review = serde_json::json!({"action": "review_requested", "repository": repo, "sender": sender,
+            "pull_request": {"number": 42, "title": "Fix `parser` crash"}, "requested_reviewer": {"login": "pat"}});
+        assert_eq!(say("pull_request", review), Ok(("nat asked for a review of pull request 42 in oracle: Fix parser crash".to_string(), false)));
+        let closed = serde_json::json!({"action": "closed", "pull_request": {"number": 42}});
+        assert_eq!(say("pull_request", closed), Err(Speech::Skip("action closed".to_string())));
+
+        let issue = serde_json::json!({"action": "opened", "repository": repo, "issue": {"number": 7, "title": "Docs"}});
+        assert_eq!(say("issues", issue), Ok(("New issue 7 in oracle: Docs".to_string(), false)));
+        let braces = serde_json::json!({"action": "opened", "repository": repo, "issue": {"number": 8, "title": "Expand {sender} in {repo}"}});
+        assert_eq!(say("issues", braces), Ok(("New issue 8 in oracle: Expand {sender} in {repo}".to_string(), false)), "values are not filled in again");
+
+        let push = serde_json::json!({"ref": "refs/heads/main", "repository": repo, "sender": sender, "commits": [{}, {}]});
+        assert_eq!(say("push", push), Ok(("nat pushed 2 commits to main in oracle".to_string(), false)));
+        assert!(matches!(say("push", serde_json::json!({"ref": "refs/heads/old", "deleted": true})), Err(Speech::Skip(_))));
+
+        assert_eq!(say("star", serde_json::json!({})), Err(Speech::Unhandled));
+        assert_eq!(say("ping", serde_json::json!({})), Err(Speech::Skip("ping".to_string())));
+        let quiet = GithubConfig { events: vec!["issues".to_string()], ..config.clone() };
+        assert!(matches!(speech(&quiet, "push", &serde_json::json!({})), Speech::Skip(_)));
+    }
+
+    #[test]
+    fn test_render() {
+        let values = [("repo", "oracle"), ("title", "{repo}")];
+        assert_eq!(render("{title} in {repo}", &values), "{repo} in oracle");
+        assert_eq!(render("{unknown} {repo} {", &values), "{unknown} oracle {");
+        assert_eq!(render("{{repo}}", &values), "{oracle}");
+    }
+
+    #[test]
+    fn test_receive_signatures_and_replays() {
+        let state = AppState::default();
+        let config = config();
+        let body = br#"{"action":"opened","repository":{"name":"oracle"},"issue":{"number":7,"title":"Docs"}}"#;
+        let signature = sign("s3cret", body);
+        let delivery = |event, id, signature| Delivery { event, id: Some(id), signature };
+
+        assert_eq!(receive(&state, &config, delivery("issues", "d1", Some("sha256=00")), body), Err(Refusal::Signature));
+        let disabled = GithubConfig { enabled: false, ..config.clone() };
+        assert_eq!(receive(&state, &disabled, delivery("issues", "d1", Some(&signature)), body), Err(Refusal::Disabled));
+
+        let Ok(Outcome::Queued(id)) = receive(&state, &config, delivery("issues", "d1", Some(&signature)), body) else {
+            panic!("not queued");
+        };
+        assert_eq!(receive(&state, &config, delivery("issues", "d1", Some(&signature)), body), Ok(Outcome::Duplicate));
+        let star = br#"{"action":"created"}"#;
+        let starred = receive(&state, &config, delivery("star", "d2", Some(&sign("s3cret", star))), star);
+        assert_eq!(starred, Ok(Outcome::Unhandled("star".to_string())));
+
+        let entry = state.timeline.lock().get(id).cloned().expect("entry");
+        assert_eq!((entry.text.as_str(), entry.agent.as_deref()), ("New issue 7 in oracle: Docs", Some(AGENT)));
+        let status = state.github.lock().clone();
+        assert_eq!((status.received, status.queued, status.rejected, status.duplicates), (3, 1, 1, 1));
+        assert_eq!(status.unhandled.get("star"), Some(&1));
+    }
+}
@@ -0,0 +1,300 @@
+/// Claude Code hooks mode
+/// Installs Stop/Notification hooks in ~/.claude/settings.json that POST the hook payload to
+/// /hooks/claude/*, and turns those payloads into the same announcements the watcher makes.
+/// When the HTTP server moves to another port the installed hooks are moved with it.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde_json::{json, Map, Value};
+use tracing::info;
+
+use crate::config::{load_watcher_config, WatcherProfile};
+use crate::i18n::{self, Phrase};
+use crate::state::AppState;
+use crate::watcher::{announce, project_name, speech_style, Alert};
+
+/// Hook events we install, with the endpoint each one posts to
+const HOOK_EVENTS: [(&str, &str); 2] = [("Stop", "stop"), ("Notification", "notification")];
+
+/// Every command we install posts to a local URL under this path, on whichever port http.port
+/// had; that is how uninstall finds them
+const HOOK_PATH: &str = "/hooks/claude/";
+
+fn hook_command(port: u16, endpoint: &str) -> String {
+    format!(
+        "curl -s -m 2 -X POST -H 'Content-Type: application/json' --data-binary @- http://127.0.0.1:{}{}{} >/dev/null 2>&1 || true",
+        port,
+        HOOK_PATH,
+        endpoint
+    )
+}
+
+pub fn claude_settings_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".claude")
+        .join("settings.json")
+}
+
+fn read_settings(path: &Path) -> Result<Value, String> {
+    if !path.exists() {
+        return Ok(json!({}));
+    }
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if content.trim().is_empty() {
+        return Ok(json!({}));
+    }
+    let settings: Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    if !settings.is_object() {
+        return Err(format!("{} is not a JSON object", path.display()));
+    }
+    Ok(settings)
+}
+
+/// Copy the settings file aside before we touch it
+fn backup_settings(path: &Path) -> Result<(), String> {
+    if path.exists() {
+        let backup = path.with_extension("json.voice-tray.bak");
+        fs::copy(path, &backup).map_err(|e| format!("Failed to back up {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+fn write_settings(path: &Path, settings: &Value) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| e.to_string())
+}
+
+fn is_ours(hook: &Value) -> bool {
+    hook.get("command")
+        .and_then(|c| c.as_str())
+        .map(|c| c.contains("http://127.0.0.1:") && c.contains(HOOK_PATH))
+        .unwrap_or(false)
+}
+
+/// Drop our hook commands, then any matcher groups and events left empty.
+/// Returns true if anything was removed.
+fn remove_our_hooks(settings: &mut Value) -> bool {
+    let Some(hooks) = settings.get_mut("hooks").and_then(|h| h.as_object_mut()) else {
+        return false;
+    };
+    let mut removed = false;
+    for groups in hooks.values_mut().filter_map(|g| g.as_array_mut()) {
+        for group in groups.iter_mut() {
+            if let Some(list) = group.get_mut("hooks").and_then(|l| l.as_array_mut()) {
+                let before = list.len();
+                list.retain(|hook| !is_ours(hook));
+                removed |= list.len() != before;
+            }
+        }
+        groups.retain(|group| {
+            group.get("hooks").and_then(|l| l.as_array()).map(|l| !l.is_empty()).unwrap_or(true)
+        });
+    }
+    hooks.retain(|_, groups| groups.as_array().map(|g| !g.is_empty()).unwrap_or(true));
+    if hooks.is_empty() {
+        if let Some(obj) = settings.as_object_mut() {
+            obj.remove("hooks");
+        }
+    }
+    removed
+}
+
+/// The commands of our hooks in a settings file
+fn our_commands(path: &Path) -> Vec<String> {
+    let Some(hooks) = read_settings(path).ok().and_then(|s| s.get("hooks").cloned()) else {
+        return Vec::new();
+    };
+    hooks.as_object()
+        .into_iter()
+        .flat_map(|hooks| hooks.values())
+        .filter_map(|g| g.as_array())
+        .flatten()
+        .filter_map(|group| group.get("hooks").and_then(|l| l.as_array()))
+        .flatten()
+        .filter(|hook| is_ours(hook))
+        .filter_map(|hook| hook.get("command").and_then(|c| c.as_str()).map(str::to_string))
+        .collect()
+}
+
+/// Whether our hooks are present in a settings file
+pub fn hooks_installed(path: &Path) -> bool {
+    !our_commands(path).is_empty()
+}
+
+/// The port one of our hook commands posts to
+fn command_port(command: &str) -> Option<u16> {
+    let rest = &command[command.find("http://127.0.0.1:")? + "http://127.0.0.1:".len()..];
+    rest[..rest.find(HOOK_PATH)?].parse().ok()
+}
+
+/// Move installed hooks to `port`, where the HTTP server now listens. Hooks left on the old
+/// port would post to nothing while hooks mode keeps the watcher from announcing completions.
+/// Returns whether the hooks were rewritten.
+pub fn follow_port(port: u16) -> Result<bool, String> {
+    follow_port_in(&claude_settings_path(), port)
+}
+
+fn follow_port_in(path: &Path, port: u16) -> Result<bool, String> {
+    let commands = our_commands(path);
+    if commands.iter().all(|command| command_port(command) == Some(port)) {
+        return Ok(false);
+    }
+    install_hooks(path, port)?;
+    info!("Moved the Claude Code hooks to port {}", port);
+    Ok(true)
+}
+
+/// Merge our hook entries into a settings file, keeping everything else as is
+pub fn install_hooks(path: &Path, port: u16) -> Result<(), String> {
+    let mut settings = read_settings(path)?;
+    backup_settings(path)?;
+    remove_our_hooks(&mut settings); // reinstalling replaces rather than duplicates
+
+    let root = settings.as_object_mut().ok_or("settings is not a JSON object")?;
+    let hooks = root.entry("hooks").or_insert_with(|| Value::Object(Map::new()));
+    let hooks = hooks.as_object_mut().ok_or("\"hooks\" is not a JSON object")?;
+    for (event, endpoint) in HOOK_EVENTS {
+        let groups = hooks.entry(event).or_insert_with(|| json!([]));
+        let groups = groups.as_array_mut().ok_or_else(|| format!("hooks.{} is not an array", event))?;
+        groups.push(json!({
+            "matcher": "",
+            "hooks": [{ "type": "command", "command": hook_command(port, endpoint) }]
+        }));
+    }
+    write_settings(path, &settings)
+}
+
+/// Remove only our hook entries. Returns false if none were installed.
+pub fn uninstall_hooks(path: &Path) -> Result<bool, String> {
+    let mut settings = read_settings(path)?;
+    if !remove_our_hooks(&mut settings) {
+        return Ok(false);
+    }
+    backup_settings(path)?;
+    write_settings(path, &settings)?;
+    Ok(true)
+}
+
+/// Record hooks mode so the watcher stops announcing what the hooks already cover
+pub fn set_hooks_mode(state: &Arc<AppState>, active: bool) {
+    state.watcher_status.lock().hooks_mode = active;
+}
+
+fn announcements_paused(state: &Arc<AppState>) -> bool {
+    state.watcher_status.lock().paused
+}
+
+fn payload_project(payload: &Value) -> String {
+    project_name(payload.get("cwd").and_then(|c| c.as_str()).map(Path::new))
+}
+
+/// Stop hook → completion announcement
+pub fn handle_stop(state: &Arc<AppState>, payload: &Value) {
+    if announcements_paused(state) {
+        return;
+    }
+    let config = load_watcher_config();
+    let profile = config
+        .active_profiles()
+        .into_iter()
+        .find(|p| p.name == "claude")
+        .unwrap_or_else(WatcherProfile::claude);
+    let project = payload_project(payload);
+    let text = profile.completion_template().replace("{project}", &project);
+    let style = speech_style(&config, "completion", &project, profile.voice.as_deref());
+    let alert = Alert { kind: "completion", project: &project, agent: &profile.agent, text: &text, style, detail: None };
+    announce(state, &config, alert);
+}
+
+/// Notification hook → approval alert for permission prompts, question phrase otherwise
+pub fn handle_notification(state: &Arc<AppState>, payload: &Value) {
+    if announcements_paused(state) {
+        return;
+    }
+    let config = load_watcher_config();
+    let message = payload.get("message").and_then(|m| m.as_str()).unwrap_or("");
+    let project = payload_project(payload);
+    let (text, kind) = if message.to_lowercase().contains("permission") {
+        (config.reminder_phrase(0), "approval_alert")
+    } else {
+        (i18n::phrase_or(Phrase::Question, &config.question_phrase).replace("{project}", &project), "question")
+    };
+    let style = speech_style(&config, kind, &project, None);
+    let detail = (!message.is_empty()).then(|| message.to_string());
+    announce(state, &config, Alert { kind, project: &project, agent: "claude", text: &text, style, detail });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_install_merges_and_uninstall_removes_only_ours() {
+        let temp = TempDir::new().expect("temp dir");
+        let path = temp.path().join("settings.json");
+        let original = json!({
+            "model": "opus",
+            "hooks": {
+                "Stop": [{ "matcher": "", "hooks": [{ "type": "command", "command": "/usr/local/bin/notify.sh" }] }]
+            }
+        });
+        fs::write(&path, original.to_string()).expect("write");
+
+        install_hooks(&path, 37779).expect("install");
+        install_hooks(&path, 40000).expect("reinstall on another port");
+        assert!(hooks_installed(&path));
+        assert!(path.with_extension("json.voice-tray.bak").exists());
+
+        let settings = read_settings(&path).expect("read");
+        assert_eq!(settings["model"], "opus");
+        assert_eq!(settings["hooks"]["Stop"].as_array().map(Vec::len), Some(2));
+        assert_eq!(settings["hooks"]["Notification"].as_array().map(Vec::len), Some(1));
+
+        assert!(uninstall_hooks(&path).expect("uninstall"));
+        assert!(!hooks_installed(&path));
+        assert_eq!(read_settings(&path).expect("read"), original);
+        assert!(!uninstall_hooks(&path).expect("second uninstall"));
+    }
+
+    #[test]
+    fn test_install_into_missing_file() {
+        let temp = TempDir::new().expect("temp dir");
+        let path = temp.path().join(".claude").join("settings.json");
+        install_hooks(&path, 37779).expect("install");
+        assert!(hooks_installed(&path));
+
+        uninstall_hooks(&path).expect("uninstall");
+        assert_eq!(read_settings(&path).expect("read"), json!({}));
+    }
+
+    #[test]
+    fn test_hooks_follow_the_port() {
+        let temp = TempDir::new().expect("temp dir");
+        let path = temp.path().join("settings.json");
+        assert!(!follow_port_in(&path, 40000).expect("nothing installed"));
+        assert!(!path.exists());
+
+        install_hooks(&path, 37779).expect("install");
+        assert!(!follow_port_in(&path, 37779).expect("same port"));
+        assert!(follow_port_in(&path, 40000).expect("moved"));
+        let commands = our_commands(&path);
+        assert_eq!(commands.len(), 2);
+        assert!(commands.iter().all(|command| command_port(command) == Some(40000)), "{:?}", commands);
+    }
+
+    #[test]
+    fn test_invalid_settings_left_untouched() {
+        let temp = TempDir::new().expect("temp dir");
+        let path = temp.path().join("settings.json");
+        fs::write(&path, "{ not json").expect("write");
+        assert!(install_hooks(&path, 37779).is_err());
+        assert_eq!(fs::read_to_string(&path).expect("read"), "{ not json");
+    }
+}
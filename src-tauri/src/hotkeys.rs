@@ -0,0 +1,356 @@
+/// Global hotkeys for stopping speech, toggling mute and speaking the clipboard
+/// Registered with tauri-plugin-global-shortcut so they fire while the popup is hidden.
+/// Bindings come from config.json's ui section and are re-applied when it changes.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Manager, Wry};
+use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutEvent, ShortcutState};
+use tracing::warn;
+
+use crate::config::TrayConfig;
+use crate::notifications;
+use crate::state::AppState;
+use crate::tray;
+
+pub const MOD_CTRL: u8 = 1;
+pub const MOD_SHIFT: u8 = 2;
+pub const MOD_ALT: u8 = 4;
+pub const MOD_SUPER: u8 = 8; // Cmd on macOS, Win on Windows
+
+/// Keys a hotkey can end in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Letter(u8), // b'A'..=b'Z'
+    Digit(u8),  // b'0'..=b'9'
+    Function(u8), // 1..=12
+    Space,
+}
+
+/// A parsed binding such as "CmdOrCtrl+Shift+S"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Accelerator {
+    pub modifiers: u8,
+    pub key: Key,
+}
+
+/// Parse "Modifier+...+Key". CmdOrCtrl is Cmd on macOS and Ctrl elsewhere; a modifier is required
+/// unless the key is F1-F12, so a binding never swallows plain typing.
+pub fn parse_accelerator(binding: &str) -> Result<Accelerator, String> {
+    let mut modifiers = 0;
+    let mut key = None;
+    for token in binding.split('+').map(str::trim) {
+        let modifier = match token.to_lowercase().as_str() {
+            "cmdorctrl" | "commandorcontrol" if cfg!(target_os = "macos") => MOD_SUPER,
+            "cmdorctrl" | "commandorcontrol" => MOD_CTRL,
+            "cmd" | "command" | "super" | "meta" | "win" => MOD_SUPER,
+            "ctrl" | "control" => MOD_CTRL,
+            "shift" => MOD_SHIFT,
+            "alt" | "option" => MOD_ALT,
+            _ => 0,
+        };
+        if modifier != 0 {
+            modifiers |= modifier;
+            continue;
+        }
+        if key.is_some() {
+            return Err(format!("more than one key (\"{}\")", token));
+        }
+        key = Some(parse_key(token).ok_or_else(|| format!("unknown key \"{}\"", token))?);
+    }
+    let key = key.ok_or("no key after the modifiers")?;
+    if modifiers == 0 && !matches!(key, Key::Function(_)) {
+        return Err("needs a modifier such as Ctrl or Shift".to_string());
+    }
+    Ok(Accelerator { modifiers, key })
+}
+
+fn parse_key(token: &str) -> Option<Key> {
+    let upper = token.to_ascii_uppercase();
+    match upper.as_bytes() {
+        [c @ b'A'..=b'Z'] => Some(Key::Letter(*c)),
+        [c @ b'0'..=b'9'] => Some(Key::Digit(*c)),
+        _ if upper == "SPACE" => Some(Key::Space),
+        [b'F', ..] => upper[1..].parse().ok().filter(|n| (1..=12).contains(n)).map(Key::Function),
+        _ => None,
+    }
+}
+
+/// What a hotkey does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HotkeyAction {
+    Stop,
+    ToggleMute,
+    SpeakClipboard,
+}
+
+impl HotkeyAction {
+    const ALL: [HotkeyAction; 3] = [HotkeyAction::Stop, HotkeyAction::ToggleMute, HotkeyAction::SpeakClipboard];
+
+    fn label(self) -> &'static str {
+        match self {
+            HotkeyAction::Stop => "Stop",
+            HotkeyAction::ToggleMute => "Mute",
+            HotkeyAction::SpeakClipboard => "Speak Clipboard",
+        }
+    }
+
+    fn binding(self, config: &TrayConfig) -> &str {
+        match self {
+            HotkeyAction::Stop => config.stop_hotkey.trim(),
+            HotkeyAction::ToggleMute => config.mute_hotkey.trim(),
+            HotkeyAction::SpeakClipboard => config.clipboard_hotkey.trim(),
+        }
+    }
+}
+
+/// Run a hotkey's action through the same paths as POST /stop and the tray menu items
+fn trigger(state: &Arc<AppState>, action: HotkeyAction) {
+    match action {
+        HotkeyAction::Stop => {
+            state.stop_speaking();
+        }
+        HotkeyAction::ToggleMute => {
+            let muted = state.is_muted();
+            if let Err(e) = tray::set_muted(state, !muted) {
+                warn!("Failed to save mute setting: {}", e);
+            }
+        }
+        HotkeyAction::SpeakClipboard => {
+            if let Err(e) = tray::speak_clipboard(state) {
+                warn!("Speak Clipboard: {}", e);
+            }
+        }
+    }
+}
+
+/// OS side of a registration
+trait Backend {
+    type Handle;
+    fn register(&mut self, accelerator: Accelerator) -> Result<Self::Handle, String>;
+    fn unregister(&mut self, handle: Self::Handle);
+}
+
+/// A registered hotkey: the binding as written, what it parsed to, and the OS handle
+struct Bound<H> {
+    binding: String,
+    accelerator: Accelerator,
+    handle: H,
+}
+
+/// Bring the registrations in line with `config`. A binding that does not parse, clashes with
+/// another one or is refused by the OS is reported and the previous binding stays active.
+fn sync<B: Backend>(backend: &mut B, bound: &mut HashMap<HotkeyAction, Bound<B::Handle>>, config: &TrayConfig) -> Vec<String> {
+    let mut failures = Vec::new();
+    for action in HotkeyAction::ALL {
+        let wanted = action.binding(config);
+        if bound.get(&action).map(|b| b.binding.as_str()).unwrap_or("") == wanted {
+            continue;
+        }
+        if wanted.is_empty() {
+            if let Some(old) = bound.remove(&action) {
+                backend.unregister(old.handle);
+            }
+            continue;
+        }
+        let accelerator = match parse_accelerator(wanted) {
+            Ok(a) => a,
+            Err(e) => {
+                failures.push(format!("{} hotkey \"{}\": {}", action.label(), wanted, e));
+                continue;
+            }
+        };
+        if let Some((other, _)) = bound.iter().find(|(a, b)| **a != action && b.accelerator == accelerator) {
+            failures.push(format!("{} hotkey \"{}\": already used by {}", action.label(), wanted, other.label()));
+            continue;
+        }
+        // Release the old binding first; the new one may differ only in spelling
+        let previous = bound.remove(&action).map(|old| {
+            backend.unregister(old.handle);
+            (old.binding, old.accelerator)
+        });
+        match backend.register(accelerator) {
+            Ok(handle) => {
+                bound.insert(action, Bound { binding: wanted.to_string(), accelerator, handle });
+            }
+            Err(e) => {
+                failures.push(format!("{} hotkey \"{}\": {}", action.label(), wanted, e));
+                if let Some((binding, accelerator)) = previous {
+                    if let Ok(handle) = backend.register(accelerator) {
+                        bound.insert(action, Bound { binding, accelerator, handle });
+                    }
+                }
+            }
+        }
+    }
+    failures
+}
+
+fn report(state: &Arc<AppState>, failures: Vec<String>) {
+    for failure in failures {
+        warn!("{}", failure);
+        notifications::notify(state, "Hotkey not changed", &failure);
+    }
+}
+
+/// The plugin's side of a registration
+struct Plugin(AppHandle);
+
+impl Backend for Plugin {
+    type Handle = Shortcut;
+
+    fn register(&mut self, accelerator: Accelerator) -> Result<Shortcut, String> {
+        let shortcut = shortcut(accelerator)?;
+        self.0.global_shortcut().register(shortcut).map_err(|e| e.to_string())?;
+        Ok(shortcut)
+    }
+
+    fn unregister(&mut self, shortcut: Shortcut) {
+        if let Err(e) = self.0.global_shortcut().unregister(shortcut) {
+            warn!("Failed to release hotkey {}: {}", shortcut, e);
+        }
+    }
+}
+
+/// The plugin's shortcut for `accelerator`
+fn shortcut(accelerator: Accelerator) -> Result<Shortcut, String> {
+    let mut modifiers = Modifiers::empty();
+    for (flag, modifier) in [(MOD_CTRL, Modifiers::CONTROL), (MOD_SHIFT, Modifiers::SHIFT), (MOD_ALT, Modifiers::ALT), (MOD_SUPER, Modifiers::SUPER)] {
+        if accelerator.modifiers & flag != 0 {
+            modifiers |= modifier;
+        }
+    }
+    let code = match accelerator.key {
+        Key::Letter(c) => format!("Key{}", c as char),
+        Key::Digit(c) => format!("Digit{}", c as char),
+        Key::Function(n) => format!("F{}", n),
+        Key::Space => "Space".to_string(),
+    };
+    let code: Code = code.parse().map_err(|_| format!("key {} not supported", code))?;
+    Ok(Shortcut::new((!modifiers.is_empty()).then_some(modifiers), code))
+}
+
+thread_local! {
+    /// What is registered, kept on the main thread, where the plugin registers and this is read
+    static BOUND: RefCell<HashMap<HotkeyAction, Bound<Shortcut>>> = RefCell::new(HashMap::new());
+}
+
+/// The global-shortcut plugin, running the action of whichever hotkey was pressed
+pub fn plugin() -> TauriPlugin<Wry> {
+    tauri_plugin_global_shortcut::Builder::new().with_handler(on_shortcut).build()
+}
+
+fn on_shortcut(app: &AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    if event.state() != ShortcutState::Pressed {
+        return;
+    }
+    let (app, shortcut) = (app.clone(), *shortcut);
+    let queued = app.clone().run_on_main_thread(move || {
+        let action = BOUND.with(|bound| bound.borrow().iter().find(|(_, b)| b.handle == shortcut).map(|(action, _)| *action));
+        if let Some(action) = action {
+            trigger(&app.state::<Arc<AppState>>(), action);
+        }
+    });
+    if let Err(e) = queued {
+        warn!("Hotkey {} not handled: {}", shortcut, e);
+    }
+}
+
+/// Register the configured hotkeys; call once the app handle is stored
+pub fn start(state: &Arc<AppState>) {
+    apply(state);
+}
+
+/// Re-register after the ui section changed
+pub fn apply(state: &Arc<AppState>) {
+    let Some(app) = state.app_handle.read().clone() else {
+        return;
+    };
+    let main_state = state.clone();
+    let backend = app.clone();
+    let queued = app.run_on_main_thread(move || {
+        let config = main_state.tray_config();
+        let failures = BOUND.with(|bound| sync(&mut Plugin(backend), &mut bound.borrow_mut(), &config));
+        report(&main_state, failures);
+    });
+    crate::error::report(state, "Registering hotkeys", queued);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_accelerator() {
+        let cmd_or_ctrl = if cfg!(target_os = "macos") { MOD_SUPER } else { MOD_CTRL };
+        assert_eq!(
+            parse_accelerator("CmdOrCtrl+Shift+S"),
+            Ok(Accelerator { modifiers: cmd_or_ctrl | MOD_SHIFT, key: Key::Letter(b'S') })
+        );
+        assert_eq!(parse_accelerator("alt + f10"), Ok(Accelerator { modifiers: MOD_ALT, key: Key::Function(10) }));
+        assert_eq!(parse_accelerator("F9").map(|a| a.key), Ok(Key::Function(9)));
+        assert_eq!(parse_accelerator("Super+Space").map(|a| a.key), Ok(Key::Space));
+        assert_eq!(parse_accelerator("S"), Err("needs a modifier such as Ctrl or Shift".to_string()));
+        assert_eq!(parse_accelerator("Ctrl+Shift"), Err("no key after the modifiers".to_string()));
+        assert_eq!(parse_accelerator("Ctrl+S+M"), Err("more than one key (\"M\")".to_string()));
+        assert_eq!(parse_accelerator("Ctrl+F13"), Err("unknown key \"F13\"".to_string()));
+    }
+
+    #[test]
+    fn test_shortcut() {
+        let shortcut_of = |binding: &str| shortcut(parse_accelerator(binding).expect("parse"));
+        assert_eq!(shortcut_of("Ctrl+Shift+S"), Ok(Shortcut::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyS)));
+        assert_eq!(shortcut_of("Alt+7"), Ok(Shortcut::new(Some(Modifiers::ALT), Code::Digit7)));
+        assert_eq!(shortcut_of("Super+Space"), Ok(Shortcut::new(Some(Modifiers::SUPER), Code::Space)));
+        assert_eq!(shortcut_of("F9"), Ok(Shortcut::new(None, Code::F9)));
+    }
+
+    /// Records registrations; refuses Ctrl+Shift+X as if another app held it
+    #[derive(Default)]
+    struct FakeBackend {
+        registered: Vec<Accelerator>,
+    }
+
+    impl Backend for FakeBackend {
+        type Handle = Accelerator;
+
+        fn register(&mut self, accelerator: Accelerator) -> Result<Self::Handle, String> {
+            if accelerator == parse_accelerator("Ctrl+Shift+X").expect("parse") {
+                return Err("already taken by another application".to_string());
+            }
+            self.registered.push(accelerator);
+            Ok(accelerator)
+        }
+
+        fn unregister(&mut self, handle: Self::Handle) {
+            self.registered.retain(|h| *h != handle);
+        }
+    }
+
+    #[test]
+    fn test_sync_keeps_previous_binding_on_failure() {
+        let mut backend = FakeBackend::default();
+        let mut bound = HashMap::new();
+        let mut config = TrayConfig { stop_hotkey: "Ctrl+Shift+S".to_string(), mute_hotkey: "Ctrl+Shift+M".to_string(), ..Default::default() };
+        assert!(sync(&mut backend, &mut bound, &config).is_empty());
+        assert_eq!(backend.registered.len(), 2);
+
+        // Invalid, refused and clashing bindings all leave the old one registered
+        for (stop, reason) in [("Ctrl+Nope", "unknown key"), ("Ctrl+Shift+X", "already taken"), ("Shift+Ctrl+M", "already used by Mute")] {
+            config.stop_hotkey = stop.to_string();
+            let failures = sync(&mut backend, &mut bound, &config);
+            assert_eq!(failures.len(), 1);
+            assert!(failures[0].contains(reason), "{}", failures[0]);
+            assert_eq!(bound[&HotkeyAction::Stop].binding, "Ctrl+Shift+S");
+            assert_eq!(backend.registered.len(), 2);
+        }
+
+        config.stop_hotkey = "Ctrl+Shift+K".to_string();
+        config.mute_hotkey = String::new();
+        assert!(sync(&mut backend, &mut bound, &config).is_empty());
+        let keys: Vec<Key> = backend.registered.iter().map(|a| a.key).collect();
+        assert_eq!(keys, vec![Key::Letter(b'K')]);
+        assert!(!bound.contains_key(&HotkeyAction::ToggleMute));
+    }
+}
@@ -1,20 +1,153 @@
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, patch, post},
     Json, Router,
-    extract::State,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
 };
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
-use chrono::Utc;
+use tracing::{info, warn};
 
-use crate::config::load_mqtt_config;
-use crate::state::{AppState, VoiceEntry, SpeakRequest, SpeakResponse};
+use crate::config::{
+    active_profile, config_dir, config_warning, get_config_path, load_config, merge_settings, validate_config, AppConfig, ConfigView, Diagnostic, SaveOutcome,
+};
+use crate::agents;
+use crate::bundle::{self, Bundle, ImportMode, ImportOutcome};
+use crate::error::{report_error, VoiceTrayError};
+use crate::github;
+use crate::hooks;
+use crate::mdns;
+use crate::config::overrides;
+use crate::relay;
+use crate::reload;
+use crate::slack;
+use crate::speak_file::{self, Refusal};
+use crate::tasks::{self, TaskState};
+use crate::tray;
+use crate::state::{AppState, ClearScope, DeleteOutcome, SpeakRequest, SpeakResponse, WATCHER_EVENT_LOG_MAX};
+use crate::stats;
+use crate::voices;
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    limit: Option<usize>,
+}
+
+/// `?agent=ci` for one agent's entries, `?limit=10` for only the newest ten (still oldest first)
+#[derive(Debug, Deserialize)]
+struct TimelineQuery {
+    agent: Option<String>,
+    limit: Option<usize>,
+}
+
+/// `?agent=deploys` names the agent of a Slack webhook's entries
+#[derive(Debug, Deserialize)]
+struct SlackQuery {
+    agent: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MuteRequest {
+    muted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct VoicesQuery {
+    #[serde(default)]
+    refresh: bool,
+}
+
+/// `?import=true` takes a config bundle instead of a patch; `preview=true` only lists what
+/// it would change
+#[derive(Debug, Deserialize)]
+struct ConfigQuery {
+    #[serde(default)]
+    import: bool,
+    #[serde(default)]
+    mode: ImportMode,
+    #[serde(default)]
+    preview: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeleteQuery {
+    #[serde(default)]
+    remove: bool,
+    #[serde(default)]
+    force: bool,
+}
+
+/// `?scope=done|all|failed`, or `?agent=ci` for one agent's finished entries
+#[derive(Debug, Deserialize)]
+struct ClearQuery {
+    scope: Option<String>,
+    agent: Option<String>,
+}
+
+impl ClearQuery {
+    fn scope(self) -> Result<ClearScope, String> {
+        match (self.agent, self.scope.as_deref()) {
+            (Some(agent), None | Some("agent")) => Ok(ClearScope::Agent(agent)),
+            (Some(_), Some(_)) => Err("agent cannot be combined with another scope".to_string()),
+            (None, None) => Ok(ClearScope::Done),
+            (None, Some(scope)) => serde_json::from_value(serde_json::Value::String(scope.to_string()))
+                .map_err(|_| format!("Unknown scope \"{}\"; use done, all, failed or agent=<name>", scope)),
+        }
+    }
+}
 
-/// HTTP server port
+/// Default for http.port in config.json
 pub const VOICE_SERVER_PORT: u16 = 37779;
 
-/// Start HTTP server for receiving voice requests
-pub async fn start_http_server(state: Arc<AppState>) {
-    let app = Router::new()
+/// Written to the config folder while the server listens, so ovt finds the port in use
+/// whatever the config says
+pub const SERVER_FILE: &str = "server.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerFile {
+    pub port: u16,
+    pub pid: u32,
+}
+
+pub fn server_file_path() -> PathBuf {
+    config_dir().join(SERVER_FILE)
+}
+
+/// Write server.json for `port`, or remove it when the server is not listening
+fn advertise(port: Option<u16>) {
+    let path = server_file_path();
+    let result = match port {
+        Some(port) => serde_json::to_string(&ServerFile { port, pid: std::process::id() })
+            .map_err(std::io::Error::other)
+            .and_then(|json| std::fs::write(&path, json)),
+        None => match std::fs::remove_file(&path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        },
+    };
+    if let Err(e) = result {
+        warn!("Failed to update {}: {}", path.display(), e);
+    }
+}
+
+/// Move the HTTP server to http.port as now configured; requests in flight finish first
+pub fn rebind(state: &AppState) {
+    state.http_rebind.notify_one();
+}
+
+/// Keep why the server is not listening for /status, reporting it when set
+fn set_error(state: &AppState, error: Option<VoiceTrayError>) {
+    if let Some(error) = &error {
+        report_error(state, "HTTP server", error);
+    }
+    *state.http_error.lock() = error.map(|e| e.to_string());
+}
+
+/// Every route of the HTTP API, served by start_http_server
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
         .route("/", get(|| async {
             axum::response::Html(r#"<!DOCTYPE html>
 <html><head><title>Voice Tray API</title>
@@ -26,9 +159,25 @@ pre{background:#222;padding:15px;border-radius:8px;overflow-x:auto}.note{color:#
 
 <h2>HTTP API</h2>
 <ul>
-<li><code>POST /speak</code> - Queue text for speech</li>
-<li><code>GET /timeline</code> - Get speech queue</li>
-<li><code>GET /status</code> - Get server status (includes MQTT state)</li>
+<li><code>POST /speak</code> - Queue text for speech; with <code>X-Relayed-From</code> it is never relayed on</li>
+<li><code>POST /speak/file</code> - Queue a text file from <code>files.allowed_dirs</code> (<code>path</code>, <code>split</code>)</li>
+<li><code>POST /slack?agent=name</code> - Queue a Slack incoming-webhook payload (<code>text</code> or section <code>blocks</code>); answers <code>ok</code></li>
+<li><code>POST /github</code> - GitHub webhook: pushes, workflow runs, review requests and new issues, signed with <code>github.secret</code></li>
+<li><code>POST /stop</code> - Stop the message being spoken</li>
+<li><code>DELETE /speak/:id?remove=true&amp;force=true</code> - Remove a finished entry, cancel a queued one, or stop the one speaking (force)</li>
+<li><code>POST /mute</code> - Mute or unmute: <code>{"muted": true}</code></li>
+<li><code>GET /agents</code> - Every agent seen: first and last active, messages, voice and rate, and whether it is muted</li>
+<li><code>PATCH /agents/:name</code> - Mute or unmute one agent: <code>{"muted": true}</code></li>
+<li><code>GET /timeline?agent=name&amp;limit=10</code> - Get speech queue, optionally one agent's and only the newest entries</li>
+<li><code>DELETE /timeline?scope=done|all|failed</code>, <code>DELETE /timeline?agent=name</code> - Clear finished entries (all also drops the queue and stops speech)</li>
+<li><code>GET /status</code> - Get server status (includes MQTT and watcher state)</li>
+<li><code>GET /stats</code> - Messages queued, spoken, failed and suppressed, and time spent speaking, per agent since start</li>
+<li><code>GET /health</code> - Liveness check with MQTT and session watcher health and the last self-test</li>
+<li><code>GET /voices?refresh=true</code> - Installed system voices (cached; refresh reads them again)</li>
+<li><code>GET /watcher/events?limit=50</code> - What the session watcher parsed and whether it spoke</li>
+<li><code>GET /config</code>, <code>PUT /config</code> - Read the settings (password redacted) or change some; invalid ones come back as diagnostics</li>
+<li><code>PUT /config?import=true&amp;mode=merge|replace&amp;preview=true</code> - Import an exported settings bundle, or preview what it would change</li>
+<li><code>POST /hooks/claude/stop</code>, <code>POST /hooks/claude/notification</code> - Claude Code hook payloads (hooks mode)</li>
 </ul>
 <pre>curl -X POST http://127.0.0.1:37779/speak \
   -H "Content-Type: application/json" \
@@ -49,66 +198,239 @@ pre{background:#222;padding:15px;border-radius:8px;overflow-x:auto}.note{color:#
 }</pre>
 </body></html>"#)
         }))
-        .route("/speak", post(|State(state): State<Arc<AppState>>, Json(req): Json<SpeakRequest>| async move {
-            let id = state.next_id.lock()
-                .map(|mut next_id| {
-                    let id = *next_id;
-                    *next_id += 1;
-                    id
-                })
-                .unwrap_or(0);
-
-            let voice = req.voice.unwrap_or_else(|| "Samantha".to_string());
-            let rate = req.rate.unwrap_or(220);
-
-            let entry = VoiceEntry {
-                id,
-                timestamp: Utc::now(),
-                text: req.text,
-                voice: voice.clone(),
-                rate,
-                agent: req.agent,
-                status: "queued".to_string(),
-            };
-
-            if let Ok(mut timeline) = state.timeline.lock() {
-                timeline.push_back(entry);
-                while timeline.len() > 100 {
-                    timeline.pop_front();
+        .route("/speak", post(|State(state): State<Arc<AppState>>, headers: HeaderMap, Json(req): Json<SpeakRequest>| async move {
+            if let Err(error) = req.validate() {
+                return Err((StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": error }))));
+            }
+            // Sent by another instance's relay; such an entry is never relayed on
+            let relayed_from = headers.get(relay::RELAYED_FROM)
+                .map(|from| from.to_str().unwrap_or_default().trim().to_string())
+                .map(|from| if from.is_empty() { "another instance".to_string() } else { from });
+            Ok::<Json<SpeakResponse>, _>(Json(state.speak_from(req, relayed_from)))
+        }))
+        .route("/speak/file", post(|State(state): State<Arc<AppState>>, Json(req): Json<speak_file::FileRequest>| async move {
+            let queued = tokio::task::spawn_blocking(move || speak_file::speak(&state, &load_config().files, req)).await;
+            let refused = |code: StatusCode, error: &str| Err((code, Json(serde_json::json!({ "error": error }))));
+            match queued {
+                Ok(Ok(response)) => Ok(Json(response)),
+                Ok(Err(refusal)) => {
+                    let code = match refusal {
+                        Refusal::NotAllowed(_) => StatusCode::FORBIDDEN,
+                        Refusal::NotFound(_) => StatusCode::NOT_FOUND,
+                        Refusal::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+                        Refusal::NotText(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                        Refusal::Invalid(_) => StatusCode::BAD_REQUEST,
+                    };
+                    refused(code, refusal.message())
                 }
+                Err(e) => refused(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+            }
+        }))
+        .route("/slack", post(|State(state): State<Arc<AppState>>, Query(query): Query<SlackQuery>, body: axum::body::Bytes| async move {
+            let bad_request = |error: String| (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": error })));
+            // Read as JSON whatever the Content-Type, which webhook senders do not all set
+            let payload: slack::Payload = serde_json::from_slice(&body).map_err(|e| bad_request(format!("Invalid payload: {}", e)))?;
+            let text = payload.speech().map_err(bad_request)?;
+            let agent = query.agent.filter(|a| !a.trim().is_empty()).unwrap_or_else(|| slack::DEFAULT_AGENT.to_string());
+            let req = SpeakRequest { text, voice: None, rate: None, agent: Some(agent) };
+            req.validate().map_err(bad_request)?;
+            state.speak(req);
+            // Senders built for Slack check for its literal answer
+            Ok::<_, (StatusCode, Json<serde_json::Value>)>("ok")
+        }))
+        .route("/github", post(|State(state): State<Arc<AppState>>, headers: HeaderMap, body: axum::body::Bytes| async move {
+            let event = headers.get(github::EVENT_HEADER).and_then(|v| v.to_str().ok()).unwrap_or_default();
+            let id = headers.get(github::DELIVERY_HEADER).and_then(|v| v.to_str().ok());
+            let signature = headers.get(github::SIGNATURE_HEADER).and_then(|v| v.to_str().ok());
+            let delivery = github::Delivery { event, id, signature };
+            let refused = |code: StatusCode, error: &str| (code, Json(serde_json::json!({ "error": error })));
+            match github::receive(&state, &load_config().github, delivery, &body) {
+                Ok(github::Outcome::Queued(id)) => (StatusCode::OK, Json(serde_json::json!({ "status": "queued", "id": id }))),
+                Ok(github::Outcome::Skipped(reason)) => (StatusCode::OK, Json(serde_json::json!({ "status": "skipped", "reason": reason }))),
+                Ok(github::Outcome::Duplicate) => (StatusCode::OK, Json(serde_json::json!({ "status": "duplicate" }))),
+                Ok(github::Outcome::Unhandled(event)) => (StatusCode::ACCEPTED, Json(serde_json::json!({ "status": "unhandled", "event": event }))),
+                Err(github::Refusal::Disabled) => refused(StatusCode::NOT_FOUND, "GitHub webhooks are off (github.enabled)"),
+                Err(github::Refusal::Signature) => refused(StatusCode::UNAUTHORIZED, "Invalid signature"),
+                Err(github::Refusal::Invalid(error)) => refused(StatusCode::BAD_REQUEST, &error),
+            }
+        }))
+        .route("/stop", post(|State(state): State<Arc<AppState>>| async move {
+            Json(serde_json::json!({ "stopped": state.stop_speaking() }))
+        }))
+        .route("/speak/:id", delete(|State(state): State<Arc<AppState>>, Path(id): Path<u64>, Query(query): Query<DeleteQuery>| async move {
+            let result = state.delete_entry(id, query.remove, query.force);
+            let code = match result {
+                DeleteOutcome::Removed | DeleteOutcome::Cancelled => StatusCode::OK,
+                DeleteOutcome::NotFound => StatusCode::NOT_FOUND,
+                DeleteOutcome::Refused => StatusCode::CONFLICT,
+            };
+            (code, Json(serde_json::json!({ "id": id, "result": result })))
+        }))
+        .route("/mute", post(|State(state): State<Arc<AppState>>, Json(req): Json<MuteRequest>| async move {
+            let muted = req.muted;
+            match tokio::task::spawn_blocking(move || tray::set_muted(&state, muted)).await {
+                Ok(Ok(())) => Ok(Json(serde_json::json!({ "muted": muted }))),
+                Ok(Err(error)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": error })))),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e.to_string() })))),
             }
-
-            Json(SpeakResponse { id, status: "queued".to_string() })
         }))
-        .route("/timeline", get(|State(state): State<Arc<AppState>>| async move {
-            let entries = state.timeline.lock()
-                .map(|t| t.iter().cloned().collect::<Vec<_>>())
-                .unwrap_or_default();
+        .route("/agents", get(|State(state): State<Arc<AppState>>| async move {
+            Json(agents::list(&state))
+        }))
+        .route("/agents/:name", patch(|State(state): State<Arc<AppState>>, Path(name): Path<String>, Json(req): Json<MuteRequest>| async move {
+            match tokio::task::spawn_blocking(move || agents::set_muted(&state, &name, req.muted)).await {
+                Ok(Ok(agent)) => Ok(Json(agent)),
+                Ok(Err(error)) => Err((StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": error })))),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e.to_string() })))),
+            }
+        }))
+        .route("/timeline", get(|State(state): State<Arc<AppState>>, Query(query): Query<TimelineQuery>| async move {
+            let mut entries = state.timeline.lock().iter().rev()
+                .filter(|e| query.agent.is_none() || e.agent == query.agent)
+                .take(query.limit.unwrap_or(usize::MAX))
+                .cloned()
+                .collect::<Vec<_>>();
+            entries.reverse();
             Json(entries)
+        }).delete(|State(state): State<Arc<AppState>>, Query(query): Query<ClearQuery>| async move {
+            match query.scope() {
+                Ok(scope) => Ok(Json(state.clear_timeline(&scope))),
+                Err(error) => Err((StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": error })))),
+            }
         }))
         .route("/status", get(|State(state): State<Arc<AppState>>| async move {
-            let (total, queued) = state.timeline.lock()
-                .map(|t| (t.len(), t.iter().filter(|e| e.status == "queued").count()))
-                .unwrap_or((0, 0));
-            let is_speaking = state.is_speaking.lock().map(|g| *g).unwrap_or(false);
-            let mqtt_status = state.mqtt_status.lock()
-                .map(|g| g.clone())
-                .unwrap_or_else(|_| "unknown".to_string());
-            let config = load_mqtt_config();
+            let (total, queued, revision) = {
+                let timeline = state.timeline.lock();
+                (timeline.len(), timeline.iter().filter(|e| e.status == "queued").count(), timeline.revision())
+            };
+            let is_speaking = state.is_speaking();
+            let mqtt_status = state.mqtt_status.read().clone();
+            let watcher = state.watcher_status.lock().clone();
+            let config = load_config().mqtt;
             Json(serde_json::json!({
                 "total": total,
                 "queued": queued,
+                "revision": revision,
                 "is_speaking": is_speaking,
+                "muted": state.is_muted(),
+                "snooze_minutes_left": state.snooze_minutes_left(chrono::Utc::now()),
                 "mqtt_status": mqtt_status,
-                "mqtt_broker": format!("{}:{}", config.broker, config.port)
+                "mqtt_broker": format!("{}:{}", config.broker, config.port),
+                "config_path": get_config_path(),
+                "profile": active_profile(),
+                "overrides": overrides::shown(),
+                "config_warning": config_warning(),
+                "http_error": state.http_error.lock().clone(),
+                "watcher": watcher,
+                "focus": state.focus.lock().clone(),
+                "ntfy": state.ntfy.lock().clone(),
+                "mdns": state.mdns.lock().clone(),
+                "github": state.github.lock().clone(),
+                "mirror": state.mirror.lock().clone(),
+                "stats": stats::snapshot(&state)
             }))
         }))
-        .with_state(state);
-
-    let listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{}", VOICE_SERVER_PORT))
-        .await
-        .expect("Failed to bind HTTP server");
+        .route("/stats", get(|State(state): State<Arc<AppState>>| async move {
+            Json(stats::snapshot(&state))
+        }))
+        .route("/health", get(|State(state): State<Arc<AppState>>| async move {
+            let mqtt_status = state.mqtt_status.read().clone();
+            let watcher = state.watcher_status.lock().clone();
+            let tasks = tasks::states(&state);
+            let healthy = tasks.values().all(|t| *t == TaskState::Running);
+            Json(serde_json::json!({
+                "status": if healthy { "ok" } else { "degraded" },
+                "version": env!("CARGO_PKG_VERSION"),
+                "mqtt_status": mqtt_status,
+                "watcher": watcher,
+                "tasks": tasks,
+                "diagnostics": state.doctor.lock().clone()
+            }))
+        }))
+        .route("/voices", get(|State(state): State<Arc<AppState>>, Query(query): Query<VoicesQuery>| async move {
+            match voices::list(&state.voice_config().voice(), query.refresh) {
+                Ok(list) => Ok(Json(list)),
+                Err(error) => Err((StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "error": error })))),
+            }
+        }))
+        .route("/watcher/events", get(|State(state): State<Arc<AppState>>, Query(query): Query<EventsQuery>| async move {
+            Json(state.recent_watcher_events(query.limit.unwrap_or(WATCHER_EVENT_LOG_MAX)))
+        }))
+        .route("/config", get(|State(state): State<Arc<AppState>>| async move {
+            Json(ConfigView::load(state.tray_config(), state.voice_config()))
+        }).put(|State(state): State<Arc<AppState>>, Query(query): Query<ConfigQuery>, Json(patch): Json<serde_json::Value>| async move {
+            if query.import {
+                let bundle = Bundle::parse(patch)
+                    .map_err(|error| (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": error }))))?;
+                let imported = tokio::task::spawn_blocking(move || bundle::import(&state, &bundle, None, query.mode, !query.preview)).await;
+                return match imported {
+                    Ok(Ok(outcome @ ImportOutcome::Rejected { .. })) => Err((StatusCode::UNPROCESSABLE_ENTITY, Json(serde_json::json!(outcome)))),
+                    Ok(Ok(outcome)) => Ok(Json(serde_json::json!(outcome))),
+                    Ok(Err(error)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": error })))),
+                    Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e.to_string() })))),
+                };
+            }
+            let current = AppConfig::load_redacted(state.tray_config(), state.voice_config());
+            let settings = merge_settings(&current, patch)
+                .map_err(|error| (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": error }))))?;
+            let saved = tokio::task::spawn_blocking(move || {
+                let diagnostics = validate_config(&settings);
+                if diagnostics.iter().any(Diagnostic::is_error) {
+                    return Err(diagnostics);
+                }
+                reload::save_settings(&state, settings).map(|outcome| SaveOutcome { warnings: diagnostics, ..outcome })
+            }).await;
+            match saved {
+                Ok(Ok(outcome)) => Ok(Json(serde_json::json!(outcome))),
+                Ok(Err(diagnostics)) => Err((StatusCode::UNPROCESSABLE_ENTITY, Json(serde_json::json!({ "diagnostics": diagnostics })))),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e.to_string() })))),
+            }
+        }))
+        .route("/hooks/claude/stop", post(|State(state): State<Arc<AppState>>, Json(payload): Json<serde_json::Value>| async move {
+            hooks::handle_stop(&state, &payload);
+            Json(serde_json::json!({ "status": "ok" }))
+        }))
+        .route("/hooks/claude/notification", post(|State(state): State<Arc<AppState>>, Json(payload): Json<serde_json::Value>| async move {
+            hooks::handle_notification(&state, &payload);
+            Json(serde_json::json!({ "status": "ok" }))
+        }))
+        .with_state(state)
+}
 
-    println!("Voice HTTP server listening on http://127.0.0.1:{}", VOICE_SERVER_PORT);
-    axum::serve(listener, app).await.unwrap();
+/// Start HTTP server for receiving voice requests
+pub async fn start_http_server(state: Arc<AppState>) {
+    let app = router(state.clone());
+    while !state.is_shutting_down() {
+        let config = load_config().http;
+        let port = config.port;
+        *state.http_port.lock() = port;
+        let addr = SocketAddr::new(config.bind_ip(), port);
+        match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => {
+                info!("Voice HTTP server listening on http://{}", addr);
+                set_error(&state, None);
+                advertise(Some(port));
+                mdns::update(&state, Some(addr));
+                if let Err(e) = hooks::follow_port(port) {
+                    warn!("Failed to move the Claude Code hooks to port {}: {}", port, e);
+                }
+                let waiter = state.clone();
+                let rebind = async move { waiter.http_rebind.notified().await };
+                let served = axum::serve(listener, app.clone()).with_graceful_shutdown(rebind).await;
+                advertise(None);
+                mdns::update(&state, None);
+                if let Err(e) = served {
+                    set_error(&state, Some(VoiceTrayError::Http(format!("server on port {} stopped: {}", port, e))));
+                    state.http_rebind.notified().await;
+                }
+            }
+            Err(e) => {
+                // Another program holds the port; the tray keeps working over MQTT until
+                // http.port is changed
+                set_error(&state, Some(VoiceTrayError::Http(format!("port {} unavailable: {}", port, e))));
+                advertise(None);
+                state.http_rebind.notified().await;
+            }
+        }
+    }
 }
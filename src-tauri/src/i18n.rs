@@ -0,0 +1,472 @@
+/// Phrases the app speaks on its own: watcher alerts, focus and snooze summaries, the test
+/// sentence and the notes prepare puts in cleaned text. Each is looked up in voice.language:
+/// first in `phrases/<language>.json` in the config folder, then in the built-in table, and
+/// last in English, phrase by phrase, so a missing translation is never silence. The
+/// language also picks the default voice while voice.default_voice is left at the English one.
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::warn;
+
+use crate::config::{config_dir, load_config};
+
+/// Folder in the config folder with the user's phrase files
+pub const PHRASES_DIR: &str = "phrases";
+
+/// Key of a phrase file's voice for the language
+const VOICE_KEY: &str = "voice";
+
+/// Languages with a built-in table, and the voice each is spoken with by default
+pub const LANGUAGES: [(&str, &str); 3] = [("en", ENGLISH_VOICE), ("th", "Kanya"), ("es", "Mónica")];
+
+/// voice.default_voice as shipped; left at this, the default voice follows the language
+pub const ENGLISH_VOICE: &str = "Samantha";
+
+/// Everything the app says in its own words
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phrase {
+    ApprovalNeeded,
+    ApprovalReminder,
+    ApprovalBlocked,
+    Question,
+    Plan,
+    Aggregate,
+    Compaction,
+    ContextLimit,
+    UsageLimit,
+    LongRunning,
+    CommandFailed,
+    ApiError,
+    ClaudeStop,
+    Done,
+    /// Names the project when several sessions could be meant
+    InProject,
+    Spawning,
+    ThisSession,
+    /// The last two of a list of names
+    And,
+    /// Zero to ten, separated by spaces
+    Numbers,
+    Second,
+    Seconds,
+    Minute,
+    Minutes,
+    Hour,
+    Hours,
+    UnderThousandTokens,
+    ThousandTokens,
+    MillionTokens,
+    LessThanCent,
+    Cent,
+    Cents,
+    Dollar,
+    Dollars,
+    FocusEndedOne,
+    FocusEnded,
+    SnoozeEndedOne,
+    SnoozeEnded,
+    TestVoice,
+    /// Spoken by the doctor's sample
+    Diagnostics,
+    CodeLeftOut,
+    Link,
+    Truncated,
+    GithubPush,
+    GithubWorkflow,
+    GithubReview,
+    GithubIssue,
+    Commit,
+    Commits,
+    /// Workflow run results
+    Passed,
+    Failed,
+    TimedOut,
+    Cancelled,
+}
+
+/// The approval phrases in the order they escalate
+pub const REMINDERS: [Phrase; 3] = [Phrase::ApprovalNeeded, Phrase::ApprovalReminder, Phrase::ApprovalBlocked];
+
+impl Phrase {
+    /// Key in phrase files
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::ApprovalNeeded => "approval_needed",
+            Self::ApprovalReminder => "approval_reminder",
+            Self::ApprovalBlocked => "approval_blocked",
+            Self::Question => "question",
+            Self::Plan => "plan",
+            Self::Aggregate => "aggregate",
+            Self::Compaction => "compaction",
+            Self::ContextLimit => "context_limit",
+            Self::UsageLimit => "usage_limit",
+            Self::LongRunning => "long_running",
+            Self::CommandFailed => "command_failed",
+            Self::ApiError => "api_error",
+            Self::ClaudeStop => "claude_stop",
+            Self::Done => "done",
+            Self::InProject => "in_project",
+            Self::Spawning => "spawning",
+            Self::ThisSession => "this_session",
+            Self::And => "and",
+            Self::Numbers => "numbers",
+            Self::Second => "second",
+            Self::Seconds => "seconds",
+            Self::Minute => "minute",
+            Self::Minutes => "minutes",
+            Self::Hour => "hour",
+            Self::Hours => "hours",
+            Self::UnderThousandTokens => "under_thousand_tokens",
+            Self::ThousandTokens => "thousand_tokens",
+            Self::MillionTokens => "million_tokens",
+            Self::LessThanCent => "less_than_cent",
+            Self::Cent => "cent",
+            Self::Cents => "cents",
+            Self::Dollar => "dollar",
+            Self::Dollars => "dollars",
+            Self::FocusEndedOne => "focus_ended_one",
+            Self::FocusEnded => "focus_ended",
+            Self::SnoozeEndedOne => "snooze_ended_one",
+            Self::SnoozeEnded => "snooze_ended",
+            Self::TestVoice => "test_voice",
+            Self::Diagnostics => "diagnostics",
+            Self::CodeLeftOut => "code_left_out",
+            Self::Link => "link",
+            Self::Truncated => "truncated",
+            Self::GithubPush => "github_push",
+            Self::GithubWorkflow => "github_workflow",
+            Self::GithubReview => "github_review",
+            Self::GithubIssue => "github_issue",
+            Self::Commit => "commit",
+            Self::Commits => "commits",
+            Self::Passed => "passed",
+            Self::Failed => "failed",
+            Self::TimedOut => "timed_out",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    /// The English phrase, which every phrase has
+    pub fn english(self) -> &'static str {
+        match self {
+            Self::ApprovalNeeded => "Action needed, please approve",
+            Self::ApprovalReminder => "Reminder, Claude is still waiting for approval",
+            Self::ApprovalBlocked => "Claude has been blocked on approval for a while",
+            Self::Question => "Claude has a question in {project}",
+            Self::Plan => "Claude has a plan ready in {project}",
+            Self::Aggregate => "{count} more sessions finished: {projects}",
+            Self::Compaction => "Context compacted in {project}",
+            Self::ContextLimit => "Context almost full in {project}",
+            Self::UsageLimit => "Usage limit reached in {project}",
+            Self::LongRunning => "Command still running in {project} after {duration}",
+            Self::CommandFailed => "Command failed in {project}",
+            Self::ApiError => "API error in {project}",
+            Self::ClaudeStop => "Claude Stop",
+            Self::Done => "Done in {project}",
+            Self::InProject => "{phrase} in {project}",
+            Self::Spawning => "Spawning {task}",
+            Self::ThisSession => "this session",
+            Self::And => "{first} and {last}",
+            Self::Numbers => "zero one two three four five six seven eight nine ten",
+            Self::Second => "one second",
+            Self::Seconds => "{n} seconds",
+            Self::Minute => "one minute",
+            Self::Minutes => "{n} minutes",
+            Self::Hour => "one hour",
+            Self::Hours => "{n} hours",
+            Self::UnderThousandTokens => "under a thousand tokens",
+            Self::ThousandTokens => "{n} thousand tokens",
+            Self::MillionTokens => "{n} million tokens",
+            Self::LessThanCent => "less than a cent",
+            Self::Cent => "about 1 cent",
+            Self::Cents => "about {n} cents",
+            Self::Dollar => "about 1 dollar",
+            Self::Dollars => "about {n} dollars",
+            Self::FocusEndedOne => "Focus ended, 1 message suppressed",
+            Self::FocusEnded => "Focus ended, {count} messages suppressed",
+            Self::SnoozeEndedOne => "Snooze ended, 1 message waiting",
+            Self::SnoozeEnded => "Snooze ended, {count} messages waiting",
+            Self::TestVoice => "Hello! Voice Tray is working.",
+            Self::Diagnostics => "Oracle Voice Tray diagnostics",
+            Self::CodeLeftOut => "Code block left out.",
+            Self::Link => "link",
+            Self::Truncated => "Text truncated, {count} more characters not read.",
+            Self::GithubPush => "{sender} pushed {commits} to {branch} in {repo}",
+            Self::GithubWorkflow => "{workflow} {result} on {branch} in {repo}",
+            Self::GithubReview => "{sender} asked for a review of pull request {number} in {repo}: {title}",
+            Self::GithubIssue => "New issue {number} in {repo}: {title}",
+            Self::Commit => "one commit",
+            Self::Commits => "{n} commits",
+            Self::Passed => "passed",
+            Self::Failed => "failed",
+            Self::TimedOut => "timed out",
+            Self::Cancelled => "was cancelled",
+        }
+    }
+}
+
+const THAI: &[(Phrase, &str)] = &[
+    (Phrase::ApprovalNeeded, "มีงานรออนุมัติ กรุณาอนุมัติ"),
+    (Phrase::ApprovalReminder, "เตือนอีกครั้ง Claude ยังรอการอนุมัติอยู่"),
+    (Phrase::ApprovalBlocked, "Claude รอการอนุมัติมาสักพักแล้ว"),
+    (Phrase::Question, "Claude มีคำถามใน {project}"),
+    (Phrase::Plan, "Claude มีแผนพร้อมแล้วใน {project}"),
+    (Phrase::Aggregate, "อีก {count} เซสชันเสร็จแล้ว: {projects}"),
+    (Phrase::Compaction, "บีบอัดบริบทแล้วใน {project}"),
+    (Phrase::ContextLimit, "บริบทใกล้เต็มแล้วใน {project}"),
+    (Phrase::UsageLimit, "ใช้งานถึงขีดจำกัดแล้วใน {project}"),
+    (Phrase::LongRunning, "คำสั่งใน {project} ยังทำงานอยู่หลังผ่านไป {duration}"),
+    (Phrase::CommandFailed, "คำสั่งล้มเหลวใน {project}"),
+    (Phrase::ApiError, "เกิดข้อผิดพลาดของ API ใน {project}"),
+    (Phrase::ClaudeStop, "Claude เสร็จแล้ว"),
+    (Phrase::Done, "เสร็จแล้วใน {project}"),
+    (Phrase::InProject, "{phrase} ใน {project}"),
+    (Phrase::Spawning, "กำลังเริ่ม {task}"),
+    (Phrase::ThisSession, "เซสชันนี้"),
+    (Phrase::And, "{first} และ {last}"),
+    (Phrase::Numbers, "ศูนย์ หนึ่ง สอง สาม สี่ ห้า หก เจ็ด แปด เก้า สิบ"),
+    (Phrase::Second, "หนึ่งวินาที"),
+    (Phrase::Seconds, "{n} วินาที"),
+    (Phrase::Minute, "หนึ่งนาที"),
+    (Phrase::Minutes, "{n} นาที"),
+    (Phrase::Hour, "หนึ่งชั่วโมง"),
+    (Phrase::Hours, "{n} ชั่วโมง"),
+    (Phrase::UnderThousandTokens, "ไม่ถึงหนึ่งพันโทเคน"),
+    (Phrase::ThousandTokens, "{n} พันโทเคน"),
+    (Phrase::MillionTokens, "{n} ล้านโทเคน"),
+    (Phrase::LessThanCent, "ไม่ถึงหนึ่งเซนต์"),
+    (Phrase::Cent, "ประมาณ 1 เซนต์"),
+    (Phrase::Cents, "ประมาณ {n} เซนต์"),
+    (Phrase::Dollar, "ประมาณ 1 ดอลลาร์"),
+    (Phrase::Dollars, "ประมาณ {n} ดอลลาร์"),
+    (Phrase::FocusEndedOne, "โหมดโฟกัสจบแล้ว ระงับไว้ 1 ข้อความ"),
+    (Phrase::FocusEnded, "โหมดโฟกัสจบแล้ว ระงับไว้ {count} ข้อความ"),
+    (Phrase::SnoozeEndedOne, "หยุดเสียงชั่วคราวจบแล้ว มี 1 ข้อความรออยู่"),
+    (Phrase::SnoozeEnded, "หยุดเสียงชั่วคราวจบแล้ว มี {count} ข้อความรออยู่"),
+    (Phrase::TestVoice, "สวัสดี! Voice Tray ทำงานแล้ว"),
+    (Phrase::Diagnostics, "ตรวจสอบระบบ Oracle Voice Tray"),
+    (Phrase::CodeLeftOut, "ข้ามบล็อกโค้ด"),
+    (Phrase::Link, "ลิงก์"),
+    (Phrase::Truncated, "ข้อความถูกตัด อีก {count} ตัวอักษรไม่ได้อ่าน"),
+    (Phrase::GithubPush, "{sender} พุช {commits} ไปที่ {branch} ใน {repo}"),
+    (Phrase::GithubWorkflow, "{workflow} {result} บน {branch} ใน {repo}"),
+    (Phrase::GithubReview, "{sender} ขอให้รีวิว pull request {number} ใน {repo}: {title}"),
+    (Phrase::GithubIssue, "มี issue ใหม่ {number} ใน {repo}: {title}"),
+    (Phrase::Commit, "หนึ่งคอมมิต"),
+    (Phrase::Commits, "{n} คอมมิต"),
+    (Phrase::Passed, "ผ่าน"),
+    (Phrase::Failed, "ล้มเหลว"),
+    (Phrase::TimedOut, "หมดเวลา"),
+    (Phrase::Cancelled, "ถูกยกเลิก"),
+];
+
+const SPANISH: &[(Phrase, &str)] = &[
+    (Phrase::ApprovalNeeded, "Se necesita tu aprobación"),
+    (Phrase::ApprovalReminder, "Recordatorio, Claude sigue esperando tu aprobación"),
+    (Phrase::ApprovalBlocked, "Claude lleva un rato esperando aprobación"),
+    (Phrase::Question, "Claude tiene una pregunta en {project}"),
+    (Phrase::Plan, "Claude tiene un plan listo en {project}"),
+    (Phrase::Aggregate, "{count} sesiones más terminaron: {projects}"),
+    (Phrase::Compaction, "Contexto compactado en {project}"),
+    (Phrase::ContextLimit, "Contexto casi lleno en {project}"),
+    (Phrase::UsageLimit, "Límite de uso alcanzado en {project}"),
+    (Phrase::LongRunning, "El comando sigue en marcha en {project} después de {duration}"),
+    (Phrase::CommandFailed, "Falló un comando en {project}"),
+    (Phrase::ApiError, "Error de la API en {project}"),
+    (Phrase::ClaudeStop, "Claude terminó"),
+    (Phrase::Done, "Terminado en {project}"),
+    (Phrase::InProject, "{phrase} en {project}"),
+    (Phrase::Spawning, "Iniciando {task}"),
+    (Phrase::ThisSession, "esta sesión"),
+    (Phrase::And, "{first} y {last}"),
+    (Phrase::Numbers, "cero uno dos tres cuatro cinco seis siete ocho nueve diez"),
+    (Phrase::Second, "un segundo"),
+    (Phrase::Seconds, "{n} segundos"),
+    (Phrase::Minute, "un minuto"),
+    (Phrase::Minutes, "{n} minutos"),
+    (Phrase::Hour, "una hora"),
+    (Phrase::Hours, "{n} horas"),
+    (Phrase::UnderThousandTokens, "menos de mil tokens"),
+    (Phrase::ThousandTokens, "{n} mil tokens"),
+    (Phrase::MillionTokens, "{n} millones de tokens"),
+    (Phrase::LessThanCent, "menos de un centavo"),
+    (Phrase::Cent, "alrededor de 1 centavo"),
+    (Phrase::Cents, "alrededor de {n} centavos"),
+    (Phrase::Dollar, "alrededor de 1 dólar"),
+    (Phrase::Dollars, "alrededor de {n} dólares"),
+    (Phrase::FocusEndedOne, "Concentración terminada, 1 mensaje suprimido"),
+    (Phrase::FocusEnded, "Concentración terminada, {count} mensajes suprimidos"),
+    (Phrase::SnoozeEndedOne, "Pausa terminada, 1 mensaje en espera"),
+    (Phrase::SnoozeEnded, "Pausa terminada, {count} mensajes en espera"),
+    (Phrase::TestVoice, "¡Hola! Voice Tray funciona."),
+    (Phrase::Diagnostics, "Diagnóstico de Oracle Voice Tray"),
+    (Phrase::CodeLeftOut, "Bloque de código omitido."),
+    (Phrase::Link, "enlace"),
+    (Phrase::Truncated, "Texto recortado, {count} caracteres más sin leer."),
+    (Phrase::GithubPush, "{sender} subió {commits} a {branch} en {repo}"),
+    (Phrase::GithubWorkflow, "{workflow} {result} en {branch} de {repo}"),
+    (Phrase::GithubReview, "{sender} pidió revisar el pull request {number} en {repo}: {title}"),
+    (Phrase::GithubIssue, "Nueva incidencia {number} en {repo}: {title}"),
+    (Phrase::Commit, "un commit"),
+    (Phrase::Commits, "{n} commits"),
+    (Phrase::Passed, "pasó"),
+    (Phrase::Failed, "falló"),
+    (Phrase::TimedOut, "agotó el tiempo"),
+    (Phrase::Cancelled, "se canceló"),
+];
+
+/// "es" for "es-MX": built-in tables are by language, not region
+fn base(language: &str) -> String {
+    language.split(['-', '_']).next().unwrap_or_default().to_ascii_lowercase()
+}
+
+fn table(language: &str) -> &'static [(Phrase, &'static str)] {
+    match base(language).as_str() {
+        "th" => THAI,
+        "es" => SPANISH,
+        _ => &[],
+    }
+}
+
+/// Whether `language` is a code like "en", "th" or "es-MX"
+pub fn valid_language(language: &str) -> bool {
+    let mut parts = language.split(['-', '_']);
+    let primary = parts.next().unwrap_or_default();
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && parts.all(|part| !part.is_empty() && part.len() <= 8 && part.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// The user's phrases for `language` from `<dir>/phrases/<language>.json`, keyed like
+/// Phrase::key; none when the file is missing or unreadable
+fn user_phrases(dir: &Path, language: &str) -> HashMap<String, String> {
+    if !valid_language(language) {
+        return HashMap::new();
+    }
+    let path = dir.join(PHRASES_DIR).join(format!("{}.json", language));
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return HashMap::new(),
+        Err(e) => {
+            warn!("Failed to read {}: {}", path.display(), e);
+            return HashMap::new();
+        }
+    };
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        warn!("Ignoring {}: {}", path.display(), e);
+        HashMap::new()
+    })
+}
+
+/// `phrase` in `language`: the user's, the built-in one, or the English one
+fn lookup(language: &str, user: &HashMap<String, String>, phrase: Phrase) -> String {
+    user.get(phrase.key())
+        .map(String::as_str)
+        .filter(|text| !text.trim().is_empty())
+        .or_else(|| table(language).iter().find(|(p, _)| *p == phrase).map(|(_, text)| *text))
+        .unwrap_or(phrase.english())
+        .to_string()
+}
+
+/// `phrase` in voice.language, placeholders left in. Every phrase the app speaks of its own
+/// goes through here.
+pub fn phrase(phrase: Phrase) -> String {
+    let language = load_config().voice.language;
+    lookup(&language, &user_phrases(&config_dir(), &language), phrase)
+}
+
+/// `configured`, the text set for `phrase` in config.json, unless it is still the English
+/// default; then the phrase in voice.language
+pub fn phrase_or(phrase: Phrase, configured: &str) -> String {
+    if configured == phrase.english() {
+        self::phrase(phrase)
+    } else {
+        configured.to_string()
+    }
+}
+
+/// The default voice for `language`: the phrase file's "voice", or the built-in one
+pub fn voice(language: &str) -> String {
+    voice_in(&config_dir(), language)
+}
+
+fn voice_in(dir: &Path, language: &str) -> String {
+    let base = base(language);
+    let builtin = || LANGUAGES.iter().find(|(code, _)| *code == base).map(|(_, voice)| *voice);
+    user_phrases(dir, language)
+        .remove(VOICE_KEY)
+        .filter(|voice| !voice.trim().is_empty())
+        .unwrap_or_else(|| builtin().unwrap_or(ENGLISH_VOICE).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const ALL: [Phrase; 52] = [
+        Phrase::ApprovalNeeded, Phrase::ApprovalReminder, Phrase::ApprovalBlocked, Phrase::Question, Phrase::Plan,
+        Phrase::Aggregate, Phrase::Compaction, Phrase::ContextLimit, Phrase::UsageLimit, Phrase::LongRunning,
+        Phrase::CommandFailed, Phrase::ApiError, Phrase::ClaudeStop, Phrase::Done, Phrase::InProject, Phrase::Spawning,
+        Phrase::ThisSession, Phrase::And, Phrase::Numbers, Phrase::Second, Phrase::Seconds, Phrase::Minute,
+        Phrase::Minutes, Phrase::Hour, Phrase::Hours, Phrase::UnderThousandTokens, Phrase::ThousandTokens,
+        Phrase::MillionTokens, Phrase::LessThanCent, Phrase::Cent, Phrase::Cents, Phrase::Dollar, Phrase::Dollars,
+        Phrase::FocusEndedOne, Phrase::FocusEnded, Phrase::SnoozeEndedOne, Phrase::SnoozeEnded, Phrase::TestVoice,
+        Phrase::Diagnostics, Phrase::CodeLeftOut, Phrase::Link, Phrase::Truncated, Phrase::GithubPush, Phrase::GithubWorkflow,
+        Phrase::GithubReview, Phrase::GithubIssue, Phrase::Commit, Phrase::Commits, Phrase::Passed, Phrase::Failed,
+        Phrase::TimedOut, Phrase::Cancelled,
+    ];
+
+    /// "{project}" and the like
+    fn placeholders(text: &str) -> Vec<&str> {
+        let mut found: Vec<&str> = text.match_indices('{').filter_map(|(i, _)| text[i..].find('}').map(|end| &text[i..=i + end])).collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn test_tables_match_english() {
+        for (language, table) in [("th", THAI), ("es", SPANISH)] {
+            for phrase in ALL {
+                let text = table.iter().find(|(p, _)| *p == phrase).map(|(_, text)| *text);
+                let text = text.unwrap_or_else(|| panic!("{} has no {}", language, phrase.key()));
+                assert_eq!(placeholders(text), placeholders(phrase.english()), "{} {}", language, phrase.key());
+            }
+            let numbers = lookup(language, &HashMap::new(), Phrase::Numbers);
+            assert_eq!(numbers.split_whitespace().count(), 11, "{}", language);
+        }
+    }
+
+    #[test]
+    fn test_lookup_falls_back_per_phrase() {
+        let dir = TempDir::new().expect("create temp dir");
+        fs::create_dir_all(dir.path().join(PHRASES_DIR)).expect("mkdir");
+        fs::write(dir.path().join("phrases/th.json"), r#"{"claude_stop": "เสร็จแล้วจ้า", "done": " ", "voice": "Narisa"}"#).expect("write");
+        fs::write(dir.path().join("phrases/de.json"), r#"{"question": "Claude hat eine Frage in {project}"}"#).expect("write");
+        fs::write(dir.path().join("phrases/fr.json"), "not json").expect("write");
+        let find = |language: &str, phrase| lookup(language, &user_phrases(dir.path(), language), phrase);
+
+        assert_eq!(find("th", Phrase::ClaudeStop), "เสร็จแล้วจ้า");
+        assert_eq!(find("th", Phrase::Done), "เสร็จแล้วใน {project}", "a blank phrase is not used");
+        assert_eq!(find("es-MX", Phrase::Question), "Claude tiene una pregunta en {project}");
+        // A language without a table: the file's phrases, English for the rest
+        assert_eq!(find("de", Phrase::Question), "Claude hat eine Frage in {project}");
+        assert_eq!(find("de", Phrase::Plan), "Claude has a plan ready in {project}");
+        assert_eq!(find("fr", Phrase::TestVoice), "Hello! Voice Tray is working.");
+        assert_eq!(find("../config", Phrase::Link), "link");
+
+        assert_eq!(voice_in(dir.path(), "th"), "Narisa");
+        assert_eq!(voice_in(dir.path(), "es-MX"), "Mónica");
+        assert_eq!(voice_in(dir.path(), "de"), ENGLISH_VOICE);
+    }
+
+    #[test]
+    fn test_valid_language() {
+        for language in ["en", "th", "es-MX", "zh_Hant", "fil"] {
+            assert!(valid_language(language), "{}", language);
+        }
+        for language in ["", "english", "e1", "es-", "../en", "th/x"] {
+            assert!(!valid_language(language), "{}", language);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+/// The MQTT password in the OS keychain: Keychain on macOS, Credential Manager on Windows and
+/// the Secret Service (GNOME Keyring, KWallet) on Linux. config.json keeps KEYRING_MARKER in its
+/// place. When the keychain fails the password stays in the file, with a warning. Each profile
+/// has its own entry.
+use std::sync::Arc;
+
+use keyring::Entry;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+use tracing::{info, warn};
+
+use crate::config::{active_profile, load_saved_config, save_mqtt_config_to_file, update_config, DEFAULT_PROFILE};
+use crate::state::AppState;
+
+const SERVICE: &str = "oracle-voice-tray";
+const ACCOUNT: &str = "mqtt-password";
+
+/// Stands in for the password in config.json while the keychain holds it
+pub const KEYRING_MARKER: &str = "<keyring>";
+
+/// "mqtt-password" for the default profile, "mqtt-password:office" for the others
+fn account(profile: &str) -> String {
+    if profile == DEFAULT_PROFILE {
+        ACCOUNT.to_string()
+    } else {
+        format!("{}:{}", ACCOUNT, profile)
+    }
+}
+
+fn entry_for(profile: &str) -> keyring::Result<Entry> {
+    Entry::new(SERVICE, &account(profile))
+}
+
+/// The active profile's entry
+fn entry() -> keyring::Result<Entry> {
+    entry_for(&active_profile())
+}
+
+/// Give a new profile, copied from `from`, its own copy of the password
+pub fn copy_password(from: &str, to: &str) {
+    let copied = entry_for(from).and_then(|from| from.get_password())
+        .and_then(|password| entry_for(to).and_then(|to| to.set_password(&password)));
+    if let Err(e) = copied {
+        warn!("Failed to copy the MQTT password to profile \"{}\": {}", to, e);
+    }
+}
+
+/// Remove a deleted profile's password
+pub fn forget_password(profile: &str) {
+    if let Err(e) = entry_for(profile).and_then(|entry| entry.delete_credential()) {
+        if !matches!(e, keyring::Error::NoEntry) {
+            warn!("Failed to remove the MQTT password of profile \"{}\": {}", profile, e);
+        }
+    }
+}
+
+/// The password for a password field from config.json: the keychain's for the marker, else
+/// the field itself. None when the keychain cannot be read.
+pub fn resolve(stored: Option<String>) -> Option<String> {
+    if stored.as_deref() != Some(KEYRING_MARKER) {
+        return stored;
+    }
+    entry().and_then(|entry| resolve_in(&entry))
+        .map_err(|e| warn!("Failed to read the MQTT password: {}", e))
+        .ok()
+}
+
+fn resolve_in(entry: &Entry) -> keyring::Result<String> {
+    entry.get_password()
+}
+
+/// The password field to write to config.json for `password`. With `use_keyring` that is the
+/// marker once the keychain holds the password; otherwise, or when the keychain fails, the
+/// password itself. `was_in_keyring` says whether the file held the marker, whose keychain
+/// entry is removed when the password is cleared or moved back to the file.
+pub fn store(password: Option<String>, use_keyring: bool, was_in_keyring: bool) -> Option<String> {
+    if !use_keyring && !was_in_keyring && password.as_deref() != Some(KEYRING_MARKER) {
+        return password;
+    }
+    match entry() {
+        Ok(entry) => store_in(&entry, password, use_keyring, was_in_keyring),
+        Err(e) => {
+            warn!("Keychain unavailable, leaving the MQTT password as it is: {}", e);
+            password
+        }
+    }
+}
+
+fn store_in(entry: &Entry, password: Option<String>, use_keyring: bool, was_in_keyring: bool) -> Option<String> {
+    let password = match password.as_deref() {
+        // Unchanged, and staying in the keychain
+        Some(KEYRING_MARKER) if use_keyring => return password,
+        // Moving back to the file takes the keychain's copy. When that cannot be read the
+        // marker and the entry both stay, rather than the password being lost.
+        Some(KEYRING_MARKER) => match resolve_in(entry) {
+            Ok(secret) => Some(secret),
+            Err(e) => {
+                warn!("Failed to read the MQTT password, leaving it in the keychain: {}", e);
+                return password;
+            }
+        },
+        _ => password,
+    };
+    if !use_keyring || password.as_deref().is_none_or(str::is_empty) {
+        if was_in_keyring {
+            if let Err(e) = entry.delete_credential() {
+                if !matches!(e, keyring::Error::NoEntry) {
+                    warn!("Failed to remove the MQTT password: {}", e);
+                }
+            }
+        }
+        return password;
+    }
+    let secret = password.as_deref()?;
+    match entry.set_password(secret) {
+        Ok(()) => Some(KEYRING_MARKER.to_string()),
+        Err(e) => {
+            warn!("Failed to store the MQTT password, keeping it in config.json: {}", e);
+            password
+        }
+    }
+}
+
+/// A password saved in plain text by an older version, while the keychain is wanted: ask once
+/// whether to move it. Yes moves it and scrubs the file; No turns mqtt.use_keyring off, so the
+/// question is not asked again. Blocks on the dialog, so call it off the main thread.
+pub fn offer_migration(state: &Arc<AppState>) {
+    let mqtt = load_saved_config().mqtt;
+    let plaintext = mqtt.password.as_deref().is_some_and(|p| !p.is_empty() && p != KEYRING_MARKER);
+    if !mqtt.use_keyring || !plaintext {
+        return;
+    }
+    let Some(app) = state.app_handle.read().clone() else {
+        return;
+    };
+    let accepted = app.dialog()
+        .message("The MQTT password is saved in plain text in config.json. Move it into the system keychain?")
+        .title("Voice Tray")
+        .buttons(MessageDialogButtons::OkCancelCustom("Move to Keychain".to_string(), "Keep in File".to_string()))
+        .blocking_show();
+    let saved = if accepted {
+        save_mqtt_config_to_file(&mqtt)
+    } else {
+        update_config(|c| c.mqtt.use_keyring = false).map(|_| ())
+    };
+    match saved {
+        Ok(()) if accepted => info!("Moved the MQTT password into the keychain"),
+        Ok(()) => info!("MQTT password kept in config.json"),
+        Err(e) => warn!("Failed to save config.json: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keyring::mock::MockCredential;
+
+    fn mock_entry() -> Entry {
+        keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+        Entry::new(SERVICE, ACCOUNT).expect("mock entry")
+    }
+
+    #[test]
+    fn test_password_round_trip() {
+        let entry = mock_entry();
+        assert_eq!(store_in(&entry, Some("s3cret".to_string()), true, false).as_deref(), Some(KEYRING_MARKER));
+        assert_eq!(resolve_in(&entry).expect("read back"), "s3cret");
+        assert_eq!(store_in(&entry, Some(KEYRING_MARKER.to_string()), true, true).as_deref(), Some(KEYRING_MARKER), "unchanged");
+        assert_eq!(resolve(Some("plain".to_string())).as_deref(), Some("plain"), "plain text passes through");
+        assert_eq!(resolve(None), None);
+    }
+
+    #[test]
+    fn test_keychain_failure_keeps_password_in_file() {
+        let entry = mock_entry();
+        let mock: &MockCredential = entry.get_credential().downcast_ref().expect("mock credential");
+        mock.set_error(keyring::Error::PlatformFailure("locked".into()));
+        assert_eq!(store_in(&entry, Some("s3cret".to_string()), true, false).as_deref(), Some("s3cret"));
+        assert_eq!(store(Some("s3cret".to_string()), false, false).as_deref(), Some("s3cret"), "opted out");
+    }
+
+    #[test]
+    fn test_unreadable_keychain_keeps_marker_and_entry() {
+        let entry = mock_entry();
+        store_in(&entry, Some("s3cret".to_string()), true, false);
+        let mock: &MockCredential = entry.get_credential().downcast_ref().expect("mock credential");
+        mock.set_error(keyring::Error::PlatformFailure("locked".into()));
+        // Moving the password back to the file while the keychain is locked
+        assert_eq!(store_in(&entry, Some(KEYRING_MARKER.to_string()), false, true).as_deref(), Some(KEYRING_MARKER));
+        assert_eq!(resolve_in(&entry).expect("still there"), "s3cret");
+
+        assert_eq!(store_in(&entry, None, true, true), None, "an empty password clears it");
+        assert!(matches!(resolve_in(&entry), Err(keyring::Error::NoEntry)));
+    }
+}
@@ -1,71 +1,187 @@
 use tauri::{
-    menu::{Menu, MenuItem},
-    tray::{TrayIconBuilder, MouseButton, MouseButtonState, TrayIconEvent},
-    image::Image,
-    Manager, AppHandle, PhysicalPosition,
+    tray::TrayIconBuilder,
+    Manager, AppHandle, PhysicalPosition, PhysicalSize, WebviewUrl, WebviewWindowBuilder,
 };
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use chrono::Utc;
+use tracing::{debug, error, info, warn};
 
+mod agents;
 mod config;
+mod doctor;
+mod deeplink;
+mod focus;
+mod github;
+mod i18n;
+mod prepare;
+mod slack;
+mod speak_file;
+mod error;
 mod state;
 mod mqtt;
+mod client;
+mod ntfy;
+mod mdns;
+mod transcript;
+mod relay;
+mod mirror;
+mod pipe;
 mod http;
 mod tray;
 mod watcher;
-
-pub use config::{MqttConfig, load_mqtt_config, save_mqtt_config_to_file};
-pub use state::{AppState, VoiceEntry, SpeakRequest, SpeakResponse};
+mod parser;
+mod permissions;
+mod hooks;
+mod notifications;
+mod badge;
+mod events;
+mod autostart;
+mod hotkeys;
+mod popup;
+mod voices;
+mod theme;
+mod clicks;
+mod export;
+mod snooze;
+mod keychain;
+mod reload;
+mod bundle;
+mod bus;
+mod tasks;
+mod timeline;
+mod lock;
+mod logging;
+mod shutdown;
+mod stats;
+
+pub use config::{AppConfig, ConfigView, Diagnostic, MqttConfig, SaveOutcome, load_mqtt_config, save_mqtt_config_to_file};
+pub use state::{AppState, ClearOutcome, ClearScope, CopyOutcome, DeleteOutcome, ReplayError, VoiceEntry, SpeakRequest, SpeakResponse, WatcherEvent};
 pub use tray::update_tray_icon;
+pub use doctor::run_cli as run_doctor;
+pub use config::saved_http_port;
+pub use http::{server_file_path, ServerFile, VOICE_SERVER_PORT};
+pub use speak_file::FileResponse;
+use error::report;
+use lock::Lock;
 
 // Debounce for click events
-static LAST_CLICK: Mutex<Option<Instant>> = Mutex::new(None);
+static LAST_CLICK: Lock<Option<Instant>> = Lock::new(None);
+
+/// Show a window and give it focus
+fn show_window(window: &tauri::WebviewWindow) -> tauri::Result<()> {
+    window.show()?;
+    window.set_focus()
+}
 
-/// Show popup window near tray icon
+/// Show popup window near tray icon; a pinned popup reopens where it was left
 fn show_popup(app: &AppHandle, x: f64, y: f64) {
+    let state = app.state::<Arc<AppState>>();
+    let pinned = *state.popup_pinned.lock();
     if let Some(window) = app.get_webview_window("main") {
-        let window_height = 490.0_f64; // window height (480) + gap (10)
+        if !pinned {
+            place_popup(app, &window, x, y);
+        }
+        report(&state, "Showing the popup", show_window(&window));
+    }
+}
 
-        // macOS: tray is in menu bar at TOP → show below (y + 30)
-        // Windows/Linux: tray is in taskbar at BOTTOM → show above (y - height)
-        #[cfg(target_os = "macos")]
-        let y_pos = (y + 30.0) as i32;
-        #[cfg(not(target_os = "macos"))]
-        let y_pos = (y - window_height) as i32;
+/// Place the popup in the work area of the monitor that was clicked, which may not be the one
+/// the window is on now. Its logical size is taken from the monitor it is on; popup::place works
+/// out the rest in the clicked monitor's logical pixels.
+fn place_popup(app: &AppHandle, window: &tauri::WebviewWindow, x: f64, y: f64) {
+    let rect = |position: PhysicalPosition<i32>, size: PhysicalSize<u32>| popup::Rect {
+        x: position.x as f64,
+        y: position.y as f64,
+        width: size.width as f64,
+        height: size.height as f64,
+    };
+    let screens: Vec<popup::Screen> = app.available_monitors().unwrap_or_default().iter()
+        .map(|m| popup::Screen {
+            bounds: rect(*m.position(), *m.size()),
+            work_area: rect(m.work_area().position, m.work_area().size),
+            scale_factor: m.scale_factor(),
+        })
+        .collect();
+    let (Ok(size), Ok(scale)) = (window.outer_size(), window.scale_factor()) else {
+        return;
+    };
+    let size = size.to_logical::<f64>(scale);
+    if let Some((x_pos, y_pos)) = popup::place((x, y), (size.width, size.height), &screens) {
+        // Physical, not logical: a logical position would be scaled by the window's current
+        // monitor, not the one it is moving to
+        report(&app.state::<Arc<AppState>>(), "Placing the popup", window.set_position(PhysicalPosition::new(x_pos, y_pos)));
+    }
+}
 
-        let x_pos = ((x - 200.0) as i32).max(0);
+/// Show the popup under the tray icon when there was no click to place it by, e.g. when a
+/// notification is clicked or the app is launched again. Centered where the tray icon's
+/// position is unknown (Linux), or where it was left if pinned.
+fn show_popup_at_tray(app: &AppHandle) {
+    let state = app.state::<Arc<AppState>>();
+    let rect = state.tray_icon.lock().as_ref().and_then(|t| t.rect().ok().flatten());
+    let (x, y) = match rect.map(|r| r.position) {
+        Some(tauri::Position::Physical(p)) => (p.x as f64, p.y as f64),
+        Some(tauri::Position::Logical(p)) => (p.x, p.y),
+        None => {
+            let pinned = *state.popup_pinned.lock();
+            if let Some(window) = app.get_webview_window("main") {
+                if !pinned {
+                    report(&state, "Centering the popup", window.center());
+                }
+                report(&state, "Showing the popup", show_window(&window));
+            }
+            return;
+        }
+    };
+    show_popup(app, x, y);
+}
 
-        let pos = PhysicalPosition::new(x_pos, y_pos);
-        let _ = window.set_position(pos);
-        let _ = window.show();
-        let _ = window.set_focus();
+/// Open the settings window, or bring it forward if it is already open
+fn open_settings(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("settings") {
+        report(&app.state::<Arc<AppState>>(), "Showing the settings window", show_window(&window));
+        return;
     }
+    // Unlike the popup it stays open when it loses focus
+    let built = WebviewWindowBuilder::new(app, "settings", WebviewUrl::App("settings.html".into()))
+        .title("Oracle Voice Tray Settings")
+        .inner_size(420.0, 620.0)
+        .min_inner_size(360.0, 480.0)
+        .center()
+        .build();
+    report(&app.state::<Arc<AppState>>(), "Opening the settings window", built);
 }
 
 /// Hide popup window
 fn hide_popup(app: &AppHandle) {
     if let Some(window) = app.get_webview_window("main") {
-        let _ = window.hide();
+        report(&app.state::<Arc<AppState>>(), "Hiding the popup", window.hide());
+    }
+}
+
+/// Hide the popup on focus loss unless it is pinned
+fn hide_popup_on_blur(app: &AppHandle) {
+    if !*app.state::<Arc<AppState>>().popup_pinned.lock() {
+        hide_popup(app);
     }
 }
 
 /// Toggle popup visibility with debounce
 fn toggle_popup(app: &AppHandle, x: f64, y: f64) {
-    if let Ok(mut last_click) = LAST_CLICK.lock() {
+    {
+        let mut last_click = LAST_CLICK.lock();
         if let Some(last) = *last_click {
             if last.elapsed() < Duration::from_millis(300) {
                 return;
             }
         }
         *last_click = Some(Instant::now());
-    } else {
-        return;
     }
 
     if let Some(window) = app.get_webview_window("main") {
         if window.is_visible().unwrap_or(false) {
-            let _ = window.hide();
+            hide_popup(app);
         } else {
             show_popup(app, x, y);
         }
@@ -75,35 +191,156 @@ fn toggle_popup(app: &AppHandle, x: f64, y: f64) {
 // Tauri commands
 #[tauri::command]
 fn get_timeline(state: tauri::State<'_, Arc<AppState>>) -> Vec<VoiceEntry> {
-    state.timeline.lock()
-        .map(|timeline| timeline.iter().cloned().collect())
-        .unwrap_or_default()
+    state.timeline.lock().iter().cloned().collect()
 }
 
 #[tauri::command]
 fn get_status(state: tauri::State<'_, Arc<AppState>>) -> serde_json::Value {
-    let (total, queued_count) = state.timeline.lock()
-        .map(|t| (t.len(), t.iter().filter(|e| e.status == "queued").count()))
-        .unwrap_or((0, 0));
-    let is_speaking = state.is_speaking.lock().map(|g| *g).unwrap_or(false);
-    let mqtt_status = state.mqtt_status.lock()
-        .map(|g| g.clone())
-        .unwrap_or_else(|_| "unknown".to_string());
+    let (total, queued_count, revision) = {
+        let timeline = state.timeline.lock();
+        (timeline.len(), timeline.iter().filter(|e| e.status == "queued").count(), timeline.revision())
+    };
+    let is_speaking = state.is_speaking();
+    let muted = state.is_muted();
+    let queue_paused = state.is_queue_paused();
+    let popup_pinned = *state.popup_pinned.lock();
+    let snoozed_until = *state.snoozed_until.lock();
+    let tts_available = *state.tts_available.lock();
+    let mqtt_status = state.mqtt_status.read().clone();
+    let watcher = state.watcher_status.lock().clone();
 
     serde_json::json!({
         "total": total,
         "queued": queued_count,
+        "revision": revision,
         "is_speaking": is_speaking,
-        "server_port": http::VOICE_SERVER_PORT,
-        "mqtt_status": mqtt_status
+        "muted": muted,
+        "queue_paused": queue_paused,
+        "popup_pinned": popup_pinned,
+        "snoozed_until": snoozed_until,
+        "snooze_minutes_left": state.snooze_minutes_left(chrono::Utc::now()),
+        "tts_available": tts_available,
+        "server_port": *state.http_port.lock(),
+        "config_path": config::get_config_path(),
+        "profile": config::active_profile(),
+        "overrides": config::overrides::shown(),
+        "config_warning": config::config_warning(),
+        "http_error": state.http_error.lock().clone(),
+        "mqtt_status": mqtt_status,
+        "watcher": watcher,
+        "focus": state.focus.lock().clone(),
+        "ntfy": state.ntfy.lock().clone(),
+        "mdns": state.mdns.lock().clone(),
+        "mirror": state.mirror.lock().clone(),
+        "stats": stats::snapshot(&state)
     })
 }
 
+/// Every agent seen, by name (see agents.rs)
 #[tauri::command]
-fn clear_timeline(state: tauri::State<'_, Arc<AppState>>) {
-    if let Ok(mut timeline) = state.timeline.lock() {
-        timeline.retain(|e| e.status != "done");
+fn get_agents(state: tauri::State<'_, Arc<AppState>>) -> Vec<agents::Agent> {
+    agents::list(&state)
+}
+
+/// Mute or unmute one agent; its messages are suppressed while muted
+#[tauri::command]
+fn set_agent_muted(name: String, muted: bool, state: tauri::State<'_, Arc<AppState>>) -> Result<agents::Agent, String> {
+    agents::set_muted(&state, &name, muted)
+}
+
+/// Zero the message counts in get_status().stats and GET /stats
+#[tauri::command]
+fn reset_stats(state: tauri::State<'_, Arc<AppState>>) -> stats::Stats {
+    stats::reset(&state)
+}
+
+/// Run the self-test checks (see doctor.rs); `speak` also plays a short sample
+#[tauri::command]
+async fn run_diagnostics(speak: Option<bool>, state: tauri::State<'_, Arc<AppState>>) -> Result<doctor::Report, String> {
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || doctor::run(&state, speak.unwrap_or(false)))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// The last self-test, from startup or run_diagnostics; None until the first has finished
+#[tauri::command]
+fn get_diagnostics(state: tauri::State<'_, Arc<AppState>>) -> Option<doctor::Report> {
+    state.doctor.lock().clone()
+}
+
+/// Token usage per session since app start (kept even when announce_usage is off)
+#[tauri::command]
+fn get_session_usage(state: tauri::State<'_, Arc<AppState>>) -> serde_json::Value {
+    serde_json::json!(state.watcher_status.lock().session_usage)
+}
+
+#[tauri::command]
+fn get_watcher_events(limit: Option<usize>, state: tauri::State<'_, Arc<AppState>>) -> Vec<WatcherEvent> {
+    state.recent_watcher_events(limit.unwrap_or(state::WATCHER_EVENT_LOG_MAX))
+}
+
+/// Clear finished entries (the default), failed ones, one agent's, or everything; returns the counts
+#[tauri::command]
+fn clear_timeline(scope: Option<ClearScope>, state: tauri::State<'_, Arc<AppState>>) -> ClearOutcome {
+    state.clear_timeline(&scope.unwrap_or_default())
+}
+
+/// Stop the message being spoken; the queue moves on to the next one
+#[tauri::command]
+fn stop_speaking(state: tauri::State<'_, Arc<AppState>>) -> bool {
+    state.stop_speaking()
+}
+
+/// Remove one timeline row; `remove` drops a queued entry instead of cancelling it, `force` stops the one speaking
+#[tauri::command]
+fn delete_entry(id: u64, remove: Option<bool>, force: Option<bool>, state: tauri::State<'_, Arc<AppState>>) -> DeleteOutcome {
+    state.delete_entry(id, remove.unwrap_or(false), force.unwrap_or(false))
+}
+
+/// MQTT connection details for the popup's connection panel; cheap enough to poll
+#[tauri::command]
+fn get_mqtt_info(state: tauri::State<'_, Arc<AppState>>) -> state::MqttInfo {
+    state.mqtt_info(chrono::Utc::now())
+}
+
+/// Save the timeline through the system save dialog. Without `format` the extension of the
+/// chosen file picks one.
+#[tauri::command]
+async fn export_timeline(
+    format: Option<export::ExportFormat>,
+    app: AppHandle,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<export::ExportOutcome, String> {
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || export::export(&app, &state, format))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Pin the popup: it stays open and on top when it loses focus, until unpinned or toggled
+/// from the tray. Unpinned it is an ordinary window again, hidden when it loses focus. Open
+/// windows hear voice://popup-pinned.
+#[tauri::command]
+fn set_popup_pinned(pinned: bool, app: AppHandle, state: tauri::State<'_, Arc<AppState>>) -> Result<(), String> {
+    *state.popup_pinned.lock() = pinned;
+    events::emit(&state, events::POPUP_PINNED, pinned);
+    if let Some(window) = app.get_webview_window("main") {
+        window.set_always_on_top(pinned).map_err(|e| e.to_string())?;
     }
+    Ok(())
+}
+
+/// Speak a done or cancelled entry again; returns the new entry's id
+#[tauri::command]
+fn replay_entry(id: u64, state: tauri::State<'_, Arc<AppState>>) -> Result<u64, ReplayError> {
+    state.replay(id)
+}
+
+/// Copy entry `id`, or the last message spoken, to the clipboard; "nothing_to_copy" if there is none
+#[tauri::command]
+fn copy_last_message(id: Option<u64>, state: tauri::State<'_, Arc<AppState>>) -> Result<CopyOutcome, String> {
+    tray::copy_to_clipboard(&state, id)
 }
 
 #[tauri::command]
@@ -112,167 +349,441 @@ fn quit_app(app: AppHandle) {
 }
 
 #[tauri::command]
-fn test_voice(state: tauri::State<'_, Arc<AppState>>) {
-    if let Ok(mut timeline) = state.timeline.lock() {
-        let id = timeline.len() as u64 + 1;
-        timeline.push_back(VoiceEntry {
-            id,
-            timestamp: Utc::now(),
-            text: "Hello! Voice Tray is working.".to_string(),
-            voice: "Samantha".to_string(),
-            rate: 175,
-            agent: Some("Test".to_string()),
-            status: "queued".to_string(),
-        });
-    }
+fn test_voice(state: tauri::State<'_, Arc<AppState>>) -> u64 {
+    tray::queue_test_voice(&state)
 }
 
+/// Speak text typed into the popup; recorded with agent "ui"
 #[tauri::command]
-fn get_mqtt_config() -> MqttConfig {
-    load_mqtt_config()
+fn speak(text: String, voice: Option<String>, rate: Option<u32>, state: tauri::State<'_, Arc<AppState>>) -> Result<SpeakResponse, String> {
+    let req = SpeakRequest { text, voice, agent: Some("ui".to_string()), rate };
+    req.validate()?;
+    Ok(state.speak(req))
 }
 
+/// Read the clipboard aloud with agent "clipboard"; fails (with a notification) when it holds no text
 #[tauri::command]
-fn save_mqtt_config(config: MqttConfig, state: tauri::State<'_, Arc<AppState>>) -> Result<String, String> {
-    // Check if config actually changed
-    let current = load_mqtt_config();
-    let changed = config.broker != current.broker
-        || config.port != current.port
-        || config.topic_speak != current.topic_speak
-        || config.topic_status != current.topic_status
-        || config.username != current.username
-        || config.password != current.password;
+fn speak_clipboard(state: tauri::State<'_, Arc<AppState>>) -> Result<SpeakResponse, String> {
+    tray::speak_clipboard(&state)
+}
 
-    save_mqtt_config_to_file(&config)?;
+/// Installed voices for the voice pickers; `refresh` reads them again after new ones were installed
+#[tauri::command]
+fn get_voices(refresh: Option<bool>, state: tauri::State<'_, Arc<AppState>>) -> Result<Vec<voices::Voice>, voices::VoicesError> {
+    voices::list(&state.voice_config().voice(), refresh.unwrap_or(false))
+}
 
-    if changed {
-        // Set status to disconnected immediately so UI shows the transition
-        if let Ok(mut status) = state.mqtt_status.lock() {
-            *status = "disconnected".to_string();
-        }
-        // Update tray icon to disconnected
-        update_tray_icon(&state, false);
-        // Signal MQTT to reconnect
-        if let Ok(mut reconnect) = state.mqtt_reconnect.lock() {
-            *reconnect = true;
-        }
+/// MQTT settings for the popup; a saved password is redacted as in get_config
+#[tauri::command]
+fn get_mqtt_config() -> config::MqttView {
+    config::MqttView::of(config::load_config().mqtt)
+}
+
+#[tauri::command]
+fn save_mqtt_config(mut config: MqttConfig, state: tauri::State<'_, Arc<AppState>>) -> Result<String, String> {
+    // The saved form: a keychain password stays the marker, even while the keychain is locked
+    config.restore_password(&config::load_config().mqtt);
+    if mqtt::apply_config(&state, &config)? {
         Ok("Settings saved. Reconnecting...".to_string())
     } else {
         Ok("Settings saved.".to_string())
     }
 }
 
-const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Show config.json in Finder, Explorer or the file manager, writing it first if it is missing
+#[tauri::command]
+fn reveal_config() -> Result<(), String> {
+    let path = config::get_config_path();
+    if !path.exists() {
+        config::update_config(|_| {})?;
+    }
+    tauri_plugin_opener::reveal_item_in_dir(&path).map_err(|e| e.to_string())
+}
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    println!("Oracle Voice Tray v{} starting...", VERSION);
+/// The last `lines` lines of the log file (200 by default), for bug reports
+#[tauri::command]
+fn get_recent_logs(lines: Option<usize>) -> Result<Vec<String>, String> {
+    logging::recent(lines.unwrap_or(200))
+}
 
-    let state = Arc::new(AppState::default());
+/// Open the folder with the log files in Finder, Explorer or the file manager
+#[tauri::command]
+fn open_log_folder() -> Result<(), String> {
+    let dir = logging::log_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    tauri_plugin_opener::open_path(&dir, None::<&str>).map_err(|e| e.to_string())
+}
+
+/// Open the transcripts folder, making it first if nothing was written yet
+#[tauri::command]
+fn open_transcripts_folder() -> Result<(), String> {
+    let dir = transcript::folder(&config::load_config().transcripts);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    tauri_plugin_opener::open_path(&dir, None::<&str>).map_err(|e| e.to_string())
+}
+
+/// The profiles for the settings window: {profiles, active}
+#[tauri::command]
+fn list_profiles() -> serde_json::Value {
+    serde_json::json!({ "profiles": config::list_profiles(), "active": config::active_profile() })
+}
+
+/// Make a profile active and apply its settings; the choice is remembered in config.json
+#[tauri::command]
+fn switch_profile(name: String, state: tauri::State<'_, Arc<AppState>>) -> Result<(), String> {
+    reload::switch_profile(&state, &name)
+}
+
+/// Add a profile holding a copy of the active one's settings; returns the sanitized name
+#[tauri::command]
+fn create_profile(name: String, state: tauri::State<'_, Arc<AppState>>) -> Result<String, String> {
+    let name = config::create_profile(&name)?;
+    tray::rebuild_profile_menu(&state);
+    Ok(name)
+}
+
+/// Delete a profile; deleting the active one switches to the default profile first
+#[tauri::command]
+fn delete_profile(name: String, state: tauri::State<'_, Arc<AppState>>) -> Result<(), String> {
+    reload::delete_profile(&state, &name)
+}
+
+/// Every section of config.json for the settings window; a saved password is redacted
+#[tauri::command]
+fn get_config(state: tauri::State<'_, Arc<AppState>>) -> ConfigView {
+    ConfigView::load(state.tray_config(), state.voice_config())
+}
+
+/// Errors and warnings for a settings form, without saving it. Async so the broker lookup
+/// never holds up the main thread.
+#[tauri::command]
+async fn validate_config(settings: AppConfig) -> Vec<Diagnostic> {
+    tauri::async_runtime::spawn_blocking(move || config::validate_config(&settings))
+        .await
+        .unwrap_or_default()
+}
+
+/// Validate and save the settings window's form; see reload::save_settings
+#[tauri::command]
+fn save_config(settings: AppConfig, state: tauri::State<'_, Arc<AppState>>) -> Result<SaveOutcome, Vec<Diagnostic>> {
+    reload::save_settings(&state, settings)
+}
+
+/// Save the active profile's settings, without the MQTT password, through the save dialog
+#[tauri::command]
+async fn export_config(app: AppHandle) -> Result<export::ExportOutcome, String> {
+    tauri::async_runtime::spawn_blocking(move || bundle::export(&app))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Preview a settings bundle, or with `apply` back up and save it. Without `path` the open
+/// dialog asks for the file.
+#[tauri::command]
+async fn import_config(
+    path: Option<String>,
+    mode: Option<bundle::ImportMode>,
+    apply: bool,
+    app: AppHandle,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<bundle::ImportOutcome, String> {
+    let state = state.inner().clone();
+    let path = path.map(std::path::PathBuf::from);
+    tauri::async_runtime::spawn_blocking(move || bundle::import_file(&app, &state, path, mode.unwrap_or_default(), apply))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+fn set_watcher_enabled(enabled: bool, state: tauri::State<'_, Arc<AppState>>) -> Result<(), String> {
+    watcher::set_watcher_enabled(&state, enabled)?;
+    if let Some(ref item) = *state.watcher_menu_item.lock() {
+        report(&state, "Checking Watch Claude Sessions", item.set_checked(enabled));
+    }
+    Ok(())
+}
+
+/// Mute or unmute; the tray check mark and tooltip follow
+#[tauri::command]
+fn set_muted(muted: bool, state: tauri::State<'_, Arc<AppState>>) -> Result<(), String> {
+    tray::set_muted(&state, muted)
+}
+
+/// Snooze for `minutes`, or end the snooze with None
+#[tauri::command]
+fn set_snooze(minutes: Option<u32>, state: tauri::State<'_, Arc<AppState>>) -> Result<(), String> {
+    match minutes {
+        Some(0) => Err("Snooze length must be at least 1 minute".to_string()),
+        Some(minutes) => snooze::snooze(&state, minutes),
+        None => {
+            snooze::cancel(&state);
+            Ok(())
+        }
+    }
+}
+
+/// Add our Stop/Notification hooks to ~/.claude/settings.json (backed up first)
+#[tauri::command]
+fn install_claude_hooks(state: tauri::State<'_, Arc<AppState>>) -> Result<String, String> {
+    let path = hooks::claude_settings_path();
+    hooks::install_hooks(&path, config::load_config().http.port)?;
+    hooks::set_hooks_mode(&state, true);
+    Ok(format!("Hooks installed in {}", path.display()))
+}
+
+/// Remove only our hook entries from ~/.claude/settings.json
+#[tauri::command]
+fn uninstall_claude_hooks(state: tauri::State<'_, Arc<AppState>>) -> Result<String, String> {
+    let path = hooks::claude_settings_path();
+    let removed = hooks::uninstall_hooks(&path)?;
+    hooks::set_hooks_mode(&state, false);
+    Ok(if removed { "Hooks removed.".to_string() } else { "No hooks were installed.".to_string() })
+}
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Queue, watcher, HTTP server and MQTT client. Started from setup, after the single-instance
+/// check, so a second launch never binds the port or connects with the same client id. The
+/// HTTP server and MQTT client are tasks on Tauri's async runtime (see tasks.rs).
+fn start_services(state: &Arc<AppState>) {
     let state_queue = state.clone();
     let state_http = state.clone();
     let state_mqtt = state.clone();
 
+    // Agents and their mutes from the last run, before anything is queued
+    agents::load(state);
+
     // Start voice queue processor
     tray::process_queue(state_queue);
 
+    // Hooks mode: if our Claude Code hooks are installed the watcher leaves completions to them
+    hooks::set_hooks_mode(state, hooks::hooks_installed(&hooks::claude_settings_path()));
+
     // Start Claude Code session watcher (hookless voice notifications)
     watcher::start_session_watcher(state.clone());
 
-    // Start HTTP server in background
-    std::thread::spawn(move || {
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(http::start_http_server(state_http));
-    });
+    // End snoozes when they run out
+    snooze::start_timer(state.clone());
+
+    // Follow macOS Focus for ui.mute_during_focus
+    focus::start_monitor(state.clone());
 
-    // Load MQTT config and start client in background
-    let mqtt_config = load_mqtt_config();
-    std::thread::spawn(move || {
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(mqtt::start_mqtt_client(state_mqtt, mqtt_config));
-    });
+    // Apply edits of config.json made outside the settings window
+    reload::start_config_watcher(state.clone());
 
+    // Consumers of the timeline bus
+    tasks::spawn(state, "events", events::forward(state.clone()));
+    tasks::spawn(state, "stats", stats::collect(state.clone()));
+    tasks::spawn(state, "transcripts", transcript::record(state.clone()));
+    tasks::spawn(state, "mirror", mirror::start(state.clone()));
+    tasks::spawn(state, "agents", agents::keep_saved(state.clone()));
+
+    tasks::spawn(state, "http", http::start_http_server(state_http));
+    tasks::spawn(state, "mqtt", mqtt::start_mqtt_client(state_mqtt, load_mqtt_config()));
+    tasks::spawn(state, "ntfy", ntfy::start_bridge(state.clone()));
+    tasks::spawn(state, "pipe", pipe::start_listener(state.clone()));
+    tasks::spawn(state, "mdns", mdns::start_responder(state.clone()));
+    tasks::spawn(state, "relay", relay::start(state.clone()));
+
+    // Self-test once the services are up; GET /health and the settings window show the result
+    doctor::start(state.clone());
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    logging::init();
+    info!("Oracle Voice Tray v{} starting...", VERSION);
+
+    let state = Arc::new(AppState::default());
+    let app_config = config::load_config();
+    logging::set_level(&app_config.log.level);
+    let tray_config = app_config.ui;
+    state.muted.store(tray_config.muted, Ordering::Relaxed);
+    *state.tray_config.write() = tray_config;
+    *state.voice_config.write() = app_config.voice;
+    let tts_available = tray::tts_available();
+    if !tts_available {
+        error!("Speech command not found — TTS unavailable");
+    }
+    *state.tts_available.lock() = tts_available;
     let state_setup = state.clone();
 
     tauri::Builder::default()
+        // Registered first: a second launch hands over to the running app here and exits
+        .plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
+            // Opening an oraclevoice:// link on Windows and Linux launches the app with it; the
+            // deep-link plugin has already passed the link on to deeplink::listen
+            if deeplink::links_in(&args).is_empty() {
+                info!("Second launch: showing the popup");
+                show_popup_at_tray(app);
+            }
+        }))
+        .plugin(tauri_plugin_deep_link::init())
         .manage(state)
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(hotkeys::plugin())
+        .plugin(autostart::plugin())
         .setup(move |app| {
+            start_services(&state_setup);
             let app_handle = app.handle().clone();
+            *state_setup.app_handle.write() = Some(app_handle.clone());
+
+            // The system is the source of truth: the login item may have been removed in system settings
+            let start_at_login = autostart::is_enabled(&state_setup);
+            let mut tray_config = state_setup.tray_config();
+            if tray_config.start_at_login != start_at_login {
+                tray_config.start_at_login = start_at_login;
+                if let Err(e) = config::save_tray_config_to_file(&tray_config) {
+                    warn!("Failed to save start_at_login: {}", e);
+                }
+                *state_setup.tray_config.write() = tray_config;
+            }
+
+            // oraclevoice:// links, the one this launch was started with first
+            deeplink::listen(&app_handle);
+
+            // A plain-text MQTT password from an older version: offer to move it to the keychain
+            let state_keychain = state_setup.clone();
+            std::thread::spawn(move || keychain::offer_migration(&state_keychain));
 
-            // Load custom icons (embedded at compile time)
-            let idle_bytes = include_bytes!("../icons/idle.png");
-            let speaking_bytes = include_bytes!("../icons/speaking.png");
-            let disconnected_bytes = include_bytes!("../icons/disconnected.png");
-
-            let idle_icon = image::load_from_memory(idle_bytes).ok().map(|img| {
-                let rgba = img.to_rgba8();
-                println!("Loaded idle icon: {}x{}", rgba.width(), rgba.height());
-                Image::new_owned(rgba.to_vec(), rgba.width(), rgba.height())
-            });
-
-            let speaking_icon = image::load_from_memory(speaking_bytes).ok().map(|img| {
-                let rgba = img.to_rgba8();
-                println!("Loaded speaking icon: {}x{}", rgba.width(), rgba.height());
-                Image::new_owned(rgba.to_vec(), rgba.width(), rgba.height())
-            });
-
-            let disconnected_icon = image::load_from_memory(disconnected_bytes).ok().map(|img| {
-                let rgba = img.to_rgba8();
-                println!("Loaded disconnected icon: {}x{}", rgba.width(), rgba.height());
-                Image::new_owned(rgba.to_vec(), rgba.width(), rgba.height())
-            });
-
-            // Store icons in state
-            *state_setup.idle_icon.lock().unwrap() = idle_icon.clone();
-            *state_setup.speaking_icon.lock().unwrap() = speaking_icon;
-            *state_setup.disconnected_icon.lock().unwrap() = disconnected_icon.clone();
+            // Icons follow the menu bar / taskbar theme
+            theme::apply_icon_theme(&state_setup);
 
             // Create right-click menu
-            let quit_item = MenuItem::with_id(app, "quit", "Quit Oracle Voice Tray", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&quit_item])?;
+            let menu = tray::build_tray_menu(app, &state_setup)?;
 
             // Use disconnected icon initially (MQTT not connected yet)
-            let initial_icon = disconnected_icon
-                .or(idle_icon)
+            let initial_icon = state_setup.disconnected_icon.read().clone()
                 .unwrap_or_else(|| app.default_window_icon().unwrap().clone());
 
-            println!("Building tray icon...");
+            debug!("Building tray icon...");
             let tray = TrayIconBuilder::new()
                 .icon(initial_icon)
+                .icon_as_template(cfg!(target_os = "macos"))
                 .menu(&menu)
                 .show_menu_on_left_click(false)
-                .tooltip("Oracle Voice Tray - MQTT + HTTP")
+                .tooltip(tray::tray_tooltip(&tray::TraySnapshot::from_state(&state_setup)))
                 .on_menu_event(move |app, event| {
-                    if event.id.as_ref() == "quit" {
-                        app.exit(0);
-                    }
-                })
-                .on_tray_icon_event(|tray, event| {
-                    if let TrayIconEvent::Click {
-                        button: MouseButton::Left,
-                        button_state: MouseButtonState::Up,
-                        position,
-                        ..
-                    } = event {
-                        toggle_popup(tray.app_handle(), position.x, position.y);
+                    match event.id.as_ref() {
+                        "quit" => app.exit(0),
+                        "watcher" => {
+                            // The check item toggles itself; mirror its new state
+                            let state = app.state::<Arc<AppState>>();
+                            let enabled = state.watcher_menu_item.lock().as_ref()
+                                .and_then(|i| i.is_checked().ok())
+                                .unwrap_or(true);
+                            if let Err(e) = watcher::set_watcher_enabled(&state, enabled) {
+                                warn!("Failed to save watcher setting: {}", e);
+                            }
+                        }
+                        "settings" => open_settings(app),
+                        "log_folder" => {
+                            if let Err(e) = open_log_folder() {
+                                warn!("Failed to open the log folder: {}", e);
+                            }
+                        }
+                        "transcripts_folder" => {
+                            if let Err(e) = open_transcripts_folder() {
+                                warn!("Failed to open the transcripts folder: {}", e);
+                            }
+                        }
+                        "stop" => {
+                            app.state::<Arc<AppState>>().stop_speaking();
+                        }
+                        "pause_queue" => {
+                            let state = app.state::<Arc<AppState>>();
+                            let paused = state.is_queue_paused();
+                            tray::set_queue_paused(&state, !paused);
+                        }
+                        "reconnect" => mqtt::reconnect(&app.state::<Arc<AppState>>()),
+                        "snooze_cancel" => snooze::cancel(&app.state::<Arc<AppState>>()),
+                        "export" => {
+                            let app = app.clone();
+                            std::thread::spawn(move || {
+                                let state = app.state::<Arc<AppState>>();
+                                if let Err(e) = export::export(&app, &state, None) {
+                                    warn!("Export failed: {}", e);
+                                    notifications::notify(&state, "Export failed", &e);
+                                }
+                            });
+                        }
+                        "copy_last" => {
+                            if let Err(e) = tray::copy_to_clipboard(&app.state::<Arc<AppState>>(), None) {
+                                warn!("Copy Last Message failed: {}", e);
+                            }
+                        }
+                        "speak_clipboard" => {
+                            if let Err(e) = tray::speak_clipboard(&app.state::<Arc<AppState>>()) {
+                                info!("Speak Clipboard: {}", e);
+                            }
+                        }
+                        "test_voice" => {
+                            tray::queue_test_voice(&app.state::<Arc<AppState>>());
+                        }
+                        "mute" => {
+                            let state = app.state::<Arc<AppState>>();
+                            let muted = state.mute_menu_item.lock().as_ref()
+                                .and_then(|i| i.is_checked().ok())
+                                .unwrap_or(false);
+                            if let Err(e) = tray::set_muted(&state, muted) {
+                                warn!("Failed to save mute setting: {}", e);
+                            }
+                        }
+                        "autostart" => {
+                            let state = app.state::<Arc<AppState>>();
+                            let enabled = state.autostart_menu_item.lock().as_ref()
+                                .and_then(|i| i.is_checked().ok())
+                                .unwrap_or(false);
+                            if let Err(e) = tray::set_start_at_login(&state, enabled) {
+                                warn!("Failed to change Start at Login: {}", e);
+                            }
+                        }
+                        id => {
+                            if let Some(entry_id) = id.strip_prefix(tray::RECENT_ID_PREFIX).and_then(|n| n.parse().ok()) {
+                                if let Err(e) = app.state::<Arc<AppState>>().replay(entry_id) {
+                                    warn!("Failed to replay entry {}: {}", entry_id, e);
+                                }
+                            } else if let Some(minutes) = id.strip_prefix(tray::SNOOZE_ID_PREFIX).and_then(|n| n.parse().ok()) {
+                                if let Err(e) = snooze::snooze(&app.state::<Arc<AppState>>(), minutes) {
+                                    warn!("Failed to snooze: {}", e);
+                                }
+                            } else if let Some(voice) = id.strip_prefix(tray::VOICE_ID_PREFIX) {
+                                if let Err(e) = tray::set_default_voice(&app.state::<Arc<AppState>>(), voice) {
+                                    warn!("Failed to save default voice: {}", e);
+                                }
+                            } else if let Some(profile) = id.strip_prefix(tray::PROFILE_ID_PREFIX) {
+                                let state = app.state::<Arc<AppState>>();
+                                if let Err(e) = reload::switch_profile(&state, profile) {
+                                    warn!("Failed to switch to profile {}: {}", profile, e);
+                                    notifications::notify(&state, "Profile not switched", &e);
+                                    tray::rebuild_profile_menu(&state);
+                                }
+                            }
+                        }
                     }
                 })
+                .on_tray_icon_event(|tray, event| clicks::handle_tray_event(tray.app_handle(), event))
                 .build(app)?;
-            println!("Tray icon created successfully!");
+            info!("Tray icon created successfully!");
 
-            *state_setup.tray_icon.lock().unwrap() = Some(tray);
+            *state_setup.tray_icon.lock() = Some(tray);
+            tray::start_tray_updater(state_setup.clone());
+            hotkeys::start(&state_setup);
 
             // Update tray icon to reflect current MQTT status (may have connected before tray was created)
             update_tray_icon(&state_setup, false);
 
-            // Hide popup when it loses focus
+            // Hide popup when it loses focus (unless pinned); the hidden popup also hears system theme changes
             let app_handle_blur = app_handle.clone();
+            let state_theme = state_setup.clone();
             if let Some(window) = app.get_webview_window("main") {
-                window.on_window_event(move |event| {
-                    if let tauri::WindowEvent::Focused(false) = event {
-                        hide_popup(&app_handle_blur);
-                    }
+                window.on_window_event(move |event| match event {
+                    tauri::WindowEvent::Focused(false) => hide_popup_on_blur(&app_handle_blur),
+                    tauri::WindowEvent::ThemeChanged(_) => theme::apply_icon_theme(&state_theme),
+                    _ => {}
                 });
             }
 
@@ -280,8 +791,31 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             get_timeline, get_status, clear_timeline, quit_app,
-            test_voice, get_mqtt_config, save_mqtt_config
+            test_voice, get_mqtt_config, save_mqtt_config, set_watcher_enabled, set_muted,
+            get_session_usage, install_claude_hooks, uninstall_claude_hooks, get_watcher_events,
+            get_config, validate_config, save_config, reveal_config, speak, speak_clipboard, delete_entry, stop_speaking, copy_last_message, replay_entry, set_popup_pinned,
+            get_voices, get_mqtt_info, export_timeline, set_snooze,
+            list_profiles, switch_profile, create_profile, delete_profile, export_config, import_config,
+            get_recent_logs, open_log_folder, open_transcripts_folder, reset_stats, run_diagnostics, get_diagnostics,
+            get_agents, set_agent_muted
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Clicking a notification activates the app: open the popup under the tray, on the timeline
+            #[cfg(target_os = "macos")]
+            if let tauri::RunEvent::Reopen { .. } = event {
+                show_popup_at_tray(app);
+                events::emit(&app.state::<Arc<AppState>>(), events::SHOW_TIMELINE, ());
+            }
+            // Quit runs the shutdown sequence first, which exits again once it is done
+            if let tauri::RunEvent::ExitRequested { api, .. } = &event {
+                if !shutdown::exit_allowed(app) {
+                    api.prevent_exit();
+                }
+            }
+            if let tauri::RunEvent::Exit = event {
+                tasks::abort_all(&app.state::<Arc<AppState>>());
+            }
+        });
 }
@@ -0,0 +1,177 @@
+/// Locks that survive a panic. A std Mutex held by a panicking thread is poisoned and every
+/// later lock() fails, which silently stalled whatever used it: the queue, the tray updater,
+/// every HTTP request reading the timeline. These log once, clear the poison and carry on with
+/// the data as the panicking thread left it; everything kept behind them (flags, the timeline,
+/// caches, menu handles) is valid after any single write.
+///
+/// Lock order: a thread holding more than one lock takes them by rank, lowest first, and debug
+/// builds assert it. Only the locks that are ever nested have a rank:
+///
+///   timeline -> stop_requested    (delete_entry stops the entry speaking)
+///   tray_icon -> shown_icon       (render_tray_icon records the icon it set)
+///
+/// Every other lock is taken on its own, and its guard dropped, before the next one.
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::{LockResult, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tracing::warn;
+
+/// Not nested with other locks; not checked
+pub const UNRANKED: u8 = 0;
+pub const RANK_TIMELINE: u8 = 10;
+pub const RANK_STOP_REQUESTED: u8 = 20;
+pub const RANK_TRAY_ICON: u8 = 30;
+pub const RANK_SHOWN_ICON: u8 = 40;
+
+thread_local! {
+    /// Ranks of the ranked locks this thread holds, in the order taken
+    static HELD: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+fn enter(rank: u8) {
+    if rank == UNRANKED || !cfg!(debug_assertions) {
+        return;
+    }
+    HELD.with(|held| {
+        let mut held = held.borrow_mut();
+        if let Some(&top) = held.last() {
+            assert!(top < rank, "lock of rank {} taken while holding rank {}; see lock.rs for the order", rank, top);
+        }
+        held.push(rank);
+    });
+}
+
+fn leave(rank: u8) {
+    if rank == UNRANKED || !cfg!(debug_assertions) {
+        return;
+    }
+    HELD.with(|held| {
+        let mut held = held.borrow_mut();
+        if let Some(index) = held.iter().rposition(|&r| r == rank) {
+            held.remove(index);
+        }
+    });
+}
+
+fn recover<G>(result: LockResult<G>, clear: impl FnOnce()) -> G {
+    result.unwrap_or_else(|poisoned| {
+        warn!("Recovered a lock poisoned by a panic");
+        clear();
+        poisoned.into_inner()
+    })
+}
+
+/// A guard that keeps the lock order bookkeeping for the lock it came from
+pub struct Guard<G> {
+    inner: G,
+    rank: u8,
+}
+
+impl<G: Deref> Deref for Guard<G> {
+    type Target = G::Target;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<G: DerefMut> DerefMut for Guard<G> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<G> Drop for Guard<G> {
+    fn drop(&mut self) {
+        leave(self.rank);
+    }
+}
+
+/// A Mutex that recovers from poisoning
+pub struct Lock<T> {
+    inner: Mutex<T>,
+    rank: u8,
+}
+
+impl<T> Lock<T> {
+    pub const fn new(value: T) -> Self {
+        Self { inner: Mutex::new(value), rank: UNRANKED }
+    }
+
+    /// A lock that is nested with others, taken in `rank` order
+    pub const fn ranked(value: T, rank: u8) -> Self {
+        Self { inner: Mutex::new(value), rank }
+    }
+
+    /// Never fails: a poisoned lock is recovered
+    pub fn lock(&self) -> Guard<MutexGuard<'_, T>> {
+        enter(self.rank);
+        let inner = recover(self.inner.lock(), || self.inner.clear_poison());
+        Guard { inner, rank: self.rank }
+    }
+}
+
+impl<T: Default> Default for Lock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+/// An RwLock that recovers from poisoning, for data read far more often than written
+pub struct Shared<T> {
+    inner: RwLock<T>,
+}
+
+impl<T> Shared<T> {
+    pub const fn new(value: T) -> Self {
+        Self { inner: RwLock::new(value) }
+    }
+
+    pub fn read(&self) -> Guard<RwLockReadGuard<'_, T>> {
+        let inner = recover(self.inner.read(), || self.inner.clear_poison());
+        Guard { inner, rank: UNRANKED }
+    }
+
+    pub fn write(&self) -> Guard<RwLockWriteGuard<'_, T>> {
+        let inner = recover(self.inner.write(), || self.inner.clear_poison());
+        Guard { inner, rank: UNRANKED }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_poisoned_lock_recovers() {
+        let lock = Arc::new(Lock::new(vec![1]));
+        let shared = Arc::new(Shared::new(String::from("connected")));
+        let (l, s) = (lock.clone(), shared.clone());
+        let panicked = std::thread::spawn(move || {
+            let _guard = s.write();
+            l.lock().push(2);
+            panic!("queue worker exploded");
+        }).join();
+        assert!(panicked.is_err());
+
+        assert_eq!(*lock.lock(), vec![1, 2]);
+        lock.lock().push(3);
+        assert_eq!(lock.lock().len(), 3);
+        assert_eq!(*shared.read(), "connected");
+    }
+
+    #[test]
+    fn test_lock_order() {
+        let timeline = Lock::ranked((), RANK_TIMELINE);
+        let stop = Lock::ranked((), RANK_STOP_REQUESTED);
+        {
+            let _timeline = timeline.lock();
+            let _stop = stop.lock();
+        }
+        let _stop = stop.lock();
+        // Out of order: only debug builds check
+        let reversed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(timeline.lock())));
+        assert_eq!(reversed.is_err(), cfg!(debug_assertions));
+    }
+}
@@ -0,0 +1,218 @@
+/// Logging through `tracing-subscriber`. Every subsystem logs with its module as target
+/// ("voice_tray_v2_lib::mqtt", ...), to stdout and to a daily file in the logs folder beside
+/// config.json, so there are logs to attach to a bug report when the app was started from Finder
+/// or Explorer. The filter is RUST_LOG when set, else log.level from config.json.
+use chrono::Local;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tracing::level_filters::LevelFilter;
+use tracing::{info, warn, Level};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+use crate::config::config_dir;
+
+/// Log files are voice-tray.YYYY-MM-DD.log, one per day
+const LOG_PREFIX: &str = "voice-tray";
+const LOG_SUFFIX: &str = "log";
+/// Daily files kept, today's included
+const KEPT_FILES: usize = 5;
+/// Most lines get_recent_logs returns
+pub const MAX_RECENT_LINES: usize = 5000;
+
+/// Targets of this crate's modules start with this
+const CRATE_PREFIX: &str = concat!(env!("CARGO_CRATE_NAME"), "::");
+
+pub fn log_dir() -> PathBuf {
+    config_dir().join("logs")
+}
+
+/// A RUST_LOG-style filter: a default level and levels per target, e.g. "info,mqtt=warn".
+/// A target without "::" also names this crate's module of that name, so "mqtt" covers
+/// "voice_tray_v2_lib::mqtt", while "voice_tray_v2_lib" covers the whole app.
+pub fn parse_filter(spec: &str) -> Result<EnvFilter, String> {
+    let level = |raw: &str| raw.trim().parse::<LevelFilter>().map_err(|_| format!("\"{}\" is not a log level (off, error, warn, info, debug or trace)", raw.trim()));
+    let mut directives = Vec::new();
+    for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        match directive.split_once('=') {
+            Some((target, raw)) if !target.trim().is_empty() => {
+                let (target, level) = (target.trim(), level(raw)?);
+                if !target.contains("::") {
+                    directives.push(format!("{}{}={}", CRATE_PREFIX, target, level));
+                }
+                directives.push(format!("{}={}", target, level));
+            }
+            Some(_) => return Err(format!("\"{}\" names no target", directive)),
+            None => directives.push(level(directive)?.to_string()),
+        }
+    }
+    EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .parse(directives.join(","))
+        .map_err(|e| e.to_string())
+}
+
+/// Swaps the filter when log.level changes
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+/// Whether RUST_LOG was set at startup; it overrides log.level
+static ENV_FILTER: OnceLock<bool> = OnceLock::new();
+
+/// Use log.level from config.json, unless RUST_LOG was set
+pub fn set_level(spec: &str) {
+    if ENV_FILTER.get().copied().unwrap_or(false) {
+        return;
+    }
+    let Some(handle) = FILTER.get() else {
+        return;
+    };
+    match parse_filter(spec) {
+        Ok(filter) => {
+            if let Err(e) = handle.reload(filter) {
+                warn!("Failed to apply log.level: {}", e);
+            }
+        }
+        Err(e) => warn!("Ignored log.level: {}", e),
+    }
+}
+
+/// Local time with milliseconds and the UTC offset, e.g. 2026-01-05T09:30:00.123+01:00
+struct LocalTime;
+
+impl FormatTime for LocalTime {
+    fn format_time(&self, w: &mut Writer<'_>) -> std::fmt::Result {
+        write!(w, "{}", Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z"))
+    }
+}
+
+/// The daily log file in `dir`, pruned to KEPT_FILES
+fn appender(dir: &Path) -> Result<RollingFileAppender, String> {
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_PREFIX)
+        .filename_suffix(LOG_SUFFIX)
+        .max_log_files(KEPT_FILES)
+        .build(dir)
+        .map_err(|e| e.to_string())
+}
+
+/// Install the logger, first thing at startup so loading config.json is logged too. Until
+/// set_level applies log.level, the filter is RUST_LOG or "info".
+pub fn init() {
+    let env = std::env::var("RUST_LOG").ok().filter(|v| !v.trim().is_empty());
+    ENV_FILTER.get_or_init(|| env.is_some());
+    let filter = match env.as_deref().map(parse_filter) {
+        Some(Ok(filter)) => filter,
+        Some(Err(e)) => {
+            eprintln!("Ignored RUST_LOG: {}", e);
+            EnvFilter::new("info")
+        }
+        None => EnvFilter::new("info"),
+    };
+    let (filter, handle) = reload::Layer::new(filter);
+    let stdout = fmt::layer()
+        .with_timer(LocalTime)
+        .with_writer(std::io::stderr.with_max_level(Level::WARN).or_else(std::io::stdout));
+    let dir = log_dir();
+    let file = appender(&dir)
+        .map_err(|e| eprintln!("Failed to open a log file in {}: {}", dir.display(), e))
+        .ok()
+        .map(|appender| fmt::layer().with_timer(LocalTime).with_ansi(false).with_writer(appender));
+    if tracing_subscriber::registry().with(filter).with(stdout).with(file).try_init().is_ok() {
+        // init runs once, before anything else could have set the handle
+        let _ = FILTER.set(handle);
+        info!("Logging to {}", dir.display());
+    }
+}
+
+/// The last `lines` lines logged, oldest first, reaching into the previous day's file if needed
+pub fn recent(lines: usize) -> Result<Vec<String>, String> {
+    recent_in(&log_dir(), lines.min(MAX_RECENT_LINES))
+}
+
+/// The log files in `dir`, newest first; their dates sort as text
+fn log_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("{}: {}", dir.display(), e)),
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.file_name().and_then(|n| n.to_str()).is_some_and(|name| {
+            name.starts_with(&format!("{}.", LOG_PREFIX)) && name.ends_with(&format!(".{}", LOG_SUFFIX))
+        }))
+        .collect();
+    files.sort_by(|a, b| b.cmp(a));
+    Ok(files)
+}
+
+fn recent_in(dir: &Path, lines: usize) -> Result<Vec<String>, String> {
+    let mut recent: Vec<String> = Vec::new();
+    for path in log_files(dir)?.into_iter().take(2) {
+        if recent.len() >= lines {
+            break;
+        }
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        let wanted = lines - recent.len();
+        let older: Vec<&str> = content.lines().collect();
+        let mut taken: Vec<String> = older[older.len().saturating_sub(wanted)..].iter().map(|l| l.to_string()).collect();
+        taken.append(&mut recent);
+        recent = taken;
+    }
+    Ok(recent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as _;
+
+    #[test]
+    fn test_filter() {
+        let filter = parse_filter("warn, mqtt=debug,voice_tray_v2_lib::watcher=off,hyper=trace").expect("filter");
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(filter), || {
+            assert!(tracing::enabled!(target: "voice_tray_v2_lib::mqtt", Level::DEBUG));
+            assert!(!tracing::enabled!(target: "voice_tray_v2_lib::mqtt", Level::TRACE));
+            assert!(!tracing::enabled!(target: "voice_tray_v2_lib::watcher", Level::ERROR));
+            assert!(tracing::enabled!(target: "hyper::proto::h1", Level::TRACE));
+            assert!(tracing::enabled!(target: "voice_tray_v2_lib::tray", Level::WARN));
+            assert!(!tracing::enabled!(target: "voice_tray_v2_lib::tray", Level::INFO));
+        });
+
+        assert!(parse_filter("").is_ok());
+        assert_eq!(parse_filter("loud").unwrap_err(), "\"loud\" is not a log level (off, error, warn, info, debug or trace)");
+        assert_eq!(parse_filter("mqtt=loud").unwrap_err(), "\"loud\" is not a log level (off, error, warn, info, debug or trace)");
+        assert_eq!(parse_filter("=debug").unwrap_err(), "\"=debug\" names no target");
+    }
+
+    #[test]
+    fn test_appender_and_recent_lines() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut log = appender(dir.path()).expect("appender");
+        writeln!(log, "today").expect("write");
+        log.flush().expect("flush");
+        let files = log_files(dir.path()).expect("files");
+        assert_eq!(files.len(), 1);
+        assert_eq!(fs::read_to_string(&files[0]).expect("today's file"), "today\n");
+
+        // Earlier days, and a file that is not a log
+        fs::write(dir.path().join("voice-tray.2020-01-02.log"), "first\nsecond\n").expect("write");
+        fs::write(dir.path().join("voice-tray.2020-01-01.log"), "too old\n").expect("write");
+        fs::write(dir.path().join("notes.txt"), "not a log\n").expect("write");
+
+        assert_eq!(recent_in(dir.path(), 2).expect("recent"), ["second", "today"]);
+        assert_eq!(recent_in(dir.path(), 10).expect("recent"), ["first", "second", "today"], "only the last two days");
+        assert_eq!(recent_in(&dir.path().join("none"), 10).expect("no logs"), Vec::<String>::new());
+    }
+}
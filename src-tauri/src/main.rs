@@ -2,5 +2,10 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    // `--doctor [--speak]` runs the self-test and exits instead of starting the app
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--doctor") {
+        std::process::exit(voice_tray_v2_lib::run_doctor(args.iter().any(|arg| arg == "--speak")));
+    }
     voice_tray_v2_lib::run()
 }
@@ -0,0 +1,215 @@
+/// mDNS (Bonjour) advertisement of the HTTP API, so other machines and phone apps find it
+/// without being told the address. While the server listens on a non-loopback http.bind and
+/// http.advertise is on, the _oracle-voice._tcp service is registered with the mdns-sd daemon,
+/// which answers for it on its own thread with TXT records for the version, the auth the API
+/// needs (none) and its base path. Unregistering sends the goodbyes that withdraw it when the
+/// server stops or moves and when the app quits. IPv4 only.
+use mdns_sd::{IfKind, ServiceDaemon, ServiceInfo, UnregisterStatus};
+use serde::Serialize;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::config::load_config;
+use crate::error::{report_error, VoiceTrayError};
+use crate::state::AppState;
+
+pub const SERVICE: &str = "_oracle-voice._tcp.local";
+
+/// Longest the daemon may take to confirm the goodbyes went out
+const GOODBYE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The advertisement as of now, for GET /status and get_status
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MdnsStatus {
+    /// Announced, and answering queries
+    pub active: bool,
+    /// e.g. "Oracle Voice Tray on nat-mbp._oracle-voice._tcp.local"
+    pub instance: Option<String>,
+    /// Address and port announced
+    pub address: Option<SocketAddr>,
+    /// Why it is not active
+    pub detail: Option<String>,
+    /// Where the HTTP server listens, as it last reported
+    #[serde(skip)]
+    listening: Option<SocketAddr>,
+}
+
+/// Tell the responder where the HTTP server now listens (None once it stopped)
+pub fn update(state: &AppState, listening: Option<SocketAddr>) {
+    state.mdns.lock().listening = listening;
+    state.mdns_update.notify_one();
+}
+
+pub fn is_active(state: &AppState) -> bool {
+    state.mdns.lock().active
+}
+
+/// What is announced
+#[derive(Debug, Clone, PartialEq)]
+struct Service {
+    /// "Oracle Voice Tray on nat-mbp"
+    instance: String,
+    /// "nat-mbp-oracle-voice.local"; not the machine's own name, which the system answers for
+    host: String,
+    ip: Ipv4Addr,
+    port: u16,
+}
+
+impl Service {
+    fn new(hostname: &str, ip: Ipv4Addr, port: u16) -> Self {
+        let label: String = hostname.split('.').next().unwrap_or_default()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+            .take(40)
+            .collect();
+        let label = label.trim_matches('-');
+        let label = if label.is_empty() { "host" } else { label };
+        Self {
+            instance: format!("Oracle Voice Tray on {}", label),
+            host: format!("{}-oracle-voice.local", label),
+            ip,
+            port,
+        }
+    }
+
+    fn instance_name(&self) -> String {
+        format!("{}.{}", self.instance, SERVICE)
+    }
+
+    /// The registration: names fully qualified, as mdns-sd wants them
+    fn info(&self) -> Result<ServiceInfo, String> {
+        let properties = [("version", env!("CARGO_PKG_VERSION")), ("auth", "none"), ("path", "/")];
+        ServiceInfo::new(&format!("{}.", SERVICE), &self.instance, &format!("{}.", self.host), IpAddr::V4(self.ip), self.port, &properties[..])
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// What to announce for a server listening on `listening`, or why nothing is
+fn plan(listening: Option<SocketAddr>, advertise: bool) -> Result<Service, String> {
+    let listening = listening.ok_or("The HTTP server is not listening")?;
+    if !advertise {
+        return Err("http.advertise is off".to_string());
+    }
+    let ip = match listening.ip() {
+        ip if ip.is_loopback() => return Err("The HTTP server listens on loopback only; set http.bind to reach it from other machines".to_string()),
+        IpAddr::V4(ip) if ip.is_unspecified() => primary_ipv4().map_err(|e| format!("No network address to announce: {}", e))?,
+        IpAddr::V4(ip) => ip,
+        IpAddr::V6(_) => return Err("Only IPv4 addresses are announced".to_string()),
+    };
+    Ok(Service::new(&gethostname::gethostname().to_string_lossy(), ip, listening.port()))
+}
+
+/// Address of the interface the default route goes out on. Connecting a UDP socket sends
+/// nothing; the address is a documentation one that is never reached.
+fn primary_ipv4() -> io::Result<Ipv4Addr> {
+    let socket = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9))?;
+    match socket.local_addr()?.ip() {
+        IpAddr::V4(ip) if !ip.is_unspecified() => Ok(ip),
+        _ => Err(io::Error::other("no IPv4 route")),
+    }
+}
+
+/// The daemon answering for the service, started on first use without IPv6
+fn daemon() -> Result<ServiceDaemon, String> {
+    static DAEMON: OnceLock<Result<ServiceDaemon, String>> = OnceLock::new();
+    DAEMON.get_or_init(|| {
+        let daemon = ServiceDaemon::new().map_err(|e| format!("cannot start the mDNS daemon: {}", e))?;
+        daemon.disable_interface(IfKind::IPv6).map_err(|e| e.to_string())?;
+        Ok(daemon)
+    }).clone()
+}
+
+/// Register `service` and return its full name, to unregister it by
+fn register(service: &Service) -> Result<(ServiceDaemon, String), String> {
+    let daemon = daemon()?;
+    let info = service.info()?;
+    let fullname = info.get_fullname().to_string();
+    daemon.register(info).map_err(|e| e.to_string())?;
+    Ok((daemon, fullname))
+}
+
+/// Unregister the service, which tells caches to drop it now rather than when its records expire
+async fn goodbye(daemon: &ServiceDaemon, fullname: &str) {
+    let receiver = match daemon.unregister(fullname) {
+        Ok(receiver) => receiver,
+        Err(e) => {
+            warn!("Failed to withdraw the mDNS advertisement: {}", e);
+            return;
+        }
+    };
+    match tokio::time::timeout(GOODBYE_TIMEOUT, receiver.recv_async()).await {
+        Ok(Ok(UnregisterStatus::OK)) => {}
+        Ok(Ok(UnregisterStatus::NotFound)) => warn!("The mDNS advertisement was already gone"),
+        Ok(Err(_)) | Err(_) => warn!("The mDNS daemon did not confirm the goodbyes"),
+    }
+}
+
+fn set_status(state: &AppState, service: Option<&Service>, detail: Option<String>) {
+    let mut status = state.mdns.lock();
+    status.active = service.is_some();
+    status.instance = service.map(Service::instance_name);
+    status.address = service.map(|s| SocketAddr::from((s.ip, s.port)));
+    status.detail = detail;
+}
+
+/// Advertise the HTTP server for the life of the app, starting over each time it rebinds
+pub async fn start_responder(state: Arc<AppState>) {
+    while !state.is_shutting_down() {
+        let listening = state.mdns.lock().listening;
+        let service = match plan(listening, load_config().http.advertise) {
+            Ok(service) => service,
+            Err(detail) => {
+                set_status(&state, None, Some(detail));
+                state.mdns_update.notified().await;
+                continue;
+            }
+        };
+        let (daemon, fullname) = match register(&service) {
+            Ok(registered) => registered,
+            Err(error) => {
+                set_status(&state, None, Some(format!("Cannot advertise over mDNS: {}", error)));
+                report_error(&state, "mDNS advertisement", &VoiceTrayError::Io(io::Error::other(error)));
+                state.mdns_update.notified().await;
+                continue;
+            }
+        };
+        info!("Advertising {} at {}:{} over mDNS", service.instance_name(), service.ip, service.port);
+        set_status(&state, Some(&service), None);
+        state.mdns_update.notified().await;
+        goodbye(&daemon, &fullname).await;
+        set_status(&state, None, Some("Withdrawn".to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan() {
+        let at = |addr: &str| Some(addr.parse::<SocketAddr>().expect("addr"));
+        assert!(plan(None, true).is_err());
+        assert!(plan(at("127.0.0.1:37779"), true).unwrap_err().contains("loopback"));
+        assert_eq!(plan(at("192.168.1.20:37779"), false), Err("http.advertise is off".to_string()));
+        let service = plan(at("192.168.1.20:37779"), true).expect("service");
+        assert_eq!((service.ip, service.port), (Ipv4Addr::new(192, 168, 1, 20), 37779));
+
+        let service = Service::new("Nat's MacBook.local", Ipv4Addr::new(192, 168, 1, 20), 37779);
+        assert_eq!(service.instance_name(), "Oracle Voice Tray on nat-s-macbook._oracle-voice._tcp.local");
+        assert_eq!(service.host, "nat-s-macbook-oracle-voice.local");
+    }
+
+    #[test]
+    fn test_service_info() {
+        let info = Service::new("nat-mbp", Ipv4Addr::new(10, 0, 0, 5), 37779).info().expect("info");
+        assert_eq!(info.get_fullname(), "Oracle Voice Tray on nat-mbp._oracle-voice._tcp.local.");
+        assert_eq!(info.get_hostname(), "nat-mbp-oracle-voice.local.");
+        assert_eq!((info.get_addresses_v4(), info.get_port()), ([&Ipv4Addr::new(10, 0, 0, 5)].into(), 37779));
+        assert_eq!(info.get_property_val_str("version"), Some(env!("CARGO_PKG_VERSION")));
+        assert_eq!((info.get_property_val_str("auth"), info.get_property_val_str("path")), (Some("none"), Some("/")));
+    }
+}
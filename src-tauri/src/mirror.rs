@@ -0,0 +1,371 @@
+/// Copies of what was spoken posted to chat webhooks, for following along away from the machine.
+/// Each entry that finishes "done", or "suppressed" with mirror.include_suppressed, from an agent
+/// in mirror.agents when the list is set, goes to every mirror.destinations webhook as
+/// "09:12:40 [ci] Build passed", in Slack's or Discord's payload. Each destination has its own
+/// task: lines arriving within BATCH_WINDOW of the first go out as one message, posts to it are
+/// at least MIN_INTERVAL apart, and a 429, 5xx or lost connection is retried with backoff.
+/// Nothing here holds up the speech queue; what could not be posted is counted in GET /status.
+use chrono::{DateTime, Local};
+use reqwest::header::{HeaderMap, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Url;
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver, Sender};
+use tokio::task::JoinSet;
+use tracing::{debug, warn};
+
+use crate::bus::{self, TimelineEvent};
+use crate::client;
+use crate::config::{load_config, MirrorConfig, MirrorDestination};
+use crate::state::{AppState, VoiceEntry};
+
+/// Payloads a destination can take
+pub const FORMATS: [&str; 2] = ["slack", "discord"];
+
+/// How long a destination gathers lines after the first before posting them together
+const BATCH_WINDOW: Duration = Duration::from_secs(2);
+/// Shortest time between two posts to one destination; Slack allows about one a second
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+/// First wait before posting again after a 429, 5xx or lost connection; doubles up to RETRY_MAX
+const RETRY_MIN: Duration = Duration::from_secs(2);
+const RETRY_MAX: Duration = Duration::from_secs(60);
+/// Tries per message, the first included
+const ATTEMPTS: u32 = 5;
+/// Longest a webhook may take to answer, connecting included
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Lines one destination may fall behind by; newer ones are dropped and counted until it catches up
+const PENDING_MAX: usize = 500;
+
+/// What was posted and what was lost, for GET /status and get_status
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MirrorStatus {
+    /// Messages posted, each one batch of lines
+    pub posted: u64,
+    /// Lines in those messages
+    pub lines: u64,
+    /// Posts tried again after a 429, 5xx or lost connection
+    pub retried: u64,
+    /// Messages given up on, with their lines
+    pub failed: u64,
+    /// Lines dropped because a destination fell PENDING_MAX behind
+    pub dropped: u64,
+    /// Why a message was last given up on
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Slack,
+    Discord,
+}
+
+impl Format {
+    fn of(format: &str) -> Self {
+        if format == "discord" { Self::Discord } else { Self::Slack }
+    }
+
+    /// Longest message text: Discord's limit, and the length Slack advises staying under
+    fn max_chars(self) -> usize {
+        match self {
+            Self::Slack => 4000,
+            Self::Discord => 2000,
+        }
+    }
+
+    /// The JSON posted for `text`. Slack's control characters are escaped and Discord is told
+    /// not to ping anyone, so a message naming @everyone stays text.
+    fn payload(self, text: &str) -> String {
+        match self {
+            Self::Slack => {
+                let text = text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+                serde_json::json!({ "text": text }).to_string()
+            }
+            Self::Discord => serde_json::json!({ "content": text, "allowed_mentions": { "parse": [] } }).to_string(),
+        }
+    }
+}
+
+/// Whether `entry`, which just got its status, is posted
+fn wanted(config: &MirrorConfig, entry: &VoiceEntry) -> bool {
+    let status = match entry.status.as_str() {
+        "done" => true,
+        "suppressed" => config.include_suppressed,
+        _ => false,
+    };
+    status && (config.agents.is_empty() || entry.agent.as_ref().is_some_and(|agent| config.agents.contains(agent)))
+}
+
+/// "09:12:40 [ci] Build passed", with "(suppressed)" before the text when it was
+fn format_line(at: DateTime<Local>, entry: &VoiceEntry) -> String {
+    let mut line = at.format("%H:%M:%S").to_string();
+    if let Some(agent) = &entry.agent {
+        line.push_str(&format!(" [{}]", agent));
+    }
+    if entry.status == "suppressed" {
+        line.push_str(" (suppressed)");
+    }
+    line.push(' ');
+    line.push_str(&entry.text.split_whitespace().collect::<Vec<_>>().join(" "));
+    line
+}
+
+/// `lines` joined into as few messages of at most `max_chars` as they fit in; a longer line is cut
+fn messages(lines: &[String], max_chars: usize) -> Vec<String> {
+    let mut messages: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut length = 0;
+    for line in lines {
+        let line = match line.chars().count() {
+            n if n > max_chars => line.chars().take(max_chars - 1).chain(['…']).collect(),
+            _ => line.clone(),
+        };
+        let chars = line.chars().count();
+        if length > 0 && length + 1 + chars > max_chars {
+            messages.push(std::mem::take(&mut current));
+            length = 0;
+        }
+        if length > 0 {
+            current.push('\n');
+            length += 1;
+        }
+        current.push_str(&line);
+        length += chars;
+    }
+    if length > 0 {
+        messages.push(current);
+    }
+    messages
+}
+
+fn update(state: &AppState, change: impl FnOnce(&mut MirrorStatus)) {
+    let mut status = state.mirror.lock();
+    change(&mut status);
+}
+
+/// Why a post did not go through
+#[derive(Debug, PartialEq)]
+enum Failure {
+    /// Worth trying again, after the wait the server asked for if it named one
+    Retry(String, Option<Duration>),
+    Fatal(String),
+}
+
+/// Ok for a 2xx answer, a retry for 429 and 5xx with the Retry-After wait when there is one
+fn judge(status: StatusCode, headers: &HeaderMap) -> Result<(), Failure> {
+    let retry_after = headers.get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .filter(|s| s.is_finite() && *s >= 0.0)
+        .map(Duration::from_secs_f64);
+    let answered = format!("the webhook answered {}", status);
+    match status.as_u16() {
+        200..=299 => Ok(()),
+        429 | 500..=599 => Err(Failure::Retry(answered, retry_after)),
+        _ => Err(Failure::Fatal(answered)),
+    }
+}
+
+/// Post `body` to `url` once
+async fn post(url: &Url, body: &str) -> Result<(), Failure> {
+    let client = client::client().map_err(Failure::Fatal)?;
+    let response = client.post(url.clone())
+        .timeout(RESPONSE_TIMEOUT)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send().await
+        .map_err(|e| Failure::Retry(client::describe(&e), None))?;
+    judge(response.status(), response.headers())
+}
+
+/// Post `body` to `url`, trying again with backoff while the failure is worth it
+async fn send(state: &AppState, url: &Url, body: &str) -> Result<(), String> {
+    let mut delay = RETRY_MIN;
+    let mut attempt = 1;
+    loop {
+        match post(url, body).await {
+            Ok(()) => return Ok(()),
+            Err(Failure::Retry(error, wait)) if attempt < ATTEMPTS => {
+                let wait = wait.unwrap_or(delay).min(RETRY_MAX);
+                debug!("Mirroring to {} failed ({}); trying again in {:?}", url.host_str().unwrap_or_default(), error, wait);
+                update(state, |status| status.retried += 1);
+                tokio::time::sleep(wait).await;
+                delay = (delay * 2).min(RETRY_MAX);
+                attempt += 1;
+            }
+            Err(Failure::Retry(error, _) | Failure::Fatal(error)) => return Err(error),
+        }
+    }
+}
+
+/// Post one destination's lines in the order they finished, until the destination is removed
+async fn deliver(state: Arc<AppState>, destination: MirrorDestination, mut lines: Receiver<String>) {
+    let format = Format::of(&destination.format);
+    let url = match Url::parse(destination.url.trim()) {
+        Ok(url) => url,
+        Err(e) => {
+            warn!("Not mirroring to an invalid webhook URL: {}", e);
+            return;
+        }
+    };
+    let host = url.host_str().unwrap_or_default().to_string();
+    while let Some(first) = lines.recv().await {
+        tokio::time::sleep(BATCH_WINDOW).await;
+        let mut batch = vec![first];
+        while let Ok(line) = lines.try_recv() {
+            batch.push(line);
+        }
+        for message in messages(&batch, format.max_chars()) {
+            let count = message.lines().count() as u64;
+            match send(&state, &url, &format.payload(&message)).await {
+                Ok(()) => update(&state, |status| {
+                    status.posted += 1;
+                    status.lines += count;
+                }),
+                Err(error) => {
+                    warn!("Mirroring to {} failed: {}", host, error);
+                    update(&state, |status| {
+                        status.failed += 1;
+                        status.last_error = Some(format!("{}: {}", host, error));
+                    });
+                }
+            }
+            tokio::time::sleep(MIN_INTERVAL).await;
+        }
+    }
+}
+
+/// Hand an entry that just finished to every destination's task, starting tasks for new
+/// destinations and ending those of removed ones
+fn route(state: &Arc<AppState>, event: &TimelineEvent, senders: &mut HashMap<MirrorDestination, Sender<String>>, tasks: &mut JoinSet<()>) {
+    let TimelineEvent::StatusChanged { entry, at } = event else {
+        return;
+    };
+    if !matches!(entry.status.as_str(), "done" | "suppressed") {
+        return;
+    }
+    let config = load_config().mirror;
+    // A destination's task finishes the lines it has and ends once its sender is dropped
+    senders.retain(|destination, _| config.destinations.contains(destination));
+    while tasks.try_join_next().is_some() {}
+    if !wanted(&config, entry) {
+        return;
+    }
+    let line = format_line(at.with_timezone(&Local), entry);
+    for destination in config.destinations {
+        let sender = senders.entry(destination.clone()).or_insert_with(|| {
+            let (sender, lines) = mpsc::channel(PENDING_MAX);
+            tasks.spawn(deliver(state.clone(), destination.clone(), lines));
+            sender
+        });
+        match sender.try_send(line.clone()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                let host = Url::parse(destination.url.trim()).ok().and_then(|url| url.host_str().map(str::to_string)).unwrap_or_default();
+                warn!("Mirroring to {} fell {} lines behind; dropped one", host, PENDING_MAX);
+                update(state, |status| status.dropped += 1);
+            }
+            // Its task ended at an invalid URL, which it logged
+            Err(TrySendError::Closed(_)) => {}
+        }
+    }
+}
+
+/// Mirror finished entries as long as the app runs; runs as the "mirror" task
+pub async fn start(state: Arc<AppState>) {
+    let mut senders = HashMap::new();
+    // Dropped with this task when it is aborted at quit, which ends the deliveries too
+    let mut tasks = JoinSet::new();
+    bus::consume(&state, "Mirror", |event| route(&state, &event, &mut senders, &mut tasks)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    fn entry(agent: Option<&str>, text: &str, status: &str) -> VoiceEntry {
+        let state = AppState::default();
+        let id = state.enqueue(text.to_string(), "Samantha".to_string(), 200, agent.map(str::to_string), 0, None);
+        let mut entry = state.timeline.lock().get(id).expect("entry").clone();
+        entry.status = status.to_string();
+        entry
+    }
+
+    #[test]
+    fn test_lines_and_filters() {
+        let at = Local.with_ymd_and_hms(2026, 10, 16, 9, 12, 40).single().expect("time");
+        assert_eq!(format_line(at, &entry(Some("ci"), "Build\n passed", "done")), "09:12:40 [ci] Build passed");
+        assert_eq!(format_line(at, &entry(None, "Hello", "suppressed")), "09:12:40 (suppressed) Hello");
+
+        let mut config = MirrorConfig::default();
+        assert!(wanted(&config, &entry(None, "Hello", "done")));
+        assert!(!wanted(&config, &entry(None, "Hello", "suppressed")));
+        assert!(!wanted(&config, &entry(None, "Hello", "failed")));
+        config.include_suppressed = true;
+        config.agents = vec!["ci".to_string()];
+        assert!(wanted(&config, &entry(Some("ci"), "Hello", "suppressed")));
+        assert!(!wanted(&config, &entry(Some("ntfy"), "Hello", "done")));
+        assert!(!wanted(&config, &entry(None, "Hello", "done")));
+    }
+
+    #[test]
+    fn test_messages_and_payloads() {
+        let lines: Vec<String> = ["one", "two", "three"].iter().map(|l| l.to_string()).collect();
+        assert_eq!(messages(&lines, 100), ["one\ntwo\nthree"]);
+        assert_eq!(messages(&lines, 7), ["one\ntwo", "three"]);
+        assert_eq!(messages(&["x".repeat(10)], 5), ["xxxx…"]);
+
+        assert_eq!(Format::Slack.payload("a < b & <!channel>"), r#"{"text":"a &lt; b &amp; &lt;!channel&gt;"}"#);
+        let discord: serde_json::Value = serde_json::from_str(&Format::Discord.payload("@everyone done")).expect("json");
+        assert_eq!(discord, serde_json::json!({ "content": "@everyone done", "allowed_mentions": { "parse": [] } }));
+    }
+
+    /// A webhook that answers each request with the next of `answers` and hands back the bodies
+    async fn webhook(answers: &'static [&'static str]) -> (Url, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let url = Url::parse(&format!("http://{}/services/T0/B0/secret", listener.local_addr().expect("addr"))).expect("url");
+        let served = tokio::spawn(async move {
+            let mut bodies = Vec::new();
+            for answer in answers {
+                let (stream, _) = listener.accept().await.expect("accept");
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                while !head.ends_with("\r\n\r\n") {
+                    reader.read_line(&mut head).await.expect("read");
+                }
+                let length: usize = head.lines()
+                    .find_map(|line| line.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("content-length")))
+                    .and_then(|(_, n)| n.trim().parse().ok())
+                    .unwrap_or(0);
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.expect("read body");
+                bodies.push(String::from_utf8_lossy(&body).into_owned());
+                reader.get_mut().write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", answer).as_bytes()).await.expect("write");
+            }
+            bodies
+        });
+        (url, served)
+    }
+
+    #[test]
+    fn test_send_retries() {
+        let state = AppState::default();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("runtime");
+        runtime.block_on(async {
+            let (url, served) = webhook(&["429 Too Many Requests\r\nRetry-After: 0", "503 Service Unavailable\r\nRetry-After: 0", "204 No Content"]).await;
+            assert_eq!(send(&state, &url, r#"{"content":"hi"}"#).await, Ok(()));
+            assert_eq!(served.await.expect("served").len(), 3);
+            assert_eq!(state.mirror.lock().retried, 2);
+
+            // A webhook that was deleted is not asked again
+            let (url, served) = webhook(&["404 Not Found"]).await;
+            assert_eq!(send(&state, &url, "{}").await, Err("the webhook answered 404 Not Found".to_string()));
+            served.await.expect("served");
+        });
+    }
+}
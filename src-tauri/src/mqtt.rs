@@ -1,72 +1,206 @@
-use rumqttc::{AsyncClient, MqttOptions, QoS, Event, Packet};
+use rumqttc::{AsyncClient, ClientError, ConnectionError, EventLoop, MqttOptions, Outgoing, QoS, Event, Packet};
 use std::sync::Arc;
 use std::time::Duration;
 use chrono::Utc;
+use tracing::{debug, info, warn};
 
-use crate::config::{MqttConfig, load_mqtt_config};
-use crate::state::{AppState, SpeakRequest, VoiceEntry};
+use crate::config::{MqttConfig, load_config, load_mqtt_config, save_mqtt_config_to_file};
+use crate::error::report;
+use crate::state::{AppState, MqttInfo, SpeakRequest};
+
+/// Client id sent to the broker; a second client with the same id would take over the session
+pub const CLIENT_ID: &str = "voice-tray-v2";
+
+/// Wait before connecting again after a failed session
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+/// Longest a quit waits for the offline status and disconnect to go out
+const OFFLINE_TIMEOUT: Duration = Duration::from_secs(1);
 use crate::tray::update_tray_icon;
 
-/// Run MQTT client with auto-reconnect on config change
+/// Save MQTT settings and reconnect if they changed. Returns whether a reconnect was started.
+pub fn apply_config(state: &Arc<AppState>, config: &MqttConfig) -> Result<bool, String> {
+    // Compared as saved, with the marker where the keychain holds the password
+    if *config == load_config().mqtt {
+        return Ok(false);
+    }
+    save_mqtt_config_to_file(config)?;
+    reconnect(state);
+    Ok(true)
+}
+
+/// Close the current session and connect again with config.json as saved
+pub fn reconnect(state: &Arc<AppState>) {
+    // Set status to disconnected immediately so UI shows the transition
+    *state.mqtt_status.write() = "disconnected".to_string();
+    // Update tray icon to disconnected
+    update_tray_icon(state, false);
+    // Signal MQTT to reconnect
+    *state.mqtt_reconnect.lock() = true;
+}
+
+/// Run MQTT client with auto-reconnect on config change; returns once the app is quitting
 pub async fn start_mqtt_client(state: Arc<AppState>, initial_config: MqttConfig) {
     let mut config = initial_config;
+    let mut first_session = true;
 
-    loop {
+    while !state.is_shutting_down() {
         // Reset reconnect flag
-        if let Ok(mut flag) = state.mqtt_reconnect.lock() {
-            *flag = false;
-        }
+        *state.mqtt_reconnect.lock() = false;
 
         // Run client until it needs to reconnect
-        run_mqtt_session(&state, &config).await;
+        if config.enabled {
+            if !first_session {
+                update_info(&state, |info| info.reconnect_count += 1);
+            }
+            first_session = false;
+            run_mqtt_session(&state, &config).await;
+        } else {
+            wait_while_disabled(&state).await;
+        }
 
         // Check if we need to reconnect with new config
-        let should_reconnect = state.mqtt_reconnect.lock()
-            .map(|g| *g)
-            .unwrap_or(false);
+        let should_reconnect = *state.mqtt_reconnect.lock();
+        if state.is_shutting_down() {
+            break;
+        }
         if should_reconnect {
-            println!("MQTT: Reconnecting with new config...");
+            info!("Reconnecting with new config...");
             config = load_mqtt_config();
         } else {
             // Wait before auto-retry on error
-            tokio::time::sleep(Duration::from_secs(5)).await;
+            update_info(&state, |info| info.retry_at = Some(Utc::now() + RETRY_DELAY));
+            let retry_at = tokio::time::Instant::now() + RETRY_DELAY;
+            while tokio::time::Instant::now() < retry_at && !state.is_shutting_down() {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+            update_info(&state, |info| info.retry_at = None);
         }
     }
 }
 
-/// Single MQTT session - returns when disconnected or reconnect signaled
-async fn run_mqtt_session(state: &Arc<AppState>, config: &MqttConfig) {
-    // Update MQTT status to connecting
-    if let Ok(mut mqtt_status) = state.mqtt_status.lock() {
-        *mqtt_status = "connecting".to_string();
+/// Change the connection details get_mqtt_info reports
+fn update_info(state: &AppState, change: impl FnOnce(&mut MqttInfo)) {
+    let mut info = state.mqtt_info.lock();
+    change(&mut info);
+}
+
+/// A client call that failed is logged, counted in GET /stats and kept for get_mqtt_info
+fn check<T>(state: &Arc<AppState>, what: &str, result: Result<T, ClientError>) -> Option<T> {
+    if let Err(e) = &result {
+        update_info(state, |info| info.last_error = Some(format!("{}: {}", what, e)));
+    }
+    report(state, what, result)
+}
+
+/// Mark the session ended; `error` is kept for get_mqtt_info
+fn session_ended(state: &Arc<AppState>, error: Option<String>) {
+    *state.mqtt_status.write() = "disconnected".to_string();
+    update_info(state, |info| {
+        info.connected_since = None;
+        info.subscribed_topics.clear();
+        if error.is_some() {
+            info.last_error = error;
+        }
+    });
+    update_tray_icon(state, false);
+}
+
+/// Sit out while MQTT is turned off, until a config change signals a reconnect
+async fn wait_while_disabled(state: &Arc<AppState>) {
+    info!("Disabled in config.json");
+    *state.mqtt_status.write() = "disabled".to_string();
+    update_tray_icon(state, false);
+    while !*state.mqtt_reconnect.lock() && !state.is_shutting_down() {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+/// Publish the retained offline status and disconnect, polling the event loop until both are
+/// sent (the client only queues them) or OFFLINE_TIMEOUT passes
+async fn go_offline(state: &Arc<AppState>, config: &MqttConfig, client: &AsyncClient, eventloop: &mut EventLoop) {
+    let connected = *state.mqtt_status.read() == "connected";
+    if connected {
+        let status_json = serde_json::json!({
+            "status": "offline",
+            "timestamp": Utc::now().to_rfc3339()
+        });
+        let published = client.publish(&config.topic_status, QoS::AtLeastOnce, true, status_json.to_string()).await;
+        check(state, "Publishing the offline status", published);
+    }
+    check(state, "Disconnecting", client.disconnect().await);
+    let sent = tokio::time::timeout(OFFLINE_TIMEOUT, async {
+        loop {
+            match eventloop.poll().await {
+                Ok(Event::Outgoing(Outgoing::Disconnect)) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+    }).await;
+    if connected && sent.is_ok() {
+        info!("Published offline status and disconnected");
     }
-    update_tray_icon(&state, false);
+    session_ended(state, None);
+}
 
-    println!("MQTT: Connecting to {}:{}", config.broker, config.port);
-    let mut mqttoptions = MqttOptions::new("voice-tray-v2", &config.broker, config.port);
+/// Client options for `config`, with credentials when a username is set
+fn options(config: &MqttConfig, client_id: &str) -> MqttOptions {
+    let mut mqttoptions = MqttOptions::new(client_id, &config.broker, config.port);
     mqttoptions.set_keep_alive(Duration::from_secs(30));
     mqttoptions.set_clean_session(true);
-
-    // Set credentials if provided
     if let (Some(username), Some(password)) = (&config.username, &config.password) {
         if !username.is_empty() {
-            println!("MQTT: Using authentication for user '{}'", username);
             mqttoptions.set_credentials(username, password);
         }
     }
+    mqttoptions
+}
+
+/// Connect once with `config` under a client id of its own, so the app's session is left
+/// alone, and disconnect again; for the doctor's MQTT check
+pub async fn test_connection(config: &MqttConfig) -> Result<(), ConnectionError> {
+    let (client, mut eventloop) = AsyncClient::new(options(config, &format!("{}-doctor", CLIENT_ID)), 10);
+    loop {
+        if let Event::Incoming(Packet::ConnAck(_)) = eventloop.poll().await? {
+            break;
+        }
+    }
+    // The connection is what was being tested; a goodbye that does not go out changes nothing
+    let _ = client.disconnect().await;
+    let _ = tokio::time::timeout(OFFLINE_TIMEOUT, async {
+        while !matches!(eventloop.poll().await, Ok(Event::Outgoing(Outgoing::Disconnect)) | Err(_)) {}
+    }).await;
+    Ok(())
+}
+
+/// Single MQTT session - returns when disconnected or reconnect signaled
+async fn run_mqtt_session(state: &Arc<AppState>, config: &MqttConfig) {
+    // Update MQTT status to connecting
+    *state.mqtt_status.write() = "connecting".to_string();
+    update_tray_icon(state, false);
 
-    let (client, mut eventloop) = AsyncClient::new(mqttoptions, 10);
+    info!("Connecting to {}:{}", config.broker, config.port);
+    let auth = matches!(&config.username, Some(username) if !username.is_empty()) && config.password.is_some();
+    update_info(state, |info| {
+        info.broker = format!("{}:{}", config.broker, config.port);
+        info.transport = "tcp".to_string();
+        info.tls = false;
+        info.auth = auth;
+        info.client_id = CLIENT_ID.to_string();
+        info.connected_since = None;
+        info.subscribed_topics.clear();
+    });
+    if auth {
+        info!("Using authentication for user '{}'", config.username.as_deref().unwrap_or_default());
+    }
+    let (client, mut eventloop) = AsyncClient::new(options(config, CLIENT_ID), 10);
 
     // Subscribe to voice/speak topic (queues the request, doesn't wait for connection)
     if let Err(e) = client.subscribe(&config.topic_speak, QoS::AtLeastOnce).await {
-        eprintln!("MQTT subscribe error: {:?}", e);
-        if let Ok(mut mqtt_status) = state.mqtt_status.lock() {
-            *mqtt_status = "disconnected".to_string();
-        }
-        update_tray_icon(&state, false);
+        warn!("Subscribe error: {:?}", e);
+        session_ended(state, Some(format!("subscribe failed: {}", e)));
         return;
     }
-    println!("MQTT: Subscribe request sent to {}", config.topic_speak);
+    debug!("Subscribe request sent to {}", config.topic_speak);
 
     // Note: "connected" status is set when we receive ConnAck in the event loop
 
@@ -76,24 +210,32 @@ async fn run_mqtt_session(state: &Arc<AppState>, config: &MqttConfig) {
         "version": "0.2.0",
         "timestamp": Utc::now().to_rfc3339()
     });
-    let _ = client.publish(
+    let published = client.publish(
         &config.topic_status,
         QoS::AtLeastOnce,
         true,
         status_json.to_string()
     ).await;
+    check(state, "Publishing the online status", published);
 
     let client_clone = client.clone();
 
     // Event loop with reconnect check
     loop {
+        if state.is_shutting_down() {
+            go_offline(state, config, &client, &mut eventloop).await;
+            return;
+        }
+
         // Check if reconnect requested
-        let reconnect_requested = state.mqtt_reconnect.lock()
-            .map(|g| *g)
-            .unwrap_or(false);
+        let reconnect_requested = *state.mqtt_reconnect.lock();
         if reconnect_requested {
-            println!("MQTT: Reconnect requested, closing session...");
-            let _ = client.disconnect().await;
+            info!("Reconnect requested, closing session...");
+            check(state, "Disconnecting", client.disconnect().await);
+            update_info(state, |info| {
+                info.connected_since = None;
+                info.subscribed_topics.clear();
+            });
             return;
         }
 
@@ -101,73 +243,59 @@ async fn run_mqtt_session(state: &Arc<AppState>, config: &MqttConfig) {
         match tokio::time::timeout(Duration::from_millis(100), eventloop.poll()).await {
             Ok(Ok(Event::Incoming(Packet::Publish(publish)))) => {
                 if publish.topic == config.topic_speak {
+                    update_info(state, |info| info.messages_in += 1);
                     match serde_json::from_slice::<SpeakRequest>(&publish.payload) {
+                        Ok(req) if req.validate().is_err() => {
+                            warn!("Ignoring message with empty or overlong text");
+                        }
                         Ok(req) => {
-                            let id = state.next_id.lock()
-                                .map(|mut next_id| {
-                                    let id = *next_id;
-                                    *next_id += 1;
-                                    id
-                                })
-                                .unwrap_or(0);
-
-                            let voice = req.voice.unwrap_or_else(|| "Samantha".to_string());
-                            let rate = req.rate.unwrap_or(220);
-
-                            let entry = VoiceEntry {
-                                id,
-                                timestamp: Utc::now(),
-                                text: req.text.clone(),
-                                voice: voice.clone(),
-                                rate,
-                                agent: req.agent.clone(),
-                                status: "queued".to_string(),
-                            };
-
-                            if let Ok(mut timeline) = state.timeline.lock() {
-                                timeline.push_back(entry);
-                                while timeline.len() > 100 {
-                                    timeline.pop_front();
-                                }
-                            }
+                            let text = req.text.clone();
+                            let agent = req.agent.clone();
+                            let id = state.speak(req).id;
 
-                            println!("MQTT: Queued voice message #{}: {}", id, req.text);
+                            info!("Queued voice message #{}: {}", id, text);
 
-                            if let Some(agent) = &req.agent {
+                            if let Some(agent) = &agent {
                                 let agent_topic = format!("voice/agent/{}/status", agent);
                                 let agent_status = serde_json::json!({
-                                    "last_message": req.text,
+                                    "last_message": text,
                                     "timestamp": Utc::now().to_rfc3339(),
                                     "id": id
                                 });
-                                let _ = client_clone.publish(
+                                let published = client_clone.publish(
                                     agent_topic,
                                     QoS::AtLeastOnce,
                                     true,
                                     agent_status.to_string()
                                 ).await;
+                                check(state, "Publishing the agent status", published);
                             }
                         }
                         Err(e) => {
-                            eprintln!("MQTT: Failed to parse message: {:?}", e);
+                            warn!("Failed to parse message: {:?}", e);
                         }
                     }
                 }
             }
             Ok(Ok(Event::Incoming(Packet::ConnAck(_)))) => {
-                println!("MQTT: Connected");
-                if let Ok(mut mqtt_status) = state.mqtt_status.lock() {
-                    *mqtt_status = "connected".to_string();
-                }
-                update_tray_icon(&state, false);
+                info!("Connected");
+                *state.mqtt_status.write() = "connected".to_string();
+                update_info(state, |info| {
+                    info.connected_since = Some(Utc::now());
+                    info.last_error = None;
+                });
+                update_tray_icon(state, false);
+            }
+            Ok(Ok(Event::Incoming(Packet::SubAck(_)))) => {
+                update_info(state, |info| info.subscribed_topics = vec![config.topic_speak.clone()]);
+            }
+            Ok(Ok(Event::Outgoing(Outgoing::Publish(_)))) => {
+                update_info(state, |info| info.messages_out += 1);
             }
             Ok(Ok(_)) => {}
             Ok(Err(e)) => {
-                eprintln!("MQTT connection error: {:?}", e);
-                if let Ok(mut mqtt_status) = state.mqtt_status.lock() {
-                    *mqtt_status = "disconnected".to_string();
-                }
-                update_tray_icon(&state, false);
+                warn!("Connection error: {:?}", e);
+                session_ended(state, Some(e.to_string()));
                 return; // Exit session, will retry
             }
             Err(_) => {
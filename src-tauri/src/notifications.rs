@@ -0,0 +1,124 @@
+/// System notifications for watcher alerts and suppressed messages (tauri-plugin-notification)
+use std::sync::Arc;
+use std::time::Duration;
+
+use tauri::Manager;
+use tauri_plugin_notification::NotificationExt;
+use tracing::warn;
+
+use crate::parser::truncate_detail;
+use crate::state::AppState;
+
+/// After a suppressed-message notification, further ones are counted for this long and shown as one roll-up
+const ROLLUP_WINDOW: Duration = Duration::from_secs(30);
+
+/// Show a notification without waiting for it. Does nothing before the app is set up;
+/// failures are only logged so they never hold up the voice path.
+pub fn notify(state: &AppState, title: &str, body: &str) {
+    let Some(app) = state.app_handle.read().clone() else {
+        return;
+    };
+    let (title, body) = (title.to_string(), body.to_string());
+    std::thread::spawn(move || {
+        if let Err(e) = app.notification().builder().title(title).body(body).show() {
+            warn!("Failed to show notification: {}", e);
+        }
+    });
+}
+
+/// Why a message was not spoken
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Suppression {
+    Muted,
+    Snoozed,
+    QuietHours,
+    Focus,
+}
+
+impl Suppression {
+    fn label(self) -> &'static str {
+        match self {
+            Suppression::Muted => "muted",
+            Suppression::Snoozed => "snoozed",
+            Suppression::QuietHours => "quiet hours",
+            Suppression::Focus => "Focus",
+        }
+    }
+}
+
+/// Counts suppressed messages so a flood becomes one notification plus one roll-up
+#[derive(Debug, Default)]
+pub struct SuppressedRollup {
+    open: bool,
+    pending: usize,
+}
+
+impl SuppressedRollup {
+    /// True for the first message of a window, which is shown on its own; later ones are counted
+    fn record(&mut self) -> bool {
+        if self.open {
+            self.pending += 1;
+            false
+        } else {
+            self.open = true;
+            true
+        }
+    }
+
+    /// End the window, returning how many messages were held back
+    fn close(&mut self) -> usize {
+        self.open = false;
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// Notify about a message that was not spoken, if the ui section enables it for `reason`.
+/// Returns false when disabled, so the caller can fall back to its own handling.
+pub fn notify_suppressed(state: &AppState, reason: Suppression, agent: Option<&str>, text: &str) -> bool {
+    let settings = state.tray_config().notify_suppressed;
+    let enabled = match reason {
+        // A snooze is a mute with an end, so it follows the same setting
+        Suppression::Muted | Suppression::Snoozed => settings.muted,
+        // Focus is treated like quiet hours
+        Suppression::QuietHours | Suppression::Focus => settings.quiet_hours,
+    };
+    if !enabled {
+        return false;
+    }
+    let first = state.suppressed_rollup.lock().record();
+    if !first {
+        return true;
+    }
+    let title = format!("{} ({})", agent.unwrap_or("Voice Tray"), reason.label());
+    notify(state, &title, &truncate_detail(text));
+
+    // Whatever arrives in the window is summed up once it closes
+    let Some(app) = state.app_handle.read().clone() else {
+        return true;
+    };
+    std::thread::spawn(move || {
+        std::thread::sleep(ROLLUP_WINDOW);
+        let state = app.state::<Arc<AppState>>();
+        let held = state.suppressed_rollup.lock().close();
+        if held > 0 {
+            let noun = if held == 1 { "message" } else { "messages" };
+            notify(&state, &format!("Voice Tray ({})", reason.label()), &format!("{} {} suppressed", held, noun));
+        }
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suppressed_rollup() {
+        let mut rollup = SuppressedRollup::default();
+        assert!(rollup.record());
+        assert!(!(0..12).any(|_| rollup.record()));
+        assert_eq!(rollup.close(), 12);
+        assert!(rollup.record(), "a new window shows its first message again");
+        assert_eq!(rollup.close(), 0);
+    }
+}
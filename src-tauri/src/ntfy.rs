@@ -0,0 +1,351 @@
+/// ntfy bridge: every topic in config.json's ntfy section is read as ntfy's JSON stream
+/// (`GET <server>/<topic>/json`) on Tauri's runtime, and its messages are queued like HTTP and
+/// MQTT requests: title and message as the text, the topic as the agent, and ntfy priorities 4
+/// and 5 spoken ahead of the rest. A dropped stream reconnects with backoff and asks for what
+/// it missed with `since=<last id>`; ids already queued are skipped.
+use chrono::{DateTime, Utc};
+use reqwest::header::ACCEPT;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Url;
+use tokio::task::JoinSet;
+use tracing::{debug, info, warn};
+
+use crate::client;
+use crate::config::{load_config, NtfyTopic};
+use crate::state::AppState;
+
+/// First wait before reconnecting a dropped stream; doubles up to RETRY_MAX
+const RETRY_MIN: Duration = Duration::from_secs(2);
+const RETRY_MAX: Duration = Duration::from_secs(120);
+/// ntfy sends a keepalive every 45 seconds; a stream silent for longer is taken as dead
+const READ_TIMEOUT: Duration = Duration::from_secs(100);
+/// Message ids remembered per topic, against repeats after a reconnect
+const SEEN_MAX: usize = 200;
+
+/// How one subscription is doing, for GET /status and get_status
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TopicStatus {
+    pub server: String,
+    pub topic: String,
+    pub connected: bool,
+    pub connected_since: Option<DateTime<Utc>>,
+    pub last_message_at: Option<DateTime<Utc>>,
+    /// Messages queued since the app started
+    pub messages: u64,
+    /// Why the stream last dropped or could not be opened
+    pub last_error: Option<String>,
+}
+
+/// A line of the JSON stream; "open" and "keepalive" events carry no message
+#[derive(Debug, Deserialize)]
+struct StreamEvent {
+    id: String,
+    event: String,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    message: Option<String>,
+    /// 1 (min) to 5 (max); 3 when left out
+    #[serde(default)]
+    priority: Option<u8>,
+}
+
+impl StreamEvent {
+    /// The text to speak and its queue priority; None for events that are not messages
+    fn speech(&self) -> Option<(String, u8)> {
+        if self.event != "message" {
+            return None;
+        }
+        let message = self.message.as_deref().unwrap_or_default().trim();
+        let text = match self.title.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+            Some(title) if message.is_empty() => title.to_string(),
+            Some(title) if title.ends_with(['.', '!', '?', ':']) => format!("{} {}", title, message),
+            Some(title) => format!("{}. {}", title, message),
+            None => message.to_string(),
+        };
+        if text.is_empty() {
+            return None;
+        }
+        // Same scale as the watcher's questions (1) and approval alerts (2)
+        let priority = match self.priority.unwrap_or(3) {
+            5 => 2,
+            4 => 1,
+            _ => 0,
+        };
+        Some((text, priority))
+    }
+}
+
+/// Restart the bridge with config.json's ntfy section as saved
+pub fn reconnect(state: &AppState) {
+    state.ntfy_reconnect.notify_one();
+}
+
+/// Keep one subscription per configured topic, starting over when the section changes
+pub async fn start_bridge(state: Arc<AppState>) {
+    while !state.is_shutting_down() {
+        let topics = load_config().ntfy.topics;
+        *state.ntfy.lock() = topics.iter()
+            .map(|t| TopicStatus { server: t.server.clone(), topic: t.topic.clone(), ..TopicStatus::default() })
+            .collect();
+        if !topics.is_empty() {
+            info!("Subscribing to {} topic(s)", topics.len());
+        }
+        // Dropped with this task when it is aborted at quit, which aborts the subscriptions too
+        let mut subscriptions = JoinSet::new();
+        for (index, topic) in topics.into_iter().enumerate() {
+            subscriptions.spawn(Subscription::new(index, topic).run(state.clone()));
+        }
+        state.ntfy_reconnect.notified().await;
+        subscriptions.shutdown().await;
+    }
+}
+
+/// One topic's stream, kept open for the life of the bridge
+struct Subscription {
+    /// Position in ntfy.topics and in AppState::ntfy
+    index: usize,
+    topic: NtfyTopic,
+    /// Id of the last message, to resume from after a reconnect
+    last_id: Option<String>,
+    /// Ids already queued, newest last
+    seen: VecDeque<String>,
+}
+
+impl Subscription {
+    fn new(index: usize, topic: NtfyTopic) -> Self {
+        Self { index, topic, last_id: None, seen: VecDeque::with_capacity(SEEN_MAX) }
+    }
+
+    fn update(&self, state: &AppState, change: impl FnOnce(&mut TopicStatus)) {
+        let mut status = state.ntfy.lock();
+        if let Some(topic) = status.get_mut(self.index) {
+            change(topic);
+        }
+    }
+
+    /// Listen, and reconnect whenever the stream drops: after RETRY_MIN once it had opened,
+    /// waiting twice as long after each attempt that failed
+    async fn run(mut self, state: Arc<AppState>) {
+        let mut delay = RETRY_MIN;
+        loop {
+            let mut opened = false;
+            let error = match self.listen(&state, &mut opened).await {
+                Ok(()) => "the server closed the stream".to_string(),
+                Err(e) => e,
+            };
+            warn!("{}: {}", self.topic.topic, error);
+            self.update(&state, |status| {
+                status.connected = false;
+                status.connected_since = None;
+                status.last_error = Some(error);
+            });
+            if opened {
+                delay = RETRY_MIN;
+            }
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(RETRY_MAX);
+        }
+    }
+
+    /// Open the stream and queue its messages until it ends
+    async fn listen(&mut self, state: &AppState, opened: &mut bool) -> Result<(), String> {
+        let url = stream_url(&self.topic, self.last_id.as_deref())?;
+        let mut body = open(&url, self.topic.token.as_deref()).await?;
+        *opened = true;
+        info!("Subscribed to {}/{}", self.topic.server.trim_end_matches('/'), self.topic.topic);
+        self.update(state, |status| {
+            status.connected = true;
+            status.connected_since = Some(Utc::now());
+            status.last_error = None;
+        });
+        loop {
+            let line = match tokio::time::timeout(READ_TIMEOUT, body.next_line()).await {
+                Err(_) => return Err(format!("nothing received for {} seconds", READ_TIMEOUT.as_secs())),
+                Ok(Err(e)) => return Err(format!("the stream broke: {}", e)),
+                Ok(Ok(None)) => return Ok(()),
+                Ok(Ok(Some(line))) => line,
+            };
+            if line.is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<StreamEvent>(&line) {
+                Ok(event) => self.receive(state, event),
+                Err(e) => warn!("Skipping an unreadable line from {}: {}", self.topic.topic, e),
+            }
+        }
+    }
+
+    /// Queue a message unless it was queued before
+    fn receive(&mut self, state: &AppState, event: StreamEvent) {
+        let Some((text, priority)) = event.speech() else {
+            return;
+        };
+        self.last_id = Some(event.id.clone());
+        if self.seen.contains(&event.id) {
+            debug!("Skipping repeated message {} from {}", event.id, self.topic.topic);
+            return;
+        }
+        if self.seen.len() == SEEN_MAX {
+            self.seen.pop_front();
+        }
+        self.seen.push_back(event.id);
+        let defaults = state.voice_config();
+        let id = state.enqueue(text, defaults.voice(), defaults.default_rate, Some(self.topic.topic.clone()), priority, None);
+        info!("Queued voice message #{} from {}", id, self.topic.topic);
+        self.update(state, |status| {
+            status.messages += 1;
+            status.last_message_at = Some(Utc::now());
+        });
+    }
+}
+
+/// `<server>/<topic>/json`, resuming after message `since` when set
+fn stream_url(topic: &NtfyTopic, since: Option<&str>) -> Result<Url, String> {
+    let mut url = Url::parse(&topic.server).map_err(|e| format!("invalid server URL {}: {}", topic.server, e))?;
+    let path = format!("{}/{}/json", url.path().trim_end_matches('/'), topic.topic);
+    url.set_path(&path);
+    url.set_query(None);
+    if let Some(id) = since {
+        url.query_pairs_mut().append_pair("since", id);
+    }
+    Ok(url)
+}
+
+/// Send the GET for `url`; anything but 200 is an error
+async fn open(url: &Url, token: Option<&str>) -> Result<Lines, String> {
+    let mut request = client::client()?.get(url.clone()).header(ACCEPT, "application/x-ndjson");
+    if let Some(token) = token.filter(|t| !t.is_empty()) {
+        request = request.bearer_auth(token);
+    }
+    let response = tokio::time::timeout(READ_TIMEOUT, request.send()).await
+        .map_err(|_| format!("no answer for {} seconds", READ_TIMEOUT.as_secs()))?
+        .map_err(|e| client::describe(&e))?;
+    match response.status() {
+        StatusCode::OK => Ok(Lines { response, pending: Vec::new() }),
+        status @ (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => Err(format!("access denied ({}); check the token", status.as_u16())),
+        status => Err(format!("the server answered {}", status)),
+    }
+}
+
+/// A response body read a line at a time
+struct Lines {
+    response: reqwest::Response,
+    /// Read but not yet returned as a line
+    pending: Vec<u8>,
+}
+
+impl Lines {
+    /// The next line of the body, trimmed; None at its end
+    async fn next_line(&mut self) -> Result<Option<String>, String> {
+        loop {
+            if let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.pending.drain(..=end).collect();
+                return Ok(Some(String::from_utf8_lossy(&line).trim().to_string()));
+            }
+            match self.response.chunk().await.map_err(|e| client::describe(&e))? {
+                Some(chunk) => self.pending.extend_from_slice(&chunk),
+                None => {
+                    // A last line without a line end
+                    let rest = std::mem::take(&mut self.pending);
+                    return Ok((!rest.is_empty()).then(|| String::from_utf8_lossy(&rest).trim().to_string()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Serve `response` as it is to one request, and read the body's lines with `token`; the
+    /// request's head comes back lowercased
+    fn lines(response: &'static [u8], token: Option<&str>) -> (Result<Vec<String>, String>, String) {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("runtime");
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+            let url = Url::parse(&format!("http://{}/alerts/json", listener.local_addr().expect("addr"))).expect("url");
+            let served = tokio::spawn(async move {
+                let (stream, _) = listener.accept().await.expect("accept");
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                while !head.ends_with("\r\n\r\n") {
+                    reader.read_line(&mut head).await.expect("read");
+                }
+                reader.get_mut().write_all(response).await.expect("write");
+                head.to_ascii_lowercase()
+            });
+            let read = async {
+                let mut body = open(&url, token).await?;
+                let mut lines = Vec::new();
+                while let Some(line) = body.next_line().await? {
+                    lines.push(line);
+                }
+                Ok(lines)
+            };
+            let lines = read.await;
+            (lines, served.await.expect("served"))
+        })
+    }
+
+    #[test]
+    fn test_stream_url() {
+        let topic = NtfyTopic { server: "https://ntfy.example.com/base/".to_string(), topic: "alerts".to_string(), token: Some("tk_1".to_string()) };
+        let url = stream_url(&topic, Some("Ab12")).expect("url");
+        assert_eq!(url.as_str(), "https://ntfy.example.com/base/alerts/json?since=Ab12");
+
+        let local = NtfyTopic { server: "http://pi.local:8080".to_string(), topic: "ci".to_string(), token: None };
+        let url = stream_url(&local, None).expect("url");
+        assert_eq!(url.as_str(), "http://pi.local:8080/ci/json");
+    }
+
+    #[test]
+    fn test_body_lines() {
+        // A line split across chunks, and chunk extensions
+        let chunked = b"HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nTransfer-Encoding: chunked\r\n\r\n\
+            10\r\n{\"event\":\"open\"}\r\n1\r\n\n\r\n8;x=y\r\n{\"id\":\"a\r\n3\r\n\"}\n\r\n0\r\n\r\n";
+        let (read, head) = lines(chunked, Some("tk_1"));
+        assert_eq!(read, Ok(vec![r#"{"event":"open"}"#.to_string(), r#"{"id":"a"}"#.to_string()]));
+        assert!(head.starts_with("get /alerts/json http/1.1\r\n"), "{}", head);
+        assert!(head.contains("\r\naccept: application/x-ndjson\r\n") && head.contains("\r\nauthorization: bearer tk_1\r\n"));
+
+        let plain = b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\none\ntwo";
+        let (read, head) = lines(plain, Some(""));
+        assert_eq!(read, Ok(vec!["one".to_string(), "two".to_string()]));
+        assert!(!head.contains("authorization"));
+        let forbidden = lines(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n", None).0;
+        assert_eq!(forbidden, Err("access denied (403); check the token".to_string()));
+        let missing = lines(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n", None).0;
+        assert_eq!(missing, Err("the server answered 404 Not Found".to_string()));
+        assert!(lines(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n10\r\nshort", None).0.is_err());
+    }
+
+    #[test]
+    fn test_receive_skips_repeats() {
+        let state = AppState::default();
+        state.ntfy.lock().push(TopicStatus::default());
+        let mut subscription = Subscription::new(0, NtfyTopic { topic: "backups".to_string(), ..NtfyTopic::default() });
+        let event = |line: &str| serde_json::from_str::<StreamEvent>(line).expect("event");
+        subscription.receive(&state, event(r#"{"id":"k1","time":1,"event":"keepalive","topic":"backups"}"#));
+        let message = r#"{"id":"m1","time":2,"event":"message","topic":"backups","title":"Backup failed","message":"Disk full","priority":5}"#;
+        subscription.receive(&state, event(message));
+        subscription.receive(&state, event(message));
+        subscription.receive(&state, event(r#"{"id":"m2","event":"message","topic":"backups","message":"Backup done"}"#));
+
+        let timeline = state.timeline.lock();
+        let queued: Vec<_> = timeline.iter().map(|e| (e.text.as_str(), e.priority, e.agent.as_deref())).collect();
+        assert_eq!(queued, vec![("Backup failed. Disk full", 2, Some("backups")), ("Backup done", 0, Some("backups"))]);
+        drop(timeline);
+        assert_eq!(subscription.last_id.as_deref(), Some("m2"));
+        let status = state.ntfy.lock()[0].clone();
+        assert_eq!(status.messages, 2);
+        assert!(status.last_message_at.is_some());
+    }
+}
@@ -0,0 +1,423 @@
+/// Session log line parsing
+/// Turns one JSON line of a Claude Code transcript (or a `mapped` profile's log) into watcher
+/// events. No filesystem access: the watcher reads the lines and keeps per-session state.
+use serde_json::Value;
+
+use crate::config::{EventMapping, JsonMatch};
+use crate::permissions::PermissionMode;
+use crate::state::TokenUsage;
+
+/// Why Claude is waiting on the user
+#[derive(Debug, PartialEq)]
+pub(crate) enum QuestionKind {
+    AskUser,          // AskUserQuestion tool
+    PlanApproval,     // ExitPlanMode tool
+    TrailingQuestion, // end_turn whose final text ends with '?'
+}
+
+/// Session housekeeping the user may want to hear about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum NoticeKind {
+    Compaction,   // context auto-compacted (system compact_boundary)
+    ContextLimit, // context running out / prompt too long
+    UsageLimit,   // plan usage limit reached
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum LineEvent {
+    Completion,            // stop_reason: end_turn → "Claude Stop"
+    Question(QuestionKind), // Claude stopped to ask the user something
+    SubagentSpawn(String), // tool_use name=Task/Agent → "Spawning <desc>"
+    ToolUse(Vec<String>),  // any other tool_use → may need approval
+    ToolResult,            // user tool_result → tool ran, approval no longer pending
+    ToolError(String),     // tool_result with is_error → "Command failed"
+    ApiError(String),      // API error recorded by Claude Code (overloaded, rate limit, ...)
+    Usage { id: Option<String>, model: Option<String>, tokens: TokenUsage }, // assistant message usage
+    Notice(NoticeKind, String), // compaction or limit, with the raw record type it came from
+    SessionMode(PermissionMode), // permission mode recorded in the session log itself
+}
+
+/// What one log line says: its events and the session's working directory, if recorded
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ParsedLine {
+    pub cwd: Option<String>,
+    pub events: Vec<LineEvent>,
+}
+
+/// Keys one of which every line we act on contains; anything else is not worth parsing
+const INTERESTING_KEYS: [&str; 7] = [
+    "stop_reason", "usage", "tool_result", "isApiErrorMessage", "system", "permissionMode", "dangerouslySkipPermissions",
+];
+
+/// Claude Code transcript line → events
+pub(crate) fn classify_line(line: &str) -> ParsedLine {
+    let mut parsed = ParsedLine::default();
+    if !INTERESTING_KEYS.iter().any(|key| line.contains(key)) {
+        return parsed;
+    }
+    let Ok(json) = serde_json::from_str::<Value>(line) else {
+        return parsed;
+    };
+    parsed.cwd = json.get("cwd").and_then(|c| c.as_str()).map(str::to_string);
+    let events = &mut parsed.events;
+    if let Some(mode) = extract_session_mode(&json) {
+        events.push(LineEvent::SessionMode(mode));
+    }
+    match json.get("type").and_then(|t| t.as_str()) {
+        Some("assistant") => classify_assistant(&json, events),
+        Some("user") if content_items(&json).iter().any(|item| item_type(item) == Some("tool_result")) => {
+            events.push(LineEvent::ToolResult);
+            if let Some(detail) = extract_tool_error(&json) {
+                events.push(LineEvent::ToolError(detail));
+            }
+        }
+        Some("system") => {
+            let subtype = json.get("subtype").and_then(|s| s.as_str());
+            let content = json.get("content").and_then(|c| c.as_str());
+            let raw_type = subtype.map(|s| format!("system/{}", s)).unwrap_or_else(|| "system".to_string());
+            if subtype == Some("compact_boundary") {
+                events.push(LineEvent::Notice(NoticeKind::Compaction, raw_type));
+            } else if let Some(notice) = content.and_then(classify_limit) {
+                events.push(LineEvent::Notice(notice, raw_type));
+            } else if json.get("level").and_then(|l| l.as_str()) == Some("error") {
+                events.push(LineEvent::ApiError(truncate_detail(content.unwrap_or("API error"))));
+            }
+        }
+        _ => {}
+    }
+    parsed
+}
+
+fn classify_assistant(json: &Value, events: &mut Vec<LineEvent>) {
+    if json.get("isApiErrorMessage").and_then(|e| e.as_bool()).unwrap_or(false) {
+        let detail = final_text(json).unwrap_or("API error");
+        match classify_limit(detail) {
+            Some(notice) => events.push(LineEvent::Notice(notice, "assistant/isApiErrorMessage".to_string())),
+            None => events.push(LineEvent::ApiError(truncate_detail(detail))),
+        }
+        return;
+    }
+    if let Some(tokens) = extract_usage(json) {
+        let text_at = |pointer| json.pointer(pointer).and_then(|v| v.as_str()).map(str::to_string);
+        events.push(LineEvent::Usage { id: text_at("/message/id"), model: text_at("/message/model"), tokens });
+    }
+    match json.pointer("/message/stop_reason").and_then(|s| s.as_str()) {
+        Some("end_turn") => {
+            if final_text(json).map(|t| t.trim_end().ends_with('?')).unwrap_or(false) {
+                events.push(LineEvent::Question(QuestionKind::TrailingQuestion));
+            } else {
+                events.push(LineEvent::Completion);
+            }
+        }
+        Some("tool_use") => {
+            let tools = extract_tool_names(json);
+            if tools.iter().any(|t| t == "AskUserQuestion") {
+                events.push(LineEvent::Question(QuestionKind::AskUser));
+            } else if tools.iter().any(|t| t == "ExitPlanMode") {
+                events.push(LineEvent::Question(QuestionKind::PlanApproval));
+            } else if let Some(spawn) = extract_task_spawn(json) {
+                events.push(LineEvent::SubagentSpawn(spawn));
+            } else {
+                events.push(LineEvent::ToolUse(tools));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Log line of a `mapped` profile → events, using the profile's JSON pointers
+pub(crate) fn classify_mapped_line(line: &str, mapping: &EventMapping) -> ParsedLine {
+    let mut parsed = ParsedLine::default();
+    let Ok(json) = serde_json::from_str::<Value>(line) else {
+        return parsed;
+    };
+    let text_at = |pointer: &Option<String>| pointer.as_deref().and_then(|p| json.pointer(p)).and_then(|v| v.as_str());
+    parsed.cwd = text_at(&mapping.cwd).map(str::to_string);
+    let events = &mut parsed.events;
+    if matches_any(&json, &mapping.tool_result) {
+        events.push(LineEvent::ToolResult);
+    }
+    if matches_any(&json, &mapping.error) {
+        events.push(LineEvent::ToolError(truncate_detail(text_at(&mapping.error_text).unwrap_or("Error"))));
+    }
+    if matches_any(&json, &mapping.tool_use) {
+        events.push(LineEvent::ToolUse(text_at(&mapping.tool_name).map(str::to_string).into_iter().collect()));
+    }
+    if matches_any(&json, &mapping.completion) {
+        events.push(LineEvent::Completion);
+    }
+    parsed
+}
+
+/// True if any matcher hits. Non-string values compare by their JSON text (`true`, `0`).
+fn matches_any(json: &Value, matchers: &[JsonMatch]) -> bool {
+    matchers.iter().any(|m| match (json.pointer(&m.pointer), &m.equals) {
+        (None | Some(Value::Null), _) => false,
+        (Some(_), None) => true,
+        (Some(Value::String(s)), Some(expected)) => s == expected,
+        (Some(value), Some(expected)) => serde_json::from_str::<Value>(expected)
+            .map(|e| e == *value)
+            .unwrap_or(false),
+    })
+}
+
+/// Permission mode carried by a transcript record: `permissionMode` on user and init records,
+/// or the `dangerouslySkipPermissions` flag
+fn extract_session_mode(json: &Value) -> Option<PermissionMode> {
+    if json.get("dangerouslySkipPermissions").and_then(|f| f.as_bool()) == Some(true) {
+        return Some(PermissionMode::SkipAll);
+    }
+    json.get("permissionMode")
+        .and_then(|m| m.as_str())
+        .and_then(PermissionMode::from_setting)
+}
+
+/// Typed items of `message.content`. Plain-string content has none; `final_text` reads it.
+fn content_items(json: &Value) -> &[Value] {
+    json.pointer("/message/content")
+        .and_then(|c| c.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or(&[])
+}
+
+fn item_type(item: &Value) -> Option<&str> {
+    item.get("type").and_then(|t| t.as_str())
+}
+
+/// Map text to Norse agent name if any keyword is found.
+fn detect_norse(text: &str) -> Option<&'static str> {
+    let t = text.to_lowercase();
+    if t.contains("thor")     { return Some("Thor") }
+    if t.contains("heimdall") { return Some("Heimdall") }
+    if t.contains("tyr")      { return Some("Tyr") }
+    if t.contains("ymir")     { return Some("Ymir") }
+    if t.contains("odin")     { return Some("Odin") }
+    if t.contains("loki")     { return Some("Loki") }
+    if t.contains("huginn")   { return Some("Huginn") }
+    None
+}
+
+/// If the assistant message contains a subagent spawn tool_use, return its name.
+fn extract_task_spawn(json: &Value) -> Option<String> {
+    for item in content_items(json) {
+        if item_type(item) != Some("tool_use") {
+            continue;
+        }
+        let tool_name = item.get("name").and_then(|n| n.as_str()).unwrap_or("");
+
+        // MCP local-llm direct calls → Norse name from tool name
+        if tool_name.contains("query_thor")     { return Some("Thor".to_string()) }
+        if tool_name.contains("query_heimdall") { return Some("Heimdall".to_string()) }
+        if tool_name.contains("query_loki")     { return Some("Loki".to_string()) }
+        if tool_name.contains("query_tyr")      { return Some("Tyr".to_string()) }
+
+        // Subagent spawn (the tool was called Task before it became Agent)
+        if tool_name == "Agent" || tool_name == "Task" {
+            let desc = item.pointer("/input/description")
+                .and_then(|d| d.as_str()).unwrap_or("");
+            let prompt = item.pointer("/input/prompt")
+                .and_then(|p| p.as_str()).unwrap_or("");
+            let subagent_type = item.pointer("/input/subagent_type")
+                .and_then(|t| t.as_str()).unwrap_or("");
+
+            // Try Norse name from description → prompt → subagent_type
+            if let Some(name) = detect_norse(desc)
+                .or_else(|| detect_norse(prompt))
+                .or_else(|| detect_norse(subagent_type))
+            {
+                return Some(name.to_string());
+            }
+
+            // Fallback: subagent_type → friendly name
+            let label = match subagent_type {
+                "Explore"         => "Heimdall",
+                "general-purpose" => "Agent",
+                "Plan"            => "Tyr",
+                other if !other.is_empty() => other,
+                _ if !desc.is_empty() => desc,
+                _ => "Agent",
+            };
+            return Some(label.to_string());
+        }
+    }
+    None
+}
+
+/// Text of the last text item in an assistant message, or the whole content when it is a string
+fn final_text(json: &Value) -> Option<&str> {
+    match json.pointer("/message/content")? {
+        Value::String(text) => Some(text),
+        Value::Array(items) => items.iter()
+            .rev()
+            .find(|item| item_type(item) == Some("text"))?
+            .get("text")?
+            .as_str(),
+        _ => None,
+    }
+}
+
+/// Error text of the first failed tool_result in a user message, skipping user rejections
+fn extract_tool_error(json: &Value) -> Option<String> {
+    for item in content_items(json) {
+        if item_type(item) != Some("tool_result") || item.get("is_error").and_then(|e| e.as_bool()) != Some(true) {
+            continue;
+        }
+        let text = match item.get("content") {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Array(parts)) => parts.iter()
+                .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                .collect::<Vec<_>>()
+                .join(" "),
+            _ => String::new(),
+        };
+        // Declining a permission prompt is recorded as an error result too
+        if text.starts_with("The user doesn't want to proceed") || text.contains("[Request interrupted by user") {
+            continue;
+        }
+        return Some(truncate_detail(if text.is_empty() { "Tool error" } else { &text }));
+    }
+    None
+}
+
+/// Recognise context and usage limit messages by their wording
+fn classify_limit(text: &str) -> Option<NoticeKind> {
+    let text = text.to_lowercase();
+    if text.contains("usage limit") || text.contains("limit reached") {
+        Some(NoticeKind::UsageLimit)
+    } else if text.contains("prompt is too long")
+        || (text.contains("context") && (text.contains("low") || text.contains("limit") || text.contains("until auto-compact")))
+    {
+        Some(NoticeKind::ContextLimit)
+    } else {
+        None
+    }
+}
+
+/// Token counts of an assistant message; None when the usage block is absent or unrecognised
+fn extract_usage(json: &Value) -> Option<TokenUsage> {
+    let usage = json.pointer("/message/usage")?;
+    let count = |key: &str| usage.get(key).and_then(|v| v.as_u64());
+    let fields = [
+        count("input_tokens"),
+        count("output_tokens"),
+        count("cache_creation_input_tokens"),
+        count("cache_read_input_tokens"),
+    ];
+    if fields.iter().all(Option::is_none) {
+        return None;
+    }
+    let [input, output, cache_creation, cache_read] = fields.map(|f| f.unwrap_or(0));
+    Some(TokenUsage {
+        input_tokens: input,
+        output_tokens: output,
+        cache_creation_tokens: cache_creation,
+        cache_read_tokens: cache_read,
+    })
+}
+
+/// First ~80 characters of an error, on one line, for the timeline
+pub(crate) fn truncate_detail(text: &str) -> String {
+    let flat = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() <= 80 {
+        return flat;
+    }
+    format!("{}…", flat.chars().take(80).collect::<String>())
+}
+
+/// Names of all tool_use items in an assistant message
+fn extract_tool_names(json: &Value) -> Vec<String> {
+    content_items(json).iter()
+        .filter(|item| item_type(item) == Some("tool_use"))
+        .filter_map(|item| item.get("name").and_then(|n| n.as_str()))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Events of every line in a captured transcript fixture
+    fn classify_fixture(fixture: &str) -> Vec<LineEvent> {
+        fixture.lines().flat_map(|line| classify_line(line).events).collect()
+    }
+
+    #[test]
+    fn test_end_turn_fixture() {
+        let events = classify_fixture(include_str!("../tests/fixtures/end_turn.jsonl"));
+        assert_eq!(events, vec![
+            LineEvent::SessionMode(PermissionMode::Normal),
+            LineEvent::Usage {
+                id: Some("msg_01XnKq8aVbqW3V2kq9y6Wm4T".to_string()),
+                model: Some("claude-opus-4-1-20250805".to_string()),
+                tokens: TokenUsage { input_tokens: 4, output_tokens: 312, cache_creation_tokens: 1838, cache_read_tokens: 24011 },
+            },
+            LineEvent::Completion,
+        ]);
+        let first = include_str!("../tests/fixtures/end_turn.jsonl").lines().next().expect("line");
+        assert_eq!(classify_line(first).cwd.as_deref(), Some("/Users/nat/Code/oracle-voice-tray"));
+    }
+
+    #[test]
+    fn test_tool_use_fixtures() {
+        let tools: Vec<LineEvent> = classify_fixture(include_str!("../tests/fixtures/tool_use.jsonl"))
+            .into_iter()
+            .filter(|e| !matches!(e, LineEvent::Usage { .. }))
+            .collect();
+        assert_eq!(tools, vec![
+            LineEvent::ToolUse(vec!["Bash".to_string()]),
+            LineEvent::SubagentSpawn("Heimdall".to_string()),
+            LineEvent::SubagentSpawn("Thor".to_string()),
+            LineEvent::ToolUse(vec!["mcp__github__create_issue".to_string()]),
+            LineEvent::ToolUse(vec!["Read".to_string(), "Grep".to_string()]),
+        ]);
+    }
+
+    #[test]
+    fn test_tool_result_fixtures() {
+        let events = classify_fixture(include_str!("../tests/fixtures/tool_result.jsonl"));
+        assert_eq!(events, vec![
+            LineEvent::ToolResult,
+            LineEvent::ToolResult,
+            LineEvent::ToolError("Exit code 101 error: could not compile `voice-tray-v2` (lib) due to 1 previous e…".to_string()),
+            LineEvent::ToolResult, // rejected permission prompt: not an error worth announcing
+            LineEvent::ToolResult,
+            LineEvent::ToolResult,
+            LineEvent::ToolError("Error: ENOENT: no such file or directory".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_string_content_and_missing_fields() {
+        // Prompts and some assistant records carry plain-string content
+        let prompt = r#"{"type":"user","message":{"role":"user","content":"what does tool_result mean?"},"cwd":"/work/oracle"}"#;
+        assert_eq!(classify_line(prompt), ParsedLine { cwd: Some("/work/oracle".to_string()), events: vec![] });
+        let text_turn = r#"{"type":"assistant","message":{"stop_reason":"end_turn","content":"Shall I continue?"}}"#;
+        assert_eq!(classify_line(text_turn).events, vec![LineEvent::Question(QuestionKind::TrailingQuestion)]);
+        let string_tool_use = r#"{"type":"assistant","message":{"stop_reason":"tool_use","content":"Running it"}}"#;
+        assert_eq!(classify_line(string_tool_use).events, vec![LineEvent::ToolUse(vec![])]);
+
+        // Items without a name or content, and spacing the old substring checks missed
+        let bare = r#"{"type": "user", "message": {"content": [{"type": "tool_result", "is_error": true}]}}"#;
+        assert_eq!(classify_line(bare).events, vec![LineEvent::ToolResult, LineEvent::ToolError("Tool error".to_string())]);
+        let system = r#"{"type": "system", "subtype": "compact_boundary"}"#;
+        assert_eq!(classify_line(system).events, vec![LineEvent::Notice(NoticeKind::Compaction, "system/compact_boundary".to_string())]);
+        assert_eq!(classify_line("not json at all, but mentions usage").events, vec![]);
+        assert_eq!(classify_line(r#"{"type":"summary","summary":"Fix tray"}"#), ParsedLine::default());
+    }
+
+    #[test]
+    fn test_usage_absent_or_renamed() {
+        let json: serde_json::Value = serde_json::from_str(r#"{"message":{"usage":{"prompt_tokens":5}}}"#).expect("json");
+        assert_eq!(extract_usage(&json), None);
+        let json: serde_json::Value = serde_json::from_str(r#"{"message":{"usage":{"output_tokens":5}}}"#).expect("json");
+        assert_eq!(extract_usage(&json).map(|u| u.billed()), Some(5));
+    }
+
+    #[test]
+    fn test_truncate_detail() {
+        assert_eq!(truncate_detail("short"), "short");
+        let long = "x".repeat(100);
+        assert_eq!(truncate_detail(&long).chars().count(), 81);
+    }
+}
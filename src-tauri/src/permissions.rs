@@ -0,0 +1,160 @@
+/// Claude Code permission mode resolution
+/// Mirrors Claude Code's settings precedence: project .claude/settings.local.json →
+/// project .claude/settings.json → ~/.claude/settings.local.json → ~/.claude/settings.json
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Effective permission mode for a Claude Code session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PermissionMode {
+    #[default]
+    Normal,      // "default" — every tool use may need approval
+    AcceptEdits, // "acceptEdits"
+    Plan,        // "plan"
+    SkipAll,     // "bypassPermissions" / --dangerously-skip-permissions
+}
+
+impl PermissionMode {
+    /// Parse the `permissions.defaultMode` value used in settings.json
+    pub fn from_setting(value: &str) -> Option<Self> {
+        match value {
+            "default" => Some(Self::Normal),
+            "acceptEdits" => Some(Self::AcceptEdits),
+            "plan" => Some(Self::Plan),
+            "bypassPermissions" => Some(Self::SkipAll),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Normal => "default",
+            Self::AcceptEdits => "acceptEdits",
+            Self::Plan => "plan",
+            Self::SkipAll => "bypassPermissions",
+        }
+    }
+}
+
+/// Read `permissions.defaultMode` from a single settings file.
+/// Returns None if the file is missing, unparsable, or does not set a mode.
+pub fn read_permission_mode(path: &Path) -> Option<PermissionMode> {
+    let content = fs::read_to_string(path).ok()?;
+    let json: serde_json::Value = serde_json::from_str(&content).ok()?;
+    json.pointer("/permissions/defaultMode")
+        .and_then(|m| m.as_str())
+        .and_then(PermissionMode::from_setting)
+}
+
+/// Settings files for a project, highest precedence first
+fn settings_files(claude_home: &Path, project: Option<&Path>) -> Vec<PathBuf> {
+    let mut files = Vec::with_capacity(4);
+    if let Some(project) = project {
+        files.push(project.join(".claude").join("settings.local.json"));
+        files.push(project.join(".claude").join("settings.json"));
+    }
+    files.push(claude_home.join("settings.local.json"));
+    files.push(claude_home.join("settings.json"));
+    files
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+struct CachedMode {
+    stamps: Vec<(PathBuf, Option<SystemTime>)>,
+    mode: PermissionMode,
+}
+
+impl CachedMode {
+    fn load(files: Vec<PathBuf>) -> Self {
+        let mode = files.iter()
+            .find_map(|f| read_permission_mode(f))
+            .unwrap_or_default();
+        let stamps = files.into_iter().map(|f| { let m = modified(&f); (f, m) }).collect();
+        Self { stamps, mode }
+    }
+
+    fn is_stale(&self) -> bool {
+        self.stamps.iter().any(|(f, m)| modified(f) != *m)
+    }
+}
+
+/// Per-project permission mode cache, refreshed when any settings file changes
+pub struct ModeCache {
+    claude_home: PathBuf,
+    projects: HashMap<Option<PathBuf>, CachedMode>,
+}
+
+impl ModeCache {
+    pub fn new(claude_home: PathBuf) -> Self {
+        Self { claude_home, projects: HashMap::new() }
+    }
+
+    /// Resolve the effective mode for a project working directory.
+    /// `None` means the project is unknown and only global settings apply.
+    pub fn resolve(&mut self, project: Option<&Path>) -> PermissionMode {
+        let key = project.map(Path::to_path_buf);
+        if let Some(cached) = self.projects.get(&key) {
+            if !cached.is_stale() {
+                return cached.mode;
+            }
+        }
+        let cached = CachedMode::load(settings_files(&self.claude_home, project));
+        let mode = cached.mode;
+        self.projects.insert(key, cached);
+        mode
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_mode(dir: &Path, file: &str, mode: &str) {
+        fs::create_dir_all(dir).expect("create dir");
+        let json = serde_json::json!({ "permissions": { "defaultMode": mode } });
+        fs::write(dir.join(file), json.to_string()).expect("write settings");
+    }
+
+    #[test]
+    fn test_read_permission_mode() {
+        let temp = TempDir::new().expect("temp dir");
+        write_mode(temp.path(), "settings.json", "acceptEdits");
+        assert_eq!(read_permission_mode(&temp.path().join("settings.json")), Some(PermissionMode::AcceptEdits));
+        assert_eq!(read_permission_mode(&temp.path().join("missing.json")), None);
+
+        fs::write(temp.path().join("other.json"), r#"{"model":"opus"}"#).expect("write");
+        assert_eq!(read_permission_mode(&temp.path().join("other.json")), None);
+    }
+
+    #[test]
+    fn test_project_settings_override_global() {
+        let home = TempDir::new().expect("temp dir");
+        let scratch = TempDir::new().expect("temp dir");
+        let work = TempDir::new().expect("temp dir");
+        write_mode(home.path(), "settings.json", "default");
+        write_mode(&scratch.path().join(".claude"), "settings.json", "acceptEdits");
+        write_mode(&scratch.path().join(".claude"), "settings.local.json", "bypassPermissions");
+
+        let mut cache = ModeCache::new(home.path().to_path_buf());
+        assert_eq!(cache.resolve(Some(scratch.path())), PermissionMode::SkipAll);
+        assert_eq!(cache.resolve(Some(work.path())), PermissionMode::Normal);
+        assert_eq!(cache.resolve(None), PermissionMode::Normal);
+    }
+
+    #[test]
+    fn test_cache_refreshes_on_change() {
+        let home = TempDir::new().expect("temp dir");
+        let project = TempDir::new().expect("temp dir");
+        let mut cache = ModeCache::new(home.path().to_path_buf());
+        assert_eq!(cache.resolve(Some(project.path())), PermissionMode::Normal);
+
+        write_mode(&project.path().join(".claude"), "settings.local.json", "plan");
+        assert_eq!(cache.resolve(Some(project.path())), PermissionMode::Plan);
+    }
+}
@@ -0,0 +1,246 @@
+/// The voice pipe, for scripts without an HTTP client: with pipe.enabled,
+/// `echo "deploy done" > voice.pipe` in the config folder speaks the line. On macOS and Linux
+/// voice.pipe is a FIFO made at startup and removed at quit; on Windows it is the named pipe
+/// \\.\pipe\oracle-voice-tray. Lines are queued with agent "pipe" and checked like POST /speak;
+/// "@Daniel/250 text" picks the voice and rate.
+use std::io;
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tracing::{info, warn};
+
+use crate::config::load_config;
+use crate::error::{report_error, VoiceTrayError};
+use crate::state::{AppState, SpeakRequest, TEXT_MAX_CHARS};
+
+/// Agent of the entries the pipe queues
+pub const AGENT: &str = "pipe";
+
+/// In the config folder
+#[cfg(unix)]
+pub const PIPE_FILE: &str = "voice.pipe";
+
+#[cfg(windows)]
+pub const PIPE_NAME: &str = r"\\.\pipe\oracle-voice-tray";
+
+/// Longest line read: TEXT_MAX_CHARS in 4-byte characters, and room for a prefix. The rest
+/// of a longer line is skipped instead of held in memory.
+const LINE_MAX_BYTES: usize = TEXT_MAX_CHARS * 4 + 64;
+
+#[cfg(unix)]
+pub fn pipe_path() -> std::path::PathBuf {
+    crate::config::config_dir().join(PIPE_FILE)
+}
+
+/// Restart the listener with config.json's pipe section as saved
+pub fn reopen(state: &AppState) {
+    state.pipe_reopen.notify_one();
+}
+
+/// Listen while pipe.enabled, starting over when the section changes
+pub async fn start_listener(state: Arc<AppState>) {
+    while !state.is_shutting_down() {
+        if !load_config().pipe.enabled {
+            remove();
+            state.pipe_reopen.notified().await;
+            continue;
+        }
+        tokio::select! {
+            result = listen(&state) => {
+                if let Err(e) = result {
+                    report_error(&state, "Opening the voice pipe", &VoiceTrayError::Io(e));
+                }
+                state.pipe_reopen.notified().await;
+            }
+            _ = state.pipe_reopen.notified() => {}
+        }
+    }
+}
+
+/// Read voice.pipe for good. The app holds a writer of its own open, so the stream does not
+/// end each time a script closes the pipe and the next `echo` is read the same way.
+#[cfg(unix)]
+async fn listen(state: &Arc<AppState>) -> io::Result<()> {
+    use tokio::net::unix::pipe::OpenOptions;
+
+    let path = pipe_path();
+    make_fifo(&path)?;
+    let receiver = OpenOptions::new().open_receiver(&path)?;
+    let _writer = OpenOptions::new().open_sender(&path)?;
+    info!("Reading lines from {}", path.display());
+    read_lines(state, receiver).await
+}
+
+/// Serve \\.\pipe\oracle-voice-tray: each writer gets an instance of its own, read until it
+/// closes, while a fresh one waits for the next
+#[cfg(windows)]
+async fn listen(state: &Arc<AppState>) -> io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let mut server = ServerOptions::new().first_pipe_instance(true).create(PIPE_NAME)?;
+    info!("Reading lines from {}", PIPE_NAME);
+    loop {
+        server.connect().await?;
+        let client = std::mem::replace(&mut server, ServerOptions::new().create(PIPE_NAME)?);
+        let state = state.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = read_lines(&state, client).await {
+                warn!("Reading {} failed: {}", PIPE_NAME, e);
+            }
+        });
+    }
+}
+
+/// Make the FIFO at `path`; a plain file there, left by a write while the app was not
+/// running, is replaced
+#[cfg(unix)]
+fn make_fifo(path: &std::path::Path) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_fifo() => return Ok(()),
+        Ok(_) => std::fs::remove_file(path)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let output = std::process::Command::new("mkfifo").arg("-m").arg("600").arg(path).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("mkfifo failed: {}", String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(())
+}
+
+/// Take the FIFO away, so a script writing to it does not wait for a reader that is gone
+#[cfg(unix)]
+pub fn remove() {
+    use std::os::unix::fs::FileTypeExt;
+
+    let path = pipe_path();
+    let is_fifo = std::fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_fifo());
+    if is_fifo {
+        if let Err(e) = std::fs::remove_file(&path) {
+            warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// The named pipe goes away with the app
+#[cfg(windows)]
+pub fn remove() {}
+
+/// Queue every line until the pipe ends
+async fn read_lines<R: AsyncRead + Unpin>(state: &AppState, pipe: R) -> io::Result<()> {
+    let mut reader = BufReader::new(pipe);
+    loop {
+        match read_line(&mut reader).await? {
+            Line::Text(line) => speak_line(state, &line),
+            Line::TooLong => warn!("Skipping a line longer than {} bytes", LINE_MAX_BYTES),
+            Line::End => return Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Line {
+    Text(String),
+    /// Longer than LINE_MAX_BYTES, and skipped
+    TooLong,
+    End,
+}
+
+/// The next line, trimmed
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Line> {
+    let mut line = Vec::new();
+    if (&mut *reader).take(LINE_MAX_BYTES as u64 + 1).read_until(b'\n', &mut line).await? == 0 {
+        return Ok(Line::End);
+    }
+    if line.len() > LINE_MAX_BYTES && line.last() != Some(&b'\n') {
+        let mut rest = Vec::new();
+        while (&mut *reader).take(LINE_MAX_BYTES as u64).read_until(b'\n', &mut rest).await? > 0 && rest.last() != Some(&b'\n') {
+            rest.clear();
+        }
+        return Ok(Line::TooLong);
+    }
+    Ok(Line::Text(String::from_utf8_lossy(&line).trim().to_string()))
+}
+
+/// The request for a line: "@Daniel/250 Deploy done" speaks "Deploy done" with voice Daniel at
+/// 250 words per minute, "@Daniel ..." sets only the voice and "@/250 ..." only the rate. A
+/// line whose prefix does not parse is spoken whole.
+fn parse_line(line: &str) -> SpeakRequest {
+    let whole = SpeakRequest { text: line.to_string(), voice: None, agent: Some(AGENT.to_string()), rate: None };
+    let Some((prefix, text)) = line.strip_prefix('@').and_then(|rest| rest.split_once(char::is_whitespace)) else {
+        return whole;
+    };
+    let (voice, rate) = match prefix.split_once('/') {
+        Some((voice, rate)) => match rate.parse() {
+            Ok(rate) => (voice, Some(rate)),
+            Err(_) => return whole,
+        },
+        None => (prefix, None),
+    };
+    SpeakRequest { text: text.trim().to_string(), voice: Some(voice.to_string()).filter(|v| !v.is_empty()), rate, ..whole }
+}
+
+/// Queue a line that passes the checks of POST /speak
+fn speak_line(state: &AppState, line: &str) {
+    if line.is_empty() {
+        return;
+    }
+    let req = parse_line(line);
+    if let Err(e) = req.validate() {
+        warn!("Skipping a line: {}", e);
+        return;
+    }
+    let id = state.speak(req).id;
+    info!("Queued voice message #{} from the pipe", id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line_prefix() {
+        let req = parse_line("@Daniel/250 Deploy done");
+        assert_eq!((req.text.as_str(), req.voice.as_deref(), req.rate, req.agent.as_deref()), ("Deploy done", Some("Daniel"), Some(250), Some("pipe")));
+        let req = parse_line("@Karen Build passed");
+        assert_eq!((req.text.as_str(), req.voice.as_deref(), req.rate), ("Build passed", Some("Karen"), None));
+        let req = parse_line("@/180 Slower");
+        assert_eq!((req.text.as_str(), req.voice, req.rate), ("Slower", None, Some(180)));
+        // Not a prefix: spoken as written
+        for line in ["@Daniel/fast Hi", "@everyone", "email me@example.com"] {
+            let req = parse_line(line);
+            assert_eq!((req.text.as_str(), req.voice, req.rate), (line, None, None));
+        }
+    }
+
+    #[test]
+    fn test_lines_queued_across_writers() {
+        let state = AppState::default();
+        let long = "x".repeat(LINE_MAX_BYTES + 10);
+        // A blank line, one over the read limit and one over the text cap are skipped
+        let input = format!("Deploy done\n\n{}\n@Daniel/250 Backup ok\n{}\nlast without newline", long, "y".repeat(TEXT_MAX_CHARS + 1));
+        let runtime = tokio::runtime::Builder::new_current_thread().build().expect("runtime");
+        runtime.block_on(async {
+            let mut reader = BufReader::new(input.as_bytes());
+            assert_eq!(read_line(&mut reader).await.expect("read"), Line::Text("Deploy done".to_string()));
+            assert_eq!(read_line(&mut reader).await.expect("read"), Line::Text(String::new()));
+            assert_eq!(read_line(&mut reader).await.expect("read"), Line::TooLong);
+            assert_eq!(read_line(&mut reader).await.expect("read"), Line::Text("@Daniel/250 Backup ok".to_string()));
+            read_lines(&state, input.as_bytes()).await.expect("read");
+        });
+        let timeline = state.timeline.lock();
+        let queued: Vec<_> = timeline.iter().map(|e| (e.text.as_str(), e.voice.as_str(), e.rate)).collect();
+        let defaults = state.voice_config();
+        let voice = defaults.voice();
+        assert_eq!(queued, vec![
+            ("Deploy done", voice.as_str(), defaults.default_rate),
+            ("Backup ok", "Daniel", 250),
+            ("last without newline", voice.as_str(), defaults.default_rate),
+        ]);
+        assert!(timeline.iter().all(|e| e.agent.as_deref() == Some(AGENT)));
+    }
+}
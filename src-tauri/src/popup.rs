@@ -0,0 +1,177 @@
+//! Where the popup opens: next to the tray icon, inside the work area of the monitor that was clicked.
+//! Monitors are reported in physical pixels; the placement is worked out in the logical pixels of
+//! the clicked monitor, so the gap and the window size come out the same at every scale factor.
+
+/// Gap between the popup and the taskbar or menu bar, in logical pixels
+const GAP: f64 = 8.0;
+
+/// Screen rectangle: physical pixels as reported by the OS, logical ones inside popup_position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    fn right(&self) -> f64 {
+        self.x + self.width
+    }
+
+    fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+
+    /// Distance from `point` to the nearest pixel of the rectangle; 0 inside it
+    fn distance(&self, (x, y): (f64, f64)) -> f64 {
+        let dx = (self.x - x).max(x - self.right()).max(0.0);
+        let dy = (self.y - y).max(y - self.bottom()).max(0.0);
+        dx.hypot(dy)
+    }
+}
+
+/// One monitor, in physical pixels of the virtual desktop
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Screen {
+    pub bounds: Rect,
+    pub work_area: Rect,
+    pub scale_factor: f64,
+}
+
+impl Screen {
+    /// Logical pixels from the monitor's top-left corner. Monitors with different scale factors
+    /// share no logical coordinate space, so positions are kept relative to this one.
+    fn to_logical(self, (x, y): (f64, f64)) -> (f64, f64) {
+        ((x - self.bounds.x) / self.scale_factor, (y - self.bounds.y) / self.scale_factor)
+    }
+
+    fn to_physical(self, (x, y): (f64, f64)) -> (i32, i32) {
+        (
+            (self.bounds.x + x * self.scale_factor).round() as i32,
+            (self.bounds.y + y * self.scale_factor).round() as i32,
+        )
+    }
+
+    fn logical_work_area(self) -> Rect {
+        let (x, y) = self.to_logical((self.work_area.x, self.work_area.y));
+        Rect { x, y, width: self.work_area.width / self.scale_factor, height: self.work_area.height / self.scale_factor }
+    }
+}
+
+/// The monitor a tray click belongs to: the one containing it, else the nearest. A click on an
+/// icon at the edge of a taskbar can be reported just outside every monitor, or past the seam
+/// on the neighbouring one.
+pub fn screen_for(click: (f64, f64), screens: &[Screen]) -> Option<&Screen> {
+    screens.iter().min_by(|a, b| a.bounds.distance(click).total_cmp(&b.bounds.distance(click)))
+}
+
+/// Physical top-left corner for a popup of `logical_size` opened by a click at physical `click`.
+/// The size is the window's own logical size, so a popup still sitting on a monitor with another
+/// scale factor lands the same distance from the tray as one already on the clicked monitor.
+pub fn place(click: (f64, f64), logical_size: (f64, f64), screens: &[Screen]) -> Option<(i32, i32)> {
+    let screen = screen_for(click, screens)?;
+    let position = popup_position(screen.to_logical(click), logical_size, screen.logical_work_area());
+    Some(screen.to_physical(position))
+}
+
+/// Top-left corner for a popup of `size` opened by a click at `click`, all in logical pixels of one
+/// monitor. The taskbar side is where the click falls outside `work_area` (menu bar on top, Windows
+/// taskbar at the bottom or a side); when the click is inside it (auto-hide taskbars) the popup
+/// opens towards the screen's middle. The result always lies inside the work area.
+pub fn popup_position(click: (f64, f64), size: (f64, f64), work_area: Rect) -> (f64, f64) {
+    let (cx, cy) = click;
+    let (width, height) = size;
+    let centered_x = cx - width / 2.0;
+    let centered_y = cy - height / 2.0;
+    let (x, y) = if cy < work_area.y {
+        (centered_x, work_area.y + GAP)
+    } else if cy >= work_area.bottom() {
+        (centered_x, work_area.bottom() - height - GAP)
+    } else if cx < work_area.x {
+        (work_area.x + GAP, centered_y)
+    } else if cx >= work_area.right() {
+        (work_area.right() - width - GAP, centered_y)
+    } else if cy > work_area.y + work_area.height / 2.0 {
+        (centered_x, cy - height - GAP)
+    } else {
+        (centered_x, cy + GAP)
+    };
+    (clamp(x, work_area.x, work_area.right() - width), clamp(y, work_area.y, work_area.bottom() - height))
+}
+
+/// Like f64::clamp, but a window larger than the area is pinned to its top-left instead of panicking
+fn clamp(value: f64, min: f64, max: f64) -> f64 {
+    value.min(max).max(min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: (f64, f64) = (320.0, 480.0);
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    #[test]
+    fn test_menu_bar_on_top() {
+        // macOS: 25 pt menu bar, icon near the right edge
+        let work_area = rect(0.0, 25.0, 1440.0, 875.0);
+        assert_eq!(popup_position((1400.0, 10.0), SIZE, work_area), (1120.0, 33.0));
+    }
+
+    #[test]
+    fn test_taskbar_at_bottom() {
+        let work_area = rect(0.0, 0.0, 1920.0, 1040.0);
+        assert_eq!(popup_position((1800.0, 1060.0), SIZE, work_area), (1600.0, 552.0));
+    }
+
+    #[test]
+    fn test_taskbar_on_the_left_of_a_secondary_monitor() {
+        // Monitor left of the primary one, so coordinates are negative
+        let work_area = rect(-1860.0, 0.0, 1800.0, 1080.0);
+        assert_eq!(popup_position((-1900.0, 1000.0), SIZE, work_area), (-1852.0, 600.0));
+    }
+
+    #[test]
+    fn test_taskbar_on_the_right() {
+        let work_area = rect(0.0, 0.0, 1860.0, 1080.0);
+        assert_eq!(popup_position((1890.0, 100.0), SIZE, work_area), (1532.0, 0.0));
+    }
+
+    #[test]
+    fn test_click_inside_work_area() {
+        let work_area = rect(0.0, 0.0, 1920.0, 1080.0);
+        assert_eq!(popup_position((1900.0, 1070.0), SIZE, work_area), (1600.0, 582.0));
+        assert_eq!(popup_position((10.0, 5.0), SIZE, work_area), (0.0, 13.0));
+        // Larger than the area: pinned to its top-left
+        assert_eq!(popup_position((10.0, 5.0), (4000.0, 4000.0), work_area), (0.0, 0.0));
+    }
+
+    /// 4K primary at 200% with a 48 px taskbar, 1080p secondary at 100% to its right
+    fn mixed_dpi() -> [Screen; 2] {
+        [
+            Screen { bounds: rect(0.0, 0.0, 3840.0, 2160.0), work_area: rect(0.0, 0.0, 3840.0, 2064.0), scale_factor: 2.0 },
+            Screen { bounds: rect(3840.0, 0.0, 1920.0, 1080.0), work_area: rect(3840.0, 0.0, 1920.0, 1032.0), scale_factor: 1.0 },
+        ]
+    }
+
+    #[test]
+    fn test_place_on_mixed_dpi_monitors() {
+        let screens = mixed_dpi();
+        // Same logical gap above the taskbar on both monitors: 8 px at 200% is 16 physical
+        assert_eq!(place((3700.0, 2130.0), SIZE, &screens), Some((3200, 2064 - 960 - 16)));
+        assert_eq!(place((3840.0 + 1800.0, 1060.0), SIZE, &screens), Some((3840 + 1600, 1032 - 480 - 8)));
+    }
+
+    #[test]
+    fn test_click_reported_off_screen() {
+        let screens = mixed_dpi();
+        // A pixel past the right edge of the secondary belongs to it, not to the primary
+        assert_eq!(screen_for((5761.0, 1060.0), &screens), Some(&screens[1]));
+        assert_eq!(screen_for((-1.0, 2150.0), &screens), Some(&screens[0]));
+        assert_eq!(place((0.0, 0.0), SIZE, &[]), None);
+    }
+}
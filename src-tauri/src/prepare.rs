@@ -0,0 +1,242 @@
+//! Text made fit to read aloud: markdown marks and URLs taken out, long text cut with a spoken
+//! notice, and everything split into sentence-sized chunks so the queue speaks (and stops) a
+//! long message a piece at a time instead of handing the speech command one huge argument.
+
+use crate::i18n::{self, Phrase};
+
+/// Longest chunk handed to the speech command at once; a sentence longer than this is split
+/// between words
+pub const CHUNK_CHARS: usize = 400;
+
+/// Markdown marks and URLs out: headings, list bullets, quotes and table rows become plain
+/// sentences, links keep their text, bare URLs become "link" and code blocks are left out
+pub fn clean(text: &str) -> String {
+    let mut sentences: Vec<String> = Vec::new();
+    let mut in_code = false;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with("```") || line.starts_with("~~~") {
+            if !in_code {
+                sentences.push(i18n::phrase(Phrase::CodeLeftOut));
+            }
+            in_code = !in_code;
+            continue;
+        }
+        if in_code || line.is_empty() || is_rule(line) {
+            continue;
+        }
+        let (body, block) = block_body(line);
+        let body = if block && body.starts_with('|') {
+            // A table row: its cells in a list; the |---|---| row under the header is a rule
+            body.split('|').map(str::trim).filter(|cell| !cell.is_empty()).collect::<Vec<_>>().join(", ")
+        } else {
+            body.to_string()
+        };
+        let mut cleaned = clean_inline(&body).split_whitespace().collect::<Vec<_>>().join(" ");
+        if cleaned.is_empty() {
+            continue;
+        }
+        // Headings and list items end without punctuation; a full stop gives the pause they need
+        if block && !cleaned.ends_with(['.', '!', '?', ':', ';', ',']) {
+            cleaned.push('.');
+        }
+        sentences.push(cleaned);
+    }
+    sentences.join(" ")
+}
+
+/// `---`, `***` or `___` on a line of its own, or a table's `|---|:--:|` line
+fn is_rule(line: &str) -> bool {
+    let marks = line.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+    (marks.len() >= 3 && ['-', '*', '_'].iter().any(|&mark| marks.chars().all(|c| c == mark)))
+        || (marks.starts_with('|') && marks.chars().all(|c| matches!(c, '|' | '-' | ':')))
+}
+
+/// A line without its block mark (`#`, `>`, `-`, `*`, `+`, `1.`), and whether it had one
+fn block_body(line: &str) -> (&str, bool) {
+    let quote = line.trim_start_matches('>').trim_start();
+    let heading = quote.trim_start_matches('#');
+    if heading.len() < quote.len() && (heading.is_empty() || heading.starts_with(' ')) {
+        return (heading.trim(), true);
+    }
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(item) = quote.strip_prefix(bullet) {
+            return (item.trim_start_matches("[ ] ").trim_start_matches("[x] "), true);
+        }
+    }
+    let digits = quote.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 {
+        if let Some(item) = quote[digits..].strip_prefix(". ").or_else(|| quote[digits..].strip_prefix(") ")) {
+            return (item, true);
+        }
+    }
+    (quote, quote.len() < line.len() || line.starts_with('|'))
+}
+
+fn clean_inline(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        if let Some((label, after)) = link(rest) {
+            out.push_str(&clean_inline(label));
+            rest = after;
+            continue;
+        }
+        if out.chars().last().is_none_or(|last| !last.is_alphanumeric()) {
+            if let Some(len) = url_len(rest) {
+                out.push_str(&i18n::phrase(Phrase::Link));
+                rest = &rest[len..];
+                continue;
+            }
+        }
+        // Emphasis, strikethrough and code marks; a lone _ is left for snake_case names
+        if rest.starts_with("__") {
+            rest = &rest[2..];
+            continue;
+        }
+        if !matches!(c, '*' | '`' | '~') {
+            out.push(c);
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+/// `[text](url)` or `![alt](url)` at the start of `text`: the text, and what follows the link
+fn link(text: &str) -> Option<(&str, &str)> {
+    let inner = text.strip_prefix("![").or_else(|| text.strip_prefix('['))?;
+    let close = inner.find(']')?;
+    let target = inner[close + 1..].strip_prefix('(')?;
+    let end = target.find(')')?;
+    Some((&inner[..close], &target[end + 1..]))
+}
+
+/// Length of the URL `text` starts with, leaving out punctuation that ends the sentence
+fn url_len(text: &str) -> Option<usize> {
+    let lower = text.get(..8).unwrap_or(text).to_ascii_lowercase();
+    if !["http://", "https://", "www."].iter().any(|scheme| lower.starts_with(scheme)) {
+        return None;
+    }
+    let end = text.find(|c: char| c.is_whitespace() || matches!(c, ')' | ']' | '>' | '"' | '\'')).unwrap_or(text.len());
+    Some(text[..end].trim_end_matches(['.', ',', ';', ':', '!', '?']).len())
+}
+
+/// `text` cut to at most `max_chars` at a sentence end (or else a word) with a notice of how
+/// much was left out; unchanged if it fits
+pub fn cap(text: &str, max_chars: usize) -> String {
+    let total = text.chars().count();
+    if total <= max_chars {
+        return text.to_string();
+    }
+    let cut = text.char_indices().nth(max_chars).map(|(i, _)| i).unwrap_or(text.len());
+    let head = &text[..cut];
+    let end = head.rfind(['.', '!', '?']).filter(|&i| i >= cut / 2).map(|i| i + 1)
+        .or_else(|| head.rfind(char::is_whitespace))
+        .unwrap_or(cut);
+    let head = head[..end].trim_end();
+    let notice = i18n::phrase(Phrase::Truncated).replace("{count}", &(total - head.chars().count()).to_string());
+    format!("{} … {}", head, notice)
+}
+
+/// `text` in chunks of whole sentences, each at most `max_chars` unless a single word is longer
+pub fn chunks(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for piece in sentences(text).into_iter().flat_map(|sentence| split_words(sentence, max_chars)) {
+        if !current.is_empty() && current.chars().count() + 1 + piece.chars().count() > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(piece);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Sentences end at `.`, `!`, `?` or `…` (and any closing quote or bracket) before a space
+fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut at_end = false;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() && at_end {
+            sentences.push(text[start..i].trim());
+            start = i;
+        }
+        at_end = matches!(c, '.' | '!' | '?' | '…') || (at_end && matches!(c, '"' | '\'' | ')' | '”' | '’'));
+    }
+    sentences.push(text[start..].trim());
+    sentences.retain(|s| !s.is_empty());
+    sentences
+}
+
+/// `sentence` in pieces of at most `max_chars`, broken between words where there are any
+fn split_words(sentence: &str, max_chars: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = sentence;
+    while rest.chars().count() > max_chars {
+        let cut = rest.char_indices().nth(max_chars).map(|(i, _)| i).unwrap_or(rest.len());
+        let at = rest[..cut].rfind(char::is_whitespace).filter(|&i| i > 0).unwrap_or(cut);
+        pieces.push(rest[..at].trim_end());
+        rest = rest[at..].trim_start();
+    }
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_markdown() {
+        let text = "# Release notes\n\
+            \n\
+            See [the docs](https://example.com/docs) or https://example.com/x.\n\
+            - **Faster** startup\n\
+            - Fixed `parse_args` for ~~old~~ new flags\n\
+            \n\
+            ```rust\nfn main() {}\n```\n\
+            > Quoted _text_ and snake_case_name\n\
+            ---\n\
+            | Name | Value |\n|---|:--:|\n| rate | 220 |\n\
+            1. ![logo](logo.png) done";
+        assert_eq!(
+            clean(text),
+            "Release notes. See the docs or link. Faster startup. Fixed parse_args for old new flags. \
+             Code block left out. Quoted _text_ and snake_case_name. Name, Value. rate, 220. logo done."
+        );
+        assert_eq!(clean("Build passed"), "Build passed");
+        assert_eq!(clean("  \n```\ncode only\n```"), "Code block left out.");
+        assert_eq!(clean(""), "");
+    }
+
+    #[test]
+    fn test_cap_at_sentence() {
+        assert_eq!(cap("Short.", 10), "Short.");
+        let text = "First sentence here. Second one runs on and on.";
+        assert_eq!(cap(text, 30), "First sentence here. … Text truncated, 27 more characters not read.");
+        // No sentence end in reach: cut between words
+        assert_eq!(cap("one two three four", 10), "one two … Text truncated, 11 more characters not read.");
+    }
+
+    #[test]
+    fn test_chunks_group_sentences() {
+        let text = "One. Two is here! Three? \"Four.\" Five";
+        assert_eq!(chunks(text, 20), vec!["One. Two is here!", "Three? \"Four.\" Five"]);
+        assert_eq!(chunks("Build passed", CHUNK_CHARS), vec!["Build passed"]);
+        assert!(chunks("   ", CHUNK_CHARS).is_empty());
+
+        let long = "word ".repeat(300);
+        let pieces = chunks(&long, CHUNK_CHARS);
+        assert!(pieces.iter().all(|p| p.chars().count() <= CHUNK_CHARS));
+        assert_eq!(pieces.join(" "), long.trim());
+        assert_eq!(chunks("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+    }
+}
@@ -0,0 +1,241 @@
+/// Relaying to another Voice Tray, for a machine that cannot speak right now: with relay.enabled,
+/// while this one is muted, snoozed, in quiet hours or a muting Focus, or has no speech command,
+/// queued entries are posted to the peer's POST /speak instead of waiting or being dropped. An
+/// entry the peer took is marked "relayed" with the id the peer gave it; one it did not take is
+/// "suppressed", with the error as its detail. Requests carry X-Relayed-From, and an entry that
+/// came in with it is never relayed again, so two instances pointed at each other cannot pass a
+/// message back and forth.
+use chrono::{Local, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Url;
+use tracing::{info, warn};
+
+use crate::client;
+use crate::config::{load_config, RelayConfig};
+use crate::focus;
+use crate::notifications::{notify_suppressed, Suppression};
+use crate::state::{AppState, SpeakResponse, VoiceEntry};
+use crate::stats;
+
+/// Names the instance a request was relayed from
+pub const RELAYED_FROM: &str = "X-Relayed-From";
+
+/// How often the queue is checked for entries to relay, as often as the queue itself
+const POLL: Duration = Duration::from_millis(100);
+
+/// Longest the peer may take to answer, connecting included
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Why this machine cannot speak now
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reason {
+    Suppressed(Suppression),
+    /// The speech command could not be started at launch
+    NoSpeech,
+}
+
+fn reason(state: &AppState) -> Option<Reason> {
+    let quiet = || load_config().watcher.quiet_hours.is_some_and(|q| q.contains(Local::now().time()));
+    if state.is_muted() {
+        Some(Reason::Suppressed(Suppression::Muted))
+    } else if state.snooze_minutes_left(Utc::now()).is_some() {
+        Some(Reason::Suppressed(Suppression::Snoozed))
+    } else if quiet() {
+        Some(Reason::Suppressed(Suppression::QuietHours))
+    } else if focus::muting(state) {
+        Some(Reason::Suppressed(Suppression::Focus))
+    } else if !*state.tts_available.lock() {
+        Some(Reason::NoSpeech)
+    } else {
+        None
+    }
+}
+
+/// Whether queued entries go to the peer now instead of the speech queue
+pub fn takes_over(state: &AppState) -> bool {
+    load_config().relay.enabled && reason(state).is_some()
+}
+
+/// What this instance calls itself in X-Relayed-From: the host name
+fn instance_name() -> String {
+    let name: String = gethostname::gethostname().to_string_lossy().chars().filter(|c| c.is_ascii_graphic()).collect();
+    if name.is_empty() { "voice-tray".to_string() } else { name }
+}
+
+/// `<url>/speak`
+fn speak_url(base: &str) -> Result<Url, String> {
+    let mut url = Url::parse(base.trim()).map_err(|e| format!("invalid peer URL {}: {}", base, e))?;
+    let path = format!("{}/speak", url.path().trim_end_matches('/'));
+    url.set_path(&path);
+    url.set_query(None);
+    Ok(url)
+}
+
+/// Post `entry` to the peer and return the id it was queued under there. The voice is only
+/// sent when it is not this machine's default, so the peer speaks with its own default.
+async fn forward(config: &RelayConfig, from: &str, entry: &VoiceEntry, default_voice: &str) -> Result<u64, String> {
+    let url = speak_url(&config.url)?;
+    let body = serde_json::json!({
+        "text": entry.text,
+        "voice": (entry.voice != default_voice).then_some(&entry.voice),
+        "rate": entry.rate,
+        "agent": entry.agent,
+    });
+    let mut request = client::client()?.post(url).timeout(RESPONSE_TIMEOUT).header(RELAYED_FROM, from).json(&body);
+    if let Some(token) = config.token.as_deref().filter(|t| !t.is_empty()) {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send().await.map_err(|e| client::describe(&e))?;
+    if !response.status().is_success() {
+        return Err(format!("the server answered {}", response.status()));
+    }
+    let answer = response.text().await.map_err(|e| format!("reading the response failed: {}", client::describe(&e)))?;
+    serde_json::from_str::<SpeakResponse>(&answer).map(|r| r.id).map_err(|_| format!("the peer answered \"{}\", not a queued message", answer.trim()))
+}
+
+/// Relay one entry taken from the queue and return it finished. After a failure the rest of the
+/// pass fails the same way without trying again.
+async fn relay(config: &RelayConfig, from: &str, default_voice: &str, mut entry: VoiceEntry, failed: &mut Option<String>) -> VoiceEntry {
+    let peer = speak_url(&config.url).ok().and_then(|url| url.host_str().map(str::to_string)).unwrap_or_else(|| config.url.clone());
+    let forwarded = match (&entry.relayed_from, failed.as_ref()) {
+        (Some(origin), _) => Err(format!("Not relayed: it was relayed here from {}", origin)),
+        (None, Some(error)) => Err(error.clone()),
+        (None, None) => forward(config, from, &entry, default_voice).await.map_err(|e| {
+            let error = format!("Relay to {} failed: {}", peer, e);
+            warn!("{}", error);
+            *failed = Some(error.clone());
+            error
+        }),
+    };
+    match forwarded {
+        Ok(remote) => {
+            info!("Relayed entry {} to {} as #{}", entry.id, peer, remote);
+            entry.status = "relayed".to_string();
+            entry.detail = Some(format!("Relayed to {} as #{}", peer, remote));
+        }
+        Err(error) => {
+            entry.status = "suppressed".to_string();
+            entry.detail = Some(error);
+        }
+    }
+    entry
+}
+
+/// Relay queued entries while this machine cannot speak; runs as the "relay" task. A paused
+/// queue is left alone, as the speech queue leaves it.
+pub async fn start(state: Arc<AppState>) {
+    let from = instance_name();
+    loop {
+        tokio::time::sleep(POLL).await;
+        let mut failed = None;
+        while !state.is_queue_paused() && !state.is_shutting_down() {
+            let config = load_config().relay;
+            let Some(reason) = reason(&state).filter(|_| config.enabled) else {
+                break;
+            };
+            let Some(entry) = state.start_relay() else {
+                break;
+            };
+            let default_voice = state.voice_config().voice();
+            let entry = relay(&config, &from, &default_voice, entry, &mut failed).await;
+            if entry.status == "suppressed" {
+                stats::record(&state, entry.agent.as_deref(), |c| c.suppressed += 1);
+                if let Reason::Suppressed(reason) = reason {
+                    notify_suppressed(&state, reason, entry.agent.as_deref(), &entry.text);
+                }
+            }
+            state.finish_entry(&entry);
+            state.notify_changed();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::SpeakRequest;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_speak_url() {
+        assert_eq!(speak_url("http://laptop.local:37779/").expect("url").as_str(), "http://laptop.local:37779/speak");
+        assert_eq!(speak_url(" https://tray.example.com/voice?x=1 ").expect("url").as_str(), "https://tray.example.com/voice/speak");
+        assert!(speak_url("laptop").is_err());
+    }
+
+    /// A peer that answers one request with `status` and `body` and hands back the request it read
+    async fn peer(status: &'static str, body: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let url = format!("http://{}", listener.local_addr().expect("addr"));
+        let served = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.expect("accept");
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            while !head.ends_with("\r\n\r\n") {
+                reader.read_line(&mut head).await.expect("read");
+            }
+            let length: usize = head.lines()
+                .find_map(|line| line.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("content-length")))
+                .and_then(|(_, n)| n.trim().parse().ok())
+                .unwrap_or(0);
+            let mut sent = vec![0; length];
+            reader.read_exact(&mut sent).await.expect("read body");
+            let response = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}", status, body.len(), body);
+            reader.get_mut().write_all(response.as_bytes()).await.expect("write");
+            head + &String::from_utf8_lossy(&sent)
+        });
+        (url, served)
+    }
+
+    #[test]
+    fn test_relay_and_loop_guard() {
+        let state = AppState::default();
+        let default_voice = state.voice_config().voice();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("runtime");
+        runtime.block_on(async {
+            let request = |text: &str| SpeakRequest { text: text.to_string(), voice: None, rate: Some(200), agent: Some("ci".to_string()) };
+            state.speak(request("Build passed"));
+            state.speak_from(request("From the laptop"), Some("laptop".to_string()));
+
+            let (url, served) = peer("200 OK", r#"{"id":42,"status":"queued"}"#).await;
+            let config = RelayConfig { enabled: true, url, token: Some("tk".to_string()) };
+            let mut failed = None;
+            let entry = relay(&config, "desktop", &default_voice, state.start_relay().expect("queued"), &mut failed).await;
+            assert_eq!((entry.status.as_str(), entry.detail.as_deref()), ("relayed", Some("Relayed to 127.0.0.1 as #42")));
+            let sent = served.await.expect("served");
+            let sent_head = sent.to_ascii_lowercase();
+            assert!(sent_head.starts_with("post /speak http/1.1\r\n"), "{}", sent);
+            assert!(sent_head.contains("\r\nx-relayed-from: desktop\r\n") && sent_head.contains("\r\nauthorization: bearer tk\r\n"));
+            let body: serde_json::Value = serde_json::from_str(sent.split("\r\n\r\n").nth(1).expect("body")).expect("json");
+            assert_eq!(body, serde_json::json!({"text": "Build passed", "voice": null, "rate": 200, "agent": "ci"}));
+
+            // What a peer relayed here does not go out again
+            let entry = relay(&config, "desktop", &default_voice, state.start_relay().expect("queued"), &mut failed).await;
+            assert_eq!((entry.status.as_str(), entry.detail.as_deref()), ("suppressed", Some("Not relayed: it was relayed here from laptop")));
+        });
+    }
+
+    #[test]
+    fn test_failure_suppresses_the_pass() {
+        let state = AppState::default();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("runtime");
+        runtime.block_on(async {
+            for text in ["One", "Two"] {
+                state.speak(SpeakRequest { text: text.to_string(), voice: None, rate: None, agent: None });
+            }
+            let (url, served) = peer("500 Internal Server Error", "").await;
+            let config = RelayConfig { enabled: true, url, token: None };
+            let mut failed = None;
+            let first = relay(&config, "desktop", "Samantha", state.start_relay().expect("queued"), &mut failed).await;
+            served.await.expect("served");
+            // The peer is not asked again; it has stopped listening
+            let second = relay(&config, "desktop", "Samantha", state.start_relay().expect("queued"), &mut failed).await;
+            for entry in [first, second] {
+                assert_eq!(entry.status, "suppressed");
+                assert!(entry.detail.as_deref().is_some_and(|d| d.starts_with("Relay to 127.0.0.1 failed: the server answered 500 Internal Server Error")), "{:?}", entry.detail);
+            }
+        });
+    }
+}
@@ -0,0 +1,225 @@
+/// Hot reload of config.json and the active profile: edits made outside the settings window are
+/// parsed, validated and applied section by section. An edit that does not parse or validate is
+/// reported and the running config stays. Profile switches go through the same path, and saves
+/// from the settings window or PUT /config apply their sections here too.
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use tracing::{info, warn};
+
+use crate::config::{
+    self, changed_sections, config_dir, get_config_path, reload_config, AppConfig, Diagnostic, SaveOutcome,
+    CONFIG_FILE,
+};
+use crate::events;
+use crate::http;
+use crate::logging;
+use crate::mqtt;
+use crate::ntfy;
+use crate::pipe;
+use crate::notifications::notify;
+use crate::state::AppState;
+use crate::tray;
+use crate::watcher;
+
+/// Quiet time after the last write before the file is read; editors often save twice
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watch the config directory and its profiles folder (editors and our own saves replace the
+/// file, so watching the file itself would lose it after the first save)
+pub fn start_config_watcher(state: Arc<AppState>) {
+    let dir = config_dir();
+    let main_file = dir.join(CONFIG_FILE);
+    std::thread::spawn(move || {
+        let (tx, rx) = mpsc::channel();
+        let mut fs_watcher = match notify::recommended_watcher(tx) {
+            Ok(w) => w,
+            Err(e) => {
+                warn!("Failed to create watcher, edits need a restart: {}", e);
+                return;
+            }
+        };
+        let watched = std::fs::create_dir_all(&dir)
+            .map_err(|e| e.to_string())
+            .and_then(|_| fs_watcher.watch(&dir, RecursiveMode::Recursive).map_err(|e| e.to_string()));
+        if let Err(e) = watched {
+            warn!("Failed to watch {}, edits need a restart: {}", dir.display(), e);
+            return;
+        }
+        info!("Watching {}", dir.display());
+
+        // config.json holds the active_profile pointer, so it counts whichever profile is active
+        let touches_config = |event: &notify::Result<notify::Event>| {
+            event.as_ref().is_ok_and(|event| {
+                let active = get_config_path();
+                matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                    && event.paths.iter().any(|p| *p == main_file || *p == active)
+            })
+        };
+        while let Ok(event) = rx.recv() {
+            if !touches_config(&event) {
+                continue;
+            }
+            // Wait for the writes to settle
+            loop {
+                match rx.recv_timeout(DEBOUNCE) {
+                    Ok(_) => continue,
+                    Err(mpsc::RecvTimeoutError::Timeout) => break,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            apply_reload(&state);
+        }
+    });
+}
+
+/// Read config.json again and apply the sections that changed
+fn apply_reload(state: &Arc<AppState>) {
+    let profile = config::active_profile();
+    let (previous, config) = match reload_config() {
+        Ok(Some(change)) => change,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Edit of {} rejected, keeping the previous settings: {}", get_config_path().display(), e);
+            notify(state, "config.json not applied", &e);
+            return;
+        }
+    };
+    if config::active_profile() != profile {
+        apply_sections(state, &previous, &config, &config::SECTIONS);
+        return;
+    }
+    let sections = changed_sections(&previous, &config);
+    info!("Reloaded {}", sections.join(", "));
+    apply_sections(state, &previous, &config, &sections);
+}
+
+/// Apply each of `sections` that differs, or may differ, between `previous` and `config`, then
+/// let the tray's Profile submenu and an open settings window catch up
+fn apply_sections(state: &Arc<AppState>, previous: &AppConfig, config: &AppConfig, sections: &[&str]) {
+    for section in sections.iter().copied() {
+        match section {
+            "mqtt" => mqtt::reconnect(state),
+            "http" if config.http != previous.http => http::rebind(state),
+            "voice" => tray::use_voice_config(state, config.voice.clone()),
+            "watcher" => watcher::use_config(state, &previous.watcher, &config.watcher),
+            "ui" => tray::use_tray_config(state, config.ui.clone()),
+            "ntfy" => ntfy::reconnect(state),
+            "pipe" => pipe::reopen(state),
+            "log" => logging::set_level(&config.log.level),
+            _ => {}
+        }
+    }
+    tray::rebuild_profile_menu(state);
+    events::emit(state, events::CONFIG_CHANGED, config::active_profile());
+}
+
+/// Switch to profile `name` and apply all of its settings. MQTT always reconnects, since the
+/// profiles' passwords live in separate keychain entries.
+pub fn switch_profile(state: &Arc<AppState>, name: &str) -> Result<(), String> {
+    let (previous, config) = config::switch_profile(name)?;
+    apply_sections(state, &previous, &config, &config::SECTIONS);
+    Ok(())
+}
+
+/// Delete profile `name`; when it was active, the default profile's settings are applied
+pub fn delete_profile(state: &Arc<AppState>, name: &str) -> Result<(), String> {
+    match config::delete_profile(name)? {
+        Some((previous, config)) => apply_sections(state, &previous, &config, &config::SECTIONS),
+        None => tray::rebuild_profile_menu(state),
+    }
+    Ok(())
+}
+
+/// Validate and save settings from the settings window or PUT /config. Only changed sections
+/// are written: MQTT and ntfy reconnect, the HTTP server moves to a new port, the watcher reloads its
+/// section and ui and voice settings apply at once. Errors block the save and come back with
+/// the warnings; on success the warnings come back in the outcome.
+pub fn save_settings(state: &Arc<AppState>, mut settings: AppConfig) -> Result<SaveOutcome, Vec<Diagnostic>> {
+    let diagnostics = settings.validate();
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        return Err(diagnostics);
+    }
+    settings.restore_password(&config::load_config().mqtt);
+    settings.ntfy.restore_tokens(&config::load_config().ntfy);
+    settings.relay.restore_token(&config::load_config().relay);
+    settings.github.restore_secret(&config::load_config().github);
+    settings.mirror.restore_urls(&config::load_config().mirror);
+    let failed = |message: String| {
+        let mut diagnostics = diagnostics.clone();
+        diagnostics.insert(0, Diagnostic::error("", message));
+        diagnostics
+    };
+    let profiles_before = serde_json::to_value(config::load_watcher_config().profiles).ok();
+
+    let mut restarted = Vec::new();
+    if mqtt::apply_config(state, &settings.mqtt).map_err(failed)? {
+        restarted.push("mqtt".to_string());
+    }
+    if watcher::apply_config(state, &settings.watcher).map_err(failed)? {
+        restarted.push("watcher".to_string());
+    }
+    let ui_changed = settings.ui != state.tray_config();
+    if ui_changed {
+        tray::apply_tray_config(state, settings.ui).map_err(failed)?;
+    }
+    let voice_changed = settings.voice != state.voice_config();
+    if voice_changed {
+        tray::apply_voice_config(state, settings.voice).map_err(failed)?;
+    }
+    let log_changed = settings.log != config::load_config().log;
+    if log_changed {
+        config::update_config(|c| c.log = settings.log.clone()).map_err(failed)?;
+        logging::set_level(&settings.log.level);
+    }
+    if settings.http != config::load_config().http {
+        config::update_config(|c| c.http = settings.http.clone()).map_err(failed)?;
+        http::rebind(state);
+        restarted.push("http".to_string());
+    }
+    if settings.ntfy != config::load_config().ntfy {
+        config::update_config(|c| c.ntfy = settings.ntfy.clone()).map_err(failed)?;
+        ntfy::reconnect(state);
+        restarted.push("ntfy".to_string());
+    }
+    if settings.pipe != config::load_config().pipe {
+        config::update_config(|c| c.pipe = settings.pipe.clone()).map_err(failed)?;
+        pipe::reopen(state);
+        restarted.push("pipe".to_string());
+    }
+    let files_changed = settings.files != config::load_config().files;
+    if files_changed {
+        config::update_config(|c| c.files = settings.files.clone()).map_err(failed)?;
+    }
+    let transcripts_changed = settings.transcripts != config::load_config().transcripts;
+    if transcripts_changed {
+        config::update_config(|c| c.transcripts = settings.transcripts.clone()).map_err(failed)?;
+    }
+    let relay_changed = settings.relay != config::load_config().relay;
+    if relay_changed {
+        config::update_config(|c| c.relay = settings.relay.clone()).map_err(failed)?;
+    }
+    let github_changed = settings.github != config::load_config().github;
+    if github_changed {
+        config::update_config(|c| c.github = settings.github.clone()).map_err(failed)?;
+    }
+    let mirror_changed = settings.mirror != config::load_config().mirror;
+    if mirror_changed {
+        config::update_config(|c| c.mirror = settings.mirror.clone()).map_err(failed)?;
+    }
+
+    let mut message = if restarted.is_empty() && !ui_changed && !voice_changed && !log_changed && !files_changed && !transcripts_changed && !relay_changed && !github_changed && !mirror_changed {
+        "No changes.".to_string()
+    } else {
+        "Settings saved.".to_string()
+    };
+    if restarted.iter().any(|s| s == "mqtt") {
+        message.push_str(" Reconnecting...");
+    }
+    if serde_json::to_value(&settings.watcher.profiles).ok() != profiles_before {
+        message.push_str(" Restart to apply watcher profiles.");
+    }
+    Ok(SaveOutcome { message, restarted, warnings: diagnostics })
+}
@@ -0,0 +1,110 @@
+/// Orderly quit, for Quit in the tray menu, quit_app and every other exit request: speech stops,
+/// the MQTT client publishes its retained offline status and disconnects, the HTTP server
+/// finishes the requests in flight, and only then does the app exit. A component that hangs
+/// cannot hold up quitting for longer than SHUTDOWN_TIMEOUT.
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tracing::{info, warn};
+
+use crate::agents;
+use crate::mdns;
+use crate::pipe;
+use crate::state::AppState;
+use crate::tasks;
+use crate::transcript;
+
+/// Longest the whole sequence may take before the app exits anyway
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest one step may take, so a hung step leaves time for the others
+const STEP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where the app is in quitting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Phase {
+    #[default]
+    Running,
+    /// The sequence is running; the queue starts nothing new and the services wind down
+    Stopping,
+    /// Done (or timed out); the next exit request goes through
+    Finished,
+}
+
+fn set_phase(state: &AppState, phase: Phase) {
+    *state.shutdown.lock() = phase;
+}
+
+/// Whether an exit request may go through now. Otherwise the caller prevents the exit and the
+/// sequence, started here on first call, exits the app when it is done.
+pub fn exit_allowed(app: &AppHandle) -> bool {
+    let state = app.state::<Arc<AppState>>().inner().clone();
+    match state.shutdown_phase() {
+        Phase::Finished => return true,
+        Phase::Stopping => return false,
+        Phase::Running => set_phase(&state, Phase::Stopping),
+    }
+    info!("Shutting down");
+    let (done_tx, done_rx) = mpsc::channel();
+    let worker = state.clone();
+    std::thread::spawn(move || {
+        run(&worker);
+        // The waiter stops listening once it has given up on the sequence
+        let _ = done_tx.send(());
+    });
+    let app = app.clone();
+    std::thread::spawn(move || {
+        if done_rx.recv_timeout(SHUTDOWN_TIMEOUT).is_err() {
+            warn!("Shutdown took over {}s, quitting anyway", SHUTDOWN_TIMEOUT.as_secs());
+        }
+        set_phase(&state, Phase::Finished);
+        app.exit(0);
+    });
+    false
+}
+
+/// Poll `done` until it holds or `timeout` passes; false on timeout
+fn wait_for(timeout: Duration, done: impl Fn() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    while !done() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    true
+}
+
+fn run(state: &Arc<AppState>) {
+    // Speech: the queue starts nothing new once stopping, so only the current entry is left
+    let cancelled = state.cancel_queued("Not spoken: the app quit");
+    state.stop_speaking();
+    if !wait_for(STEP_TIMEOUT, || !state.is_speaking()) {
+        warn!("Speech did not stop");
+    }
+    info!("Speech stopped, {} queued messages cancelled", cancelled);
+
+    // The MQTT session notices the phase, publishes offline and disconnects
+    if !wait_for(STEP_TIMEOUT, || !tasks::is_running(state, "mqtt")) {
+        warn!("MQTT client did not disconnect");
+    }
+
+    // The HTTP server stops accepting and drains the requests in flight
+    state.http_rebind.notify_one();
+    if !wait_for(STEP_TIMEOUT, || !tasks::is_running(state, "http")) {
+        warn!("HTTP server did not drain");
+    }
+
+    // Other machines drop the mDNS advertisement instead of waiting for it to expire
+    if !wait_for(STEP_TIMEOUT, || !mdns::is_active(state)) {
+        warn!("mDNS advertisement was not withdrawn");
+    }
+
+    // What was spoken last is still waiting for the transcript's next write
+    transcript::flush(state);
+    agents::save(state);
+
+    // Without a reader, scripts writing to the voice pipe would wait forever
+    pipe::remove();
+    info!("Shutdown complete");
+}
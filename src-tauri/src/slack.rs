@@ -0,0 +1,123 @@
+/// Slack incoming-webhook payloads, for POST /slack: tools that can only post
+/// `{"text": "...", "blocks": [...]}` to a webhook URL get spoken like POST /speak. The text is
+/// the top-level `text`, or else the text of the section blocks, with Slack's `<url|label>`
+/// markup and escapes undone and mrkdwn marks taken out.
+use serde::Deserialize;
+
+use crate::prepare;
+
+/// Agent of the entries when the URL names none
+pub const DEFAULT_AGENT: &str = "slack";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Payload {
+    text: Option<String>,
+    blocks: Vec<Block>,
+}
+
+/// A layout block; only sections are read, other types carry no message text
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Block {
+    #[serde(rename = "type")]
+    kind: String,
+    text: Option<TextObject>,
+    fields: Vec<TextObject>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct TextObject {
+    text: String,
+}
+
+impl Payload {
+    /// What to speak: `text`, or the section blocks' text and fields when it is empty
+    pub fn speech(&self) -> Result<String, String> {
+        let raw = match self.text.as_deref().filter(|t| !t.trim().is_empty()) {
+            Some(text) => text.to_string(),
+            None => self.blocks.iter()
+                .filter(|block| block.kind == "section")
+                .flat_map(|block| block.text.iter().chain(&block.fields))
+                .map(|text| text.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        let text = prepare::clean(&unescape(&unlink(&raw)));
+        if text.is_empty() {
+            return Err("text or a section block with text is required".to_string());
+        }
+        Ok(text)
+    }
+}
+
+/// `<url|label>` becomes the label, `<url>` the URL (read as "link" later) and `<!here>` "@here"
+fn unlink(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let inner = &rest[start + 1..start + len];
+        match inner.split_once('|') {
+            Some((_, label)) => out.push_str(label),
+            None => match inner.strip_prefix('!') {
+                Some(special) => {
+                    out.push('@');
+                    out.push_str(special);
+                }
+                None => out.push_str(inner),
+            },
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Slack escapes these three in message text
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn speech(json: &str) -> Result<String, String> {
+        serde_json::from_str::<Payload>(json).expect("payload").speech()
+    }
+
+    #[test]
+    fn test_text_and_links() {
+        assert_eq!(
+            speech(r#"{"text": "*Deploy* of <https://ci.example.com/42|build 42> done &amp; live <!here>"}"#),
+            Ok("Deploy of build 42 done & live @here".to_string())
+        );
+        assert_eq!(speech(r#"{"text": "Logs at <https://logs.example.com/x>"}"#), Ok("Logs at link".to_string()));
+    }
+
+    #[test]
+    fn test_blocks_when_text_missing() {
+        let payload = r#"{
+            "text": " ",
+            "blocks": [
+                {"type": "header", "text": {"type": "plain_text", "text": "Ignored header"}},
+                {"type": "section", "text": {"type": "mrkdwn", "text": "Backup *failed* on <#C01|ops>."}},
+                {"type": "divider"},
+                {"type": "section", "fields": [{"type": "mrkdwn", "text": "Host: db1"}, {"type": "mrkdwn", "text": "Disk: 98%"}]}
+            ]
+        }"#;
+        assert_eq!(speech(payload), Ok("Backup failed on ops. Host: db1 Disk: 98%".to_string()));
+    }
+
+    #[test]
+    fn test_nothing_to_speak() {
+        let error = Err("text or a section block with text is required".to_string());
+        assert_eq!(speech("{}"), error);
+        assert_eq!(speech(r#"{"blocks": [{"type": "image", "image_url": "https://x/y.png"}]}"#), error);
+    }
+}
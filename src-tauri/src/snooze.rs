@@ -0,0 +1,146 @@
+/// Snooze: a mute that ends by itself. While snoozed, queued messages wait and watcher alerts
+/// become notifications, as with Mute; when it ends, the waiting messages are announced first.
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Utc};
+use tracing::info;
+
+use crate::config::load_watcher_config;
+use crate::i18n::{self, Phrase};
+use crate::notifications::notify;
+use crate::state::AppState;
+use crate::tray;
+
+/// How often the timer checks for the end of a snooze and refreshes the minutes left
+const TICK: Duration = Duration::from_secs(1);
+
+/// "Snooze ended" is spoken before the messages it announces
+const ENDED_PRIORITY: u8 = u8::MAX;
+
+/// Snooze for `minutes`, replacing any snooze in progress. Unmutes first: the snooze
+/// takes over from an indefinite mute.
+pub fn snooze(state: &Arc<AppState>, minutes: u32) -> Result<(), String> {
+    if state.is_muted() {
+        tray::set_muted(state, false)?;
+    }
+    let until = Utc::now() + chrono::Duration::minutes(minutes as i64);
+    *state.snoozed_until.lock() = Some(until);
+    info!("Snoozed for {} until {}", duration_label(minutes), until.with_timezone(&Local).format("%H:%M"));
+    state.notify_changed();
+    Ok(())
+}
+
+/// End a snooze early without the "snooze ended" announcement
+pub fn cancel(state: &AppState) {
+    let cancelled = state.snoozed_until.lock().take().is_some();
+    if cancelled {
+        info!("Cancelled");
+        state.notify_changed();
+    }
+}
+
+/// Clear the snooze if it ran out by `now`; true when it did
+fn take_expired(state: &AppState, now: DateTime<Utc>) -> bool {
+    let mut snoozed = state.snoozed_until.lock();
+    if snoozed.is_some_and(|until| until <= now) {
+        *snoozed = None;
+        return true;
+    }
+    false
+}
+
+/// "Snooze ended, 3 messages waiting"; None when nothing waited
+pub fn ended_phrase(waiting: usize) -> Option<String> {
+    match waiting {
+        0 => None,
+        1 => Some(i18n::phrase(Phrase::SnoozeEndedOne)),
+        n => Some(i18n::phrase(Phrase::SnoozeEnded).replace("{count}", &n.to_string())),
+    }
+}
+
+/// Announce the end of a snooze. Inside quiet hours, the longer suppression, it is only
+/// a notification.
+fn announce_end(state: &AppState) {
+    let waiting = state.timeline.lock().iter().filter(|e| e.status == "queued").count();
+    info!("Ended with {} waiting", waiting);
+    let Some(phrase) = ended_phrase(waiting) else {
+        return;
+    };
+    let quiet = load_watcher_config().quiet_hours.is_some_and(|q| q.contains(Local::now().time()));
+    if quiet {
+        notify(state, "Voice Tray", &phrase);
+        return;
+    }
+    let defaults = state.voice_config();
+    state.enqueue(phrase, defaults.voice(), defaults.default_rate, Some("snooze".to_string()), ENDED_PRIORITY, None);
+}
+
+/// Ends snoozes when they run out, and refreshes the tooltip as the minutes left go down
+pub fn start_timer(state: Arc<AppState>) {
+    std::thread::spawn(move || {
+        let mut shown_left = None;
+        loop {
+            std::thread::sleep(TICK);
+            let now = Utc::now();
+            if take_expired(&state, now) {
+                announce_end(&state);
+            }
+            let left = state.snooze_minutes_left(now);
+            if left != shown_left {
+                shown_left = left;
+                state.notify_changed();
+            }
+        }
+    });
+}
+
+/// Menu label for a snooze length: "15 minutes", "1 hour", "90 minutes"
+pub fn duration_label(minutes: u32) -> String {
+    match minutes {
+        1 => "1 minute".to_string(),
+        60 => "1 hour".to_string(),
+        m if m % 60 == 0 => format!("{} hours", m / 60),
+        m => format!("{} minutes", m),
+    }
+}
+
+/// Time left for tooltips and the menu: "25m", "1h 05m"
+pub fn left_label(minutes_left: i64) -> String {
+    if minutes_left < 60 {
+        format!("{}m", minutes_left)
+    } else {
+        format!("{}h {:02}m", minutes_left / 60, minutes_left % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snooze_expiry() {
+        let state = AppState::default();
+        let now = Utc::now();
+        assert!(!take_expired(&state, now));
+        *state.snoozed_until.lock() = Some(now + chrono::Duration::seconds(90));
+        assert_eq!(state.snooze_minutes_left(now), Some(2));
+        assert!(state.silenced());
+        assert!(!take_expired(&state, now));
+        assert!(take_expired(&state, now + chrono::Duration::seconds(90)));
+        assert_eq!(state.snooze_minutes_left(now), None);
+        assert!(!state.silenced());
+    }
+
+    #[test]
+    fn test_labels() {
+        assert_eq!(duration_label(15), "15 minutes");
+        assert_eq!(duration_label(60), "1 hour");
+        assert_eq!(duration_label(120), "2 hours");
+        assert_eq!(duration_label(90), "90 minutes");
+        assert_eq!(left_label(25), "25m");
+        assert_eq!(left_label(65), "1h 05m");
+        assert_eq!(ended_phrase(0), None);
+        assert_eq!(ended_phrase(3).as_deref(), Some("Snooze ended, 3 messages waiting"));
+    }
+}
@@ -0,0 +1,256 @@
+/// Text files read aloud, for POST /speak/file and `ovt say --file`. Only files under a folder in
+/// files.allowed_dirs are read (there are none by default), up to files.max_bytes, and only
+/// UTF-8 text, so a stray request cannot have the app read out whatever it can open. The text
+/// is cleaned like the clipboard's and queued as one entry, which the queue speaks a few
+/// sentences at a time, or with `split` as one entry per paragraph.
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::config::FilesConfig;
+use crate::prepare;
+use crate::state::{AppState, SpeakRequest, TEXT_MAX_CHARS};
+use crate::watcher::expand_home;
+
+/// Most entries one split file may queue; more would push unspoken ones out of the timeline
+pub const SPLIT_MAX_ENTRIES: usize = 25;
+
+#[derive(Debug, Deserialize)]
+pub struct FileRequest {
+    /// Absolute, or starting with `~`
+    pub path: String,
+    pub voice: Option<String>,
+    pub rate: Option<u32>,
+    pub agent: Option<String>,
+    /// One entry per paragraph instead of one for the whole file
+    #[serde(default)]
+    pub split: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileResponse {
+    /// The entries queued, in reading order
+    pub ids: Vec<u64>,
+    /// Characters to speak once cleaned
+    pub characters: usize,
+    pub status: String,
+}
+
+/// Why a file was not queued; each maps to an HTTP status
+#[derive(Debug, PartialEq)]
+pub enum Refusal {
+    /// Not under files.allowed_dirs (403)
+    NotAllowed(String),
+    NotFound(String),
+    /// Over files.max_bytes (413)
+    TooLarge(String),
+    /// Binary or not UTF-8 (415)
+    NotText(String),
+    Invalid(String),
+}
+
+impl Refusal {
+    pub fn message(&self) -> &str {
+        match self {
+            Self::NotAllowed(m) | Self::NotFound(m) | Self::TooLarge(m) | Self::NotText(m) | Self::Invalid(m) => m,
+        }
+    }
+}
+
+/// Queue the file `req` names
+pub fn speak(state: &AppState, config: &FilesConfig, req: FileRequest) -> Result<FileResponse, Refusal> {
+    let home = dirs::home_dir();
+    let path = resolve(config, &req.path, home.as_deref())?;
+    let text = read_text(&path, config.max_bytes)?;
+    let pieces = pieces(&text, req.split)?;
+    let requests: Vec<SpeakRequest> = pieces.into_iter()
+        .map(|text| SpeakRequest { text, voice: req.voice.clone(), rate: req.rate, agent: req.agent.clone() })
+        .collect();
+    for request in &requests {
+        request.validate().map_err(Refusal::Invalid)?;
+    }
+    let characters = requests.iter().map(|r| r.text.chars().count()).sum();
+    let ids = requests.into_iter().map(|request| state.speak(request).id).collect();
+    Ok(FileResponse { ids, characters, status: "queued".to_string() })
+}
+
+/// `path` with links and `..` resolved, if it is a file under one of the allowed folders
+fn resolve(config: &FilesConfig, path: &str, home: Option<&Path>) -> Result<PathBuf, Refusal> {
+    let expand = |path: &str| match home {
+        Some(home) => expand_home(path.trim(), home),
+        None => PathBuf::from(path.trim()),
+    };
+    let requested = expand(path);
+    if !requested.is_absolute() {
+        return Err(Refusal::Invalid("path must be absolute".to_string()));
+    }
+    let written: Vec<PathBuf> = config.allowed_dirs.iter().map(|dir| expand(dir)).filter(|dir| dir.is_absolute()).collect();
+    let resolved: Vec<PathBuf> = written.iter().filter_map(|dir| dir.canonicalize().ok()).collect();
+    let not_allowed = |path: &Path| Refusal::NotAllowed(format!("{} is not in a folder listed in files.allowed_dirs", path.display()));
+    let path = requested.canonicalize().map_err(|e| {
+        // Outside the folders a missing file is refused like any other, so requests cannot
+        // probe which files exist
+        if !written.iter().chain(&resolved).any(|dir| requested.starts_with(dir)) {
+            return not_allowed(&requested);
+        }
+        match e.kind() {
+            std::io::ErrorKind::NotFound => Refusal::NotFound(format!("{} does not exist", requested.display())),
+            _ => Refusal::Invalid(format!("{}: {}", requested.display(), e)),
+        }
+    })?;
+    // Resolved, so `..` and links cannot lead out of a folder
+    if !resolved.iter().any(|dir| path.starts_with(dir)) {
+        return Err(not_allowed(&path));
+    }
+    if !path.is_file() {
+        return Err(Refusal::Invalid(format!("{} is not a file", path.display())));
+    }
+    Ok(path)
+}
+
+/// The file's text, if it is UTF-8 text of at most `max_bytes`
+fn read_text(path: &Path, max_bytes: u64) -> Result<String, Refusal> {
+    let failed = |e: std::io::Error| Refusal::Invalid(format!("{}: {}", path.display(), e));
+    let file = std::fs::File::open(path).map_err(failed)?;
+    // Read one byte past the limit, in case the file grew since it was checked
+    let mut bytes = Vec::new();
+    file.take(max_bytes + 1).read_to_end(&mut bytes).map_err(failed)?;
+    if bytes.len() as u64 > max_bytes {
+        return Err(Refusal::TooLarge(format!("{} is over {} bytes (files.max_bytes)", path.display(), max_bytes)));
+    }
+    let not_text = || Refusal::NotText(format!("{} is not a UTF-8 text file", path.display()));
+    if bytes.contains(&0) {
+        return Err(not_text());
+    }
+    let text = String::from_utf8(bytes).map_err(|_| not_text())?;
+    Ok(text.strip_prefix('\u{feff}').map(str::to_string).unwrap_or(text))
+}
+
+/// The cleaned text to queue: all of it, or each paragraph with `split`
+fn pieces(text: &str, split: bool) -> Result<Vec<String>, Refusal> {
+    let pieces: Vec<String> = if split {
+        paragraphs(text).iter()
+            .map(|paragraph| prepare::clean(paragraph))
+            .flat_map(|paragraph| prepare::chunks(&paragraph, TEXT_MAX_CHARS))
+            .collect()
+    } else {
+        vec![prepare::clean(text)].into_iter().filter(|text| !text.is_empty()).collect()
+    };
+    if pieces.is_empty() {
+        return Err(Refusal::Invalid("the file has nothing to speak".to_string()));
+    }
+    if pieces.len() > SPLIT_MAX_ENTRIES {
+        return Err(Refusal::Invalid(format!("the file has {} paragraphs; split queues at most {}", pieces.len(), SPLIT_MAX_ENTRIES)));
+    }
+    let characters = pieces[0].chars().count();
+    if !split && characters > TEXT_MAX_CHARS {
+        return Err(Refusal::Invalid(format!("the file has {} characters to speak; one entry takes at most {}, so use split", characters, TEXT_MAX_CHARS)));
+    }
+    Ok(pieces)
+}
+
+/// The paragraphs of `text`: runs of lines between blank ones. A blank line inside a code
+/// block does not end one, so the block stays whole and prepare::clean leaves it out.
+fn paragraphs(text: &str) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut in_code = false;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+        }
+        if trimmed.is_empty() && !in_code {
+            if !current.is_empty() {
+                paragraphs.push(current.join("\n"));
+                current.clear();
+            }
+            continue;
+        }
+        current.push(line);
+    }
+    if !current.is_empty() {
+        paragraphs.push(current.join("\n"));
+    }
+    paragraphs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resolve_only_allowed_dirs() {
+        let home = TempDir::new().expect("create temp dir");
+        let notes = home.path().join("notes");
+        fs::create_dir_all(notes.join("sub")).expect("mkdir");
+        fs::write(notes.join("sub/today.txt"), "Hello").expect("write");
+        fs::write(home.path().join("secret.txt"), "key").expect("write");
+        let config = FilesConfig { allowed_dirs: vec!["~/notes".to_string()], ..FilesConfig::default() };
+        let resolve = |path: &str| resolve(&config, path, Some(home.path()));
+
+        assert_eq!(resolve("~/notes/sub/today.txt"), Ok(notes.join("sub/today.txt").canonicalize().expect("canonical")));
+        // .. cannot climb out of an allowed folder
+        assert!(matches!(resolve("~/notes/../secret.txt"), Err(Refusal::NotAllowed(_))));
+        assert!(matches!(resolve("~/notes/missing.txt"), Err(Refusal::NotFound(_))));
+        assert!(matches!(resolve("~/notes/sub"), Err(Refusal::Invalid(_))));
+        assert_eq!(resolve("notes/today.txt"), Err(Refusal::Invalid("path must be absolute".to_string())));
+        let nothing_allowed = FilesConfig::default();
+        assert!(matches!(super::resolve(&nothing_allowed, "~/notes/sub/today.txt", Some(home.path())), Err(Refusal::NotAllowed(_))));
+        assert!(matches!(resolve("~/missing.txt"), Err(Refusal::NotAllowed(_))));
+    }
+
+    #[test]
+    fn test_read_text_guards() {
+        let dir = TempDir::new().expect("create temp dir");
+        let write = |name: &str, bytes: &[u8]| {
+            let path = dir.path().join(name);
+            fs::write(&path, bytes).expect("write");
+            path
+        };
+        assert_eq!(read_text(&write("bom.txt", "\u{feff}Hello".as_bytes()), 100), Ok("Hello".to_string()));
+        assert!(matches!(read_text(&write("big.txt", &[b'a'; 101]), 100), Err(Refusal::TooLarge(_))));
+        assert!(matches!(read_text(&write("image.png", &[0x89, b'P', b'N', b'G', 0, 0]), 100), Err(Refusal::NotText(_))));
+        assert!(matches!(read_text(&write("latin1.txt", &[b'c', b'a', b'f', 0xE9]), 100), Err(Refusal::NotText(_))));
+    }
+
+    #[test]
+    fn test_pieces_and_split() {
+        let text = "# Notes\n\nFirst paragraph\nstill first.\n\n```\nlet x = 1;\n\nlet y = 2;\n```\n\n\nLast one.";
+        assert_eq!(pieces(text, false), Ok(vec!["Notes. First paragraph still first. Code block left out. Last one.".to_string()]));
+        assert_eq!(pieces(text, true), Ok(vec![
+            "Notes.".to_string(),
+            "First paragraph still first.".to_string(),
+            "Code block left out.".to_string(),
+            "Last one.".to_string(),
+        ]));
+        assert_eq!(pieces("  \n\n", false), Err(Refusal::Invalid("the file has nothing to speak".to_string())));
+
+        let long = "word ".repeat(TEXT_MAX_CHARS / 5 + 10);
+        assert!(matches!(pieces(&long, false), Err(Refusal::Invalid(e)) if e.contains("use split")));
+        assert_eq!(pieces(&long, true).map(|p| p.len()), Ok(2));
+        assert!(pieces(&"Line.\n\n".repeat(SPLIT_MAX_ENTRIES + 1), true).is_err());
+    }
+
+    #[test]
+    fn test_speak_queues_entries() {
+        let dir = TempDir::new().expect("create temp dir");
+        fs::write(dir.path().join("standup.md"), "Done: *deploy*.\n\nNext: tests.").expect("write");
+        let config = FilesConfig { allowed_dirs: vec![dir.path().display().to_string()], ..FilesConfig::default() };
+        let state = AppState::default();
+        let req = |split| FileRequest {
+            path: dir.path().join("standup.md").display().to_string(),
+            voice: Some("Daniel".to_string()),
+            rate: None,
+            agent: Some("notes".to_string()),
+            split,
+        };
+        let queued = speak(&state, &config, req(true)).expect("queued");
+        assert_eq!((queued.ids.len(), queued.characters), (2, 25));
+        let timeline = state.timeline.lock();
+        let entry = timeline.get(queued.ids[0]).expect("entry");
+        assert_eq!((entry.text.as_str(), entry.voice.as_str(), entry.agent.as_deref()), ("Done: deploy.", "Daniel", Some("notes")));
+    }
+}
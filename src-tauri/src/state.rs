@@ -1,11 +1,32 @@
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
-use std::sync::Mutex;
-use chrono::{DateTime, Utc};
-use tauri::{tray::TrayIcon, image::Image};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, PoisonError};
+use tokio::sync::broadcast;
+use chrono::{DateTime, Local, Utc};
+use crate::agents::Registry;
+use crate::bus::{self, TimelineEvent};
+use crate::config::{TrayConfig, VoiceConfig};
+use crate::doctor::Report;
+use crate::focus::FocusStatus;
+use crate::github::GithubStatus;
+use crate::mdns::MdnsStatus;
+use crate::mirror::MirrorStatus;
+use crate::ntfy::TopicStatus;
+use crate::http::VOICE_SERVER_PORT;
+use crate::lock::{self, Lock, Shared};
+use crate::notifications::{self, SuppressedRollup, Suppression};
+use crate::relay;
+use crate::shutdown::Phase;
+use crate::stats::{self, Stats};
+use crate::tasks::Task;
+use crate::theme::IconLook;
+use crate::timeline::Timeline;
+use crate::transcript::Pending;
+use tauri::{tray::TrayIcon, image::Image, menu::{CheckMenuItem, Menu, MenuItem, Submenu}, AppHandle, Wry};
 
 /// Voice entry for timeline
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct VoiceEntry {
     pub id: u64,
     pub timestamp: DateTime<Utc>,
@@ -13,9 +34,38 @@ pub struct VoiceEntry {
     pub voice: String,
     pub rate: u32,
     pub agent: Option<String>,
-    pub status: String, // "queued", "speaking", "done"
+    pub status: String, // "queued", "speaking", "done", "cancelled", "failed", "suppressed", "relaying", "relayed"
+    #[serde(default)]
+    pub priority: u8, // higher is spoken first; 0 = normal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>, // shown in the timeline, not spoken
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay_of: Option<u64>, // id of the entry this one speaks again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relayed_from: Option<String>, // the peer that relayed it here (see relay.rs); never relayed on
 }
 
+impl VoiceEntry {
+    /// Done, cancelled, failed, suppressed or relayed: nothing more will happen to it
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status.as_str(), "done" | "cancelled" | "failed" | "suppressed" | "relayed")
+    }
+
+    /// `format` with `{text}`, `{agent}` (empty if none) and `{time}` (local HH:MM:SS) filled in
+    pub fn clipboard_text(&self, format: &str) -> String {
+        let time = self.timestamp.with_timezone(&Local).format("%H:%M:%S").to_string();
+        format
+            .replace("{agent}", self.agent.as_deref().unwrap_or(""))
+            .replace("{time}", &time)
+            .replace("{text}", &self.text)
+    }
+}
+
+/// Longest text a request may ask to speak (about ten minutes); longer requests are refused
+pub const TEXT_MAX_CHARS: usize = 12_000;
+/// Longest agent name a request may carry; agents are listed and muted by name in the popup
+pub const AGENT_MAX_CHARS: usize = 64;
+
 /// Request to speak
 #[derive(Debug, Deserialize)]
 pub struct SpeakRequest {
@@ -25,38 +75,650 @@ pub struct SpeakRequest {
     pub rate: Option<u32>,
 }
 
+impl SpeakRequest {
+    /// Checks shared by HTTP, MQTT, links, the voice pipe and the popup's speak command
+    pub fn validate(&self) -> Result<(), String> {
+        if self.text.trim().is_empty() {
+            return Err("text must not be empty".to_string());
+        }
+        let chars = self.text.chars().count();
+        if chars > TEXT_MAX_CHARS {
+            return Err(format!("text must be at most {} characters, not {}", TEXT_MAX_CHARS, chars));
+        }
+        if let Some(agent) = &self.agent {
+            if agent.chars().any(char::is_control) {
+                return Err("agent must not contain control characters".to_string());
+            }
+            let chars = agent.chars().count();
+            if chars > AGENT_MAX_CHARS {
+                return Err(format!("agent must be at most {} characters, not {}", AGENT_MAX_CHARS, chars));
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Response from speak endpoint
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SpeakResponse {
     pub id: u64,
     pub status: String,
 }
 
+/// What delete_entry did, for the popup and DELETE /speak/:id
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeleteOutcome {
+    Removed,
+    Cancelled, // queued entry marked cancelled, or speaking entry stopped with force
+    NotFound,
+    Refused, // entry is speaking and force was not given
+}
+
+/// Which entries clear_timeline and DELETE /timeline remove
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClearScope {
+    /// Every finished entry (done, cancelled or failed)
+    #[default]
+    Done,
+    /// Everything: finished entries, the queue, and the entry speaking now, which is stopped
+    All,
+    /// Entries whose speech command failed
+    Failed,
+    /// Finished entries from one agent: `{"agent": "ci"}`
+    Agent(String),
+}
+
+/// What clear_timeline did, for "Cleared 27 entries"
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ClearOutcome {
+    pub removed: usize,   // finished entries taken out of the timeline
+    pub cancelled: usize, // queued entries dropped, plus the one stopped
+}
+
+/// Why replay_entry queued nothing
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayError {
+    NotFound,    // the id is no longer in the timeline
+    NotTerminal, // still queued or speaking
+    Muted,       // muted or snoozed: the copy would only wait in the queue
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ReplayError::NotFound => "entry not found",
+            ReplayError::NotTerminal => "entry has not finished",
+            ReplayError::Muted => "muted",
+        })
+    }
+}
+
+/// What copy_last_message did
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyOutcome {
+    Copied,
+    NothingToCopy, // nothing spoken yet, or the id is no longer in the timeline
+}
+
+/// Token counts reported in a session log
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_tokens += other.cache_creation_tokens;
+        self.cache_read_tokens += other.cache_read_tokens;
+    }
+
+    pub fn sub(&mut self, other: &TokenUsage) {
+        self.input_tokens = self.input_tokens.saturating_sub(other.input_tokens);
+        self.output_tokens = self.output_tokens.saturating_sub(other.output_tokens);
+        self.cache_creation_tokens = self.cache_creation_tokens.saturating_sub(other.cache_creation_tokens);
+        self.cache_read_tokens = self.cache_read_tokens.saturating_sub(other.cache_read_tokens);
+    }
+
+    /// Tokens worth announcing: everything except cache reads, which repeat every request
+    pub fn billed(&self) -> u64 {
+        self.input_tokens + self.output_tokens + self.cache_creation_tokens
+    }
+}
+
+/// Usage accumulated by one session since app start
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SessionUsage {
+    #[serde(flatten)]
+    pub tokens: TokenUsage,
+    pub cost_usd: Option<f64>, // only when a configured price matched the session's model
+    pub turns: u32,
+}
+
+/// One processed watcher event, for GET /watcher/events
+#[derive(Debug, Clone, Serialize)]
+pub struct WatcherEvent {
+    pub at: DateTime<Utc>,
+    pub file: String,
+    pub project: String,
+    pub kind: String,             // "completion", "tool_use", "mode_change", "approval_alert", ...
+    pub detail: Option<String>,   // tool names, spawn name, error text, mode change
+    pub raw_type: Option<String>, // log record type/subtype, for compaction and limit events
+    pub outcome: String,          // "queued", "held", "tracked", "suppressed"
+    pub reason: Option<String>,   // why it was suppressed
+}
+
+/// Result of the last sweep of tracked session files
+#[derive(Debug, Clone, Serialize)]
+pub struct PruneStats {
+    pub at: DateTime<Utc>,
+    pub before: usize,
+    pub after: usize,
+}
+
+/// Session watcher health, surfaced in /status, /health and get_status
+#[derive(Debug, Clone, Serialize)]
+pub struct WatcherStatus {
+    pub state: String, // "starting", "active", "failed", "stopped"
+    pub reason: Option<String>,
+    pub paused: bool, // events are ignored while paused (set_watcher_enabled)
+    pub hooks_mode: bool, // Claude Code hooks installed; they announce completions instead
+    pub tracked_files: usize,
+    pub last_prune: Option<PruneStats>, // tracked files before/after the last sweep of deleted and idle ones
+    pub profiles: Vec<String>, // watcher profiles whose log directories are being watched
+    pub permission_mode: String,
+    pub last_event: Option<String>,
+    pub last_event_at: Option<DateTime<Utc>>,
+    pub pending_approvals: usize, // sessions with a tool_use waiting for approval
+    pub approval_reminders: u32,  // most reminders sent for any pending approval
+    pub session_usage: BTreeMap<String, SessionUsage>, // keyed by session log file stem
+}
+
+/// MQTT connection details for get_mqtt_info, kept up to date by the MQTT client
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MqttInfo {
+    pub broker: String, // "host:port" of the session in progress or last tried
+    pub transport: String, // "tcp"; TLS is not supported yet
+    pub tls: bool,
+    pub auth: bool, // connecting with a username and password
+    pub client_id: String,
+    pub connected_since: Option<DateTime<Utc>>,
+    pub reconnect_count: u32, // sessions started after the first one
+    pub last_error: Option<String>,
+    pub subscribed_topics: Vec<String>, // acknowledged by the broker
+    pub messages_in: u64,  // publishes received on the speak topic
+    pub messages_out: u64, // status publishes sent
+    #[serde(skip)]
+    pub retry_at: Option<DateTime<Utc>>, // next attempt after a failed session
+    pub retrying_in_secs: Option<i64>,   // derived from retry_at when read
+}
+
+/// Entries kept in AppState::timeline
+pub const TIMELINE_MAX: usize = 100;
+
+/// Entries kept in AppState::watcher_events
+pub const WATCHER_EVENT_LOG_MAX: usize = 200;
+
+impl Default for WatcherStatus {
+    fn default() -> Self {
+        Self {
+            state: "starting".to_string(),
+            reason: None,
+            paused: false,
+            hooks_mode: false,
+            tracked_files: 0,
+            last_prune: None,
+            profiles: Vec::new(),
+            permission_mode: "default".to_string(),
+            last_event: None,
+            last_event_at: None,
+            pending_approvals: 0,
+            approval_reminders: 0,
+            session_usage: BTreeMap::new(),
+        }
+    }
+}
+
 /// Shared application state
 pub struct AppState {
-    pub timeline: Mutex<VecDeque<VoiceEntry>>,
-    pub next_id: Mutex<u64>,
-    pub is_speaking: Mutex<bool>,
-    pub mqtt_status: Mutex<String>,
-    pub mqtt_reconnect: Mutex<bool>,
-    pub tray_icon: Mutex<Option<TrayIcon>>,
-    pub idle_icon: Mutex<Option<Image<'static>>>,
-    pub speaking_icon: Mutex<Option<Image<'static>>>,
-    pub disconnected_icon: Mutex<Option<Image<'static>>>,
+    pub timeline: Lock<Timeline>,
+    pub is_speaking: AtomicBool,
+    pub stop_requested: Lock<bool>, // kill the speech command now playing
+    pub muted: AtomicBool,
+    pub snoozed_until: Lock<Option<DateTime<Utc>>>, // muted until then (see snooze.rs)
+    pub queue_paused: AtomicBool, // hold the queue without muting watcher alerts
+    pub popup_pinned: Lock<bool>, // popup stays open and in place when it loses focus
+    pub tts_available: Lock<bool>, // speech command found at startup
+    pub http_port: Lock<u16>, // port the HTTP server last bound, or tried to
+    pub http_rebind: tokio::sync::Notify, // wakes the HTTP server to bind http.port again
+    pub http_error: Lock<Option<String>>, // why the HTTP server is not listening
+    pub tray_config: Shared<TrayConfig>, // config.json's ui section as last loaded or saved
+    pub voice_config: Shared<VoiceConfig>, // config.json's voice section
+    pub mqtt_status: Shared<String>,
+    pub mqtt_info: Lock<MqttInfo>,
+    pub mqtt_reconnect: Lock<bool>,
+    pub watcher_status: Lock<WatcherStatus>,
+    pub watcher_events: Lock<VecDeque<WatcherEvent>>, // newest last
+    pub app_handle: Shared<Option<AppHandle>>, // set once the Tauri app is up; used for notifications
+    pub tray_icon: Lock<Option<TrayIcon>>,
+    pub tray_menu: Lock<Option<Menu<Wry>>>,
+    pub header_menu_item: Lock<Option<MenuItem<Wry>>>,
+    pub stop_menu_item: Lock<Option<MenuItem<Wry>>>,
+    pub pause_menu_item: Lock<Option<MenuItem<Wry>>>,
+    pub copy_menu_item: Lock<Option<MenuItem<Wry>>>,
+    pub reconnect_menu_item: Lock<Option<MenuItem<Wry>>>,
+    pub watcher_menu_item: Lock<Option<CheckMenuItem<Wry>>>,
+    pub mute_menu_item: Lock<Option<CheckMenuItem<Wry>>>,
+    pub autostart_menu_item: Lock<Option<CheckMenuItem<Wry>>>,
+    pub recent_menu: Lock<Option<Submenu<Wry>>>,
+    pub snooze_menu: Lock<Option<Submenu<Wry>>>,
+    pub voice_menu: Lock<Option<Submenu<Wry>>>,
+    pub profile_menu: Lock<Option<Submenu<Wry>>>,
+    pub idle_icon: Shared<Option<Image<'static>>>,
+    pub speaking_icon: Shared<Option<Image<'static>>>,
+    pub disconnected_icon: Shared<Option<Image<'static>>>,
+    pub icon_look: Shared<Option<IconLook>>, // what the three icons above were drawn for
+    pub badge_icons: Lock<HashMap<(&'static str, String), Image<'static>>>, // (base icon, badge label)
+    pub shown_icon: Lock<Option<(&'static str, Option<String>)>>, // what the tray shows now
+    pub suppressed_rollup: Lock<SuppressedRollup>, // suppressed-message notifications in the current window
+    pub tasks: Lock<Vec<Task>>, // async services on Tauri's runtime (see tasks.rs)
+    pub shutdown: Lock<Phase>, // see shutdown.rs
+    pub stats: Lock<Stats>, // per-run message counts (see stats.rs)
+    pub doctor: Lock<Option<Report>>, // last self-test, for GET /health (see doctor.rs)
+    pub focus: Lock<FocusStatus>, // last macOS Focus reading (see focus.rs)
+    pub ntfy: Lock<Vec<TopicStatus>>, // one per ntfy.topics entry (see ntfy.rs)
+    pub ntfy_reconnect: tokio::sync::Notify, // restarts the ntfy bridge with config.json's ntfy section
+    pub pipe_reopen: tokio::sync::Notify, // restarts the voice pipe listener with config.json's pipe section
+    pub mdns: Lock<MdnsStatus>, // the HTTP API's mDNS advertisement (see mdns.rs)
+    pub mdns_update: tokio::sync::Notify, // the HTTP server bound or stopped; the responder starts over
+    pub transcript: Lock<Pending>, // finished entries not yet written to the day's transcript
+    pub github: Lock<GithubStatus>, // webhook deliveries counted, and the ids seen (see github.rs)
+    pub mirror: Lock<MirrorStatus>, // posts to chat webhooks counted (see mirror.rs)
+    pub agents: Lock<Registry>, // every agent seen, with its mute; saved to agents.json (see agents.rs)
+    pub bus: broadcast::Sender<TimelineEvent>, // timeline changes for every outbound surface (see bus.rs)
+    changed: Mutex<bool>, // set by notify_changed, cleared by wait_changed
+    changed_cv: Condvar,
+}
+
+impl AppState {
+    /// Queue an entry under the next id, dropping the oldest past TIMELINE_MAX. Returns the id.
+    /// While muted the entry waits in the queue (see notify_suppressed).
+    pub fn enqueue(&self, text: String, voice: String, rate: u32, agent: Option<String>, priority: u8, detail: Option<String>) -> u64 {
+        self.enqueue_entry(VoiceEntry {
+            id: 0,
+            timestamp: Utc::now(),
+            text,
+            voice,
+            rate,
+            agent,
+            status: "queued".to_string(),
+            priority,
+            detail,
+            replay_of: None,
+            relayed_from: None,
+        })
+    }
+
+    /// Queue `entry` under the next id and the current time; enqueue and replay share this
+    fn enqueue_entry(&self, entry: VoiceEntry) -> u64 {
+        let mut entry = VoiceEntry { timestamp: Utc::now(), status: "queued".to_string(), ..entry };
+        // A muted agent's entry is finished as it arrives, and never spoken or relayed. The
+        // registry is done with before the timeline is locked, so the two are never nested.
+        let agent_muted = self.agents.lock().seen(&entry);
+        if agent_muted {
+            entry.status = "suppressed".to_string();
+            entry.detail = Some(format!("Agent {} is muted", entry.agent.as_deref().unwrap_or_default()));
+        }
+        let mut timeline = self.timeline.lock();
+        entry.id = timeline.take_id();
+        let id = entry.id;
+        let dropped = timeline.push(entry.clone());
+        // Published before the timeline is let go: the queue may take the entry the moment it
+        // is, and its "speaking" must not be overtaken by this "queued"
+        bus::publish(self, TimelineEvent::Queued { entry: entry.clone() });
+        for old in dropped {
+            bus::publish(self, TimelineEvent::Removed { entry: old });
+        }
+        drop(timeline);
+        self.notify_changed();
+        // Held until unmuted or the snooze ends; optionally say so now. relay.rs says so for
+        // what it takes instead
+        let held = !agent_muted && !relay::takes_over(self);
+        if agent_muted {
+            stats::record(self, entry.agent.as_deref(), |c| c.suppressed += 1);
+        } else if held && self.is_muted() {
+            notifications::notify_suppressed(self, Suppression::Muted, entry.agent.as_deref(), &entry.text);
+        } else if held && self.snooze_minutes_left(Utc::now()).is_some() {
+            notifications::notify_suppressed(self, Suppression::Snoozed, entry.agent.as_deref(), &entry.text);
+        }
+        if agent_muted {
+            bus::status_changed(self, &entry);
+        }
+        id
+    }
+
+    /// Queue a speak request, filling voice and rate from the voice defaults
+    pub fn speak(&self, req: SpeakRequest) -> SpeakResponse {
+        self.speak_from(req, None)
+    }
+
+    /// Queue a speak request, marked with the peer that relayed it when there is one
+    pub fn speak_from(&self, req: SpeakRequest, relayed_from: Option<String>) -> SpeakResponse {
+        let defaults = self.voice_config();
+        let id = self.enqueue_entry(VoiceEntry {
+            id: 0,
+            timestamp: Utc::now(),
+            text: req.text,
+            voice: req.voice.unwrap_or_else(|| defaults.voice()),
+            rate: req.rate.unwrap_or(defaults.default_rate),
+            agent: req.agent,
+            status: "queued".to_string(),
+            priority: 0,
+            detail: None,
+            replay_of: None,
+            relayed_from,
+        });
+        SpeakResponse { id, status: "queued".to_string() }
+    }
+
+    /// MQTT connection details as of `now`, with the wait before the next attempt filled in
+    pub fn mqtt_info(&self, now: DateTime<Utc>) -> MqttInfo {
+        let mut info = self.mqtt_info.lock().clone();
+        info.retrying_in_secs = info.retry_at
+            .map(|at| (at - now).num_milliseconds())
+            .filter(|ms| *ms > 0)
+            .map(|ms| (ms + 999) / 1000);
+        info
+    }
+
+    /// Whole minutes left in the snooze, rounded up; None when not snoozed
+    pub fn snooze_minutes_left(&self, now: DateTime<Utc>) -> Option<i64> {
+        let until = (*self.snoozed_until.lock())?;
+        let secs = (until - now).num_seconds();
+        (secs > 0).then(|| (secs + 59) / 60)
+    }
+
+    /// Muted or snoozed: queued entries wait and watcher alerts become notifications
+    pub fn silenced(&self) -> bool {
+        self.is_muted() || self.snooze_minutes_left(Utc::now()).is_some()
+    }
+
+    pub fn is_speaking(&self) -> bool {
+        self.is_speaking.load(Ordering::Relaxed)
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted.load(Ordering::Relaxed)
+    }
+
+    pub fn is_queue_paused(&self) -> bool {
+        self.queue_paused.load(Ordering::Relaxed)
+    }
+
+    pub fn shutdown_phase(&self) -> Phase {
+        *self.shutdown.lock()
+    }
+
+    /// Whether the app is quitting: the queue starts nothing new and the services wind down
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown_phase() != Phase::Running
+    }
+
+    /// Current tray settings
+    pub fn tray_config(&self) -> TrayConfig {
+        self.tray_config.read().clone()
+    }
+
+    /// Current voice defaults
+    pub fn voice_config(&self) -> VoiceConfig {
+        self.voice_config.read().clone()
+    }
+
+    /// Queue a fresh copy of a done or cancelled entry, marked with `replay_of`. Refused while
+    /// muted or snoozed, where the copy would only wait in the queue.
+    pub fn replay(&self, id: u64) -> Result<u64, ReplayError> {
+        let original = self.timeline.lock().get(id).cloned().ok_or(ReplayError::NotFound)?;
+        if !original.is_finished() {
+            return Err(ReplayError::NotTerminal);
+        }
+        if self.silenced() {
+            return Err(ReplayError::Muted);
+        }
+        Ok(self.enqueue_entry(VoiceEntry { replay_of: Some(id), ..original }))
+    }
+
+    /// Queue the newest done entry again, like clicking it in Recent
+    pub fn replay_last(&self) -> Result<u64, ReplayError> {
+        let id = self.timeline.lock().iter().rev()
+            .find(|e| e.status == "done")
+            .map(|e| e.id)
+            .ok_or(ReplayError::NotFound)?;
+        self.replay(id)
+    }
+
+    /// Remove a done or cancelled entry, cancel (or with `remove`, drop) a queued one.
+    /// The speaking entry is only stopped with `force`; it is then marked cancelled.
+    pub fn delete_entry(&self, id: u64, remove: bool, force: bool) -> DeleteOutcome {
+        let mut timeline = self.timeline.lock();
+        let Some(status) = timeline.get(id).map(|e| e.status.clone()) else {
+            return DeleteOutcome::NotFound;
+        };
+        // The stopped entry's status event comes from the queue once the speech command exits
+        let (outcome, event) = match status.as_str() {
+            "speaking" if !force => return DeleteOutcome::Refused,
+            "speaking" => {
+                self.stop_speaking();
+                (DeleteOutcome::Cancelled, None)
+            }
+            "queued" if !remove => {
+                let event = timeline.set_status(id, "cancelled", None)
+                    .map(|entry| TimelineEvent::StatusChanged { entry, at: Utc::now() });
+                (DeleteOutcome::Cancelled, event)
+            }
+            _ => {
+                let removed = timeline.remove(id).map(|entry| TimelineEvent::Removed { entry });
+                (DeleteOutcome::Removed, removed)
+            }
+        };
+        drop(timeline);
+        self.notify_changed();
+        if let Some(event) = event {
+            bus::publish(self, event);
+        }
+        outcome
+    }
+
+    /// Remove the entries `scope` covers. Clearing All drops the queue and stops the entry
+    /// speaking through stop_speaking, so the queue still reaps the speech command and resets
+    /// the tray icon; that entry stays as cancelled.
+    pub fn clear_timeline(&self, scope: &ClearScope) -> ClearOutcome {
+        let taken = self.timeline.lock().retain(|e| {
+            let finished = match scope {
+                ClearScope::Done | ClearScope::All => e.is_finished(),
+                ClearScope::Failed => e.status == "failed",
+                ClearScope::Agent(agent) => e.is_finished() && e.agent.as_deref() == Some(agent.as_str()),
+            };
+            let dropped = *scope == ClearScope::All && e.status == "queued";
+            !finished && !dropped
+        });
+        // The rest are the queued entries clearing All drops
+        let (removed, dropped): (Vec<_>, Vec<_>) = taken.into_iter().partition(VoiceEntry::is_finished);
+        let stopped = *scope == ClearScope::All && self.stop_speaking();
+        let outcome = ClearOutcome { removed: removed.len(), cancelled: dropped.len() + usize::from(stopped) };
+        for entry in removed.into_iter().chain(dropped) {
+            bus::publish(self, TimelineEvent::Removed { entry });
+        }
+        self.notify_changed();
+        outcome
+    }
+
+    /// Clipboard text for entry `id`, or without one for the newest done entry, in the ui section's copy_format
+    pub fn clipboard_text(&self, id: Option<u64>) -> Option<String> {
+        let format = self.tray_config().copy_format;
+        let timeline = self.timeline.lock();
+        let entry = match id {
+            Some(id) => timeline.get(id),
+            None => timeline.iter().rev().find(|e| e.status == "done"),
+        };
+        entry.map(|e| e.clipboard_text(&format))
+    }
+
+    /// Mark every queued entry cancelled with `detail`, when the app quits. Returns how many.
+    pub fn cancel_queued(&self, detail: &str) -> usize {
+        self.finish_queued("cancelled", detail).len()
+    }
+
+    /// Give every queued entry the final `status` and `detail` without speaking it; returns them
+    pub fn finish_queued(&self, status: &str, detail: &str) -> Vec<VoiceEntry> {
+        let finished: Vec<VoiceEntry> = {
+            let mut timeline = self.timeline.lock();
+            let queued: Vec<u64> = timeline.iter().filter(|e| e.status == "queued").map(|e| e.id).collect();
+            queued.into_iter()
+                .filter_map(|id| timeline.set_status(id, status, Some(detail.to_string())))
+                .collect()
+        };
+        for entry in &finished {
+            bus::status_changed(self, entry);
+        }
+        self.notify_changed();
+        finished
+    }
+
+    /// Mark the next queued entry speaking and return it: highest priority first, oldest
+    /// first within a priority
+    pub fn start_next(&self) -> Option<VoiceEntry> {
+        self.take_next("speaking")
+    }
+
+    /// Mark the next queued entry relaying and return it, in the queue's order; the queue
+    /// leaves it to relay.rs, which finishes it like the queue finishes one it spoke
+    pub fn start_relay(&self) -> Option<VoiceEntry> {
+        self.take_next("relaying")
+    }
+
+    fn take_next(&self, status: &str) -> Option<VoiceEntry> {
+        let entry = {
+            let mut timeline = self.timeline.lock();
+            let next = timeline.iter()
+                .filter(|e| e.status == "queued")
+                .min_by_key(|e| std::cmp::Reverse(e.priority))?
+                .id;
+            timeline.set_status(next, status, None)?
+        };
+        bus::status_changed(self, &entry);
+        Some(entry)
+    }
+
+    /// Record how the entry speaking ended: its final status and detail
+    pub fn finish_entry(&self, entry: &VoiceEntry) {
+        self.timeline.lock().set_status(entry.id, &entry.status, entry.detail.clone());
+        bus::status_changed(self, entry);
+    }
+
+    /// Stop the entry speaking now; the queue marks it cancelled and moves on.
+    /// Shared by delete_entry, POST /stop, the stop_speaking command and the stop hotkey.
+    pub fn stop_speaking(&self) -> bool {
+        let speaking = self.is_speaking();
+        if speaking {
+            *self.stop_requested.lock() = true;
+        }
+        speaking
+    }
+
+    /// Take a pending stop request, clearing it
+    pub fn take_stop_request(&self) -> bool {
+        std::mem::take(&mut *self.stop_requested.lock())
+    }
+
+    /// Signal that queue, speaking, MQTT or mute/pause state changed (wakes the tray updater)
+    pub fn notify_changed(&self) {
+        // A bool is valid whatever a panicking holder was doing
+        *self.changed.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        self.changed_cv.notify_all();
+    }
+
+    /// Block until notify_changed has been called since the last wait
+    pub fn wait_changed(&self) {
+        let guard = self.changed.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut changed = self.changed_cv.wait_while(guard, |changed| !*changed).unwrap_or_else(PoisonError::into_inner);
+        *changed = false;
+    }
+
+    /// Most recent watcher events, oldest first
+    pub fn recent_watcher_events(&self, limit: usize) -> Vec<WatcherEvent> {
+        let events = self.watcher_events.lock();
+        events.iter().skip(events.len().saturating_sub(limit)).cloned().collect()
+    }
 }
 
 impl Default for AppState {
     fn default() -> Self {
         Self {
-            timeline: Mutex::new(VecDeque::with_capacity(100)),
-            next_id: Mutex::new(1),
-            is_speaking: Mutex::new(false),
-            mqtt_status: Mutex::new("disconnected".to_string()),
-            mqtt_reconnect: Mutex::new(false),
-            tray_icon: Mutex::new(None),
-            idle_icon: Mutex::new(None),
-            speaking_icon: Mutex::new(None),
-            disconnected_icon: Mutex::new(None),
+            timeline: Lock::ranked(Timeline::new(TIMELINE_MAX), lock::RANK_TIMELINE),
+            is_speaking: AtomicBool::new(false),
+            stop_requested: Lock::ranked(false, lock::RANK_STOP_REQUESTED),
+            muted: AtomicBool::new(false),
+            snoozed_until: Lock::new(None),
+            queue_paused: AtomicBool::new(false),
+            popup_pinned: Lock::new(false),
+            tts_available: Lock::new(true),
+            http_port: Lock::new(VOICE_SERVER_PORT),
+            http_rebind: tokio::sync::Notify::new(),
+            http_error: Lock::new(None),
+            tray_config: Shared::new(TrayConfig::default()),
+            voice_config: Shared::new(VoiceConfig::default()),
+            mqtt_status: Shared::new("disconnected".to_string()),
+            mqtt_info: Lock::new(MqttInfo::default()),
+            mqtt_reconnect: Lock::new(false),
+            watcher_status: Lock::new(WatcherStatus::default()),
+            watcher_events: Lock::new(VecDeque::with_capacity(WATCHER_EVENT_LOG_MAX)),
+            app_handle: Shared::new(None),
+            tray_icon: Lock::ranked(None, lock::RANK_TRAY_ICON),
+            tray_menu: Lock::new(None),
+            header_menu_item: Lock::new(None),
+            stop_menu_item: Lock::new(None),
+            pause_menu_item: Lock::new(None),
+            copy_menu_item: Lock::new(None),
+            reconnect_menu_item: Lock::new(None),
+            watcher_menu_item: Lock::new(None),
+            mute_menu_item: Lock::new(None),
+            autostart_menu_item: Lock::new(None),
+            recent_menu: Lock::new(None),
+            snooze_menu: Lock::new(None),
+            voice_menu: Lock::new(None),
+            profile_menu: Lock::new(None),
+            idle_icon: Shared::new(None),
+            speaking_icon: Shared::new(None),
+            disconnected_icon: Shared::new(None),
+            icon_look: Shared::new(None),
+            badge_icons: Lock::new(HashMap::new()),
+            shown_icon: Lock::ranked(None, lock::RANK_SHOWN_ICON),
+            suppressed_rollup: Lock::new(SuppressedRollup::default()),
+            tasks: Lock::new(Vec::new()),
+            shutdown: Lock::new(Phase::Running),
+            stats: Lock::new(Stats::default()),
+            doctor: Lock::new(None),
+            focus: Lock::new(FocusStatus::default()),
+            ntfy: Lock::new(Vec::new()),
+            ntfy_reconnect: tokio::sync::Notify::new(),
+            pipe_reopen: tokio::sync::Notify::new(),
+            mdns: Lock::new(MdnsStatus::default()),
+            mdns_update: tokio::sync::Notify::new(),
+            transcript: Lock::new(Pending::default()),
+            github: Lock::new(GithubStatus::default()),
+            mirror: Lock::new(MirrorStatus::default()),
+            agents: Lock::new(Registry::default()),
+            bus: bus::channel(),
+            changed: Mutex::new(false),
+            changed_cv: Condvar::new(),
         }
     }
 }
@@ -64,6 +726,7 @@ impl Default for AppState {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Arc;
 
     #[test]
     fn test_voice_entry_serialization() {
@@ -75,6 +738,10 @@ mod tests {
             rate: 200,
             agent: Some("test-agent".to_string()),
             status: "queued".to_string(),
+            priority: 0,
+            detail: None,
+            replay_of: None,
+            relayed_from: None,
         };
 
         let json = serde_json::to_string(&entry).expect("serialize");
@@ -86,6 +753,7 @@ mod tests {
         assert_eq!(parsed.rate, entry.rate);
         assert_eq!(parsed.agent, entry.agent);
         assert_eq!(parsed.status, entry.status);
+        assert_eq!(parsed.priority, entry.priority);
     }
 
     #[test]
@@ -105,48 +773,302 @@ mod tests {
         assert_eq!(req.rate, Some(150));
     }
 
+    #[test]
+    fn test_speak_applies_defaults() {
+        let state = AppState::default();
+        let blank = SpeakRequest { text: "  ".to_string(), voice: None, agent: None, rate: None };
+        assert!(blank.validate().is_err());
+        let long = SpeakRequest { text: "a".repeat(TEXT_MAX_CHARS + 1), voice: None, agent: None, rate: None };
+        assert_eq!(long.validate(), Err("text must be at most 12000 characters, not 12001".to_string()));
+        let multiline = SpeakRequest { text: "hi".to_string(), voice: None, agent: Some("ci\nbot".to_string()), rate: None };
+        assert_eq!(multiline.validate(), Err("agent must not contain control characters".to_string()));
+        let long_agent = SpeakRequest { text: "hi".to_string(), voice: None, agent: Some("a".repeat(AGENT_MAX_CHARS + 1)), rate: None };
+        assert_eq!(long_agent.validate(), Err("agent must be at most 64 characters, not 65".to_string()));
+        let req = SpeakRequest { text: "Stand-up in 5".to_string(), voice: None, agent: Some("ui".to_string()), rate: Some(180) };
+        assert!(req.validate().is_ok());
+        let response = state.speak(req);
+        assert_eq!(response.status, "queued");
+        let timeline = state.timeline.lock();
+        let entry = timeline.last().expect("entry");
+        assert_eq!((entry.id, entry.voice.as_str(), entry.rate, entry.agent.as_deref()), (response.id, "Samantha", 180, Some("ui")));
+    }
+
     #[test]
     fn test_app_state_default() {
         let state = AppState::default();
 
-        let timeline = state.timeline.lock().expect("lock");
+        let timeline = state.timeline.lock();
         assert!(timeline.is_empty());
+        assert_eq!(timeline.next_id(), 1);
         drop(timeline);
 
-        let next_id = state.next_id.lock().expect("lock");
-        assert_eq!(*next_id, 1);
-        drop(next_id);
-
-        let is_speaking = state.is_speaking.lock().expect("lock");
-        assert!(!*is_speaking);
-        drop(is_speaking);
+        assert!(!state.is_speaking());
 
-        let mqtt_status = state.mqtt_status.lock().expect("lock");
+        let mqtt_status = state.mqtt_status.read();
         assert_eq!(*mqtt_status, "disconnected");
+        drop(mqtt_status);
+
+        let watcher = state.watcher_status.lock();
+        assert_eq!(watcher.state, "starting");
+        assert_eq!(watcher.tracked_files, 0);
+        assert!(watcher.last_event.is_none());
     }
 
     #[test]
     fn test_timeline_capacity() {
         let state = AppState::default();
 
+        for i in 0..105 {
+            state.enqueue(format!("Message {}", i), "Samantha".to_string(), 200, None, 0, None);
+        }
+        let timeline = state.timeline.lock();
+        assert_eq!(timeline.len(), TIMELINE_MAX);
+        assert_eq!(timeline.iter().next().map(|e| e.id), Some(6));
+        assert!(timeline.get(5).is_none());
+    }
+
+    #[test]
+    fn test_enqueue_unique_ids() {
+        let state = AppState::default();
+        let first = state.enqueue("one".to_string(), "Samantha".to_string(), 220, None, 0, None);
+        state.timeline.lock().retain(|_| false);
+        let second = state.enqueue("two".to_string(), "Daniel".to_string(), 200, Some("Test".to_string()), 0, None);
+        assert_eq!((first, second), (1, 2));
+        let timeline = state.timeline.lock();
+        assert_eq!(timeline.last().map(|e| (e.id, e.status.as_str())), Some((2, "queued")));
+    }
+
+    #[test]
+    fn test_mqtt_info_retry_countdown() {
+        let state = AppState::default();
+        let now = Utc::now();
+        assert_eq!(state.mqtt_info(now).retrying_in_secs, None);
+        state.mqtt_info.lock().retry_at = Some(now + chrono::Duration::milliseconds(4200));
+        assert_eq!(state.mqtt_info(now).retrying_in_secs, Some(5));
+        assert_eq!(state.mqtt_info(now + chrono::Duration::seconds(5)).retrying_in_secs, None, "retry is due");
+    }
+
+    #[test]
+    fn test_replay_terminal_entries() {
+        let state = AppState::default();
+        let id = state.enqueue("Build passed".to_string(), "Daniel".to_string(), 200, Some("ci".to_string()), 0, None);
+        assert_eq!(state.replay(id), Err(ReplayError::NotTerminal));
+        state.timeline.lock().set_status(id, "done", None);
+        let copy = state.replay(id).expect("replayed");
+        assert_eq!(state.replay(99), Err(ReplayError::NotFound));
         {
-            let mut timeline = state.timeline.lock().expect("lock");
-            for i in 0..105 {
-                timeline.push_back(VoiceEntry {
-                    id: i,
-                    timestamp: Utc::now(),
-                    text: format!("Message {}", i),
-                    voice: "Samantha".to_string(),
-                    rate: 200,
-                    agent: None,
-                    status: "done".to_string(),
-                });
-                while timeline.len() > 100 {
-                    timeline.pop_front();
+            let timeline = state.timeline.lock();
+            let entry = timeline.get(copy).expect("copy");
+            assert_eq!((entry.text.as_str(), entry.voice.as_str(), entry.status.as_str()), ("Build passed", "Daniel", "queued"));
+            assert_eq!(entry.replay_of, Some(id));
+        }
+
+        state.muted.store(true, Ordering::Relaxed);
+        assert_eq!(state.replay(id), Err(ReplayError::Muted));
+    }
+
+    #[test]
+    fn test_delete_entry_by_status() {
+        let state = AppState::default();
+        let done = state.enqueue("done".to_string(), "Samantha".to_string(), 220, None, 0, None);
+        let speaking = state.enqueue("speaking".to_string(), "Samantha".to_string(), 220, None, 0, None);
+        let queued = state.enqueue("queued".to_string(), "Samantha".to_string(), 220, None, 0, None);
+        let dropped = state.enqueue("dropped".to_string(), "Samantha".to_string(), 220, None, 0, None);
+        {
+            let mut timeline = state.timeline.lock();
+            timeline.set_status(done, "done", None);
+            timeline.set_status(speaking, "speaking", None);
+        }
+        state.is_speaking.store(true, Ordering::Relaxed);
+
+        assert_eq!(state.delete_entry(done, false, false), DeleteOutcome::Removed);
+        assert_eq!(state.delete_entry(done, false, false), DeleteOutcome::NotFound);
+        assert_eq!(state.delete_entry(queued, false, false), DeleteOutcome::Cancelled);
+        assert_eq!(state.delete_entry(dropped, true, false), DeleteOutcome::Removed);
+        assert_eq!(state.delete_entry(speaking, false, false), DeleteOutcome::Refused);
+        assert!(!state.take_stop_request());
+        assert_eq!(state.delete_entry(speaking, false, true), DeleteOutcome::Cancelled);
+        assert!(state.take_stop_request());
+
+        let timeline = state.timeline.lock();
+        let statuses: Vec<&str> = timeline.iter().map(|e| e.status.as_str()).collect();
+        assert_eq!(statuses, vec!["speaking", "cancelled"]);
+    }
+
+    #[test]
+    fn test_cancel_queued_on_shutdown() {
+        let state = AppState::default();
+        for status in ["done", "speaking", "queued", "queued"] {
+            let id = state.enqueue(status.to_string(), "Samantha".to_string(), 220, None, 0, None);
+            state.timeline.lock().set_status(id, status, None);
+        }
+        assert!(!state.is_shutting_down());
+
+        *state.shutdown.lock() = Phase::Stopping;
+        assert!(state.is_shutting_down());
+        assert_eq!(state.cancel_queued("Not spoken: the app quit"), 2);
+        let timeline = state.timeline.lock();
+        let statuses: Vec<&str> = timeline.iter().map(|e| e.status.as_str()).collect();
+        assert_eq!(statuses, vec!["done", "speaking", "cancelled", "cancelled"]);
+        assert_eq!(timeline.get(4).and_then(|e| e.detail.as_deref()), Some("Not spoken: the app quit"));
+    }
+
+    /// Clients call /speak, /timeline, /status and /stop through the HTTP router at once while
+    /// the queue worker drains the queue; nothing may deadlock, fail or lose an entry
+    #[test]
+    fn test_concurrent_speak_and_queue() {
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        async fn call(router: axum::Router, request: Request<Body>) -> StatusCode {
+            let response = router.oneshot(request).await.expect("response");
+            let status = response.status();
+            axum::body::to_bytes(response.into_body(), usize::MAX).await.expect("body");
+            status
+        }
+
+        let state = Arc::new(AppState::default());
+        // A panic while holding the timeline used to poison it for every later caller
+        let poisoner = state.clone();
+        let _ = std::thread::spawn(move || {
+            let _timeline = poisoner.timeline.lock();
+            panic!("queue worker exploded");
+        }).join();
+
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        let worker = state.clone();
+        std::thread::spawn(move || {
+            let speakers_left = Arc::new(std::sync::atomic::AtomicUsize::new(3));
+            let queue = {
+                let (state, speakers_left) = (worker.clone(), speakers_left.clone());
+                // Entries pushed out past TIMELINE_MAX are never started, so run until the speakers are done
+                std::thread::spawn(move || loop {
+                    let Some(mut entry) = state.start_next() else {
+                        if speakers_left.load(Ordering::SeqCst) == 0 {
+                            return;
+                        }
+                        std::thread::yield_now();
+                        continue;
+                    };
+                    state.is_speaking.store(true, Ordering::Relaxed);
+                    entry.status = if state.take_stop_request() { "cancelled" } else { "done" }.to_string();
+                    state.is_speaking.store(false, Ordering::Relaxed);
+                    state.finish_entry(&entry);
+                })
+            };
+            let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(4).enable_all().build().expect("runtime");
+            let router = crate::http::router(worker.clone());
+            runtime.block_on(async {
+                let mut clients = tokio::task::JoinSet::new();
+                for agent in ["ci", "reviewer", "deploy"] {
+                    let (router, speakers_left) = (router.clone(), speakers_left.clone());
+                    clients.spawn(async move {
+                        for i in 0..200 {
+                            let body = serde_json::json!({ "text": format!("{} {}", agent, i), "agent": agent }).to_string();
+                            let request = Request::post("/speak").header("content-type", "application/json").body(Body::from(body)).expect("request");
+                            assert_eq!(call(router.clone(), request).await, StatusCode::OK);
+                        }
+                        speakers_left.fetch_sub(1, Ordering::SeqCst);
+                    });
+                }
+                for (method, uri) in [("GET", "/timeline?limit=20"), ("GET", "/status"), ("POST", "/stop")] {
+                    let router = router.clone();
+                    clients.spawn(async move {
+                        for _ in 0..300 {
+                            let request = Request::builder().method(method).uri(uri).body(Body::empty()).expect("request");
+                            assert_eq!(call(router.clone(), request).await, StatusCode::OK, "{} {}", method, uri);
+                        }
+                    });
                 }
+                while let Some(client) = clients.join_next().await {
+                    client.expect("client");
+                }
+            });
+            queue.join().expect("queue worker");
+            let _ = done_tx.send(());
+        });
+
+        // The sender is dropped unsent when a client or the worker panics
+        assert!(done_rx.recv_timeout(std::time::Duration::from_secs(30)).is_ok(), "speak and queue deadlocked or failed");
+        let timeline = state.timeline.lock();
+        assert_eq!(timeline.next_id(), 601);
+        assert_eq!(timeline.len(), TIMELINE_MAX);
+        assert!(timeline.iter().all(|e| e.is_finished()));
+    }
+
+    #[test]
+    fn test_clear_timeline_scopes() {
+        let state = AppState::default();
+        for (text, agent, status) in [
+            ("a", "ci", "done"),
+            ("b", "ci", "failed"),
+            ("c", "ui", "cancelled"),
+            ("d", "ui", "speaking"),
+            ("e", "ci", "queued"),
+            ("f", "ui", "done"),
+        ] {
+            let id = state.enqueue(text.to_string(), "Samantha".to_string(), 220, Some(agent.to_string()), 0, None);
+            state.timeline.lock().set_status(id, status, None);
+        }
+        state.is_speaking.store(true, Ordering::Relaxed);
+        let texts = |state: &AppState| -> Vec<String> {
+            state.timeline.lock().iter().map(|e| e.text.clone()).collect()
+        };
+
+        assert_eq!(state.clear_timeline(&ClearScope::Failed), ClearOutcome { removed: 1, cancelled: 0 });
+        assert_eq!(state.clear_timeline(&ClearScope::Agent("ui".to_string())), ClearOutcome { removed: 2, cancelled: 0 });
+        assert_eq!(texts(&state), vec!["a", "d", "e"]);
+        assert!(!state.take_stop_request(), "only All stops speech");
+        assert_eq!(state.clear_timeline(&ClearScope::All), ClearOutcome { removed: 1, cancelled: 2 });
+        assert_eq!(texts(&state), vec!["d"]);
+        assert!(state.take_stop_request());
+
+        let scope: ClearScope = serde_json::from_str(r#"{"agent": "ci"}"#).expect("parse");
+        assert_eq!(scope, ClearScope::Agent("ci".to_string()));
+        assert_eq!(serde_json::from_str::<ClearScope>(r#""all""#).expect("parse"), ClearScope::All);
+    }
+
+    #[test]
+    fn test_clipboard_text() {
+        let state = AppState::default();
+        assert_eq!(state.clipboard_text(None), None);
+        let first = state.enqueue("Tests passed".to_string(), "Samantha".to_string(), 220, Some("ci".to_string()), 0, None);
+        let queued = state.enqueue("PR #42 ready".to_string(), "Samantha".to_string(), 220, None, 0, None);
+        // Only done entries count as spoken
+        assert_eq!(state.clipboard_text(None), None);
+        state.timeline.lock().set_status(first, "done", None);
+        assert_eq!(state.clipboard_text(None).as_deref(), Some("Tests passed"));
+        assert_eq!(state.clipboard_text(Some(queued)).as_deref(), Some("PR #42 ready"));
+        assert_eq!(state.clipboard_text(Some(99)), None);
+
+        state.tray_config.write().copy_format = "[{agent}] {text}".to_string();
+        assert_eq!(state.clipboard_text(Some(first)).as_deref(), Some("[ci] Tests passed"));
+        let entry = state.timeline.lock().get(first).cloned().expect("entry");
+        let time = entry.timestamp.with_timezone(&Local).format("%H:%M:%S").to_string();
+        assert_eq!(entry.clipboard_text("{time} {text}"), format!("{} Tests passed", time));
+    }
+
+    #[test]
+    fn test_recent_watcher_events_limit() {
+        let state = AppState::default();
+        {
+            let mut events = state.watcher_events.lock();
+            for kind in ["tool_use", "tool_result", "completion"] {
+                events.push_back(WatcherEvent {
+                    at: Utc::now(),
+                    file: "/tmp/session.jsonl".to_string(),
+                    project: "oracle".to_string(),
+                    kind: kind.to_string(),
+                    detail: None,
+                    raw_type: None,
+                    outcome: "tracked".to_string(),
+                    reason: None,
+                });
             }
-            assert_eq!(timeline.len(), 100);
-            assert_eq!(timeline.front().map(|e| e.id), Some(5));
         }
+        let kinds: Vec<String> = state.recent_watcher_events(2).into_iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec!["tool_result", "completion"]);
+        assert_eq!(state.recent_watcher_events(WATCHER_EVENT_LOG_MAX).len(), 3);
     }
 }
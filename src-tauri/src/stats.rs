@@ -0,0 +1,157 @@
+/// Message counts and speaking time, overall and per agent, and error counts, since app start
+/// or the last reset_stats. Kept in memory only: the timeline is not saved, so the counters
+/// start over with each run too. Message counts come from the timeline bus (the "stats" task).
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use crate::bus::{self, TimelineEvent};
+use crate::state::AppState;
+
+/// What happened to the messages of one agent (or of all of them)
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Counters {
+    pub queued: u64,
+    pub spoken: u64,
+    pub failed: u64,
+    /// Watcher alerts shown only as a notification because of mute, snooze or quiet hours, and
+    /// messages from a muted agent
+    pub suppressed: u64,
+    /// Characters of the messages spoken to the end
+    pub characters: u64,
+    /// Time spent speaking, including messages stopped or failed partway
+    pub speaking_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub since: DateTime<Utc>,
+    pub total: Counters,
+    /// Messages sent without an agent count toward the total only
+    pub agents: BTreeMap<String, Counters>,
+    /// Failures reported through error::report, by kind ("tts", "mqtt", "ui", ...)
+    pub errors: BTreeMap<String, u64>,
+    /// When each entry now speaking started, by id
+    #[serde(skip)]
+    speaking_since: HashMap<u64, DateTime<Utc>>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            since: Utc::now(),
+            total: Counters::default(),
+            agents: BTreeMap::new(),
+            errors: BTreeMap::new(),
+            speaking_since: HashMap::new(),
+        }
+    }
+}
+
+impl Stats {
+    fn record(&mut self, agent: Option<&str>, change: impl Fn(&mut Counters)) {
+        change(&mut self.total);
+        if let Some(agent) = agent {
+            change(self.agents.entry(agent.to_string()).or_default());
+        }
+    }
+
+    /// Count what a timeline event says happened
+    fn apply(&mut self, event: &TimelineEvent) {
+        match event {
+            TimelineEvent::Queued { entry } => self.record(entry.agent.as_deref(), |c| c.queued += 1),
+            TimelineEvent::StatusChanged { entry, at } if entry.status == "speaking" => {
+                self.speaking_since.insert(entry.id, *at);
+            }
+            // Only the queue ends an entry it started speaking; cancelling a queued one is not counted
+            TimelineEvent::StatusChanged { entry, at } => {
+                let Some(started) = self.speaking_since.remove(&entry.id) else {
+                    return;
+                };
+                let took = (*at - started).num_milliseconds().max(0) as u64;
+                let characters = entry.text.chars().count() as u64;
+                let (spoken, failed) = (entry.status == "done", entry.status == "failed");
+                self.record(entry.agent.as_deref(), |c| {
+                    if spoken {
+                        c.spoken += 1;
+                        c.characters += characters;
+                    }
+                    if failed {
+                        c.failed += 1;
+                    }
+                    c.speaking_ms += took;
+                });
+            }
+            TimelineEvent::Removed { .. } | TimelineEvent::StatusSnapshot { .. } => {}
+        }
+    }
+}
+
+/// Count timeline events as they come; runs as the "stats" task
+pub async fn collect(state: Arc<AppState>) {
+    bus::consume(&state, "Stats", |event| {
+        state.stats.lock().apply(&event);
+    }).await
+}
+
+/// Apply `change` to the totals and to `agent`'s counters
+pub fn record(state: &AppState, agent: Option<&str>, change: impl Fn(&mut Counters)) {
+    state.stats.lock().record(agent, change);
+}
+
+pub fn record_error(state: &AppState, kind: &str) {
+    *state.stats.lock().errors.entry(kind.to_string()).or_default() += 1;
+}
+
+pub fn snapshot(state: &AppState) -> Stats {
+    state.stats.lock().clone()
+}
+
+/// Zero every counter and start counting from now; the entry speaking is still counted when it ends
+pub fn reset(state: &AppState) -> Stats {
+    let mut stats = state.stats.lock();
+    *stats = Stats { speaking_since: std::mem::take(&mut stats.speaking_since), ..Stats::default() };
+    stats.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_per_agent() {
+        let state = AppState::default();
+        let mut events = bus::subscribe(&state);
+        state.enqueue("Build passed".to_string(), "Samantha".to_string(), 220, Some("ci".to_string()), 0, None);
+        state.enqueue("Deploy".to_string(), "Samantha".to_string(), 220, Some("ci".to_string()), 0, None);
+        state.enqueue("Hi".to_string(), "Samantha".to_string(), 220, None, 0, None);
+        state.delete_entry(3, false, false);
+        for (status, ms) in [("done", 1500), ("failed", 200)] {
+            let mut entry = state.start_next().expect("entry");
+            entry.status = status.to_string();
+            state.finish_entry(&entry);
+            // Speaking time is taken from the events' own times
+            while let Ok(mut event) = events.try_recv() {
+                if let TimelineEvent::StatusChanged { entry, at } = &mut event {
+                    if entry.status == status {
+                        *at = state.stats.lock().speaking_since[&entry.id] + chrono::Duration::milliseconds(ms);
+                    }
+                }
+                state.stats.lock().apply(&event);
+            }
+        }
+        record(&state, Some("reviewer"), |c| c.suppressed += 1);
+
+        let stats = snapshot(&state);
+        assert_eq!(stats.total, Counters { queued: 3, spoken: 1, failed: 1, suppressed: 1, characters: 12, speaking_ms: 1700 });
+        assert_eq!(stats.agents["ci"], Counters { queued: 2, spoken: 1, failed: 1, suppressed: 0, characters: 12, speaking_ms: 1700 });
+        assert_eq!(stats.agents["reviewer"].suppressed, 1);
+        assert_eq!(stats.agents.len(), 2);
+
+        let fresh = reset(&state);
+        assert_eq!(fresh.total, Counters::default());
+        assert!(snapshot(&state).agents.is_empty());
+        assert!(fresh.since >= stats.since);
+    }
+}
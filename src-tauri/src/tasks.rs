@@ -0,0 +1,125 @@
+/// Long-running async services (the HTTP server, the MQTT client) as tasks on Tauri's tokio
+/// runtime, the one runtime every async feature spawns onto. Each task is watched: a panic or
+/// an unexpected return is logged and reported in /health, and shutdown aborts them all.
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::task::AbortHandle;
+use tracing::{error, info, warn};
+
+use crate::state::AppState;
+
+/// How a service task is doing
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    /// Returned, which the services never do on their own
+    Stopped,
+    Failed { error: String },
+    /// Aborted at shutdown
+    Aborted,
+}
+
+/// A task started by `spawn`
+pub struct Task {
+    name: &'static str,
+    state: TaskState,
+    abort: AbortHandle,
+}
+
+fn set_state(state: &AppState, name: &'static str, task_state: TaskState) {
+    let mut tasks = state.tasks.lock();
+    if let Some(task) = tasks.iter_mut().find(|t| t.name == name) {
+        task.state = task_state;
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload.downcast_ref::<&str>().map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panicked".to_string())
+}
+
+/// Run `service` as task `name`, replacing the record of an earlier task of that name
+pub fn spawn<F>(state: &Arc<AppState>, name: &'static str, service: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let handle = tauri::async_runtime::spawn(service);
+    let task = Task { name, state: TaskState::Running, abort: handle.inner().abort_handle() };
+    {
+        let mut tasks = state.tasks.lock();
+        tasks.retain(|t| t.name != name);
+        tasks.push(task);
+    }
+    let state = state.clone();
+    tauri::async_runtime::spawn(async move {
+        let ended = match handle.await {
+            Ok(()) => TaskState::Stopped,
+            Err(tauri::Error::JoinError(e)) if e.is_cancelled() => TaskState::Aborted,
+            Err(tauri::Error::JoinError(e)) if e.is_panic() => TaskState::Failed { error: panic_message(e.into_panic()) },
+            Err(e) => TaskState::Failed { error: e.to_string() },
+        };
+        match &ended {
+            TaskState::Failed { error } => error!("{} failed: {}", name, error),
+            TaskState::Stopped if state.is_shutting_down() => info!("{} stopped", name),
+            TaskState::Stopped => warn!("{} stopped", name),
+            TaskState::Running | TaskState::Aborted => {}
+        }
+        set_state(&state, name, ended);
+    });
+}
+
+/// Each task's state by name, for /health
+pub fn states(state: &AppState) -> BTreeMap<&'static str, TaskState> {
+    state.tasks.lock().iter().map(|t| (t.name, t.state.clone())).collect()
+}
+
+/// Whether task `name` was started and has not ended
+pub fn is_running(state: &AppState, name: &str) -> bool {
+    state.tasks.lock().iter().any(|t| t.name == name && t.state == TaskState::Running)
+}
+
+/// Abort every task still running when the app exits
+pub fn abort_all(state: &AppState) {
+    let tasks = state.tasks.lock();
+    for task in tasks.iter() {
+        task.abort.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn wait_for(state: &AppState, name: &str, expected: &TaskState) -> TaskState {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let current = states(state).get(name).cloned();
+            if current.as_ref() == Some(expected) || Instant::now() > deadline {
+                return current.unwrap_or(TaskState::Stopped);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_task_outcomes() {
+        let state = Arc::new(AppState::default());
+        spawn(&state, "returns", async {});
+        spawn(&state, "panics", async { panic!("broker exploded") });
+        spawn(&state, "runs", std::future::pending());
+
+        let failed = TaskState::Failed { error: "broker exploded".to_string() };
+        assert_eq!(wait_for(&state, "returns", &TaskState::Stopped), TaskState::Stopped);
+        assert_eq!(wait_for(&state, "panics", &failed), failed);
+        assert_eq!(states(&state)["runs"], TaskState::Running);
+
+        abort_all(&state);
+        assert_eq!(wait_for(&state, "runs", &TaskState::Aborted), TaskState::Aborted);
+        assert_eq!(serde_json::to_value(&failed).unwrap(), serde_json::json!({ "state": "failed", "error": "broker exploded" }));
+    }
+}
@@ -0,0 +1,201 @@
+/// Tray icons for the menu bar / taskbar theme. The embedded PNGs are white glyphs; macOS
+/// gets black template copies the system recolors, Windows and Linux a dark or light copy
+/// picked from the system theme or the ui section's icon_theme. Disconnected adds a status dot.
+use std::sync::Arc;
+
+use image::{Rgba, RgbaImage};
+use tauri::image::Image;
+#[cfg(target_os = "linux")]
+use tauri::Manager;
+use tracing::info;
+
+use crate::state::AppState;
+use crate::tray::update_tray_icon;
+
+const IDLE_PNG: &[u8] = include_bytes!("../icons/idle.png");
+const SPEAKING_PNG: &[u8] = include_bytes!("../icons/speaking.png");
+const DISCONNECTED_PNG: &[u8] = include_bytes!("../icons/disconnected.png");
+
+/// Dot color on the disconnected icon where the icon keeps its colors
+const ACCENT: [u8; 3] = [245, 158, 11];
+
+/// How the tray icons are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IconLook {
+    Template, // macOS: black glyphs, recolored by the system
+    Light,    // light taskbar/panel: dark glyphs
+    Dark,     // dark taskbar/panel: white glyphs
+}
+
+impl IconLook {
+    fn glyph_color(self) -> [u8; 3] {
+        match self {
+            IconLook::Template => [0, 0, 0],
+            IconLook::Light => [32, 32, 32],
+            IconLook::Dark => [255, 255, 255],
+        }
+    }
+
+    fn accent_color(self) -> [u8; 3] {
+        if self == IconLook::Template { [0, 0, 0] } else { ACCENT }
+    }
+}
+
+/// `icon_theme` from the ui section: "light" or "dark" fix the look, anything else ("auto") follows the system
+pub fn look_for(icon_theme: &str, system: impl FnOnce() -> IconLook) -> IconLook {
+    if cfg!(target_os = "macos") {
+        return IconLook::Template;
+    }
+    match icon_theme {
+        "light" => IconLook::Light,
+        "dark" => IconLook::Dark,
+        _ => system(),
+    }
+}
+
+/// Taskbar theme from the registry; the taskbar follows the system, not the app, setting
+#[cfg(target_os = "windows")]
+fn system_look(_state: &AppState) -> IconLook {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    let output = std::process::Command::new("reg")
+        .args([
+            "query",
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize",
+            "/v",
+            "SystemUsesLightTheme",
+        ])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output();
+    match output {
+        Ok(out) if String::from_utf8_lossy(&out.stdout).trim_end().ends_with("0x1") => IconLook::Light,
+        _ => IconLook::Dark,
+    }
+}
+
+/// GTK theme as reported to the popup window; panels are dark unless the theme says light
+#[cfg(target_os = "linux")]
+fn system_look(state: &AppState) -> IconLook {
+    let theme = state.app_handle.read().clone()
+        .and_then(|app| app.get_webview_window("main"))
+        .and_then(|window| window.theme().ok());
+    match theme {
+        Some(tauri::Theme::Light) => IconLook::Light,
+        _ => IconLook::Dark,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn system_look(_state: &AppState) -> IconLook {
+    IconLook::Template
+}
+
+/// Copy of `rgba` with every pixel set to `color`, keeping its alpha
+pub fn recolor(rgba: &[u8], color: [u8; 3]) -> Vec<u8> {
+    rgba.chunks_exact(4)
+        .flat_map(|px| [color[0], color[1], color[2], px[3]])
+        .collect()
+}
+
+/// Copy of `rgba` with a dot in the bottom-right corner, cut out of the glyph with a clear
+/// ring so it stays visible when the system recolors a template image. None if the buffer
+/// does not match the size.
+pub fn with_dot(rgba: &[u8], width: u32, height: u32, color: [u8; 3]) -> Option<Vec<u8>> {
+    if rgba.len() != (width * height * 4) as usize {
+        return None;
+    }
+    let mut img = RgbaImage::from_raw(width, height, rgba.to_vec())?;
+    let radius = width.min(height) as f32 * 0.2;
+    let ring = radius * 1.4;
+    let (cx, cy) = (width as f32 - ring, height as f32 - ring);
+    for y in 0..height {
+        for x in 0..width {
+            let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+            let distance = dx * dx + dy * dy;
+            if distance <= radius * radius {
+                img.put_pixel(x, y, Rgba([color[0], color[1], color[2], 255]));
+            } else if distance <= ring * ring {
+                img.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+            }
+        }
+    }
+    Some(img.into_raw())
+}
+
+fn load(png: &[u8], look: IconLook, dot: bool) -> Option<Image<'static>> {
+    let img = image::load_from_memory(png).ok()?.to_rgba8();
+    let (width, height) = img.dimensions();
+    let mut rgba = recolor(img.as_raw(), look.glyph_color());
+    if dot {
+        rgba = with_dot(&rgba, width, height, look.accent_color())?;
+    }
+    Some(Image::new_owned(rgba, width, height))
+}
+
+/// Icons that do not decode or draw in some look, for the doctor; empty when all is well
+pub fn broken_icons() -> Vec<&'static str> {
+    [("idle", IDLE_PNG, false), ("speaking", SPEAKING_PNG, false), ("disconnected", DISCONNECTED_PNG, true)]
+        .into_iter()
+        .filter(|(_, png, dot)| [IconLook::Template, IconLook::Light, IconLook::Dark].into_iter().any(|look| load(png, look, *dot).is_none()))
+        .map(|(name, _, _)| name)
+        .collect()
+}
+
+/// Redraw the icons if the look changed: at startup, when the system theme changes and when
+/// icon_theme is saved. Badged copies are dropped so they are redrawn on the new icons.
+pub fn apply_icon_theme(state: &Arc<AppState>) {
+    let look = look_for(&state.tray_config().icon_theme, || system_look(state));
+    {
+        let mut current = state.icon_look.write();
+        if *current == Some(look) {
+            return;
+        }
+        *current = Some(look);
+    }
+    info!("Tray icons: {:?}", look);
+    for (slot, png, dot) in [
+        (&state.idle_icon, IDLE_PNG, false),
+        (&state.speaking_icon, SPEAKING_PNG, false),
+        (&state.disconnected_icon, DISCONNECTED_PNG, true),
+    ] {
+        *slot.write() = load(png, look, dot);
+    }
+    state.badge_icons.lock().clear();
+    *state.shown_icon.lock() = None;
+    let speaking = state.is_speaking();
+    update_tray_icon(state, speaking);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recolor_keeps_alpha() {
+        let rgba = [255, 255, 255, 200, 10, 20, 30, 0];
+        assert_eq!(recolor(&rgba, [32, 32, 32]), vec![32, 32, 32, 200, 32, 32, 32, 0]);
+    }
+
+    #[test]
+    fn test_with_dot() {
+        let glyph = recolor(&[255, 255, 255, 255].repeat(32 * 32), [0, 0, 0]);
+        let img = RgbaImage::from_raw(32, 32, with_dot(&glyph, 32, 32, ACCENT).expect("dot")).expect("image");
+        // Dot center, clear ring around it, glyph untouched elsewhere
+        assert_eq!(img.get_pixel(23, 23), &Rgba([245, 158, 11, 255]));
+        assert_eq!(img.get_pixel(23, 15), &Rgba([0, 0, 0, 0]));
+        assert_eq!(img.get_pixel(2, 2), &Rgba([0, 0, 0, 255]));
+        assert_eq!(with_dot(&glyph, 16, 16, ACCENT), None);
+    }
+
+    #[test]
+    fn test_look_for() {
+        if cfg!(target_os = "macos") {
+            assert_eq!(look_for("light", || IconLook::Dark), IconLook::Template);
+            return;
+        }
+        assert_eq!(look_for("light", || IconLook::Dark), IconLook::Light);
+        assert_eq!(look_for("dark", || IconLook::Light), IconLook::Dark);
+        assert_eq!(look_for("auto", || IconLook::Light), IconLook::Light);
+    }
+}
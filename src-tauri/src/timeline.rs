@@ -0,0 +1,200 @@
+/// The timeline: entries in arrival order with an index by id, so status updates, replays,
+/// deletes and lookups by id do not walk the whole history. Also keeps the capacity (the
+/// oldest entries are evicted past it), the id counter, and a revision that changes with every
+/// change.
+use std::collections::{HashMap, VecDeque};
+
+use crate::state::VoiceEntry;
+
+pub struct Timeline {
+    order: VecDeque<u64>, // ids, oldest first; ids only grow, so this is sorted
+    entries: HashMap<u64, VoiceEntry>,
+    capacity: usize,
+    next_id: u64,
+    revision: u64,
+}
+
+impl Timeline {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            order: VecDeque::with_capacity(capacity),
+            entries: HashMap::with_capacity(capacity),
+            capacity,
+            next_id: 1,
+            revision: 0,
+        }
+    }
+
+    /// Add `entry` as the newest; returns the entries evicted to stay within the capacity
+    pub fn push(&mut self, entry: VoiceEntry) -> Vec<VoiceEntry> {
+        debug_assert!(self.order.back().is_none_or(|&last| last < entry.id), "timeline ids must grow");
+        self.order.push_back(entry.id);
+        self.entries.insert(entry.id, entry);
+        let mut evicted = Vec::new();
+        while self.order.len() > self.capacity {
+            evicted.extend(self.order.pop_front().and_then(|id| self.entries.remove(&id)));
+        }
+        self.revision += 1;
+        evicted
+    }
+
+    /// Take the id for a new entry. Taken and pushed under the one lock, entries go in in id
+    /// order.
+    pub fn take_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// The id the next entry will get
+    pub fn next_id(&self) -> u64 {
+        self.next_id
+    }
+
+    pub fn get(&self, id: u64) -> Option<&VoiceEntry> {
+        self.entries.get(&id)
+    }
+
+    /// Set an entry's status, and its detail unless `detail` is None. Returns the updated entry.
+    pub fn set_status(&mut self, id: u64, status: &str, detail: Option<String>) -> Option<VoiceEntry> {
+        let entry = self.entries.get_mut(&id)?;
+        entry.status = status.to_string();
+        if detail.is_some() {
+            entry.detail = detail;
+        }
+        self.revision += 1;
+        Some(entry.clone())
+    }
+
+    pub fn remove(&mut self, id: u64) -> Option<VoiceEntry> {
+        let entry = self.entries.remove(&id)?;
+        if let Ok(index) = self.order.binary_search(&id) {
+            self.order.remove(index);
+        }
+        self.revision += 1;
+        Some(entry)
+    }
+
+    /// Keep the entries `keep` accepts; returns the others, oldest first
+    pub fn retain(&mut self, mut keep: impl FnMut(&VoiceEntry) -> bool) -> Vec<VoiceEntry> {
+        let mut removed = Vec::new();
+        let entries = &mut self.entries;
+        self.order.retain(|id| {
+            if entries.get(id).is_none_or(&mut keep) {
+                return true;
+            }
+            removed.extend(entries.remove(id));
+            false
+        });
+        if !removed.is_empty() {
+            self.revision += 1;
+        }
+        removed
+    }
+
+    /// Oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &VoiceEntry> + '_ {
+        self.order.iter().filter_map(|id| self.entries.get(id))
+    }
+
+    pub fn last(&self) -> Option<&VoiceEntry> {
+        self.order.back().and_then(|id| self.entries.get(id))
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Changes with every push, status change and removal
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn entry(id: u64) -> VoiceEntry {
+        VoiceEntry {
+            id,
+            timestamp: Utc::now(),
+            text: format!("Message {}", id),
+            voice: "Samantha".to_string(),
+            rate: 220,
+            agent: None,
+            status: "queued".to_string(),
+            priority: 0,
+            detail: None,
+            replay_of: None,
+            relayed_from: None,
+        }
+    }
+
+    fn filled(count: u64) -> Timeline {
+        let mut timeline = Timeline::new(count as usize);
+        for id in 1..=count {
+            timeline.push(entry(id));
+        }
+        timeline
+    }
+
+    #[test]
+    fn test_push_evicts_oldest() {
+        let mut timeline = filled(3);
+        let evicted = timeline.push(entry(4));
+        assert_eq!(evicted.iter().map(|e| e.id).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(timeline.iter().map(|e| e.id).collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert!(timeline.get(1).is_none());
+        assert_eq!(timeline.last().map(|e| e.id), Some(4));
+    }
+
+    #[test]
+    fn test_status_remove_and_retain() {
+        let mut timeline = filled(5);
+        let revision = timeline.revision();
+        let done = timeline.set_status(2, "done", None).expect("entry");
+        assert_eq!((done.status.as_str(), done.detail), ("done", None));
+        timeline.set_status(3, "cancelled", Some("Not spoken".to_string()));
+        timeline.set_status(3, "cancelled", None);
+        assert_eq!(timeline.get(3).and_then(|e| e.detail.as_deref()), Some("Not spoken"));
+        assert!(timeline.set_status(9, "done", None).is_none());
+
+        assert_eq!(timeline.remove(4).map(|e| e.id), Some(4));
+        assert!(timeline.remove(4).is_none());
+        let removed = timeline.retain(|e| !e.is_finished());
+        assert_eq!(removed.iter().map(|e| e.id).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(timeline.iter().rev().map(|e| e.id).collect::<Vec<_>>(), vec![5, 1]);
+        assert_eq!(timeline.len(), 2);
+        assert!(timeline.revision() > revision);
+    }
+
+    /// After evictions the index holds exactly the entries still in order, so lookups and status
+    /// changes at either end find them by id and evicted ids are gone
+    #[test]
+    fn test_index_follows_evictions() {
+        let mut timeline = filled(1_000);
+        for id in 1_001..=1_010 {
+            timeline.push(entry(id));
+        }
+        assert_eq!((timeline.order.len(), timeline.entries.len()), (1_000, 1_000));
+        assert!(timeline.order.iter().all(|id| timeline.entries.get(id).map(|e| e.id) == Some(*id)));
+
+        for evicted in [1, 10] {
+            assert!(timeline.get(evicted).is_none());
+            assert!(timeline.set_status(evicted, "done", None).is_none());
+            assert!(!timeline.entries.contains_key(&evicted));
+        }
+        for id in [11, 1_010] {
+            assert_eq!(timeline.get(id).map(|e| e.id), Some(id));
+            assert_eq!(timeline.set_status(id, "done", None).map(|e| e.id), Some(id));
+            assert_eq!(timeline.entries[&id].status, "done");
+        }
+        assert_eq!(timeline.iter().filter(|e| e.status == "done").map(|e| e.id).collect::<Vec<_>>(), vec![11, 1_010]);
+    }
+}
@@ -0,0 +1,241 @@
+/// Daily transcripts of what was spoken, a record kept apart from the in-memory timeline and the
+/// app's own logs. With transcripts.enabled each entry that finishes "done" is appended to
+/// YYYY-MM-DD.md (the local date it finished) in the transcripts folder, with its time, agent
+/// and text; suppressed and failed entries are written with a marker when asked. Entries are
+/// gathered from the timeline bus and written every FLUSH_INTERVAL, off the speech queue, and
+/// files older than transcripts.keep_days are deleted once a day. Should the transcript fall
+/// behind the bus and miss events, a "(missed)" line marks where.
+use chrono::{DateTime, Local, NaiveDate};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::bus::{self, TimelineEvent};
+use crate::config::{config_dir, load_config, TranscriptConfig};
+use crate::error::{report_error, VoiceTrayError};
+use crate::state::AppState;
+use crate::watcher::expand_home;
+
+/// Folder in the config folder when transcripts.dir is unset
+const DEFAULT_DIR: &str = "transcripts";
+
+/// How long finished entries wait before they are written
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A finished entry waiting to be written
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    at: DateTime<Local>,
+    agent: Option<String>,
+    text: String,
+    /// "done", "suppressed" or "failed"; "missed" marks events lost on the bus
+    status: String,
+}
+
+/// Entries not written yet, and the day files were last pruned
+#[derive(Debug, Default)]
+pub struct Pending {
+    lines: Vec<Line>,
+    pruned_on: Option<NaiveDate>,
+}
+
+/// The folder transcripts are written to
+pub fn folder(config: &TranscriptConfig) -> PathBuf {
+    match (config.dir.as_deref().map(str::trim).filter(|dir| !dir.is_empty()), dirs::home_dir()) {
+        (Some(dir), Some(home)) => expand_home(dir, &home),
+        (Some(dir), None) => PathBuf::from(dir),
+        (None, _) => config_dir().join(DEFAULT_DIR),
+    }
+}
+
+/// Keep an entry that just finished, if transcripts take it
+fn collect(state: &AppState, event: &TimelineEvent) {
+    let TimelineEvent::StatusChanged { entry, at } = event else {
+        return;
+    };
+    if !matches!(entry.status.as_str(), "done" | "suppressed" | "failed") {
+        return;
+    }
+    let config = load_config().transcripts;
+    let wanted = match entry.status.as_str() {
+        "suppressed" => config.include_suppressed,
+        "failed" => config.include_failed,
+        _ => true,
+    };
+    if !config.enabled || !wanted {
+        return;
+    }
+    let line = Line { at: at.with_timezone(&Local), agent: entry.agent.clone(), text: entry.text.clone(), status: entry.status.clone() };
+    keep(state, line);
+}
+
+/// Mark where the transcript fell `events` behind the bus: entries finished then are lost
+fn missed(state: &AppState, events: u64) {
+    warn!("Transcripts fell behind and missed {} timeline events", events);
+    if !load_config().transcripts.enabled {
+        return;
+    }
+    let text = format!("{} timeline events were missed here, and the entries among them are not in this transcript", events);
+    keep(state, Line { at: Local::now(), agent: None, text, status: "missed".to_string() });
+}
+
+fn keep(state: &AppState, line: Line) {
+    state.transcript.lock().lines.push(line);
+}
+
+/// Write the entries gathered so far, and prune old files on the first write of a day
+pub fn flush(state: &AppState) {
+    let today = Local::now().date_naive();
+    let (lines, prune_due) = {
+        let mut pending = state.transcript.lock();
+        let prune_due = pending.pruned_on != Some(today);
+        pending.pruned_on = Some(today);
+        (std::mem::take(&mut pending.lines), prune_due)
+    };
+    let config = load_config().transcripts;
+    if !config.enabled {
+        return;
+    }
+    let dir = folder(&config);
+    if let Err(e) = write(&dir, &lines) {
+        report_error(state, "Writing the transcript", &VoiceTrayError::Io(e));
+    }
+    if prune_due && config.keep_days > 0 {
+        if let Err(e) = prune(&dir, today, config.keep_days) {
+            report_error(state, "Pruning transcripts", &VoiceTrayError::Io(e));
+        }
+    }
+}
+
+/// Append `lines` to the file of the day each finished on, making the folder and files as needed
+fn write(dir: &Path, lines: &[Line]) -> io::Result<()> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+    fs::create_dir_all(dir)?;
+    for day in lines.chunk_by(|a, b| a.at.date_naive() == b.at.date_naive()) {
+        let date = day[0].at.date_naive();
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(dir.join(format!("{}.md", date)))?;
+        let mut out = String::new();
+        if file.metadata()?.len() == 0 {
+            out.push_str(&format!("# Spoken on {}\n\n", date.format("%A, %-d %B %Y")));
+        }
+        for line in day {
+            out.push_str(&format_line(line));
+            out.push('\n');
+        }
+        file.write_all(out.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// "- 09:12:40 **ci**: Build passed", with "(failed)" or "(suppressed)" after the agent
+fn format_line(line: &Line) -> String {
+    let time = line.at.format("%H:%M:%S");
+    let text = line.text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let marker = match line.status.as_str() {
+        "done" => String::new(),
+        status => format!(" ({})", status),
+    };
+    match &line.agent {
+        Some(agent) => format!("- {} **{}**{}: {}", time, agent, marker, text),
+        None if marker.is_empty() => format!("- {} {}", time, text),
+        None => format!("- {}{}: {}", time, marker, text),
+    }
+}
+
+/// Delete the day files older than the last `keep_days` days; other files are left alone
+fn prune(dir: &Path, today: NaiveDate, keep_days: u32) -> io::Result<usize> {
+    let oldest = today - chrono::Days::new(u64::from(keep_days.saturating_sub(1)));
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut removed = 0;
+    for entry in entries {
+        let path = entry?.path();
+        let date = path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".md"))
+            .and_then(|stem| NaiveDate::parse_from_str(stem, "%Y-%m-%d").ok());
+        if date.is_some_and(|date| date < oldest) {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Gather finished entries and write them every FLUSH_INTERVAL; runs as the "transcripts" task
+pub async fn record(state: Arc<AppState>) {
+    // Not bus::consume: a lag is written into the transcript, not only logged
+    let collecting = async {
+        let mut events = bus::subscribe(&state);
+        loop {
+            match events.recv().await {
+                Ok(event) => collect(&state, &event),
+                Err(RecvError::Lagged(events)) => missed(&state, events),
+                Err(RecvError::Closed) => return,
+            }
+        }
+    };
+    let writing = async {
+        loop {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+            let state = state.clone();
+            if let Err(e) = tokio::task::spawn_blocking(move || flush(&state)).await {
+                warn!("Writing the transcript did not finish: {}", e);
+            }
+        }
+    };
+    tokio::join!(collecting, writing);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn line(at: DateTime<Local>, agent: Option<&str>, text: &str, status: &str) -> Line {
+        Line { at, agent: agent.map(str::to_string), text: text.to_string(), status: status.to_string() }
+    }
+
+    #[test]
+    fn test_write_across_midnight() {
+        let dir = TempDir::new().expect("create temp dir");
+        let before = Local.with_ymd_and_hms(2026, 10, 16, 23, 59, 58).single().expect("time");
+        let after = Local.with_ymd_and_hms(2026, 10, 17, 0, 0, 3).single().expect("time");
+        write(dir.path(), &[line(before, Some("ci"), "Build\npassed", "done"), line(before, None, "Hello", "done")]).expect("write");
+        write(dir.path(), &[line(before, Some("ntfy"), "Disk full", "failed"), line(after, None, "Deploy done", "suppressed")]).expect("write");
+        write(dir.path(), &[line(after, None, "3 timeline events were missed", "missed")]).expect("write");
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join("2026-10-16.md")).expect("read"),
+            "# Spoken on Friday, 16 October 2026\n\n- 23:59:58 **ci**: Build passed\n- 23:59:58 Hello\n- 23:59:58 **ntfy** (failed): Disk full\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("2026-10-17.md")).expect("read"),
+            "# Spoken on Saturday, 17 October 2026\n\n- 00:00:03 (suppressed): Deploy done\n- 00:00:03 (missed): 3 timeline events were missed\n"
+        );
+    }
+
+    #[test]
+    fn test_prune_keeps_recent_days() {
+        let dir = TempDir::new().expect("create temp dir");
+        for name in ["2026-10-14.md", "2026-10-15.md", "2026-10-16.md", "notes.md", "2026-01-01.txt"] {
+            fs::write(dir.path().join(name), "x").expect("write");
+        }
+        let today = NaiveDate::from_ymd_opt(2026, 10, 16).expect("date");
+        assert_eq!(prune(dir.path(), today, 2).expect("prune"), 1);
+        let mut left: Vec<String> = fs::read_dir(dir.path()).expect("read").map(|e| e.expect("entry").file_name().to_string_lossy().into_owned()).collect();
+        left.sort();
+        assert_eq!(left, ["2026-01-01.txt", "2026-10-15.md", "2026-10-16.md", "notes.md"]);
+        assert_eq!(prune(&dir.path().join("missing"), today, 2).expect("prune"), 0);
+    }
+}
@@ -1,41 +1,660 @@
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::process::Command;
 
-use crate::state::AppState;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::{
+    image::Image,
+    menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu},
+    AppHandle, Manager, Wry,
+};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use crate::autostart;
+use crate::badge::{badge_label, composite_badge};
+use crate::bus::{self, TimelineEvent};
+use crate::config::{active_profile, config_warning, list_profiles, save_tray_config_to_file, save_voice_config_to_file, TrayConfig, VoiceConfig};
+use crate::error::{report, report_error, VoiceTrayError};
+use crate::focus;
+use crate::hotkeys;
+use crate::i18n::{self, Phrase};
+use crate::lock::Lock;
+use crate::notifications;
+use crate::prepare;
+use crate::relay;
+use crate::snooze;
+use crate::state::{AppState, CopyOutcome, SpeakRequest, SpeakResponse};
+use crate::theme;
+#[cfg(target_os = "macos")]
+use crate::voices;
+
+/// Minimum time between tooltip updates
+const TOOLTIP_THROTTLE: Duration = Duration::from_millis(250);
+
+/// How often a playing speech command checks for a stop request
+const STOP_POLL: Duration = Duration::from_millis(50);
+
+/// Done entries listed in the Recent submenu
+const RECENT_MAX: usize = 8;
+
+/// Characters of entry text shown in a Recent item
+const RECENT_TEXT_CHARS: usize = 40;
+
+/// Menu id prefix of Recent items; the rest is the entry id
+pub const RECENT_ID_PREFIX: &str = "recent:";
+
+/// Menu id prefix of Snooze lengths; the rest is the number of minutes
+pub const SNOOZE_ID_PREFIX: &str = "snooze:";
+
+/// Menu id prefix of Voice items; the rest is the voice name
+pub const VOICE_ID_PREFIX: &str = "voice:";
+
+/// Menu id prefix of Profile items; the rest is the profile name
+pub const PROFILE_ID_PREFIX: &str = "profile:";
 
 /// Update tray icon based on speaking state and MQTT connection
-/// Uses a specific lock order to prevent deadlocks: mqtt_status -> icons -> tray_icon
 pub fn update_tray_icon(state: &Arc<AppState>, speaking: bool) {
-    let mqtt_status = match state.mqtt_status.lock() {
-        Ok(guard) => guard.clone(),
-        Err(_) => return,
+    // Called on every speaking and MQTT transition; the tooltip follows the same changes
+    state.notify_changed();
+    let queued = TraySnapshot::from_state(state).queued;
+    render_tray_icon(state, speaking, queued);
+}
+
+/// Set the icon for the MQTT and speaking state, badged with the queued count.
+/// Skipped when the tray already shows that icon.
+/// Holds tray_icon while recording shown_icon, the order lock.rs gives for them
+fn render_tray_icon(state: &AppState, speaking: bool, queued: usize) {
+    let mqtt_status = state.mqtt_status.read().clone();
+
+    // Turned-off MQTT is not a fault, so it keeps the normal icons
+    let (base, base_icon) = if mqtt_status != "connected" && mqtt_status != "disabled" {
+        ("disconnected", &state.disconnected_icon)
+    } else if speaking {
+        ("speaking", &state.speaking_icon)
+    } else {
+        ("idle", &state.idle_icon)
+    };
+    let label = badge_label(queued);
+    let key = (base, label.clone());
+    if state.shown_icon.lock().as_ref() == Some(&key) {
+        return;
+    }
+    let icon = base_icon.read().clone();
+    let icon = match (icon, label) {
+        (Some(img), Some(label)) => Some(badged_icon(state, base, &img, &label)),
+        (icon, None) => icon,
+        (None, Some(_)) => None,
     };
 
-    let icon = if mqtt_status != "connected" {
-        match state.disconnected_icon.lock() {
-            Ok(guard) => guard.clone(),
-            Err(_) => return,
+    if let Some(ref tray) = *state.tray_icon.lock() {
+        if let Some(img) = icon {
+            if report(state, "Setting the tray icon", tray.set_icon(Some(img))).is_some() {
+                *state.shown_icon.lock() = Some(key);
+            }
         }
-    } else if speaking {
-        match state.speaking_icon.lock() {
-            Ok(guard) => guard.clone(),
-            Err(_) => return,
+    }
+}
+
+/// Base icon with a count badge, composited once per (base, label)
+fn badged_icon(state: &AppState, base: &'static str, img: &Image<'static>, label: &str) -> Image<'static> {
+    let key = (base, label.to_string());
+    if let Some(cached) = state.badge_icons.lock().get(&key).cloned() {
+        return cached;
+    }
+    let template = cfg!(target_os = "macos");
+    let Some(rgba) = composite_badge(img.rgba(), img.width(), img.height(), label, template) else {
+        return img.clone();
+    };
+    let badged = Image::new_owned(rgba, img.width(), img.height());
+    state.badge_icons.lock().insert(key, badged.clone());
+    badged
+}
+
+/// What the tooltip shows, read from AppState in one go; also the voice://status payload,
+/// named like the matching get_status fields
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TraySnapshot {
+    pub queued: usize,
+    #[serde(rename = "is_speaking")]
+    pub speaking: bool,
+    pub mqtt_status: String,
+    pub muted: bool,
+    #[serde(rename = "watcher_paused")]
+    pub paused: bool,
+    pub queue_paused: bool,
+    pub snooze_minutes_left: Option<i64>,
+    /// Set while config.json is from a newer version and not being saved
+    pub config_warning: Option<String>,
+}
+
+impl TraySnapshot {
+    pub fn from_state(state: &AppState) -> Self {
+        Self {
+            queued: state.timeline.lock().iter().filter(|e| e.status == "queued").count(),
+            speaking: state.is_speaking(),
+            mqtt_status: state.mqtt_status.read().clone(),
+            muted: state.is_muted(),
+            paused: state.watcher_status.lock().paused,
+            queue_paused: state.is_queue_paused(),
+            snooze_minutes_left: state.snooze_minutes_left(Utc::now()),
+            config_warning: config_warning(),
         }
+    }
+}
+
+/// "3 queued • speaking • MQTT ok", with "queue paused", "watcher paused" and " (muted)" or
+/// " (snoozed, 25m left)" when set, and a warning line when config.json is from a newer version
+pub fn tray_tooltip(snapshot: &TraySnapshot) -> String {
+    let mut parts = Vec::new();
+    if snapshot.queued > 0 {
+        parts.push(format!("{} queued", snapshot.queued));
+    }
+    if snapshot.speaking {
+        parts.push("speaking".to_string());
+    } else if snapshot.queued == 0 {
+        parts.push("idle".to_string());
+    }
+    parts.push(match snapshot.mqtt_status.as_str() {
+        "connected" => "MQTT ok".to_string(),
+        "disconnected" | "" => "MQTT offline".to_string(),
+        other => format!("MQTT {}", other),
+    });
+    if snapshot.queue_paused {
+        parts.push("queue paused".to_string());
+    }
+    if snapshot.paused {
+        parts.push("watcher paused".to_string());
+    }
+    let text = parts.join(" • ");
+    let text = match snapshot.snooze_minutes_left {
+        _ if snapshot.muted => format!("{} (muted)", text),
+        Some(left) => format!("{} (snoozed, {} left)", text, snooze::left_label(left)),
+        None => text,
+    };
+    match snapshot.config_warning {
+        Some(_) => format!("{}\n⚠ config.json is from a newer version, settings not saved", text),
+        None => text,
+    }
+}
+
+/// Disabled first line of the tray menu: "3 queued • connected", "speaking • MQTT off"
+pub fn menu_header(snapshot: &TraySnapshot) -> String {
+    let activity = if snapshot.queued > 0 {
+        format!("{} queued", snapshot.queued)
+    } else if snapshot.speaking {
+        "speaking".to_string()
+    } else {
+        "idle".to_string()
+    };
+    let connection = match snapshot.mqtt_status.as_str() {
+        "connected" => "connected",
+        "connecting" => "connecting…",
+        "disabled" => "MQTT off",
+        _ => "offline",
+    };
+    if snapshot.queue_paused {
+        format!("{} (paused) • {}", activity, connection)
     } else {
-        match state.idle_icon.lock() {
-            Ok(guard) => guard.clone(),
-            Err(_) => return,
+        format!("{} • {}", activity, connection)
+    }
+}
+
+fn pause_queue_label(paused: bool) -> &'static str {
+    if paused { "Resume Queue" } else { "Pause Queue" }
+}
+
+/// Build the tray menu and keep handles to the items that follow state. Every item is
+/// created here, so the set and order are the same on all platforms; refresh_menu only
+/// changes labels and enabled flags, and Reconnect MQTT comes and goes with MQTT.
+pub fn build_tray_menu<M: Manager<Wry>>(app: &M, state: &AppState) -> tauri::Result<Menu<Wry>> {
+    let snapshot = TraySnapshot::from_state(state);
+    let header_item = MenuItem::with_id(app, "header", menu_header(&snapshot), false, None::<&str>)?;
+    let header_separator = PredefinedMenuItem::separator(app)?;
+    let stop_item = MenuItem::with_id(app, "stop", "Stop Speaking", snapshot.speaking, None::<&str>)?;
+    let pause_item = MenuItem::with_id(app, "pause_queue", pause_queue_label(snapshot.queue_paused), true, None::<&str>)?;
+    let copy_item = MenuItem::with_id(app, "copy_last", "Copy Last Message", state.clipboard_text(None).is_some(), None::<&str>)?;
+    let clipboard_item = MenuItem::with_id(app, "speak_clipboard", "Speak Clipboard", true, None::<&str>)?;
+    // Recent is refilled by the tray updater; the items below it never move
+    let recent_menu = Submenu::with_id(app, "recent", "Recent", true)?;
+    recent_menu.append(&MenuItem::with_id(app, "recent_empty", "No messages yet", false, None::<&str>)?)?;
+    let export_item = MenuItem::with_id(app, "export", "Export Timeline…", true, None::<&str>)?;
+    let recent_separator = PredefinedMenuItem::separator(app)?;
+    let watcher_item = CheckMenuItem::with_id(app, "watcher", "Watch Claude Sessions", true, !snapshot.paused, None::<&str>)?;
+    let mute_item = CheckMenuItem::with_id(app, "mute", "Mute", true, snapshot.muted, None::<&str>)?;
+    let snooze_menu = Submenu::with_id(app, "snooze", snooze_label(snapshot.snooze_minutes_left), true)?;
+    fill_snooze_menu(app, &snooze_menu, &state.tray_config().snooze_minutes, snapshot.snooze_minutes_left.is_some())?;
+    let voice_menu = Submenu::with_id(app, "voice", "Voice", true)?;
+    fill_voice_menu(app, &voice_menu, &state.voice_config())?;
+    let profile_menu = Submenu::with_id(app, "profile", "Profile", true)?;
+    fill_profile_menu(app, &profile_menu)?;
+    let start_at_login = state.tray_config().start_at_login;
+    let autostart_item = CheckMenuItem::with_id(app, "autostart", "Start at Login", true, start_at_login, None::<&str>)?;
+    let tts_available = *state.tts_available.lock();
+    let test_item = MenuItem::with_id(app, "test_voice", test_voice_label(tts_available), tts_available, None::<&str>)?;
+    let reconnect_item = MenuItem::with_id(app, "reconnect", "Reconnect MQTT", true, None::<&str>)?;
+    let settings_item = MenuItem::with_id(app, "settings", "Open Settings…", true, None::<&str>)?;
+    let log_item = MenuItem::with_id(app, "log_folder", "Open Log Folder", true, None::<&str>)?;
+    let transcripts_item = MenuItem::with_id(app, "transcripts_folder", "Open Transcripts Folder", true, None::<&str>)?;
+    let separator = PredefinedMenuItem::separator(app)?;
+    let quit_item = MenuItem::with_id(app, "quit", "Quit Oracle Voice Tray", true, None::<&str>)?;
+    let menu = Menu::with_items(app, &[
+        &header_item, &header_separator, &stop_item, &pause_item, &copy_item, &clipboard_item, &recent_menu, &export_item, &recent_separator,
+        &watcher_item, &mute_item, &snooze_menu, &voice_menu, &profile_menu, &autostart_item, &test_item, &reconnect_item, &settings_item,
+        &log_item, &transcripts_item, &separator, &quit_item,
+    ])?;
+    if snapshot.mqtt_status == "disabled" {
+        menu.remove(&reconnect_item)?;
+    }
+
+    let store = |slot: &Lock<Option<MenuItem<Wry>>>, item: MenuItem<Wry>| {
+        *slot.lock() = Some(item);
+    };
+    store(&state.header_menu_item, header_item);
+    store(&state.stop_menu_item, stop_item);
+    store(&state.pause_menu_item, pause_item);
+    store(&state.copy_menu_item, copy_item);
+    store(&state.reconnect_menu_item, reconnect_item);
+    *state.watcher_menu_item.lock() = Some(watcher_item);
+    *state.mute_menu_item.lock() = Some(mute_item);
+    *state.autostart_menu_item.lock() = Some(autostart_item);
+    *state.recent_menu.lock() = Some(recent_menu);
+    *state.snooze_menu.lock() = Some(snooze_menu);
+    *state.voice_menu.lock() = Some(voice_menu);
+    *state.profile_menu.lock() = Some(profile_menu);
+    *state.tray_menu.lock() = Some(menu.clone());
+    Ok(menu)
+}
+
+fn snooze_label(minutes_left: Option<i64>) -> String {
+    match minutes_left {
+        Some(left) => format!("Snoozed ({} left)", snooze::left_label(left)),
+        None => "Snooze".to_string(),
+    }
+}
+
+/// One item per configured length, then Cancel Snooze (enabled while snoozed)
+fn fill_snooze_menu<M: Manager<Wry>>(app: &M, menu: &Submenu<Wry>, lengths: &[u32], snoozed: bool) -> tauri::Result<()> {
+    for minutes in lengths {
+        let id = format!("{}{}", SNOOZE_ID_PREFIX, minutes);
+        menu.append(&MenuItem::with_id(app, id, snooze::duration_label(*minutes), true, None::<&str>)?)?;
+    }
+    menu.append(&PredefinedMenuItem::separator(app)?)?;
+    menu.append(&MenuItem::with_id(app, "snooze_cancel", "Cancel Snooze", snoozed, None::<&str>)?)?;
+    Ok(())
+}
+
+/// Empty a stored submenu and fill it again, e.g. after the settings it lists were saved
+fn refill_submenu(
+    state: &AppState,
+    slot: &Lock<Option<Submenu<Wry>>>,
+    fill: impl FnOnce(&AppHandle, &Submenu<Wry>) -> tauri::Result<()>,
+) -> tauri::Result<()> {
+    let Some(app) = state.app_handle.read().clone() else {
+        return Ok(());
+    };
+    let Some(menu) = slot.lock().clone() else {
+        return Ok(());
+    };
+    while menu.remove_at(0)?.is_some() {}
+    fill(&app, &menu)
+}
+
+fn rebuild_snooze_menu(state: &AppState) -> tauri::Result<()> {
+    let snoozed = state.snooze_minutes_left(Utc::now()).is_some();
+    refill_submenu(state, &state.snooze_menu, |app, menu| {
+        fill_snooze_menu(app, menu, &state.tray_config().snooze_minutes, snoozed)
+    })
+}
+
+/// The Voice submenu's voices: the default first unless it is a favorite, then the favorites
+fn voice_shortlist(config: &VoiceConfig) -> Vec<String> {
+    let mut voices: Vec<String> = Vec::new();
+    let default = config.voice();
+    if !config.favorite_voices.iter().any(|v| v.eq_ignore_ascii_case(&default)) {
+        voices.push(default);
+    }
+    for voice in &config.favorite_voices {
+        if !voices.iter().any(|v| v.eq_ignore_ascii_case(voice)) {
+            voices.push(voice.clone());
+        }
+    }
+    voices
+}
+
+/// One check item per voice, the default checked; voices the speech command lacks are disabled
+fn fill_voice_menu<M: Manager<Wry>>(app: &M, menu: &Submenu<Wry>, config: &VoiceConfig) -> tauri::Result<()> {
+    let default = config.voice();
+    for voice in voice_shortlist(config) {
+        let installed = voice_installed(&voice);
+        let label = if installed { voice.clone() } else { format!("{} (not installed)", voice) };
+        let checked = voice.eq_ignore_ascii_case(&default);
+        let id = format!("{}{}", VOICE_ID_PREFIX, voice);
+        menu.append(&CheckMenuItem::with_id(app, id, label, installed, checked, None::<&str>)?)?;
+    }
+    if config.favorite_voices.is_empty() {
+        menu.append(&PredefinedMenuItem::separator(app)?)?;
+        menu.append(&MenuItem::with_id(app, "voice_hint", "Add favorites in Settings", false, None::<&str>)?)?;
+    }
+    Ok(())
+}
+
+fn rebuild_voice_menu(state: &AppState) -> tauri::Result<()> {
+    refill_submenu(state, &state.voice_menu, |app, menu| fill_voice_menu(app, menu, &state.voice_config()))
+}
+
+/// One check item per profile, the active one checked
+fn fill_profile_menu<M: Manager<Wry>>(app: &M, menu: &Submenu<Wry>) -> tauri::Result<()> {
+    let active = active_profile();
+    for name in list_profiles() {
+        let id = format!("{}{}", PROFILE_ID_PREFIX, name);
+        menu.append(&CheckMenuItem::with_id(app, id, &name, true, name == active, None::<&str>)?)?;
+    }
+    Ok(())
+}
+
+/// Refill the Profile submenu after a switch, or after profiles were added or deleted
+pub fn rebuild_profile_menu(state: &AppState) {
+    report(state, "Updating the Profile menu", refill_submenu(state, &state.profile_menu, fill_profile_menu));
+}
+
+/// Bring the state-dependent items in line with `snapshot`. The items are changed in place:
+/// replacing the menu would close it if the user has it open.
+fn refresh_menu(state: &AppState, snapshot: &TraySnapshot) -> tauri::Result<()> {
+    let item = |slot: &Lock<Option<MenuItem<Wry>>>| slot.lock().clone();
+    if let Some(header) = item(&state.header_menu_item) {
+        header.set_text(menu_header(snapshot))?;
+    }
+    if let Some(stop) = item(&state.stop_menu_item) {
+        stop.set_enabled(snapshot.speaking)?;
+    }
+    if let Some(pause) = item(&state.pause_menu_item) {
+        pause.set_text(pause_queue_label(snapshot.queue_paused))?;
+    }
+    if let Some(snooze_menu) = state.snooze_menu.lock().clone() {
+        snooze_menu.set_text(snooze_label(snapshot.snooze_minutes_left))?;
+        if let Some(cancel) = snooze_menu.get("snooze_cancel").and_then(|i| i.as_menuitem().cloned()) {
+            cancel.set_enabled(snapshot.snooze_minutes_left.is_some())?;
         }
+    }
+    let (Some(menu), Some(reconnect)) = (
+        state.tray_menu.lock().clone(),
+        item(&state.reconnect_menu_item),
+    ) else {
+        return Ok(());
+    };
+    // Only touched when MQTT is turned on or off, which happens from the settings window
+    let shown = menu.get("reconnect").is_some();
+    let wanted = snapshot.mqtt_status != "disabled";
+    if shown && !wanted {
+        menu.remove(&reconnect)?;
+    } else if wanted && !shown {
+        let settings_position = menu.items()?.iter().position(|i| i.id() == "settings");
+        menu.insert(&reconnect, settings_position.unwrap_or(0))?;
+    }
+    Ok(())
+}
+
+/// Menu bar title next to the icon: the queued count, none when the queue is empty
+pub fn tray_title(snapshot: &TraySnapshot) -> Option<String> {
+    (snapshot.queued > 0).then(|| snapshot.queued.to_string())
+}
+
+/// "just now", "5m ago", "2h ago", "3d ago"
+fn relative_time(at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let secs = (now - at).num_seconds().max(0);
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+/// Menu label for a Recent entry: one line, truncated, with `&` escaped so it is not read as a
+/// mnemonic, then its age
+pub fn recent_label(text: &str, at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let flat: String = text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect();
+    let short = if flat.chars().count() > RECENT_TEXT_CHARS {
+        format!("{}…", flat.chars().take(RECENT_TEXT_CHARS).collect::<String>().trim_end())
+    } else {
+        flat
     };
+    format!("{} · {}", short.replace('&', "&&"), relative_time(at, now))
+}
+
+/// Last done entries as (id, label), newest first
+fn recent_items(state: &AppState) -> Vec<(u64, String)> {
+    let now = Utc::now();
+    state.timeline.lock().iter()
+        .rev()
+        .filter(|e| e.status == "done")
+        .take(RECENT_MAX)
+        .map(|e| (e.id, recent_label(&e.text, e.timestamp, now)))
+        .collect()
+}
+
+/// Replace the Recent submenu's items; the rest of the menu is left alone
+fn rebuild_recent_menu(state: &AppState, items: &[(u64, String)]) -> tauri::Result<()> {
+    let Some(app) = state.app_handle.read().clone() else {
+        return Ok(());
+    };
+    let Some(menu) = state.recent_menu.lock().clone() else {
+        return Ok(());
+    };
+    while menu.remove_at(0)?.is_some() {}
+    if items.is_empty() {
+        menu.append(&MenuItem::with_id(&app, "recent_empty", "No messages yet", false, None::<&str>)?)?;
+    }
+    for (id, label) in items {
+        menu.append(&MenuItem::with_id(&app, format!("{}{}", RECENT_ID_PREFIX, id), label, true, None::<&str>)?)?;
+    }
+    Ok(())
+}
 
-    if let Ok(tray_guard) = state.tray_icon.lock() {
-        if let Some(ref tray) = *tray_guard {
-            if let Some(img) = icon {
-                let _ = tray.set_icon(Some(img));
+/// Keep the tooltip, the macOS title, the menu items and the Recent submenu current: wakes on
+/// AppState::notify_changed, at most once per TOOLTIP_THROTTLE
+pub fn start_tray_updater(state: Arc<AppState>) {
+    std::thread::spawn(move || {
+        let mut shown_tooltip = String::new();
+        let mut shown_title = None;
+        let mut shown_recent = Vec::new();
+        let mut shown_snapshot = TraySnapshot::default();
+        loop {
+            state.wait_changed();
+            // Let a burst of changes settle into one update
+            std::thread::sleep(TOOLTIP_THROTTLE);
+            let snapshot = TraySnapshot::from_state(&state);
+            if snapshot != shown_snapshot {
+                bus::publish(&state, TimelineEvent::StatusSnapshot { snapshot: snapshot.clone() });
+                report(&state, "Updating the tray menu", refresh_menu(&state, &snapshot));
+                shown_snapshot = snapshot.clone();
+            }
+            let tooltip = tray_tooltip(&snapshot);
+            let title = if state.tray_config().show_queue_count { tray_title(&snapshot) } else { None };
+            // Enqueues and finished entries change the badge without an icon transition
+            render_tray_icon(&state, snapshot.speaking, snapshot.queued);
+            let recent = recent_items(&state);
+            if recent != shown_recent {
+                // Recent lists the same done entries Copy Last Message picks from
+                if let Some(copy) = state.copy_menu_item.lock().clone() {
+                    report(&state, "Updating Copy Last Message", copy.set_enabled(!recent.is_empty()));
+                }
+                if report(&state, "Updating the Recent menu", rebuild_recent_menu(&state, &recent)).is_some() {
+                    shown_recent = recent;
+                }
+            }
+            if tooltip == shown_tooltip && title == shown_title {
+                continue;
+            }
+            if let Some(ref tray) = *state.tray_icon.lock() {
+                report(&state, "Setting the tray tooltip", tray.set_tooltip(Some(&tooltip)));
+                // Only macOS draws a title in the menu bar
+                #[cfg(target_os = "macos")]
+                report(&state, "Setting the tray title", tray.set_title(title.as_deref()));
+                shown_tooltip = tooltip;
+                shown_title = title;
             }
         }
+    });
+}
+
+/// Put an entry's text on the clipboard, formatted per the ui section's copy_format: entry `id`,
+/// or without one the last message spoken
+pub fn copy_to_clipboard(state: &AppState, id: Option<u64>) -> Result<CopyOutcome, String> {
+    let Some(text) = state.clipboard_text(id) else {
+        return Ok(CopyOutcome::NothingToCopy);
+    };
+    let app = state.app_handle.read().clone()
+        .ok_or_else(|| "app not started".to_string())?;
+    app.clipboard().write_text(text).map_err(|e| format!("Failed to copy: {}", e))?;
+    Ok(CopyOutcome::Copied)
+}
+
+/// Agent of the entries Speak Clipboard queues
+pub const CLIPBOARD_AGENT: &str = "clipboard";
+
+/// Longest clipboard text read out (about ten minutes); the rest is cut with a notice
+pub const CLIPBOARD_MAX_CHARS: usize = 12_000;
+
+/// Read the clipboard aloud: markdown and URLs taken out, capped at CLIPBOARD_MAX_CHARS and
+/// queued with agent "clipboard". An empty clipboard, or one without text, gets a notification.
+pub fn speak_clipboard(state: &AppState) -> Result<SpeakResponse, String> {
+    let app = state.app_handle.read().clone()
+        .ok_or_else(|| "app not started".to_string())?;
+    let text = app.clipboard().read_text().map(|text| prepare::clean(&text)).unwrap_or_default();
+    if text.is_empty() {
+        notifications::notify(state, "Nothing to read", "The clipboard has no text");
+        return Err("The clipboard has no text".to_string());
+    }
+    let req = SpeakRequest {
+        text: prepare::cap(&text, CLIPBOARD_MAX_CHARS),
+        voice: None,
+        agent: Some(CLIPBOARD_AGENT.to_string()),
+        rate: None,
+    };
+    Ok(state.speak(req))
+}
+
+/// Hold or release the queue; unlike mute, watcher alerts are still spoken once released
+pub fn set_queue_paused(state: &AppState, paused: bool) {
+    state.queue_paused.store(paused, Ordering::Relaxed);
+    state.notify_changed();
+}
+
+/// Mute or unmute from any surface, keeping the menu check mark, tooltip and config.json in step
+pub fn set_muted(state: &Arc<AppState>, muted: bool) -> Result<(), String> {
+    show_muted(state, muted);
+    let config = {
+        let mut config = state.tray_config.write();
+        config.muted = muted;
+        config.clone()
+    };
+    save_tray_config_to_file(&config)
+}
+
+/// Make `voice` the default from the Voice submenu. The check mark moves to it, and it says its
+/// own name unless announce_voice_switch is off or the tray is muted or snoozed.
+pub fn set_default_voice(state: &Arc<AppState>, voice: &str) -> Result<(), String> {
+    let config = {
+        let mut config = state.voice_config.write();
+        config.default_voice = voice.to_string();
+        config.clone()
+    };
+    save_voice_config_to_file(&config)?;
+    // Also puts the check mark back when the checked item was clicked again
+    report(state, "Updating the Voice menu", rebuild_voice_menu(state));
+    if config.announce_voice_switch && !state.silenced() {
+        state.enqueue(voice.to_string(), voice.to_string(), config.default_rate, Some("Voice".to_string()), 0, None);
+    }
+    Ok(())
+}
+
+/// Save the ui settings and apply them at once: queue count title, mute, hotkeys and icon
+pub fn apply_tray_config(state: &Arc<AppState>, config: TrayConfig) -> Result<(), String> {
+    if config.start_at_login != autostart::is_enabled(state) {
+        apply_start_at_login(state, config.start_at_login)?;
+    }
+    save_tray_config_to_file(&config)?;
+    use_tray_config(state, config);
+    Ok(())
+}
+
+/// Apply ui settings that are already saved, e.g. reloaded after an edit of config.json
+pub fn use_tray_config(state: &Arc<AppState>, config: TrayConfig) {
+    if config.start_at_login != autostart::is_enabled(state) {
+        // A failure is notified and the check mark keeps what the system reports
+        let _ = apply_start_at_login(state, config.start_at_login);
+    }
+    let muted = config.muted;
+    let previous = state.tray_config();
+    let lengths_changed = config.snooze_minutes != previous.snooze_minutes;
+    *state.tray_config.write() = config;
+    show_muted(state, muted);
+    if lengths_changed {
+        report(state, "Updating the Snooze menu", rebuild_snooze_menu(state));
+    }
+    hotkeys::apply(state);
+    theme::apply_icon_theme(state);
+}
+
+/// Save the voice defaults and apply them
+pub fn apply_voice_config(state: &AppState, config: VoiceConfig) -> Result<(), String> {
+    save_voice_config_to_file(&config)?;
+    use_voice_config(state, config);
+    Ok(())
+}
+
+/// Apply voice defaults that are already saved; the Voice submenu follows when the default or
+/// the favorites changed
+pub fn use_voice_config(state: &AppState, config: VoiceConfig) {
+    let previous = state.voice_config();
+    let voices_changed = config.favorite_voices != previous.favorite_voices || config.default_voice != previous.default_voice;
+    *state.voice_config.write() = config;
+    if voices_changed {
+        report(state, "Updating the Voice menu", rebuild_voice_menu(state));
+    }
+}
+
+/// Start at Login from the tray menu or settings. Failures (e.g. sandbox restrictions) are
+/// shown as a notification and the check mark goes back to what the system reports.
+pub fn set_start_at_login(state: &Arc<AppState>, enabled: bool) -> Result<(), String> {
+    apply_start_at_login(state, enabled)?;
+    let mut config = state.tray_config();
+    config.start_at_login = enabled;
+    save_tray_config_to_file(&config)?;
+    *state.tray_config.write() = config;
+    Ok(())
+}
+
+fn apply_start_at_login(state: &Arc<AppState>, enabled: bool) -> Result<(), String> {
+    let result = autostart::set_enabled(state, enabled);
+    if let Err(ref e) = result {
+        notifications::notify(state, "Start at Login failed", e);
+    }
+    if let Some(ref item) = *state.autostart_menu_item.lock() {
+        report(state, "Checking Start at Login", item.set_checked(autostart::is_enabled(state)));
     }
+    result
+}
+
+/// Set the mute flag and its check mark; the tooltip follows through notify_changed.
+/// Muting or unmuting ends a snooze.
+fn show_muted(state: &Arc<AppState>, muted: bool) {
+    let changed = state.muted.swap(muted, Ordering::Relaxed) != muted;
+    if changed {
+        snooze::cancel(state);
+    }
+    if let Some(ref item) = *state.mute_menu_item.lock() {
+        report(state, "Checking Mute", item.set_checked(muted));
+    }
+    state.notify_changed();
 }
 
 /// Map voice name to Windows SAPI voice (David=male, Zira=female)
@@ -56,9 +675,9 @@ fn wpm_to_sapi_rate(wpm: u32) -> i32 {
     (delta / 15).clamp(-10, 10)
 }
 
-/// Speak text using Windows SAPI via PowerShell (hidden — CREATE_NO_WINDOW)
+/// Speech command using Windows SAPI via PowerShell (hidden — CREATE_NO_WINDOW)
 #[cfg(target_os = "windows")]
-pub fn speak_text(text: &str, voice: &str, rate: u32) {
+fn speech_command(text: &str, voice: &str, rate: u32) -> Command {
     use std::os::windows::process::CommandExt;
     const CREATE_NO_WINDOW: u32 = 0x08000000;
 
@@ -74,64 +693,164 @@ pub fn speak_text(text: &str, voice: &str, rate: u32) {
          $s.Speak('{}')",
         sapi_voice, sapi_rate, safe_text
     );
-    let _ = Command::new("powershell")
+    let mut command = Command::new("powershell");
+    command
         .args(["-NoProfile", "-NonInteractive", "-Command", &ps_script])
-        .creation_flags(CREATE_NO_WINDOW)
-        .spawn()
-        .and_then(|mut child| child.wait());
+        .creation_flags(CREATE_NO_WINDOW);
+    command
 }
 
-/// Speak text using macOS say command with rate
+/// Speech command using macOS say with rate
 #[cfg(target_os = "macos")]
-pub fn speak_text(text: &str, voice: &str, rate: u32) {
-    let _ = Command::new("say")
-        .args(["-v", voice, "-r", &rate.to_string(), text])
-        .spawn()
-        .and_then(|mut child| child.wait());
+fn speech_command(text: &str, voice: &str, rate: u32) -> Command {
+    let mut command = Command::new("say");
+    command.args(["-v", voice, "-r", &rate.to_string(), text]);
+    command
 }
 
-/// Speak text using espeak on Linux
+/// Speech command using espeak on Linux
 #[cfg(target_os = "linux")]
-pub fn speak_text(text: &str, _voice: &str, rate: u32) {
-    let _ = Command::new("espeak")
-        .args(["-s", &rate.to_string(), text])
-        .spawn()
-        .and_then(|mut child| child.wait());
+fn speech_command(text: &str, _voice: &str, rate: u32) -> Command {
+    let mut command = Command::new("espeak");
+    command.args(["-s", &rate.to_string(), text]);
+    command
+}
+
+/// How a speech command ended
+#[derive(Debug)]
+pub enum Spoken {
+    Done,
+    Stopped,
+    /// The command could not be started, or exited with an error
+    Failed(VoiceTrayError),
+}
+
+/// Speak text, killing the command if a stop is requested
+pub fn speak_text(state: &AppState, text: &str, voice: &str, rate: u32) -> Spoken {
+    let mut child = match speech_command(text, voice, rate).spawn() {
+        Ok(child) => child,
+        Err(e) => return Spoken::Failed(VoiceTrayError::Tts(format!("Speech command failed to start: {}", e))),
+    };
+    loop {
+        if state.take_stop_request() {
+            // Fails only if it already exited, which is what the stop wants
+            let _ = child.kill();
+            // Reaps the process; the exit status of a stopped command means nothing
+            let _ = child.wait();
+            return Spoken::Stopped;
+        }
+        match child.try_wait() {
+            Ok(None) => std::thread::sleep(STOP_POLL),
+            Ok(Some(status)) if !status.success() => return Spoken::Failed(VoiceTrayError::Tts(format!("Speech command exited with {}", status))),
+            Ok(Some(_)) => return Spoken::Done,
+            Err(e) => return Spoken::Failed(VoiceTrayError::Io(e)),
+        }
+    }
+}
+
+/// Speak `text` a few sentences at a time (see prepare::chunks), so a stop ends a long message
+/// partway and no single command gets all of it. Stops at the first chunk not spoken to the end.
+fn speak_chunks(state: &AppState, text: &str, voice: &str, rate: u32) -> Spoken {
+    for chunk in prepare::chunks(text, prepare::CHUNK_CHARS) {
+        match speak_text(state, &chunk, voice, rate) {
+            Spoken::Done => {}
+            ended => return ended,
+        }
+    }
+    Spoken::Done
+}
+
+/// Speak `text` once, outside the queue, for the doctor's sample. The command is killed if it
+/// runs past `timeout`.
+pub fn speak_sample(text: &str, voice: &str, rate: u32, timeout: Duration) -> Result<(), VoiceTrayError> {
+    let mut child = speech_command(text, voice, rate).spawn()
+        .map_err(|e| VoiceTrayError::Tts(format!("Speech command failed to start: {}", e)))?;
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait()? {
+            Some(status) if status.success() => return Ok(()),
+            Some(status) => return Err(VoiceTrayError::Tts(format!("Speech command exited with {}", status))),
+            None if Instant::now() >= deadline => {
+                // Fails only if it exited just now
+                let _ = child.kill();
+                // Reaps the process; how a killed command exited means nothing
+                let _ = child.wait();
+                return Err(VoiceTrayError::Tts(format!("Speech command still running after {} s", timeout.as_secs())));
+            }
+            None => std::thread::sleep(STOP_POLL),
+        }
+    }
+}
+
+/// Whether `say` knows a voice
+#[cfg(target_os = "macos")]
+pub fn voice_installed(voice: &str) -> bool {
+    voices::installed(voice)
+}
+
+/// Windows maps every name to a SAPI voice and espeak ignores it
+#[cfg(not(target_os = "macos"))]
+pub fn voice_installed(_voice: &str) -> bool {
+    true
+}
+
+/// Whether the platform speech command can be started
+pub fn tts_available() -> bool {
+    #[cfg(target_os = "macos")]
+    let probe = Command::new("say").args(["-v", "?"]).output();
+    #[cfg(target_os = "linux")]
+    let probe = Command::new("espeak").arg("--version").output();
+    #[cfg(target_os = "windows")]
+    let probe = Command::new("powershell").args(["-NoProfile", "-Command", "exit"]).output();
+    probe.is_ok()
+}
+
+/// Label of the Test Voice menu item
+pub fn test_voice_label(tts_available: bool) -> &'static str {
+    if tts_available { "Test Voice" } else { "Test Voice (TTS unavailable)" }
+}
+
+/// Queue the Test Voice sample with the configured default voice and rate
+pub fn queue_test_voice(state: &Arc<AppState>) -> u64 {
+    let config = state.voice_config();
+    let text = i18n::phrase_or(Phrase::TestVoice, &config.test_voice_text);
+    state.enqueue(text, config.voice(), config.default_rate, Some("Test".to_string()), 0, None)
 }
 
 /// Process voice queue in a background thread
 pub fn process_queue(state: Arc<AppState>) {
     std::thread::spawn(move || {
         loop {
-            let entry_opt = {
-                let Ok(mut timeline) = state.timeline.lock() else {
-                    std::thread::sleep(Duration::from_millis(100));
-                    continue;
-                };
-                if let Some(e) = timeline.iter_mut().find(|e| e.status == "queued") {
-                    e.status = "speaking".to_string();
-                    Some(e.clone())
-                } else {
-                    None
-                }
-            };
-
-            if let Some(entry) = entry_opt {
-                if let Ok(mut is_speaking) = state.is_speaking.lock() {
-                    *is_speaking = true;
-                }
+            // Muted, snoozed or paused: leave entries queued until released. Quitting: start nothing new.
+            // Relaying: relay.rs takes them for the peer
+            if state.silenced() || state.is_queue_paused() || state.is_shutting_down() || relay::takes_over(&state) {
+                std::thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            // Focus with ui.mute_during_focus: what was queued is suppressed, not held
+            if focus::muting(&state) {
+                focus::suppress_queued(&state);
+                std::thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            if let Some(mut entry) = state.start_next() {
+                state.is_speaking.store(true, Ordering::Relaxed);
                 update_tray_icon(&state, true);
 
-                speak_text(&entry.text, &entry.voice, entry.rate);
-
-                if let Ok(mut timeline) = state.timeline.lock() {
-                    if let Some(e) = timeline.iter_mut().find(|e| e.id == entry.id) {
-                        e.status = "done".to_string();
+                // A stop meant for an entry that already finished must not cut this one
+                state.take_stop_request();
+                match speak_chunks(&state, &entry.text, &entry.voice, entry.rate) {
+                    Spoken::Done => entry.status = "done".to_string(),
+                    Spoken::Stopped => entry.status = "cancelled".to_string(),
+                    Spoken::Failed(error) => {
+                        report_error(&state, &format!("Speaking entry {}", entry.id), &error);
+                        entry.status = "failed".to_string();
+                        // A watcher alert's own detail is kept
+                        entry.detail.get_or_insert(error.to_string());
                     }
                 }
-                if let Ok(mut is_speaking) = state.is_speaking.lock() {
-                    *is_speaking = false;
-                }
+                state.finish_entry(&entry);
+                state.is_speaking.store(false, Ordering::Relaxed);
                 update_tray_icon(&state, false);
             }
 
@@ -139,3 +858,127 @@ pub fn process_queue(state: Arc<AppState>) {
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// POST /speak goes through AppState::speak into the timeline process_queue reads, which
+    /// hands the entry to this platform's speech command, not `say`
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "windows"))]
+    fn test_http_speak_uses_platform_command() {
+        use tower::ServiceExt;
+        let state = Arc::new(AppState::default());
+        let request = axum::http::Request::post("/speak")
+            .header("content-type", "application/json")
+            .body(axum::body::Body::from(r#"{"text": "Build passed", "rate": 180}"#))
+            .expect("request");
+        let runtime = tokio::runtime::Builder::new_current_thread().build().expect("runtime");
+        let response = runtime.block_on(crate::http::router(state.clone()).oneshot(request)).expect("response");
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let body = runtime.block_on(axum::body::to_bytes(response.into_body(), usize::MAX)).expect("body");
+        let queued: crate::state::SpeakResponse = serde_json::from_slice(&body).expect("parse");
+
+        let entry = state.timeline.lock().get(queued.id).cloned().expect("entry");
+        let command = speech_command(&entry.text, &entry.voice, entry.rate);
+        let program = command.get_program().to_string_lossy().into_owned();
+        let args: Vec<_> = command.get_args().map(|a| a.to_string_lossy().into_owned()).collect();
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(program, "espeak");
+            assert_eq!(args, ["-s", "180", "Build passed"]);
+        }
+        #[cfg(target_os = "windows")]
+        {
+            assert_eq!(program, "powershell");
+            let script = args.last().expect("script");
+            assert!(script.contains("$s.Speak('Build passed')"), "{}", script);
+        }
+    }
+
+    #[test]
+    fn test_tray_tooltip() {
+        let mut snapshot = TraySnapshot { mqtt_status: "connected".to_string(), ..Default::default() };
+        assert_eq!(tray_tooltip(&snapshot), "idle • MQTT ok");
+        snapshot.queued = 3;
+        snapshot.speaking = true;
+        assert_eq!(tray_tooltip(&snapshot), "3 queued • speaking • MQTT ok");
+        snapshot.speaking = false;
+        snapshot.mqtt_status = "connecting".to_string();
+        assert_eq!(tray_tooltip(&snapshot), "3 queued • MQTT connecting");
+        snapshot.queued = 0;
+        snapshot.mqtt_status = "disconnected".to_string();
+        snapshot.paused = true;
+        snapshot.muted = true;
+        assert_eq!(tray_tooltip(&snapshot), "idle • MQTT offline • watcher paused (muted)");
+        snapshot.queue_paused = true;
+        assert_eq!(tray_tooltip(&snapshot), "idle • MQTT offline • queue paused • watcher paused (muted)");
+        snapshot.muted = false;
+        snapshot.snooze_minutes_left = Some(25);
+        assert_eq!(tray_tooltip(&snapshot), "idle • MQTT offline • queue paused • watcher paused (snoozed, 25m left)");
+        snapshot.config_warning = Some("newer".to_string());
+        assert!(tray_tooltip(&snapshot).ends_with("\n⚠ config.json is from a newer version, settings not saved"));
+    }
+
+    #[test]
+    fn test_status_event_payload() {
+        let snapshot = TraySnapshot { queued: 2, speaking: true, mqtt_status: "connected".to_string(), ..Default::default() };
+        assert_eq!(serde_json::to_value(&snapshot).expect("serialize"), serde_json::json!({
+            "queued": 2, "is_speaking": true, "mqtt_status": "connected", "muted": false, "watcher_paused": false,
+            "queue_paused": false, "snooze_minutes_left": null, "config_warning": null
+        }));
+    }
+
+    #[test]
+    fn test_menu_header() {
+        let mut snapshot = TraySnapshot { queued: 3, mqtt_status: "connected".to_string(), ..Default::default() };
+        assert_eq!(menu_header(&snapshot), "3 queued • connected");
+        snapshot.queue_paused = true;
+        assert_eq!(menu_header(&snapshot), "3 queued (paused) • connected");
+        snapshot.queued = 0;
+        snapshot.queue_paused = false;
+        snapshot.speaking = true;
+        snapshot.mqtt_status = "disabled".to_string();
+        assert_eq!(menu_header(&snapshot), "speaking • MQTT off");
+        snapshot.speaking = false;
+        snapshot.mqtt_status = "disconnected".to_string();
+        assert_eq!(menu_header(&snapshot), "idle • offline");
+    }
+
+    #[test]
+    fn test_voice_shortlist() {
+        let mut config = VoiceConfig { default_voice: "Samantha".to_string(), ..Default::default() };
+        assert_eq!(voice_shortlist(&config), vec!["Samantha"]);
+        config.favorite_voices = vec!["Daniel".to_string(), "samantha".to_string(), "Daniel".to_string()];
+        assert_eq!(voice_shortlist(&config), vec!["Daniel", "samantha"]);
+        config.default_voice = "Karen".to_string();
+        assert_eq!(voice_shortlist(&config), vec!["Karen", "Daniel", "samantha"]);
+    }
+
+    #[test]
+    fn test_tray_title_counts_queued() {
+        let mut snapshot = TraySnapshot::default();
+        assert_eq!(tray_title(&snapshot), None);
+        snapshot.queued = 3;
+        assert_eq!(tray_title(&snapshot).as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn test_recent_label_is_menu_safe() {
+        let now = Utc::now();
+        assert_eq!(recent_label("Build passed", now, now), "Build passed · just now");
+        assert_eq!(
+            recent_label("Tom & Jerry\n\tdone\u{7}", now - chrono::Duration::minutes(5), now),
+            "Tom && Jerry done · 5m ago",
+        );
+        let long = recent_label(&"word ".repeat(20), now - chrono::Duration::hours(3), now);
+        assert_eq!(long, format!("{}… · 3h ago", "word ".repeat(8).trim_end()));
+    }
+
+    #[test]
+    fn test_voice_label_when_unavailable() {
+        assert_eq!(test_voice_label(true), "Test Voice");
+        assert_eq!(test_voice_label(false), "Test Voice (TTS unavailable)");
+    }
+}
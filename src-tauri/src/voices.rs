@@ -0,0 +1,203 @@
+/// Installed system voices, read from the platform speech tool once and cached.
+/// Shared by the get_voices command, GET /voices and the watcher's voice check.
+use serde::Serialize;
+use std::process::Command;
+
+use crate::lock::Lock;
+
+/// Last enumeration; None until the first one succeeds
+static CACHE: Lock<Option<Vec<Voice>>> = Lock::new(None);
+
+/// A voice the speech command accepts
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Voice {
+    pub name: String,
+    pub locale: Option<String>, // e.g. "en_US" (macOS), "en-gb" (espeak), "en-US" (Windows)
+    pub gender: Option<String>, // "male" / "female" where the platform reports it
+    pub is_default: bool,       // config.json's voice.default_voice
+}
+
+/// Why the voice list could not be read; the UI then offers a free-text field
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum VoicesError {
+    Unavailable(String), // the speech tool could not be started
+    Unreadable(String),  // it ran but listed nothing usable
+}
+
+impl std::fmt::Display for VoicesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VoicesError::Unavailable(message) | VoicesError::Unreadable(message) => f.write_str(message),
+        }
+    }
+}
+
+/// Installed voices with `default_voice` flagged. `refresh` reads the list again, e.g. after
+/// new system voices were installed; otherwise the cached list is used.
+pub fn list(default_voice: &str, refresh: bool) -> Result<Vec<Voice>, VoicesError> {
+    let cached = if refresh { None } else { CACHE.lock().clone() };
+    let voices = match cached {
+        Some(voices) => voices,
+        None => {
+            let voices = enumerate()?;
+            *CACHE.lock() = Some(voices.clone());
+            voices
+        }
+    };
+    Ok(voices.into_iter()
+        .map(|v| Voice { is_default: v.name.eq_ignore_ascii_case(default_voice), ..v })
+        .collect())
+}
+
+/// Whether the speech command knows a voice. Allows everything when the list cannot be read.
+#[cfg(target_os = "macos")]
+pub fn installed(voice: &str) -> bool {
+    list("", false)
+        .map(|voices| voices.iter().any(|v| v.name.eq_ignore_ascii_case(voice)))
+        .unwrap_or(true)
+}
+
+fn enumerate() -> Result<Vec<Voice>, VoicesError> {
+    let output = list_command().output()
+        .map_err(|e| VoicesError::Unavailable(format!("Could not list voices: {}", e)))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let voices = parse(&stdout);
+    if !output.status.success() || voices.is_empty() {
+        return Err(VoicesError::Unreadable("The speech command listed no voices".to_string()));
+    }
+    Ok(voices)
+}
+
+#[cfg(target_os = "macos")]
+fn list_command() -> Command {
+    let mut command = Command::new("say");
+    command.args(["-v", "?"]);
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn parse(output: &str) -> Vec<Voice> {
+    parse_say(output)
+}
+
+#[cfg(target_os = "windows")]
+fn list_command() -> Command {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    let ps_script = "Add-Type -AssemblyName System.Speech; \
+         (New-Object System.Speech.Synthesis.SpeechSynthesizer).GetInstalledVoices() | \
+         ForEach-Object { $i = $_.VoiceInfo; \"$($i.Name)|$($i.Culture.Name)|$($i.Gender)\" }";
+    let mut command = Command::new("powershell");
+    command
+        .args(["-NoProfile", "-NonInteractive", "-Command", ps_script])
+        .creation_flags(CREATE_NO_WINDOW);
+    command
+}
+
+#[cfg(target_os = "windows")]
+fn parse(output: &str) -> Vec<Voice> {
+    parse_sapi(output)
+}
+
+#[cfg(target_os = "linux")]
+fn list_command() -> Command {
+    let mut command = Command::new("espeak");
+    command.arg("--voices");
+    command
+}
+
+#[cfg(target_os = "linux")]
+fn parse(output: &str) -> Vec<Voice> {
+    parse_espeak(output)
+}
+
+fn voice(name: &str, locale: &str, gender: Option<&str>) -> Voice {
+    let some = |s: &str| (!s.is_empty()).then(|| s.to_string());
+    Voice { name: name.to_string(), locale: some(locale), gender: gender.and_then(some), is_default: false }
+}
+
+/// `say -v ?` lines: "Albert              en_US    # Hello! My name is Albert."
+/// Names may contain spaces, e.g. "Eddy (English (UK))".
+#[cfg(any(target_os = "macos", test))]
+fn parse_say(output: &str) -> Vec<Voice> {
+    output.lines()
+        .filter_map(|line| line.split('#').next()?.trim_end().rsplit_once(char::is_whitespace))
+        .map(|(name, locale)| voice(name.trim(), locale, None))
+        .filter(|v| !v.name.is_empty())
+        .collect()
+}
+
+/// `espeak --voices` rows under a header: "Pty Language Age/Gender VoiceName File Other Languages",
+/// e.g. " 5  en-gb          M  english              default"
+#[cfg(any(target_os = "linux", test))]
+fn parse_espeak(output: &str) -> Vec<Voice> {
+    output.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (locale, age_gender, name) = (fields.get(1)?, fields.get(2)?, fields.get(3)?);
+            let gender = match age_gender.chars().last() {
+                Some('M') => Some("male"),
+                Some('F') => Some("female"),
+                _ => None,
+            };
+            Some(voice(name, locale, gender))
+        })
+        .collect()
+}
+
+/// "Name|Culture|Gender" lines printed by the PowerShell enumeration
+#[cfg(any(target_os = "windows", test))]
+fn parse_sapi(output: &str) -> Vec<Voice> {
+    output.lines()
+        .filter_map(|line| {
+            let mut fields = line.trim().splitn(3, '|');
+            let name = fields.next().filter(|n| !n.is_empty())?;
+            let locale = fields.next().unwrap_or("");
+            let gender = match fields.next().map(str::to_ascii_lowercase).as_deref() {
+                Some("male") => Some("male"),
+                Some("female") => Some("female"),
+                _ => None,
+            };
+            Some(voice(name, locale, gender))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_say() {
+        let output = "Albert              en_US    # Hello! My name is Albert.\n\
+                      Eddy (English (UK)) en_GB    # Hello! My name is Eddy.\n";
+        assert_eq!(parse_say(output), vec![
+            voice("Albert", "en_US", None),
+            voice("Eddy (English (UK))", "en_GB", None),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_espeak() {
+        let output = "Pty Language Age/Gender VoiceName          File          Other Languages\n \
+                      5  af             M  afrikaans            other/af\n \
+                      5  en-gb          M  english              default\n \
+                      5  en-us        --/F  english-us           en-us         (en 2)\n";
+        let voices = parse_espeak(output);
+        assert_eq!(voices.len(), 3);
+        assert_eq!(voices[1], voice("english", "en-gb", Some("male")));
+        assert_eq!(voices[2], voice("english-us", "en-us", Some("female")));
+    }
+
+    #[test]
+    fn test_parse_sapi() {
+        let output = "Microsoft David Desktop|en-US|Male\r\nMicrosoft Zira Desktop|en-US|Female\r\n\r\n";
+        assert_eq!(parse_sapi(output), vec![
+            voice("Microsoft David Desktop", "en-US", Some("male")),
+            voice("Microsoft Zira Desktop", "en-US", Some("female")),
+        ]);
+    }
+}
@@ -1,101 +1,947 @@
-/// Claude Code Session Watcher
-/// Watches ~/.claude/projects/**/*.jsonl for assistant completions and subagent spawns.
-/// Approval alerts are handled by PreToolUse hooks in ~/.claude/settings.json.
-use std::collections::HashMap;
+/// Coding CLI Session Watcher
+/// Watches session logs for assistant completions, subagent spawns and tool uses left
+/// waiting for approval. Each profile (Claude Code built in, others from config.json)
+/// has its own log glob, format and file positions; all run on the one watcher thread.
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::mpsc;
 use std::time::{Duration, Instant};
 
-use chrono::Utc;
+use chrono::{Local, Utc};
 use notify::{EventKind, RecursiveMode, Watcher};
+use tracing::{info, warn};
 
-use crate::state::{AppState, VoiceEntry};
+use crate::config::{
+    config_generation, load_config, load_watcher_config, save_watcher_config_to_file, CompletionGrouping, Delivery, LogFormat, SpeechStyle,
+    TokenPrices, WatcherConfig, WatcherProfile,
+};
+use crate::error::{report_error, VoiceTrayError};
+use crate::focus;
+use crate::i18n::{self, Phrase};
+use crate::parser::{classify_line, classify_mapped_line, LineEvent, NoticeKind, QuestionKind};
+use crate::permissions::{ModeCache, PermissionMode};
+use crate::state::{AppState, PruneStats, TokenUsage, WatcherEvent, WATCHER_EVENT_LOG_MAX};
+use crate::notifications::{notify, notify_suppressed, Suppression};
+use crate::stats;
+use crate::tray::voice_installed;
 
-#[derive(Debug, PartialEq)]
-enum LineEvent {
-    None,
-    Completion,            // stop_reason: end_turn → "Claude Stop"
-    SubagentSpawn(String), // tool_use name=Task → "Spawning <desc>"
+/// Bytes read from one session file per tick; larger appends are spread over several ticks
+const READ_BUDGET_PER_TICK: u64 = 1024 * 1024;
+
+/// How often tracked files are swept for deleted or idle sessions
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a tool_use may wait for its tool_result before we assume it needs approval
+const APPROVAL_ALERT_DELAY: Duration = Duration::from_secs(8);
+
+/// A tool_use waiting for its result, remembered with the session's project
+struct PendingApproval {
+    profile: usize,
+    tools: Vec<String>,
+    since: Instant,
+    project: Option<PathBuf>,
+    alerts_sent: u32,
+    last_alert: Option<Instant>,
+}
+
+/// A long-running-capable tool (Bash by default) still waiting for its result
+struct RunningCommand {
+    profile: usize,
+    tool: String,
+    since: Instant,
+    project: Option<PathBuf>,
+    alerts_sent: u32,
+    last_alert: Option<Instant>,
+}
+
+/// Usage of the turn in progress. Claude Code writes one line per content block, each
+/// repeating the message's usage, so a line for the last message replaces its share.
+#[derive(Default)]
+struct TurnUsage {
+    tokens: TokenUsage,
+    cost: Option<f64>,
+    last_message: Option<(String, TokenUsage, Option<f64>)>,
+}
+
+/// A completion held back to be announced together with others (aggregate grouping)
+struct GroupedCompletion {
+    agent: String,
+    style: SpeechStyle,
+    text: String,
+    project: String,
+}
+
+/// Flips the watcher status to "stopped" when the thread exits, even on panic
+struct StopGuard(Arc<AppState>);
+
+impl Drop for StopGuard {
+    fn drop(&mut self) {
+        let mut status = self.0.watcher_status.lock();
+        if status.state == "active" {
+            status.state = "stopped".to_string();
+        }
+    }
+}
+
+fn set_watcher_state(state: &Arc<AppState>, new_state: &str, reason: Option<String>) {
+    let mut status = state.watcher_status.lock();
+    status.state = new_state.to_string();
+    status.reason = reason;
+}
+
+/// The watcher could not start: shown in /status and counted in GET /stats
+fn watcher_failed(state: &Arc<AppState>, error: VoiceTrayError) {
+    report_error(state, "Session watcher", &error);
+    set_watcher_state(state, "failed", Some(error.to_string()));
+}
+
+/// Record the last processed event for /status (`mode` is only known for Claude sessions)
+fn record_event(state: &Arc<AppState>, event: &LineEvent, mode: Option<PermissionMode>) -> &'static str {
+    let kind = match event {
+        LineEvent::Completion => "completion",
+        LineEvent::Question(_) => "question",
+        LineEvent::SubagentSpawn(_) => "subagent_spawn",
+        LineEvent::ToolUse(_) => "tool_use",
+        LineEvent::ToolResult => "tool_result",
+        LineEvent::ToolError(_) => "tool_error",
+        LineEvent::ApiError(_) => "api_error",
+        LineEvent::Usage { .. } => "usage",
+        LineEvent::SessionMode(_) => "session_mode",
+        LineEvent::Notice(NoticeKind::Compaction, _) => "compaction",
+        LineEvent::Notice(NoticeKind::ContextLimit, _) => "context_limit",
+        LineEvent::Notice(NoticeKind::UsageLimit, _) => "usage_limit",
+    };
+    {
+        let mut status = state.watcher_status.lock();
+        status.last_event = Some(kind.to_string());
+        status.last_event_at = Some(Utc::now());
+        if let Some(mode) = mode {
+            status.permission_mode = mode.as_str().to_string();
+        }
+    }
+    kind
+}
+
+/// What the watcher did with an event, for the event log
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Outcome {
+    Queued,                   // a voice entry was queued
+    Notified,                 // shown as a system notification only (delivery, mute or quiet hours)
+    Held,                     // waiting to be aggregated with other completions
+    Tracked,                  // state only (timers, pending approvals)
+    Suppressed(&'static str), // debounce, hooks, cooldown, disabled, mode, mute, inactive
+}
+
+/// What handle_event decided to do with an event, before project filtering
+enum Action {
+    Speak { text: String, detail: Option<String> },
+    Hold { text: String }, // completion to aggregate (CompletionGrouping::Aggregate)
+    Skip(Outcome),
+}
+
+/// Append to the watcher event log (GET /watcher/events)
+fn log_event(
+    state: &Arc<AppState>,
+    path: &Path,
+    project: Option<&Path>,
+    kind: &str,
+    detail: Option<String>,
+    raw_type: Option<String>,
+    outcome: Outcome,
+) {
+    let (outcome, reason) = match outcome {
+        Outcome::Queued => ("queued", None),
+        Outcome::Notified => ("notified", None),
+        Outcome::Held => ("held", None),
+        Outcome::Tracked => ("tracked", None),
+        Outcome::Suppressed(reason) => ("suppressed", Some(reason.to_string())),
+    };
+    let mut events = state.watcher_events.lock();
+    events.push_back(WatcherEvent {
+        at: Utc::now(),
+        file: path.display().to_string(),
+        project: project_name(project),
+        kind: kind.to_string(),
+        detail,
+        raw_type,
+        outcome: outcome.to_string(),
+        reason,
+    });
+    while events.len() > WATCHER_EVENT_LOG_MAX {
+        events.pop_front();
+    }
+}
+
+/// Pause or resume announcements and persist the choice in config.json.
+/// Pausing drops any pending approval; resuming skips whatever was written meanwhile.
+pub fn set_watcher_enabled(state: &Arc<AppState>, enabled: bool) -> Result<(), String> {
+    state.watcher_status.lock().paused = !enabled;
+    state.notify_changed();
+    let mut config = load_watcher_config();
+    config.enabled = enabled;
+    save_watcher_config_to_file(&config)
+}
+
+/// Save the watcher section from the settings window; the running watcher picks it up through
+/// config_generation. Returns false, writing nothing, when it is unchanged.
+pub fn apply_config(state: &Arc<AppState>, config: &WatcherConfig) -> Result<bool, String> {
+    let current = load_watcher_config();
+    let as_value = |c: &WatcherConfig| serde_json::to_value(c).map_err(|e| e.to_string());
+    if as_value(config)? == as_value(&current)? {
+        return Ok(false);
+    }
+    save_watcher_config_to_file(config)?;
+    use_config(state, &current, config);
+    Ok(true)
+}
+
+/// Follow a watcher section that is already saved: pause or resume when `enabled` changed
+pub fn use_config(state: &AppState, previous: &WatcherConfig, config: &WatcherConfig) {
+    if config.enabled != previous.enabled {
+        state.watcher_status.lock().paused = !config.enabled;
+        state.notify_changed();
+    }
 }
 
 pub fn start_session_watcher(state: Arc<AppState>) {
+    let config = load_watcher_config();
+    state.watcher_status.lock().paused = !config.enabled;
+
     std::thread::spawn(move || {
+        let _guard = StopGuard(state.clone());
+
         let Some(home) = dirs::home_dir() else {
-            println!("[watcher] home dir not found — session watcher disabled");
+            watcher_failed(&state, VoiceTrayError::Watcher("home directory not found".to_string()));
             return;
         };
 
-        let projects_dir = home.join(".claude").join("projects");
-        if !projects_dir.exists() {
-            println!("[watcher] ~/.claude/projects not found — session watcher disabled");
-            return;
-        }
-
-        println!("[watcher] Watching: {}", projects_dir.display());
-
-        let mut file_positions: HashMap<PathBuf, u64> = HashMap::new();
-        let mut last_completion_notify: Option<Instant> = None;
-
         let (tx, rx) = std::sync::mpsc::channel();
         let mut watcher = match notify::recommended_watcher(tx) {
             Ok(w) => w,
             Err(e) => {
-                println!("[watcher] Failed to create watcher: {}", e);
+                watcher_failed(&state, VoiceTrayError::Watcher(format!("failed to create watcher: {}", e)));
                 return;
             }
         };
 
-        if let Err(e) = watcher.watch(&projects_dir, RecursiveMode::Recursive) {
-            println!("[watcher] Failed to watch projects dir: {}", e);
+        let mut profiles = Vec::new();
+        let mut problems = Vec::new();
+        for profile in config.active_profiles() {
+            let watched = ProfileState::new(profile, &home).and_then(|p| {
+                watcher.watch(&p.root, RecursiveMode::Recursive)
+                    .map_err(|e| format!("failed to watch {}: {}", p.root.display(), e))
+                    .map(|_| p)
+            });
+            match watched {
+                Ok(p) => {
+                    info!("Watching {}: {}", p.profile.name, p.root.display());
+                    profiles.push(p);
+                }
+                Err(e) => {
+                    warn!("Profile skipped: {}", e);
+                    problems.push(e);
+                }
+            }
+        }
+        if profiles.is_empty() {
+            info!("No session log directory found — session watcher disabled");
+            set_watcher_state(&state, "failed", Some(problems.join("; ")));
             return;
         }
+        state.watcher_status.lock().profiles = profiles.iter().map(|p| p.profile.name.clone()).collect();
+
+        set_watcher_state(&state, "active", None);
+
+        let mut was_paused = !config.enabled;
+        let mut sessions = SessionWatcher::new(state.clone(), config, profiles, home.join(".claude"));
+        let mut last_prune = Instant::now();
+        let mut config_seen = config_generation();
 
         loop {
-            match rx.recv_timeout(Duration::from_millis(500)) {
-                Ok(Ok(event)) => {
-                    if !matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_)) {
-                        continue;
-                    }
+            let timeout = if sessions.backlog.is_empty() { 500 } else { 10 };
+            let received = rx.recv_timeout(Duration::from_millis(timeout));
+
+            let paused = state.watcher_status.lock().paused;
+            if paused != was_paused {
+                was_paused = paused;
+                if paused {
+                    info!("Paused");
+                    sessions.clear_all_approvals();
+                    sessions.backlog.clear();
+                    sessions.grouped_completions.clear();
+                } else {
+                    info!("Resumed");
+                    sessions.skip_to_end();
+                }
+            }
 
+            match received {
+                Ok(Ok(event)) if !paused && matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_)) => {
+                    for path in &event.paths {
+                        sessions.check_file(path);
+                    }
+                }
+                Ok(Ok(event)) if matches!(event.kind, EventKind::Remove(_)) => {
                     for path in &event.paths {
-                        if !path.extension().map(|e| e == "jsonl").unwrap_or(false) {
-                            continue;
-                        }
-
-                        match check_new_lines(path, &mut file_positions) {
-                            LineEvent::Completion => {
-                                let should_notify = last_completion_notify
-                                    .map(|t| t.elapsed() > Duration::from_secs(2))
-                                    .unwrap_or(true);
-                                if should_notify {
-                                    last_completion_notify = Some(Instant::now());
-                                    queue_voice(&state, "Claude Stop", 220);
-                                }
-                            }
-                            LineEvent::SubagentSpawn(desc) => {
-                                queue_voice(&state, &format!("Spawning {}", desc), 230);
-                            }
-                            LineEvent::None => {}
-                        }
+                        sessions.forget(path);
                     }
+                    sessions.publish_tracked_files();
                 }
+                Ok(Ok(_)) => {}
                 Ok(Err(_)) | Err(mpsc::RecvTimeoutError::Disconnected) => break,
                 Err(mpsc::RecvTimeoutError::Timeout) => {}
             }
+
+            if !paused {
+                sessions.drain_backlog();
+            }
+            sessions.flush_completions();
+            sessions.check_approvals();
+            sessions.check_running_commands();
+            // Follow saves and reloads of the config (profiles and watch paths still need a restart)
+            let generation = config_generation();
+            if generation != config_seen {
+                config_seen = generation;
+                sessions.config = load_watcher_config();
+            }
+
+            if last_prune.elapsed() >= PRUNE_INTERVAL {
+                sessions.prune_files();
+                last_prune = Instant::now();
+            }
         }
     });
 }
 
-/// Read new lines appended to a .jsonl file since last check.
+/// A watched CLI: its profile, compiled log glob and its own file positions
+struct ProfileState {
+    profile: WatcherProfile,
+    pattern: glob::Pattern,
+    root: PathBuf, // deepest directory above the first wildcard
+    file_positions: HashMap<PathBuf, u64>,
+}
+
+impl ProfileState {
+    fn new(profile: WatcherProfile, home: &Path) -> Result<Self, String> {
+        let full = expand_home(&profile.path, home);
+        let pattern = glob::Pattern::new(&full.to_string_lossy())
+            .map_err(|e| format!("{}: invalid path glob: {}", profile.name, e))?;
+        let root = glob_root(&full);
+        if !root.is_dir() {
+            return Err(format!("{} not found", root.display()));
+        }
+        Ok(Self { profile, pattern, root, file_positions: HashMap::new() })
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let options = glob::MatchOptions { require_literal_separator: true, ..Default::default() };
+        self.pattern.matches_path_with(path, options)
+    }
+}
+
+/// Replace a leading `~` with the home directory
+pub fn expand_home(path: &str, home: &Path) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) => home.join(rest.trim_start_matches('/')),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Directory to watch for a glob: its components up to the first one with a wildcard
+pub fn glob_root(pattern: &Path) -> PathBuf {
+    pattern.components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect()
+}
+
+/// Per-session watcher state, keyed by log file path
+struct SessionWatcher {
+    state: Arc<AppState>,
+    config: WatcherConfig,
+    mode_cache: ModeCache,
+    profiles: Vec<ProfileState>,
+    session_cwds: HashMap<PathBuf, PathBuf>,
+    pending_approvals: HashMap<PathBuf, PendingApproval>,
+    running_commands: HashMap<PathBuf, RunningCommand>,
+    last_completion_notify: HashMap<PathBuf, Instant>,
+    grouped_completions: Vec<GroupedCompletion>,
+    last_completion_spoken: Option<Instant>, // any session; starts the aggregation window
+    last_error_notify: HashMap<PathBuf, Instant>,
+    last_notice: HashMap<(PathBuf, NoticeKind), Instant>,
+    session_modes: HashMap<PathBuf, PermissionMode>, // last resolved mode, to log changes
+    log_modes: HashMap<PathBuf, PermissionMode>, // mode recorded in the session log; beats settings.json
+    backlog: HashSet<PathBuf>, // files with more appended data than one tick's read budget
+    turn_usage: HashMap<PathBuf, TurnUsage>,
+}
+
+impl SessionWatcher {
+    fn new(state: Arc<AppState>, config: WatcherConfig, profiles: Vec<ProfileState>, claude_home: PathBuf) -> Self {
+        Self {
+            state,
+            config,
+            mode_cache: ModeCache::new(claude_home),
+            profiles,
+            session_cwds: HashMap::new(),
+            pending_approvals: HashMap::new(),
+            running_commands: HashMap::new(),
+            last_completion_notify: HashMap::new(),
+            grouped_completions: Vec::new(),
+            last_completion_spoken: None,
+            last_error_notify: HashMap::new(),
+            last_notice: HashMap::new(),
+            session_modes: HashMap::new(),
+            log_modes: HashMap::new(),
+            backlog: HashSet::new(),
+            turn_usage: HashMap::new(),
+        }
+    }
+
+    /// Claude Code hooks announce completions and permission prompts themselves
+    fn hooks_mode(&self) -> bool {
+        self.state.watcher_status.lock().hooks_mode
+    }
+
+    fn profile_for(&self, path: &Path) -> Option<usize> {
+        self.profiles.iter().position(|p| p.matches(path))
+    }
+
+    /// Permission mode for a session; only Claude Code sessions have one
+    fn permission_mode(&mut self, profile: usize, path: &Path, project: Option<&Path>) -> Option<PermissionMode> {
+        (self.profiles[profile].profile.format == LogFormat::ClaudeJsonl)
+            .then(|| session_mode(&self.log_modes, &mut self.mode_cache, path, project))
+    }
+
+    /// Jump every tracked file to EOF so nothing written meanwhile is announced
+    fn skip_to_end(&mut self) {
+        for (path, pos) in self.profiles.iter_mut().flat_map(|p| p.file_positions.iter_mut()) {
+            if let Ok(metadata) = std::fs::metadata(path) {
+                *pos = metadata.len();
+            }
+        }
+    }
+
+    /// Read new lines from a session file and act on them
+    fn check_file(&mut self, path: &PathBuf) {
+        let Some(index) = self.profile_for(path) else { return };
+        let watched = &mut self.profiles[index];
+        let previous = watched.file_positions.get(path).copied();
+        let (events, more) = check_new_lines(
+            path, &mut watched.file_positions, &mut self.session_cwds, self.config.max_line_bytes, &watched.profile,
+        );
+        let current = watched.file_positions.get(path).copied();
+        if more {
+            self.backlog.insert(path.clone());
+        } else {
+            self.backlog.remove(path);
+        }
+        if previous.zip(current).map(|(old, new)| new < old).unwrap_or(false) {
+            self.clear_approval(path); // truncated/rotated: the old tool_use is gone
+            self.running_commands.remove(path);
+        }
+        self.publish_tracked_files();
+        for line_event in events {
+            self.handle_event(index, path, line_event);
+        }
+    }
+
+    fn tracked_files(&self) -> usize {
+        self.profiles.iter().map(|p| p.file_positions.len()).sum()
+    }
+
+    fn publish_tracked_files(&self) {
+        self.state.watcher_status.lock().tracked_files = self.tracked_files();
+    }
+
+    /// Drop everything remembered about a session file
+    fn forget(&mut self, path: &Path) {
+        for watched in &mut self.profiles {
+            watched.file_positions.remove(path);
+        }
+        self.session_cwds.remove(path);
+        self.running_commands.remove(path);
+        self.last_completion_notify.remove(path);
+        self.last_error_notify.remove(path);
+        self.last_notice.retain(|(p, _), _| p != path);
+        self.session_modes.remove(path);
+        self.log_modes.remove(path);
+        self.backlog.remove(path);
+        self.turn_usage.remove(path);
+        self.clear_approval(path);
+    }
+
+    /// Forget deleted session files, and idle ones with nothing pending. An idle file that is
+    /// written again is re-tracked from EOF like any newly seen file.
+    fn prune_files(&mut self) {
+        let before = self.tracked_files();
+        let idle_limit = Duration::from_secs(self.config.idle_untrack_secs);
+        let stale: Vec<PathBuf> = self.profiles.iter()
+            .flat_map(|p| p.file_positions.keys())
+            .filter(|path| {
+                let busy = self.pending_approvals.contains_key(*path)
+                    || self.running_commands.contains_key(*path)
+                    || self.backlog.contains(*path);
+                match std::fs::metadata(path).and_then(|m| m.modified()) {
+                    Err(_) => true, // deleted
+                    Ok(modified) => !busy && modified.elapsed().map(|idle| idle >= idle_limit).unwrap_or(false),
+                }
+            })
+            .cloned()
+            .collect();
+        for path in &stale {
+            self.forget(path);
+        }
+        let after = self.tracked_files();
+        if after != before {
+            info!("Pruned {} stale session files ({} → {})", before - after, before, after);
+        }
+        let mut status = self.state.watcher_status.lock();
+        status.tracked_files = after;
+        status.last_prune = Some(PruneStats { at: Utc::now(), before, after });
+    }
+
+    /// Continue reading files left over from a previous tick's budget
+    fn drain_backlog(&mut self) {
+        let paths: Vec<PathBuf> = self.backlog.iter().cloned().collect();
+        for path in paths {
+            self.check_file(&path);
+        }
+    }
+
+    fn handle_event(&mut self, profile: usize, path: &PathBuf, line_event: LineEvent) {
+        let line_event = match line_event {
+            LineEvent::Question(QuestionKind::TrailingQuestion)
+                if !self.config.detect_trailing_questions => LineEvent::Completion,
+            other => other,
+        };
+        if let LineEvent::Usage { id, model, tokens } = line_event {
+            return self.record_usage(path, id, model, tokens);
+        }
+        if let LineEvent::SessionMode(mode) = line_event {
+            // Logged as a mode_change with the session's next event
+            self.log_modes.insert(path.clone(), mode);
+            return;
+        }
+        let turn = match line_event {
+            LineEvent::Completion | LineEvent::Question(_) => self.finish_turn(path),
+            _ => None,
+        };
+        let project = self.session_cwds.get(path).cloned();
+        let mode = self.permission_mode(profile, path, project.as_deref());
+        if let Some(mode) = mode {
+            match self.session_modes.insert(path.clone(), mode) {
+                Some(old) if old != mode => {
+                    let change = format!("{} → {}", old.as_str(), mode.as_str());
+                    log_event(&self.state, path, project.as_deref(), "mode_change", Some(change), None, Outcome::Tracked);
+                }
+                _ => {}
+            }
+        }
+        let kind = record_event(&self.state, &line_event, mode);
+        let (detail, raw_type) = match &line_event {
+            LineEvent::SubagentSpawn(name) => (Some(name.clone()), None),
+            LineEvent::ToolUse(tools) => (Some(tools.join(", ")), None),
+            LineEvent::ToolError(text) | LineEvent::ApiError(text) => (Some(text.clone()), None),
+            LineEvent::Notice(_, raw) => (None, Some(raw.clone())),
+            _ => (None, None),
+        };
+        let watched = self.profiles[profile].profile.clone();
+        let (agent, voice) = (watched.agent.as_str(), watched.voice.as_deref());
+        let name = project_name(project.as_deref());
+        let action = match line_event {
+            LineEvent::Completion => {
+                let hooked = watched.format == LogFormat::ClaudeJsonl && self.hooks_mode();
+                let window = Duration::from_millis(self.config.completion_window_ms);
+                let debounced = self.last_completion_notify.get(path)
+                    .map(|t| t.elapsed() <= window)
+                    .unwrap_or(false);
+                let action = if hooked {
+                    Action::Skip(Outcome::Suppressed("hooks"))
+                } else if debounced {
+                    Action::Skip(Outcome::Suppressed("debounce"))
+                } else {
+                    let aggregate_window = Duration::from_millis(self.config.aggregate_window_ms);
+                    let aggregating = self.config.completion_grouping == CompletionGrouping::Aggregate
+                        && self.last_completion_spoken.map(|t| t.elapsed() < aggregate_window).unwrap_or(false);
+                    // Another session finished moments ago: say which one this is
+                    let others = aggregating || self.last_completion_notify.iter()
+                        .any(|(p, t)| p != path && t.elapsed() <= window);
+                    let template = watched.completion_template();
+                    let mut text = template.replace("{project}", &name);
+                    if others && !template.contains("{project}") {
+                        text = in_project(&text, &name);
+                    }
+                    if let Some((tokens, cost)) = turn.filter(|_| self.config.announce_usage) {
+                        text = format!("{}, {}", text, usage_phrase(&tokens, cost));
+                    }
+                    self.last_completion_notify.insert(path.clone(), Instant::now());
+                    if aggregating {
+                        Action::Hold { text }
+                    } else {
+                        Action::Speak { text, detail: None }
+                    }
+                };
+                self.clear_approval(path);
+                self.running_commands.remove(path);
+                action
+            }
+            LineEvent::Question(kind) => {
+                // Never debounced: this is the moment the user must come back
+                let template = match kind {
+                    QuestionKind::PlanApproval => i18n::phrase_or(Phrase::Plan, &self.config.plan_phrase),
+                    _ => i18n::phrase_or(Phrase::Question, &self.config.question_phrase),
+                };
+                let text = template.replace("{project}", &name);
+                self.last_completion_notify.insert(path.clone(), Instant::now());
+                self.clear_approval(path);
+                self.running_commands.remove(path);
+                Action::Speak { text, detail: None }
+            }
+            LineEvent::SubagentSpawn(desc) => {
+                Action::Speak { text: i18n::phrase(Phrase::Spawning).replace("{task}", &desc), detail: None }
+            }
+            LineEvent::ToolUse(tools) => {
+                if let Some(tool) = tools.iter().find(|t| self.config.long_running_tools.contains(t)) {
+                    self.running_commands.insert(path.clone(), RunningCommand {
+                        profile,
+                        tool: tool.clone(),
+                        since: Instant::now(),
+                        project: project.clone(),
+                        alerts_sent: 0,
+                        last_alert: None,
+                    });
+                }
+                self.pending_approvals.entry(path.clone()).or_insert_with(|| PendingApproval {
+                    profile,
+                    tools,
+                    since: Instant::now(),
+                    project: project.clone(),
+                    alerts_sent: 0,
+                    last_alert: None,
+                });
+                self.publish_approval_status();
+                Action::Skip(Outcome::Tracked)
+            }
+            LineEvent::ToolResult => {
+                self.clear_approval(path);
+                self.running_commands.remove(path);
+                Action::Skip(Outcome::Tracked)
+            }
+            LineEvent::Usage { .. } | LineEvent::SessionMode(_) => Action::Skip(Outcome::Tracked), // handled above
+            LineEvent::Notice(notice, _) => {
+                let (enabled, phrase, template) = match notice {
+                    NoticeKind::Compaction => (self.config.announce_compaction, Phrase::Compaction, &self.config.compaction_phrase),
+                    NoticeKind::ContextLimit => (self.config.announce_limits, Phrase::ContextLimit, &self.config.context_limit_phrase),
+                    NoticeKind::UsageLimit => (self.config.announce_limits, Phrase::UsageLimit, &self.config.usage_limit_phrase),
+                };
+                let cooldown = Duration::from_secs(self.config.notice_cooldown_secs);
+                let key = (path.clone(), notice);
+                let due = self.last_notice.get(&key).map(|t| t.elapsed() >= cooldown).unwrap_or(true);
+                if !enabled {
+                    Action::Skip(Outcome::Suppressed("disabled"))
+                } else if !due {
+                    Action::Skip(Outcome::Suppressed("cooldown"))
+                } else {
+                    self.last_notice.insert(key, Instant::now());
+                    Action::Speak { text: i18n::phrase_or(phrase, template).replace("{project}", &name), detail: None }
+                }
+            }
+            LineEvent::ToolError(detail) | LineEvent::ApiError(detail) => {
+                let cooldown = Duration::from_secs(self.config.error_cooldown_secs);
+                let due = self.last_error_notify.get(path)
+                    .map(|t| t.elapsed() >= cooldown)
+                    .unwrap_or(true);
+                if !self.config.announce_errors {
+                    Action::Skip(Outcome::Suppressed("disabled"))
+                } else if !due {
+                    Action::Skip(Outcome::Suppressed("cooldown"))
+                } else {
+                    let template = match (kind, &watched.error_phrase) {
+                        ("api_error", _) => i18n::phrase_or(Phrase::ApiError, &self.config.api_error_phrase),
+                        (_, Some(phrase)) => phrase.clone(),
+                        _ => i18n::phrase_or(Phrase::CommandFailed, &self.config.error_phrase),
+                    };
+                    self.last_error_notify.insert(path.clone(), Instant::now());
+                    Action::Speak { text: template.replace("{project}", &name), detail: Some(detail) }
+                }
+            }
+        };
+        // State above is updated either way; excluded projects just never reach the queue
+        let outcome = match action {
+            Action::Speak { .. } | Action::Hold { .. } if !self.config.project_allowed(&name) => {
+                Outcome::Suppressed("excluded")
+            }
+            Action::Speak { text, detail } => {
+                let style = speech_style(&self.config, kind, &name, voice);
+                let outcome = announce(&self.state, &self.config, Alert { kind, project: &name, agent, text: &text, style, detail });
+                if kind == "completion" {
+                    self.last_completion_spoken = Some(Instant::now()); // opens the aggregation window
+                }
+                outcome
+            }
+            Action::Hold { text } => {
+                self.grouped_completions.push(GroupedCompletion {
+                    agent: agent.to_string(),
+                    style: speech_style(&self.config, kind, &name, voice),
+                    text,
+                    project: name,
+                });
+                Outcome::Held
+            }
+            Action::Skip(outcome) => outcome,
+        };
+        log_event(&self.state, path, project.as_deref(), kind, detail, raw_type, outcome);
+    }
+
+    /// Aggregate grouping: once the window opened by the last spoken completion closes, speak
+    /// what was held meanwhile, alone or as one follow-up naming every project
+    fn flush_completions(&mut self) {
+        let window = Duration::from_millis(self.config.aggregate_window_ms);
+        let open = self.last_completion_spoken.map(|t| t.elapsed() < window).unwrap_or(false);
+        if self.grouped_completions.is_empty() || open {
+            return;
+        }
+        let group = std::mem::take(&mut self.grouped_completions);
+        let mut projects: Vec<&str> = Vec::new();
+        for done in &group {
+            if !projects.contains(&done.project.as_str()) {
+                projects.push(&done.project);
+            }
+        }
+        let text = if let [only] = group.as_slice() {
+            only.text.clone()
+        } else {
+            i18n::phrase_or(Phrase::Aggregate, &self.config.aggregate_phrase)
+                .replace("{count}", &count_word(group.len()))
+                .replace("{projects}", &join_names(&projects))
+        };
+        let alert = Alert {
+            kind: "completion",
+            project: &join_names(&projects),
+            agent: &group[0].agent,
+            text: &text,
+            style: group[0].style.clone(),
+            detail: None,
+        };
+        announce(&self.state, &self.config, alert);
+    }
+
+    /// Add an assistant message's usage to the session's turn and its totals in /status
+    fn record_usage(&mut self, path: &Path, id: Option<String>, model: Option<String>, tokens: TokenUsage) {
+        let cost = model.as_deref()
+            .and_then(|m| self.config.prices_for(m))
+            .map(|prices| usage_cost(prices, &tokens));
+        let turn = self.turn_usage.entry(path.to_path_buf()).or_default();
+        let replaced = match (&id, &turn.last_message) {
+            (Some(id), Some((last_id, old, old_cost))) if id == last_id => Some((*old, *old_cost)),
+            _ => None,
+        };
+        if let Some((old, old_cost)) = replaced {
+            turn.tokens.sub(&old);
+            turn.cost = add_cost(turn.cost, old_cost.map(|c| -c));
+        }
+        turn.tokens.add(&tokens);
+        turn.cost = add_cost(turn.cost, cost);
+        turn.last_message = id.map(|id| (id, tokens, cost));
+
+        let mut status = self.state.watcher_status.lock();
+        let totals = status.session_usage.entry(session_key(path)).or_default();
+        if let Some((old, old_cost)) = replaced {
+            totals.tokens.sub(&old);
+            totals.cost_usd = add_cost(totals.cost_usd, old_cost.map(|c| -c));
+        }
+        totals.tokens.add(&tokens);
+        totals.cost_usd = add_cost(totals.cost_usd, cost);
+    }
+
+    /// End the session's turn, returning its usage if any was reported
+    fn finish_turn(&mut self, path: &Path) -> Option<(TokenUsage, Option<f64>)> {
+        let turn = self.turn_usage.remove(path)?;
+        self.state.watcher_status.lock().session_usage.entry(session_key(path)).or_default().turns += 1;
+        (turn.tokens.billed() > 0).then_some((turn.tokens, turn.cost))
+    }
+
+    fn clear_approval(&mut self, path: &Path) {
+        if self.pending_approvals.remove(path).is_some() {
+            self.publish_approval_status();
+        }
+    }
+
+    fn clear_all_approvals(&mut self) {
+        self.pending_approvals.clear();
+        self.running_commands.clear();
+        self.publish_approval_status();
+    }
+
+    fn publish_approval_status(&self) {
+        let mut status = self.state.watcher_status.lock();
+        status.pending_approvals = self.pending_approvals.len();
+        status.approval_reminders = self.pending_approvals.values()
+            .map(|p| p.alerts_sent.saturating_sub(1))
+            .max()
+            .unwrap_or(0);
+    }
+
+    /// Approval alert + reminder chain, evaluated per session. Only sessions running in
+    /// Normal mode ever wait on the user; muting the app or switching a session to
+    /// SkipAll cancels its chain, and deleted session files are dropped.
+    fn check_approvals(&mut self) {
+        if self.pending_approvals.is_empty() {
+            return;
+        }
+        let muted = self.state.silenced();
+        let before = self.pending_approvals.len();
+        let mode_cache = &mut self.mode_cache;
+        let log_modes = &self.log_modes;
+        let profiles = &self.profiles;
+        let state = &self.state;
+        self.pending_approvals.retain(|path, pending| {
+            let gated = profiles[pending.profile].profile.format == LogFormat::ClaudeJsonl;
+            // Muting or snoozing ends a reminder chain; the first alert still goes out as a notification
+            let reason = if muted && pending.alerts_sent > 0 {
+                "mute"
+            } else if !path.exists() {
+                "deleted"
+            } else if gated && session_mode(log_modes, mode_cache, path, pending.project.as_deref()) != PermissionMode::Normal {
+                "mode"
+            } else {
+                return true;
+            };
+            let tools = Some(pending.tools.join(", "));
+            log_event(state, path, pending.project.as_deref(), "approval_alert", tools, None, Outcome::Suppressed(reason));
+            false
+        });
+
+        let reminder_interval = Duration::from_secs(self.config.reminder_interval_secs);
+        let hooks_mode = self.hooks_mode();
+        let several = self.pending_approvals.len() > 1;
+        let mut alerted = false;
+        for (path, pending) in self.pending_approvals.iter_mut() {
+            let due = match pending.last_alert {
+                None => pending.since.elapsed() >= APPROVAL_ALERT_DELAY,
+                Some(last) => pending.alerts_sent <= self.config.reminder_max
+                    && last.elapsed() >= reminder_interval,
+            };
+            if !due {
+                continue;
+            }
+            let phrase = self.config.reminder_phrase(pending.alerts_sent);
+            // With several sessions blocked, say which one this is
+            let text = if several {
+                in_project(&phrase, &project_name(pending.project.as_deref()))
+            } else {
+                phrase
+            };
+            let watched = &self.profiles[pending.profile].profile;
+            let skip = if hooks_mode && pending.alerts_sent == 0 && watched.format == LogFormat::ClaudeJsonl {
+                Some("hooks") // the Notification hook already announced the permission prompt
+            } else if !self.config.project_allowed(&project_name(pending.project.as_deref())) {
+                Some("excluded")
+            } else {
+                None
+            };
+            if let Some(reason) = skip {
+                pending.alerts_sent += 1;
+                pending.last_alert = Some(Instant::now());
+                let tools = Some(pending.tools.join(", "));
+                log_event(&self.state, path, pending.project.as_deref(), "approval_alert", tools, None, Outcome::Suppressed(reason));
+                continue;
+            }
+            let name = project_name(pending.project.as_deref());
+            let mut style = speech_style(&self.config, "approval_alert", &name, watched.voice.as_deref());
+            if pending.alerts_sent > 0 && self.config.reminder_voice.is_some() {
+                style.voice = self.config.reminder_voice.clone();
+            }
+            info!("Approval pending for {} (alert {})", pending.tools.join(", "), pending.alerts_sent + 1);
+            let alert = Alert { kind: "approval_alert", project: &name, agent: &watched.agent, text: &text, style, detail: None };
+            let outcome = announce(&self.state, &self.config, alert);
+            let tools = Some(pending.tools.join(", "));
+            log_event(&self.state, path, pending.project.as_deref(), "approval_alert", tools, None, outcome);
+            pending.alerts_sent += 1;
+            pending.last_alert = Some(Instant::now());
+            alerted = true;
+        }
+
+        if alerted || self.pending_approvals.len() != before {
+            self.publish_approval_status();
+        }
+    }
+
+    /// "Still running" alerts for commands with no tool result after the threshold, repeated
+    /// at a longer interval. Independent of the permission mode. Commands are dropped once their
+    /// session log is deleted or untouched for `long_running_inactive_secs`, or when muted.
+    fn check_running_commands(&mut self) {
+        if self.running_commands.is_empty() {
+            return;
+        }
+        let muted = self.state.silenced();
+        let inactive = Duration::from_secs(self.config.long_running_inactive_secs);
+        let state = &self.state;
+        self.running_commands.retain(|path, running| {
+            let idle = std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .map(|t| t.elapsed().unwrap_or_default());
+            let reason = if muted && running.alerts_sent > 0 {
+                "mute"
+            } else if matches!(idle, Ok(idle) if idle < inactive) {
+                return true;
+            } else {
+                "inactive"
+            };
+            let tool = Some(running.tool.clone());
+            log_event(state, path, running.project.as_deref(), "long_running", tool, None, Outcome::Suppressed(reason));
+            false
+        });
+
+        let threshold = Duration::from_secs(self.config.long_running_after_secs);
+        let interval = Duration::from_secs(self.config.long_running_interval_secs);
+        for (path, running) in self.running_commands.iter_mut() {
+            let due = match running.last_alert {
+                None => running.since.elapsed() >= threshold,
+                Some(last) => running.alerts_sent <= self.config.long_running_max && last.elapsed() >= interval,
+            };
+            if !due {
+                continue;
+            }
+            let name = project_name(running.project.as_deref());
+            if !self.config.project_allowed(&name) {
+                running.alerts_sent += 1;
+                running.last_alert = Some(Instant::now());
+                let tool = Some(running.tool.clone());
+                log_event(&self.state, path, running.project.as_deref(), "long_running", tool, None, Outcome::Suppressed("excluded"));
+                continue;
+            }
+            let text = i18n::phrase_or(Phrase::LongRunning, &self.config.long_running_phrase)
+                .replace("{project}", &name)
+                .replace("{duration}", &speak_duration(running.since.elapsed()));
+            let watched = &self.profiles[running.profile].profile;
+            info!("{} still running (alert {})", running.tool, running.alerts_sent + 1);
+            let style = speech_style(&self.config, "long_running", &name, watched.voice.as_deref());
+            let alert = Alert { kind: "long_running", project: &name, agent: &watched.agent, text: &text, style, detail: None };
+            let outcome = announce(&self.state, &self.config, alert);
+            let tool = Some(running.tool.clone());
+            log_event(&self.state, path, running.project.as_deref(), "long_running", tool, None, outcome);
+            running.alerts_sent += 1;
+            running.last_alert = Some(Instant::now());
+        }
+    }
+}
+
+/// Read new lines appended to a session log since last check and parse them per the profile's format.
+/// Also records the session's working directory for permission lookups and announcements.
+/// The bool is true when the read budget ran out before EOF.
 fn check_new_lines(
     path: &PathBuf,
     positions: &mut HashMap<PathBuf, u64>,
-) -> LineEvent {
-    let Ok(mut file) = File::open(path) else { return LineEvent::None };
-    let Ok(metadata) = file.metadata() else { return LineEvent::None };
+    cwds: &mut HashMap<PathBuf, PathBuf>,
+    max_line_bytes: usize,
+    profile: &WatcherProfile,
+) -> (Vec<LineEvent>, bool) {
+    let mut events = Vec::new();
+    let Ok(mut file) = File::open(path) else { return (events, false) };
+    let Ok(metadata) = file.metadata() else { return (events, false) };
     let file_size = metadata.len();
 
     // First time seeing this file — skip history, start tracking from current end
@@ -105,126 +951,879 @@ fn check_new_lines(
         *pos = 0; // file truncated/rotated
     }
     if file_size == *pos {
-        return LineEvent::None;
+        return (events, false);
     }
 
-    let _ = file.seek(SeekFrom::Start(*pos));
-    let mut new_content = String::new();
-    let _ = file.read_to_string(&mut new_content);
-    *pos = file_size;
+    let batch = read_complete_lines(&mut file, pos, READ_BUDGET_PER_TICK, max_line_bytes);
+    for line in batch.lines.iter().filter(|l| !l.is_empty()) {
+        let parsed = match profile.format {
+            LogFormat::ClaudeJsonl => classify_line(line),
+            LogFormat::Mapped => classify_mapped_line(line, &profile.mapping),
+        };
+        record_cwd(path, parsed.cwd.as_deref(), cwds);
+        events.extend(parsed.events);
+    }
+    (events, batch.more)
+}
+
+/// A session's permission mode: what its log recorded last, else the settings files
+fn session_mode(
+    log_modes: &HashMap<PathBuf, PermissionMode>,
+    mode_cache: &mut ModeCache,
+    path: &Path,
+    project: Option<&Path>,
+) -> PermissionMode {
+    log_modes.get(path).copied().unwrap_or_else(|| mode_cache.resolve(project))
+}
+
+/// Lines read from a session file in one pass
+struct LineBatch {
+    lines: Vec<String>,
+    more: bool, // stopped at the per-tick budget; the rest is read next tick
+}
+
+/// Read complete lines from `*pos`, advancing `*pos` only past the last newline consumed.
+/// A trailing fragment (Claude Code mid-write) stays unread until it is completed, and a
+/// position that lands mid-line (first sight or rewritten file) skips to the next line start.
+/// Reads stop at the first line boundary after `budget` bytes, and lines longer than
+/// `max_line_bytes` are skipped without being buffered.
+fn read_complete_lines(file: &mut File, pos: &mut u64, budget: u64, max_line_bytes: usize) -> LineBatch {
+    let mut batch = LineBatch { lines: Vec::new(), more: false };
+
+    // Read one byte before the position to tell whether we start on a line boundary
+    let read_from = pos.saturating_sub(1);
+    if file.seek(SeekFrom::Start(read_from)).is_err() {
+        return batch;
+    }
+    let mut reader = BufReader::new(file);
 
-    let mut result = LineEvent::None;
+    let mut consumed: u64 = 0;  // bytes consumed since read_from
+    let mut committed: u64 = 0; // consumed up to the last complete line
+    let mut skipping_fragment = false;
+    if *pos > 0 {
+        match reader.fill_buf() {
+            Ok([first, ..]) => skipping_fragment = *first != b'\n',
+            _ => return batch,
+        }
+        reader.consume(1);
+        consumed = 1;
+        committed = 1;
+    }
 
-    for line in new_content.lines() {
-        if line.is_empty() || !line.contains("stop_reason") {
-            continue;
+    let mut line: Vec<u8> = Vec::new();
+    let mut oversized = false;
+    loop {
+        if committed >= budget {
+            batch.more = true;
+            break;
         }
-        let Ok(json) = serde_json::from_str::<serde_json::Value>(line) else {
-            continue;
+        let buf = match reader.fill_buf() {
+            Ok([]) | Err(_) => break, // EOF: an unterminated tail stays unread
+            Ok(buf) => buf,
         };
-        if json.get("type").and_then(|t| t.as_str()) != Some("assistant") {
-            continue;
+        let (chunk, terminated) = match buf.iter().position(|b| *b == b'\n') {
+            Some(i) => (&buf[..i], true),
+            None => (buf, false),
+        };
+        if !skipping_fragment && !oversized {
+            if line.len() + chunk.len() > max_line_bytes {
+                oversized = true;
+                line = Vec::new();
+            } else {
+                line.extend_from_slice(chunk);
+            }
         }
-        match json.pointer("/message/stop_reason").and_then(|s| s.as_str()) {
-            Some("end_turn") => {
-                result = LineEvent::Completion;
+        let used = chunk.len() + terminated as usize;
+        reader.consume(used);
+        consumed += used as u64;
+
+        if terminated {
+            if oversized {
+                info!("Skipped a session line over {} bytes", max_line_bytes);
+            } else if !skipping_fragment {
+                batch.lines.push(String::from_utf8_lossy(&line).trim_end_matches('\r').to_string());
             }
-            Some("tool_use") => {
-                if let Some(spawn) = extract_task_spawn(&json) {
-                    return LineEvent::SubagentSpawn(spawn);
-                }
-                // Non-Task tool_use: approval handled by PreToolUse hook
-            }
-            _ => {}
-        }
-    }
-    result
-}
-
-/// Map text to Norse agent name if any keyword is found.
-fn detect_norse(text: &str) -> Option<&'static str> {
-    let t = text.to_lowercase();
-    if t.contains("thor")     { return Some("Thor") }
-    if t.contains("heimdall") { return Some("Heimdall") }
-    if t.contains("tyr")      { return Some("Tyr") }
-    if t.contains("ymir")     { return Some("Ymir") }
-    if t.contains("odin")     { return Some("Odin") }
-    if t.contains("loki")     { return Some("Loki") }
-    if t.contains("huginn")   { return Some("Huginn") }
-    None
-}
-
-/// If the assistant message contains a subagent spawn tool_use, return its name.
-fn extract_task_spawn(json: &serde_json::Value) -> Option<String> {
-    let content = json.pointer("/message/content")?.as_array()?;
-    for item in content {
-        if item.get("type").and_then(|t| t.as_str()) != Some("tool_use") {
-            continue;
-        }
-        let tool_name = item.get("name").and_then(|n| n.as_str()).unwrap_or("");
-
-        // MCP local-llm direct calls → Norse name from tool name
-        if tool_name.contains("query_thor")     { return Some("Thor".to_string()) }
-        if tool_name.contains("query_heimdall") { return Some("Heimdall".to_string()) }
-        if tool_name.contains("query_loki")     { return Some("Loki".to_string()) }
-        if tool_name.contains("query_tyr")      { return Some("Tyr".to_string()) }
-
-        // Agent subagent spawn
-        if tool_name == "Agent" {
-            let desc = item.pointer("/input/description")
-                .and_then(|d| d.as_str()).unwrap_or("");
-            let prompt = item.pointer("/input/prompt")
-                .and_then(|p| p.as_str()).unwrap_or("");
-            let subagent_type = item.pointer("/input/subagent_type")
-                .and_then(|t| t.as_str()).unwrap_or("");
-
-            // Try Norse name from description → prompt → subagent_type
-            if let Some(name) = detect_norse(desc)
-                .or_else(|| detect_norse(prompt))
-                .or_else(|| detect_norse(subagent_type))
-            {
-                return Some(name.to_string());
-            }
-
-            // Fallback: subagent_type → friendly name
-            let label = match subagent_type {
-                "Explore"         => "Heimdall",
-                "general-purpose" => "Agent",
-                "Plan"            => "Tyr",
-                other if !other.is_empty() => other,
-                _ if !desc.is_empty() => desc,
-                _ => "Agent",
-            };
-            return Some(label.to_string());
-        }
-    }
-    None
-}
-
-fn queue_voice(state: &Arc<AppState>, text: &str, rate: u32) {
-    let id = state
-        .next_id
-        .lock()
-        .map(|mut n| {
-            let i = *n;
-            *n += 1;
-            i
-        })
-        .unwrap_or(0);
-
-    if let Ok(mut timeline) = state.timeline.lock() {
-        timeline.push_back(VoiceEntry {
-            id,
-            timestamp: Utc::now(),
-            text: text.to_string(),
-            voice: "Samantha".to_string(),
-            rate,
-            agent: Some("claude".to_string()),
-            status: "queued".to_string(),
-        });
-        while timeline.len() > 100 {
-            timeline.pop_front();
+            skipping_fragment = false;
+            oversized = false;
+            line.clear();
+            committed = consumed;
+        }
+    }
+
+    *pos = read_from + committed;
+    batch
+}
+
+/// Remember the project directory a session runs in
+fn record_cwd(path: &Path, cwd: Option<&str>, cwds: &mut HashMap<PathBuf, PathBuf>) {
+    if let Some(cwd) = cwd {
+        if cwds.get(path).map(|p| p.as_os_str() != cwd).unwrap_or(true) {
+            cwds.insert(path.to_path_buf(), PathBuf::from(cwd));
+        }
+    }
+}
+
+fn usage_cost(prices: &TokenPrices, tokens: &TokenUsage) -> f64 {
+    (tokens.input_tokens as f64 * prices.input
+        + tokens.output_tokens as f64 * prices.output
+        + tokens.cache_creation_tokens as f64 * prices.cache_write
+        + tokens.cache_read_tokens as f64 * prices.cache_read)
+        / 1_000_000.0
+}
+
+fn add_cost(total: Option<f64>, cost: Option<f64>) -> Option<f64> {
+    match (total, cost) {
+        (None, None) => None,
+        (total, cost) => Some(total.unwrap_or(0.0) + cost.unwrap_or(0.0)),
+    }
+}
+
+/// Key for a session in /status: its log file name without extension
+fn session_key(path: &Path) -> String {
+    path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// Spoken summary of a turn's usage: the cost when priced, otherwise the token count
+fn usage_phrase(tokens: &TokenUsage, cost: Option<f64>) -> String {
+    match cost {
+        Some(usd) => speak_cost(usd),
+        None => speak_tokens(tokens.billed()),
+    }
+}
+
+/// Round a token count to something worth saying: "4 thousand tokens", "1.2 million tokens"
+fn speak_tokens(n: u64) -> String {
+    let with = |phrase, n: f64| i18n::phrase(phrase).replace("{n}", &n.to_string());
+    if n < 1_000 {
+        return i18n::phrase(Phrase::UnderThousandTokens);
+    }
+    if n < 1_000_000 {
+        let thousands = n as f64 / 1_000.0;
+        let rounded = if thousands < 10.0 { thousands.round() } else { round_two_digits(thousands) };
+        return with(Phrase::ThousandTokens, rounded);
+    }
+    with(Phrase::MillionTokens, (n as f64 / 100_000.0).round() / 10.0)
+}
+
+/// "about 3 cents", "about 1.4 dollars", "about 12 dollars"
+fn speak_cost(usd: f64) -> String {
+    let with = |phrase, n: f64| i18n::phrase(phrase).replace("{n}", &n.to_string());
+    let cents = (usd * 100.0).round();
+    if cents < 1.0 {
+        i18n::phrase(Phrase::LessThanCent)
+    } else if cents == 1.0 {
+        i18n::phrase(Phrase::Cent)
+    } else if cents < 100.0 {
+        with(Phrase::Cents, cents)
+    } else if usd < 10.0 {
+        let dollars = (usd * 10.0).round() / 10.0;
+        if dollars == 1.0 { i18n::phrase(Phrase::Dollar) } else { with(Phrase::Dollars, dollars) }
+    } else {
+        with(Phrase::Dollars, round_two_digits(usd))
+    }
+}
+
+/// Round to two significant digits (123 → 120)
+fn round_two_digits(x: f64) -> f64 {
+    let scale = 10f64.powi(x.log10().floor() as i32 - 1);
+    (x / scale).round() * scale
+}
+
+/// "five minutes", "one hour", "45 seconds"
+fn speak_duration(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    let (n, one, many) = if secs < 60 {
+        (secs, Phrase::Second, Phrase::Seconds)
+    } else if secs < 3600 {
+        (secs / 60, Phrase::Minute, Phrase::Minutes)
+    } else {
+        (secs / 3600, Phrase::Hour, Phrase::Hours)
+    };
+    match n {
+        1 => i18n::phrase(one),
+        n => i18n::phrase(many).replace("{n}", &count_word(n as usize)),
+    }
+}
+
+/// Small counts read better as words: "two sessions finished"
+fn count_word(n: usize) -> String {
+    i18n::phrase(Phrase::Numbers).split_whitespace().nth(n).map(str::to_string).unwrap_or_else(|| n.to_string())
+}
+
+/// "oracle", "oracle and dashboard", "oracle, api and dashboard"
+fn join_names(names: &[&str]) -> String {
+    match names {
+        [] => String::new(),
+        [only] => only.to_string(),
+        [rest @ .., last] => i18n::phrase(Phrase::And).replace("{first}", &rest.join(", ")).replace("{last}", last),
+    }
+}
+
+/// "Action needed, please approve in oracle"
+fn in_project(phrase: &str, project: &str) -> String {
+    i18n::phrase(Phrase::InProject).replace("{phrase}", phrase).replace("{project}", project)
+}
+
+/// Project name for announcements: the last component of the session's working directory
+pub(crate) fn project_name(cwd: Option<&Path>) -> String {
+    cwd.and_then(|p| p.file_name())
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| i18n::phrase(Phrase::ThisSession))
+}
+
+/// A watcher alert on its way to the voice queue and/or a system notification
+pub(crate) struct Alert<'a> {
+    pub kind: &'a str, // event log kind; keys `voices` and `delivery`
+    pub project: &'a str,
+    pub agent: &'a str,
+    pub text: &'a str,
+    pub style: SpeechStyle,
+    pub detail: Option<String>,
+}
+
+/// Deliver an alert as configured for its kind. While muted, snoozed, in quiet hours or in a
+/// muting Focus it is only shown as a notification, unless relay.enabled queues it for the peer
+/// (see relay.rs); the notification never delays the voice entry.
+pub(crate) fn announce(state: &Arc<AppState>, config: &WatcherConfig, alert: Alert) -> Outcome {
+    let muted = state.is_muted();
+    let snoozed = state.snooze_minutes_left(Utc::now()).is_some();
+    let quiet = config.quiet_hours.as_ref().is_some_and(|q| q.contains(Local::now().time()));
+    let focused = focus::muting(state);
+    let relaying = (muted || snoozed || quiet || focused) && load_config().relay.enabled;
+    let delivery = if (muted || snoozed || quiet || focused) && !relaying { Delivery::Notification } else { config.delivery_for(alert.kind) };
+    if delivery != Delivery::Notification {
+        let style = &alert.style;
+        queue_voice(state, alert.agent, alert.text, style.voice.as_deref(), style.rate, style.priority, alert.detail);
+    }
+    let suppression = if relaying {
+        None
+    } else if muted {
+        Some(Suppression::Muted)
+    } else if snoozed {
+        Some(Suppression::Snoozed)
+    } else if quiet {
+        Some(Suppression::QuietHours)
+    } else if focused {
+        Some(Suppression::Focus)
+    } else {
+        None
+    };
+    if suppression.is_some() {
+        stats::record(state, Some(alert.agent), |c| c.suppressed += 1);
+    }
+    // With notify_suppressed on for the reason, a burst of alerts is rolled up into one notification
+    let rolled_up = suppression.is_some_and(|reason| notify_suppressed(state, reason, Some(alert.agent), alert.text));
+    if delivery != Delivery::Voice && !rolled_up {
+        notify(state, alert.project, alert.text);
+    }
+    match delivery {
+        Delivery::Notification => Outcome::Notified,
+        _ => Outcome::Queued,
+    }
+}
+
+/// Voice settings for an announcement; a voice that is not installed falls back to the default
+pub(crate) fn speech_style(config: &WatcherConfig, kind: &str, project: &str, profile_voice: Option<&str>) -> SpeechStyle {
+    let mut style = config.voice_style(kind, project, profile_voice);
+    if let Some(voice) = style.voice.as_deref() {
+        if !voice_installed(voice) {
+            warn!("Voice \"{}\" for {} is not installed, using the default", voice, kind);
+            style.voice = None;
+        }
+    }
+    style
+}
+
+pub(crate) fn queue_voice(
+    state: &Arc<AppState>,
+    agent: &str,
+    text: &str,
+    voice: Option<&str>,
+    rate: u32,
+    priority: u8,
+    detail: Option<String>,
+) {
+    let voice = voice.map(str::to_string).unwrap_or_else(|| state.voice_config().voice());
+    state.enqueue(text.to_string(), voice, rate, Some(agent.to_string()), priority, detail);
+    info!("Voice queued: {}", text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::Ordering;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    const END_TURN: &str = r#"{"type":"assistant","cwd":"/work/oracle","message":{"stop_reason":"end_turn","content":[{"type":"text","text":"Done."}]}}"#;
+    const TOOL_USE: &str = r#"{"type":"assistant","cwd":"/work/oracle","message":{"stop_reason":"tool_use","content":[{"type":"tool_use","name":"Bash","input":{"command":"ls"}}]}}"#;
+
+    struct Session {
+        _dir: TempDir,
+        path: PathBuf,
+        positions: HashMap<PathBuf, u64>,
+        cwds: HashMap<PathBuf, PathBuf>,
+        profile: WatcherProfile,
+    }
+
+    impl Session {
+        fn new(initial: &str) -> Self {
+            Self::with_profile(initial, WatcherProfile::claude())
+        }
+
+        fn with_profile(initial: &str, profile: WatcherProfile) -> Self {
+            let dir = TempDir::new().expect("temp dir");
+            let path = dir.path().join("session.jsonl");
+            std::fs::write(&path, initial).expect("write");
+            let mut session = Self { _dir: dir, path, positions: HashMap::new(), cwds: HashMap::new(), profile };
+            assert!(session.check().is_empty(), "history must be skipped");
+            session
         }
+
+        fn append(&self, text: &str) {
+            let mut file = std::fs::OpenOptions::new().append(true).open(&self.path).expect("open");
+            file.write_all(text.as_bytes()).expect("append");
+        }
+
+        fn check(&mut self) -> Vec<LineEvent> {
+            check_new_lines(&self.path, &mut self.positions, &mut self.cwds, usize::MAX, &self.profile).0
+        }
+    }
+
+    #[test]
+    fn test_question_detection() {
+        let mut session = Session::new("");
+        let ask = r#"{"type":"assistant","message":{"stop_reason":"tool_use","content":[{"type":"tool_use","name":"AskUserQuestion","input":{}}]}}"#;
+        let plan = r#"{"type":"assistant","message":{"stop_reason":"tool_use","content":[{"type":"tool_use","name":"ExitPlanMode","input":{"plan":"..."}}]}}"#;
+        let trailing = r#"{"type":"assistant","message":{"stop_reason":"end_turn","content":[{"type":"text","text":"Should I also update the docs? "}]}}"#;
+
+        session.append(&format!("{}\n{}\n{}\n{}\n", ask, plan, trailing, END_TURN));
+        assert_eq!(session.check(), vec![
+            LineEvent::Question(QuestionKind::AskUser),
+            LineEvent::Question(QuestionKind::PlanApproval),
+            LineEvent::Question(QuestionKind::TrailingQuestion),
+            LineEvent::Completion,
+        ]);
+    }
+
+    #[test]
+    fn test_tool_and_api_errors() {
+        let mut session = Session::new("");
+        let failed = r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","is_error":true,"content":"Exit code 1\nerror[E0425]: cannot find value `x` in this scope"}]}}"#;
+        let rejected = r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t2","is_error":true,"content":"The user doesn't want to proceed with this tool use."}]}}"#;
+        let ok = r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t3","content":"ok"}]}}"#;
+        let api = r#"{"type":"assistant","isApiErrorMessage":true,"message":{"stop_reason":"stop_sequence","content":[{"type":"text","text":"API Error: 529 Overloaded"}]}}"#;
+
+        session.append(&format!("{}\n{}\n{}\n{}\n", failed, rejected, ok, api));
+        assert_eq!(session.check(), vec![
+            LineEvent::ToolResult,
+            LineEvent::ToolError("Exit code 1 error[E0425]: cannot find value `x` in this scope".to_string()),
+            LineEvent::ToolResult,
+            LineEvent::ToolResult,
+            LineEvent::ApiError("API Error: 529 Overloaded".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_compaction_and_limit_notices() {
+        let mut session = Session::new("");
+        let compact = r#"{"type":"system","subtype":"compact_boundary","content":"Conversation compacted","compactMetadata":{"trigger":"auto"}}"#;
+        let context = r#"{"type":"system","content":"Context low (8% remaining) · Run /compact to compact & continue","level":"warning"}"#;
+        let usage = r#"{"type":"assistant","isApiErrorMessage":true,"message":{"content":[{"type":"text","text":"Claude AI usage limit reached|1767225600"}]}}"#;
+        let info = r#"{"type":"system","subtype":"informational","content":"Running hook"}"#;
+
+        session.append(&format!("{}\n{}\n{}\n{}\n", compact, context, usage, info));
+        assert_eq!(session.check(), vec![
+            LineEvent::Notice(NoticeKind::Compaction, "system/compact_boundary".to_string()),
+            LineEvent::Notice(NoticeKind::ContextLimit, "system".to_string()),
+            LineEvent::Notice(NoticeKind::UsageLimit, "assistant/isApiErrorMessage".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_notices_rate_limited_and_logged() {
+        let state = Arc::new(AppState::default());
+        let config = WatcherConfig { announce_limits: false, ..WatcherConfig::default() };
+        let (_claude_home, mut watcher) = test_watcher(&state, config);
+        let session = Session::new("");
+        watcher.check_file(&session.path);
+
+        let compact = r#"{"type":"system","subtype":"compact_boundary","cwd":"/work/oracle"}"#;
+        let context = r#"{"type":"system","content":"Prompt is too long"}"#;
+        session.append(&format!("{}\n{}\n{}\n", compact, compact, context));
+        watcher.check_file(&session.path);
+
+        assert_eq!(spoken(&state), vec!["Context compacted in oracle".to_string()]);
+        let events = state.watcher_events.lock();
+        let logged: Vec<(&str, Option<&str>, &str, Option<&str>)> = events.iter()
+            .map(|e| (e.kind.as_str(), e.raw_type.as_deref(), e.outcome.as_str(), e.reason.as_deref()))
+            .collect();
+        assert_eq!(logged, vec![
+            ("compaction", Some("system/compact_boundary"), "queued", None),
+            ("compaction", Some("system/compact_boundary"), "suppressed", Some("cooldown")),
+            ("context_limit", Some("system"), "suppressed", Some("disabled")),
+        ]);
+    }
+
+    #[test]
+    fn test_project_name() {
+        assert_eq!(project_name(Some(Path::new("/work/oracle"))), "oracle");
+        assert_eq!(project_name(None), "this session");
+    }
+
+    fn tool_use_in(cwd: &str) -> String {
+        TOOL_USE.replace("/work/oracle", cwd)
+    }
+
+    fn end_turn_in(cwd: &str) -> String {
+        END_TURN.replace("/work/oracle", cwd)
+    }
+
+    /// Watcher with the Claude profile matching any temp session file
+    fn test_watcher(state: &Arc<AppState>, config: WatcherConfig) -> (TempDir, SessionWatcher) {
+        let claude_home = TempDir::new().expect("temp dir");
+        let profile = WatcherProfile { path: "/**/*.jsonl".to_string(), ..WatcherProfile::claude() };
+        let profiles = vec![ProfileState::new(profile, Path::new("/")).expect("profile")];
+        let watcher = SessionWatcher::new(state.clone(), config, profiles, claude_home.path().to_path_buf());
+        (claude_home, watcher)
+    }
+
+    fn spoken(state: &Arc<AppState>) -> Vec<String> {
+        state.timeline.lock().iter().map(|e| e.text.clone()).collect()
+    }
+
+    #[test]
+    fn test_completions_debounced_per_session() {
+        let state = Arc::new(AppState::default());
+        let (_claude_home, mut watcher) = test_watcher(&state, WatcherConfig::default());
+        let a = Session::new("");
+        let b = Session::new("");
+        watcher.check_file(&a.path);
+        watcher.check_file(&b.path);
+
+        a.append(&format!("{}\n", end_turn_in("/work/oracle")));
+        watcher.check_file(&a.path);
+        b.append(&format!("{}\n", end_turn_in("/work/dashboard")));
+        watcher.check_file(&b.path);
+        a.append(&format!("{}\n", end_turn_in("/work/oracle")));
+        watcher.check_file(&a.path);
+
+        // B is not swallowed by A's debounce; A's repeat is
+        assert_eq!(spoken(&state), vec!["Claude Stop".to_string(), "Claude Stop in dashboard".to_string()]);
+    }
+
+    #[test]
+    fn test_excluded_projects_not_announced() {
+        let state = Arc::new(AppState::default());
+        let config = WatcherConfig { exclude: vec!["oss-*".to_string()], ..WatcherConfig::default() };
+        let (_claude_home, mut watcher) = test_watcher(&state, config);
+        let a = Session::new("");
+        let b = Session::new("");
+        watcher.check_file(&a.path);
+        watcher.check_file(&b.path);
+
+        b.append(&format!("{}\n", end_turn_in("/work/oracle")));
+        watcher.check_file(&b.path);
+        a.append(&format!("{}\n{}\n", tool_use_in("/src/oss-serde"), end_turn_in("/src/oss-serde")));
+        watcher.check_file(&a.path);
+
+        assert_eq!(spoken(&state), vec!["Claude Stop".to_string()]);
+        assert!(watcher.pending_approvals.is_empty(), "excluded sessions still track state");
+        let events = state.recent_watcher_events(10);
+        let excluded = events.iter().find(|e| e.project == "oss-serde" && e.kind == "completion").expect("logged");
+        assert_eq!(excluded.reason.as_deref(), Some("excluded"));
+
+        // Lists are read from the live config, so a reload applies to the next event
+        watcher.config.include = vec!["oss-serde".to_string()];
+        a.append(&format!("{}\n", end_turn_in("/src/oss-serde")));
+        watcher.last_completion_notify.clear();
+        watcher.check_file(&a.path);
+        assert_eq!(spoken(&state).len(), 2);
+    }
+
+    #[test]
+    fn test_long_running_command_alerts() {
+        let state = Arc::new(AppState::default());
+        let config = WatcherConfig { long_running_max: 1, ..WatcherConfig::default() };
+        let (_claude_home, mut watcher) = test_watcher(&state, config);
+        let session = Session::new("");
+        watcher.check_file(&session.path);
+
+        session.append(&format!("{}\n", TOOL_USE));
+        watcher.check_file(&session.path);
+        watcher.check_running_commands();
+        assert!(spoken(&state).is_empty(), "not yet past the threshold");
+
+        let running = watcher.running_commands.get_mut(&session.path).expect("timer started");
+        running.since = Instant::now() - Duration::from_secs(300);
+        watcher.check_running_commands();
+        assert_eq!(spoken(&state), vec!["Command still running in oracle after five minutes".to_string()]);
+
+        // Repeats wait for the longer interval, up to long_running_max
+        watcher.check_running_commands();
+        assert_eq!(spoken(&state).len(), 1);
+        for _ in 0..3 {
+            let running = watcher.running_commands.get_mut(&session.path).expect("still running");
+            running.last_alert = Some(Instant::now() - Duration::from_secs(600));
+            watcher.check_running_commands();
+        }
+        assert_eq!(spoken(&state).len(), 2);
+
+        // The tool result cancels the timer
+        session.append(r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"t1","content":"ok"}]}}"#);
+        session.append("\n");
+        watcher.check_file(&session.path);
+        assert!(watcher.running_commands.is_empty());
+    }
+
+    #[test]
+    fn test_long_running_only_for_configured_tools() {
+        let state = Arc::new(AppState::default());
+        let (_claude_home, mut watcher) = test_watcher(&state, WatcherConfig::default());
+        let session = Session::new("");
+        watcher.check_file(&session.path);
+        session.append(&format!("{}\n", TOOL_USE.replace("\"Bash\"", "\"Read\"")));
+        watcher.check_file(&session.path);
+        assert!(watcher.running_commands.is_empty());
+
+        session.append(&format!("{}\n{}\n", TOOL_USE, END_TURN));
+        watcher.check_file(&session.path);
+        assert!(watcher.running_commands.is_empty(), "completion cancels the timer");
+        assert_eq!(speak_duration(Duration::from_secs(3600)), "one hour");
+    }
+
+    #[test]
+    fn test_prune_deleted_and_idle_files() {
+        let state = Arc::new(AppState::default());
+        let config = WatcherConfig { idle_untrack_secs: 0, ..WatcherConfig::default() };
+        let (_claude_home, mut watcher) = test_watcher(&state, config);
+        let idle = Session::new("");
+        let deleted = Session::new("");
+        let busy = Session::new("");
+        for session in [&idle, &deleted, &busy] {
+            watcher.check_file(&session.path);
+        }
+        busy.append(&format!("{}\n", TOOL_USE));
+        watcher.check_file(&busy.path);
+        std::fs::remove_file(&deleted.path).expect("remove");
+
+        watcher.prune_files();
+        let tracked: Vec<&PathBuf> = watcher.profiles[0].file_positions.keys().collect();
+        assert_eq!(tracked, vec![&busy.path], "sessions with a pending tool are kept");
+        let status = state.watcher_status.lock().last_prune.clone().expect("stats");
+        assert_eq!((status.before, status.after), (3, 1));
+
+        // An untracked file is picked up again at EOF: only lines after re-tracking count
+        idle.append(&format!("{}\n", END_TURN));
+        watcher.check_file(&idle.path);
+        assert!(spoken(&state).is_empty());
+        idle.append(&format!("{}\n", END_TURN));
+        watcher.check_file(&idle.path);
+        assert_eq!(spoken(&state), vec!["Claude Stop".to_string()]);
+    }
+
+    #[test]
+    fn test_completions_aggregated() {
+        let state = Arc::new(AppState::default());
+        let config = WatcherConfig {
+            completion_grouping: CompletionGrouping::Aggregate,
+            completion_window_ms: 50,
+            aggregate_window_ms: 100,
+            exclude: vec!["scratch".to_string()],
+            ..WatcherConfig::default()
+        };
+        let (_claude_home, mut watcher) = test_watcher(&state, config);
+        let sessions: Vec<Session> = (0..4).map(|_| Session::new("")).collect();
+        for session in &sessions {
+            watcher.check_file(&session.path);
+        }
+
+        // The first completion is not delayed; the rest of the window is held
+        for (session, cwd) in sessions.iter().zip(["/work/oracle", "/work/dashboard", "/work/scratch", "/work/infra"]) {
+            session.append(&format!("{}\n", end_turn_in(cwd)));
+            watcher.check_file(&session.path);
+        }
+        watcher.flush_completions();
+        assert_eq!(spoken(&state), vec!["Claude Stop".to_string()]);
+
+        std::thread::sleep(Duration::from_millis(110));
+        watcher.flush_completions();
+        assert_eq!(spoken(&state)[1], "two more sessions finished: dashboard and infra", "excluded project left out");
+
+        // After the window a completion is spoken at once again; one held completion names its project
+        sessions[0].append(&format!("{}\n", end_turn_in("/work/oracle")));
+        watcher.check_file(&sessions[0].path);
+        sessions[1].append(&format!("{}\n", end_turn_in("/work/dashboard")));
+        watcher.check_file(&sessions[1].path);
+        assert_eq!(spoken(&state).len(), 3);
+        std::thread::sleep(Duration::from_millis(110));
+        watcher.flush_completions();
+        assert_eq!(spoken(&state)[3], "Claude Stop in dashboard");
+    }
+
+    #[test]
+    fn test_session_mode_from_log_overrides_settings() {
+        let state = Arc::new(AppState::default());
+        let (_claude_home, mut watcher) = test_watcher(&state, WatcherConfig::default());
+        let session = Session::new("");
+        watcher.check_file(&session.path);
+
+        // Launched with --dangerously-skip-permissions: no approval alerts
+        session.append(r#"{"type":"user","cwd":"/work/oracle","permissionMode":"bypassPermissions","message":{"content":"go"}}"#);
+        session.append(&format!("\n{}\n", TOOL_USE));
+        watcher.check_file(&session.path);
+        watcher.pending_approvals.values_mut().for_each(|p| p.since = Instant::now() - APPROVAL_ALERT_DELAY);
+        watcher.check_approvals();
+        assert!(spoken(&state).is_empty());
+        assert_eq!(state.watcher_status.lock().permission_mode, "bypassPermissions");
+
+        // Shift+tab back to default mid-session is picked up from the next user record
+        session.append(r#"{"type":"user","cwd":"/work/oracle","permissionMode":"default","message":{"content":"careful now"}}"#);
+        session.append(&format!("\n{}\n", TOOL_USE));
+        watcher.check_file(&session.path);
+        watcher.pending_approvals.values_mut().for_each(|p| p.since = Instant::now() - APPROVAL_ALERT_DELAY);
+        watcher.check_approvals();
+        assert_eq!(spoken(&state), vec!["Action needed, please approve".to_string()]);
+        let events = state.recent_watcher_events(10);
+        let change = events.iter().find(|e| e.kind == "mode_change").expect("mode change logged");
+        assert_eq!(change.detail.as_deref(), Some("bypassPermissions → default"));
+    }
+
+    #[test]
+    fn test_muted_alerts_become_notifications() {
+        let state = Arc::new(AppState::default());
+        let config = WatcherConfig { reminder_interval_secs: 0, ..WatcherConfig::default() };
+        let (_claude_home, mut watcher) = test_watcher(&state, config);
+        let session = Session::new("");
+        watcher.check_file(&session.path);
+        state.muted.store(true, Ordering::Relaxed);
+
+        session.append(&format!("{}\n", TOOL_USE));
+        watcher.check_file(&session.path);
+        watcher.pending_approvals.values_mut().for_each(|p| p.since = Instant::now() - APPROVAL_ALERT_DELAY);
+        watcher.check_approvals();
+        assert!(spoken(&state).is_empty());
+        let alert = state.recent_watcher_events(1).pop().expect("logged");
+        assert_eq!((alert.kind.as_str(), alert.outcome.as_str()), ("approval_alert", "notified"));
+
+        // No reminders while muted: the chain ends after the notification
+        watcher.check_approvals();
+        assert!(watcher.pending_approvals.is_empty());
+        assert_eq!(state.recent_watcher_events(1)[0].reason.as_deref(), Some("mute"));
+    }
+
+    #[test]
+    fn test_pending_approvals_per_session() {
+        let state = Arc::new(AppState::default());
+        let (_claude_home, mut watcher) = test_watcher(&state, WatcherConfig::default());
+        let a = Session::new("");
+        let b = Session::new("");
+        watcher.check_file(&a.path);
+        watcher.check_file(&b.path);
+
+        a.append(&format!("{}\n", tool_use_in("/work/oracle")));
+        b.append(&format!("{}\n", tool_use_in("/work/dashboard")));
+        watcher.check_file(&a.path);
+        watcher.check_file(&b.path);
+        assert_eq!(watcher.pending_approvals.len(), 2);
+
+        // Both overdue → one alert each, naming the project
+        for pending in watcher.pending_approvals.values_mut() {
+            pending.since = Instant::now() - APPROVAL_ALERT_DELAY;
+        }
+        watcher.check_approvals();
+        let mut texts: Vec<String> = state.timeline.lock().iter().map(|e| e.text.clone()).collect();
+        texts.sort();
+        assert_eq!(texts, vec![
+            "Action needed, please approve in dashboard".to_string(),
+            "Action needed, please approve in oracle".to_string(),
+        ]);
+
+        // A's tool result must not clear B's pending approval
+        a.append(r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"t1","content":"ok"}]}}"#);
+        a.append("\n");
+        watcher.check_file(&a.path);
+        assert!(!watcher.pending_approvals.contains_key(&a.path));
+        assert!(watcher.pending_approvals.contains_key(&b.path));
+        assert_eq!(state.watcher_status.lock().pending_approvals, 1);
+
+        // Deleted session files are dropped on the next tick
+        std::fs::remove_file(&b.path).expect("remove");
+        watcher.check_approvals();
+        assert!(watcher.pending_approvals.is_empty());
+    }
+
+    #[test]
+    fn test_mapped_profile_events() {
+        let profile: WatcherProfile = serde_json::from_str(r#"{
+            "name": "codex",
+            "mapping": {
+                "completion": [{"pointer": "/payload/type", "equals": "task_complete"}],
+                "tool_use": [{"pointer": "/payload/type", "equals": "function_call"}],
+                "tool_result": [{"pointer": "/payload/type", "equals": "function_call_output"}],
+                "error": [{"pointer": "/payload/failed", "equals": "true"}],
+                "tool_name": "/payload/name",
+                "error_text": "/payload/output",
+                "cwd": "/payload/cwd"
+            }
+        }"#).expect("profile");
+        let mut session = Session::with_profile("", profile);
+        session.append(concat!(
+            r#"{"type":"turn_context","payload":{"cwd":"/work/codex-app"}}"#, "\n",
+            r#"{"type":"response_item","payload":{"type":"function_call","name":"shell"}}"#, "\n",
+            r#"{"type":"response_item","payload":{"type":"function_call_output","failed":true,"output":"exit 1"}}"#, "\n",
+            "not json\n",
+            r#"{"type":"event_msg","payload":{"type":"task_complete"}}"#, "\n",
+        ));
+        assert_eq!(session.check(), vec![
+            LineEvent::ToolUse(vec!["shell".to_string()]),
+            LineEvent::ToolResult,
+            LineEvent::ToolError("exit 1".to_string()),
+            LineEvent::Completion,
+        ]);
+        assert_eq!(session.cwds.get(&session.path), Some(&PathBuf::from("/work/codex-app")));
+    }
+
+    #[test]
+    fn test_profile_glob() {
+        let home = TempDir::new().expect("temp dir");
+        std::fs::create_dir_all(home.path().join(".codex/sessions")).expect("dir");
+        let profile = WatcherProfile { path: "~/.codex/sessions/**/*.jsonl".to_string(), ..WatcherProfile::default() };
+        let watched = ProfileState::new(profile, home.path()).expect("profile");
+        assert_eq!(watched.root, home.path().join(".codex/sessions"));
+        assert!(watched.matches(&home.path().join(".codex/sessions/2026/01/rollout.jsonl")));
+        assert!(!watched.matches(&home.path().join(".codex/sessions/rollout.json")));
+        assert!(!watched.matches(&home.path().join(".claude/projects/a/b.jsonl")));
+
+        let missing = WatcherProfile { path: "~/.gemini/tmp/**/*.json".to_string(), ..WatcherProfile::default() };
+        assert!(ProfileState::new(missing, home.path()).is_err());
+    }
+
+    #[test]
+    fn test_usage_phrases() {
+        assert_eq!(speak_tokens(420), "under a thousand tokens");
+        assert_eq!(speak_tokens(4_321), "4 thousand tokens");
+        assert_eq!(speak_tokens(12_345), "12 thousand tokens");
+        assert_eq!(speak_tokens(123_456), "120 thousand tokens");
+        assert_eq!(speak_tokens(1_234_567), "1.2 million tokens");
+        assert_eq!(speak_cost(0.003), "less than a cent");
+        assert_eq!(speak_cost(0.0312), "about 3 cents");
+        assert_eq!(speak_cost(1.04), "about 1 dollar");
+        assert_eq!(speak_cost(1.44), "about 1.4 dollars");
+        assert_eq!(speak_cost(123.4), "about 120 dollars");
+    }
+
+    #[test]
+    fn test_turn_usage_announced() {
+        let state = Arc::new(AppState::default());
+        let config: WatcherConfig = serde_json::from_str(
+            r#"{"announce_usage":true,"usage_prices":{"opus":{"input":15,"output":75}}}"#,
+        ).expect("config");
+        let (_claude_home, mut watcher) = test_watcher(&state, config);
+        let session = Session::new("");
+        watcher.check_file(&session.path);
+
+        let usage = |id: &str, output: u64, stop: &str| format!(
+            r#"{{"type":"assistant","message":{{"id":"{}","model":"claude-opus-4","stop_reason":{},"usage":{{"input_tokens":1000,"output_tokens":{}}},"content":[{{"type":"text","text":"ok"}}]}}}}"#,
+            id, stop, output,
+        );
+        // Streaming lines repeat msg_1's usage; only the last one counts
+        session.append(&format!("{}\n{}\n{}\n", usage("msg_1", 100, "null"), usage("msg_1", 200, "null"), usage("msg_2", 300, "\"end_turn\"")));
+        watcher.check_file(&session.path);
+
+        let spoken = state.timeline.lock().last().map(|e| e.text.clone());
+        // 2000 input × $15/M + 500 output × $75/M = 6.75 cents
+        assert_eq!(spoken.as_deref(), Some("Claude Stop, about 7 cents"));
+        let status = state.watcher_status.lock();
+        let totals = &status.session_usage["session"];
+        assert_eq!((totals.tokens.input_tokens, totals.tokens.output_tokens, totals.turns), (2000, 500, 1));
+    }
+
+    fn read_all(path: &Path, pos: &mut u64, budget: u64, max_line_bytes: usize) -> LineBatch {
+        let mut file = File::open(path).expect("open");
+        read_complete_lines(&mut file, pos, budget, max_line_bytes)
+    }
+
+    #[test]
+    fn test_read_budget_resumes_next_tick() {
+        let session = Session::new("");
+        let lines: Vec<String> = (0..50).map(|i| format!("{{\"n\":{}}}", i)).collect();
+        session.append(&format!("{}\n", lines.join("\n")));
+
+        let mut pos = 0;
+        let mut seen = Vec::new();
+        let mut ticks = 0;
+        loop {
+            let batch = read_all(&session.path, &mut pos, 64, usize::MAX);
+            seen.extend(batch.lines);
+            ticks += 1;
+            if !batch.more {
+                break;
+            }
+        }
+        assert_eq!(seen, lines);
+        assert!(ticks > 1, "budget must split the read");
+    }
+
+    #[test]
+    fn test_oversized_line_skipped() {
+        let session = Session::new("");
+        let huge = format!("{{\"type\":\"user\",\"blob\":\"{}\"}}", "x".repeat(10_000));
+        session.append(&format!("{}\n{}\n", huge, END_TURN));
+
+        let mut pos = 0;
+        let batch = read_all(&session.path, &mut pos, u64::MAX, 4096);
+        assert_eq!(batch.lines, vec![END_TURN.to_string()]);
+        assert_eq!(pos, std::fs::metadata(&session.path).expect("metadata").len());
+    }
+
+    #[test]
+    fn test_line_split_across_reads() {
+        let mut session = Session::new("");
+        let (head, tail) = END_TURN.split_at(40);
+
+        session.append(head);
+        assert!(session.check().is_empty());
+
+        session.append(tail);
+        assert!(session.check().is_empty(), "no newline yet");
+
+        session.append("\n");
+        assert_eq!(session.check(), vec![LineEvent::Completion]);
+        assert!(session.check().is_empty(), "line must be parsed exactly once");
+        assert_eq!(session.cwds.get(&session.path), Some(&PathBuf::from("/work/oracle")));
+    }
+
+    #[test]
+    fn test_complete_and_partial_lines_in_one_read() {
+        let mut session = Session::new("");
+        let (head, tail) = END_TURN.split_at(25);
+
+        session.append(&format!("{}\n{}", TOOL_USE, head));
+        assert_eq!(session.check(), vec![LineEvent::ToolUse(vec!["Bash".to_string()])]);
+
+        session.append(&format!("{}\n", tail));
+        assert_eq!(session.check(), vec![LineEvent::Completion]);
+    }
+
+    #[test]
+    fn test_first_sight_mid_line_skips_fragment() {
+        let (head, tail) = END_TURN.split_at(30);
+        let mut session = Session::new(&format!("{}\n{}", TOOL_USE, head));
+
+        session.append(&format!("{}\n{}\n", tail, END_TURN));
+        assert_eq!(session.check(), vec![LineEvent::Completion]);
+    }
+
+    #[test]
+    fn test_truncation_resets_to_start() {
+        let mut session = Session::new(&format!("{}\n{}\n", TOOL_USE, TOOL_USE));
+        let (head, tail) = END_TURN.split_at(50);
+
+        std::fs::write(&session.path, head).expect("truncate");
+        assert!(session.check().is_empty());
+
+        session.append(&format!("{}\n", tail));
+        assert_eq!(session.check(), vec![LineEvent::Completion]);
     }
-    println!("[watcher] Voice queued: {}", text);
 }
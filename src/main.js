@@ -1,4 +1,5 @@
 const { invoke } = window.__TAURI__.core;
+const { listen } = window.__TAURI__.event;
 
 let timelineEl;
 let statusEl;
@@ -6,7 +7,10 @@ let statusTextEl;
 let mqttStatusEl;
 let timelineView;
 let settingsView;
-let pollInterval;
+let entries = [];
+let mqttConfig = {};
+let pinned = false;
+let mqttInfoTimer = null;
 
 // Format timestamp to HH:MM:SS
 function formatTime(timestamp) {
@@ -26,11 +30,16 @@ function renderEntry(entry) {
       <div class="time">${formatTime(entry.timestamp)}</div>
       <div class="content">
         <div class="text">${escapeHtml(entry.text)}</div>
+        ${entry.detail ? `<div class="detail">${escapeHtml(entry.detail)}</div>` : ''}
         <div class="meta">
           ${entry.agent ? `<span class="agent">${escapeHtml(entry.agent)}</span>` : ''}
           <span class="voice-name">${escapeHtml(entry.voice)}</span>
+          ${entry.replay_of != null ? `<span class="replay-of">↻ #${entry.replay_of}</span>` : ''}
         </div>
       </div>
+      ${['done', 'cancelled', 'failed', 'suppressed', 'relayed'].includes(entry.status) ? `<button class="replay-btn" data-id="${entry.id}" title="Speak again">↻</button>` : ''}
+      <button class="copy-btn" data-id="${entry.id}" title="Copy">⧉</button>
+      <button class="delete-btn" data-id="${entry.id}" data-status="${entry.status}" title="${entry.status === 'speaking' ? 'Stop' : entry.status === 'queued' ? 'Cancel' : 'Remove'}">×</button>
     </div>
   `;
 }
@@ -43,84 +52,284 @@ function escapeHtml(text) {
   return div.innerHTML.replace(/\\n/g, '<br>').replace(/\n/g, '<br>');
 }
 
-// Update the timeline UI
-async function updateTimeline() {
+// Header indicators from get_status or a voice://status payload
+function renderStatus(status) {
+  // Update MQTT status indicator
+  const mqttStatus = status.mqtt_status || 'disconnected';
+  mqttStatusEl.className = `mqtt-status ${mqttStatus}`;
+  mqttStatusEl.title = `MQTT: ${mqttStatus} (click for details)`;
+
+  // Show label for all states
+  const mqttLabel = document.getElementById('mqtt-label');
+  if (mqttStatus === 'connected') {
+    mqttLabel.textContent = 'connected';
+  } else if (mqttStatus === 'connecting') {
+    mqttLabel.textContent = 'connecting...';
+  } else if (mqttStatus === 'disabled') {
+    mqttLabel.textContent = 'off';
+  } else {
+    mqttLabel.textContent = 'offline';
+  }
+
+  // Update status indicator
+  if (status.is_speaking) {
+    statusEl.className = 'status speaking';
+    statusTextEl.textContent = 'Speaking...';
+  } else if (status.queued > 0) {
+    statusEl.className = 'status queued';
+    statusTextEl.textContent = `${status.queued} queued`;
+  } else {
+    statusEl.className = 'status';
+    statusTextEl.textContent = 'Idle';
+  }
+  if (status.snooze_minutes_left != null) {
+    statusTextEl.textContent += ` • snoozed ${status.snooze_minutes_left}m`;
+  }
+}
+
+// Connection details under the header; polled while shown
+async function refreshMqttInfo() {
   try {
-    const timeline = await invoke('get_timeline');
-    const status = await invoke('get_status');
+    const info = await invoke('get_mqtt_info');
+    const since = info.connected_since ? formatTime(info.connected_since) : '—';
+    const rows = [
+      ['Broker', info.broker || '—'],
+      ['Transport', `${info.transport || 'tcp'}${info.tls ? ' + TLS' : ''}${info.auth ? ', auth' : ''}`],
+      ['Client id', info.client_id || '—'],
+      ['Connected since', since],
+      ['Topics', info.subscribed_topics.join(', ') || '—'],
+      ['Messages', `${info.messages_in} in / ${info.messages_out} out`],
+      ['Reconnects', info.reconnect_count],
+    ];
+    if (info.retrying_in_secs != null) rows.push(['Retrying in', `${info.retrying_in_secs}s`]);
+    if (info.last_error) rows.push(['Last error', info.last_error]);
+    document.getElementById('mqtt-info').innerHTML = rows
+      .map(([label, value]) => `<dt>${label}</dt><dd>${escapeHtml(String(value))}</dd>`)
+      .join('');
+  } catch (err) {
+    console.error('Failed to get MQTT info:', err);
+  }
+}
 
-    // Update MQTT status indicator
-    const mqttStatus = status.mqtt_status || 'disconnected';
-    mqttStatusEl.className = `mqtt-status ${mqttStatus}`;
-    mqttStatusEl.title = `MQTT: ${mqttStatus}`;
-
-    // Show label for all states
-    const mqttLabel = document.getElementById('mqtt-label');
-    if (mqttStatus === 'connected') {
-      mqttLabel.textContent = 'connected';
-    } else if (mqttStatus === 'connecting') {
-      mqttLabel.textContent = 'connecting...';
-    } else {
-      mqttLabel.textContent = 'offline';
-    }
+function toggleMqttInfo() {
+  const panel = document.getElementById('mqtt-info');
+  const show = panel.classList.contains('hidden');
+  panel.classList.toggle('hidden', !show);
+  clearInterval(mqttInfoTimer);
+  mqttInfoTimer = null;
+  if (show) {
+    refreshMqttInfo();
+    mqttInfoTimer = setInterval(refreshMqttInfo, 1000);
+  }
+}
 
-    // Update status indicator
-    if (status.is_speaking) {
-      statusEl.className = 'status speaking';
-      statusTextEl.textContent = 'Speaking...';
-    } else if (status.queued > 0) {
-      statusEl.className = 'status queued';
-      statusTextEl.textContent = `${status.queued} queued`;
-    } else {
-      statusEl.className = 'status';
-      statusTextEl.textContent = 'Idle';
-    }
+// One row of the agents panel. Built as elements: agent names come from any client, so they
+// are never parsed as HTML, and the mute button's data-name keeps the name exactly.
+function renderAgent(agent) {
+  const row = document.createElement('li');
+  row.className = `agent-row${agent.muted ? ' muted' : ''}`;
+  const name = document.createElement('span');
+  name.className = 'agent';
+  name.textContent = agent.name;
+  const meta = document.createElement('span');
+  meta.className = 'agent-meta';
+  meta.title = `First seen ${new Date(agent.first_seen).toLocaleString()}`;
+  meta.textContent = `${agent.messages} msg · ${agent.voice} ${agent.rate} · ${formatTime(agent.last_active)}`;
+  const btn = document.createElement('button');
+  btn.className = 'agent-mute-btn';
+  btn.dataset.name = agent.name;
+  btn.dataset.muted = String(agent.muted);
+  btn.title = agent.muted ? 'Unmute this agent' : 'Mute this agent';
+  btn.textContent = agent.muted ? '🔇' : '🔊';
+  row.append(name, meta, btn);
+  return row;
+}
 
-    // Update timeline
-    if (timeline.length === 0) {
-      timelineEl.innerHTML = '<div class="empty-state">No voice messages yet</div>';
+// Agents panel under the header; refreshed while shown as agents send messages or are muted
+async function refreshAgents() {
+  const panel = document.getElementById('agents');
+  if (panel.classList.contains('hidden')) return;
+  try {
+    const agents = await invoke('get_agents');
+    if (agents.length === 0) {
+      panel.innerHTML = '<li class="empty-state">No agents yet</li>';
     } else {
-      // Show newest first
-      const reversed = [...timeline].reverse();
-      timelineEl.innerHTML = reversed.map(renderEntry).join('');
+      panel.replaceChildren(...agents.map(renderAgent));
     }
+  } catch (err) {
+    console.error('Failed to get agents:', err);
+  }
+}
+
+function toggleAgents() {
+  const panel = document.getElementById('agents');
+  panel.classList.toggle('hidden');
+  refreshAgents();
+}
+
+async function toggleAgentMuted(event) {
+  const btn = event.target.closest('.agent-mute-btn');
+  if (!btn) return;
+  try {
+    await invoke('set_agent_muted', { name: btn.dataset.name, muted: btn.dataset.muted !== 'true' });
+  } catch (err) {
+    console.error('Failed to mute agent:', err);
+  }
+}
+
+// Pin button from get_status or a voice://popup-pinned event
+function renderPinned(value) {
+  pinned = value;
+  const btn = document.getElementById('pin-btn');
+  btn.classList.toggle('pinned', pinned);
+  btn.title = pinned ? 'Unpin: hide when focus moves away' : 'Pin: keep open when focus moves away';
+}
+
+async function togglePinned() {
+  try {
+    await invoke('set_popup_pinned', { pinned: !pinned });
+  } catch (err) {
+    console.error('Failed to pin popup:', err);
+  }
+}
+
+// Render the entries kept from the last snapshot and events since
+function renderTimeline() {
+  if (entries.length === 0) {
+    timelineEl.innerHTML = '<div class="empty-state">No voice messages yet</div>';
+  } else {
+    // Show newest first
+    const reversed = [...entries].reverse();
+    timelineEl.innerHTML = reversed.map(renderEntry).join('');
+  }
+}
+
+// Fetch a full snapshot; events keep it current afterwards
+async function updateTimeline() {
+  try {
+    entries = await invoke('get_timeline');
+    const status = await invoke('get_status');
+    renderStatus(status);
+    renderPinned(status.popup_pinned);
+    renderTimeline();
   } catch (err) {
     console.error('Failed to update timeline:', err);
   }
 }
 
+// Apply a voice://entry-queued or voice://entry-status event
+function applyEntry(entry) {
+  const index = entries.findIndex(e => e.id === entry.id);
+  if (entry.status === 'removed') {
+    if (index !== -1) entries.splice(index, 1);
+  } else if (index !== -1) {
+    entries[index] = entry;
+  } else {
+    entries.push(entry);
+  }
+  renderTimeline();
+}
+
 // Test voice using Tauri command
 async function testVoice() {
   try {
     await invoke('test_voice');
-    // Immediately refresh
-    setTimeout(updateTimeline, 100);
   } catch (err) {
     console.error('Failed to test voice:', err);
   }
 }
 
-// Clear done entries
-async function clearDone() {
+// Speak the text typed into the popup
+async function speakText(event) {
+  event.preventDefault();
+  const input = document.getElementById('speak-text');
+  const text = input.value.trim();
+  if (!text) return;
+  try {
+    await invoke('speak', { text });
+    input.value = '';
+  } catch (err) {
+    console.error('Failed to speak:', err);
+  }
+}
+
+// Remove one row; the entry speaking now is stopped
+async function deleteEntry(event) {
+  const btn = event.target.closest('.delete-btn');
+  if (!btn) return;
+  try {
+    const result = await invoke('delete_entry', {
+      id: Number(btn.dataset.id),
+      force: btn.dataset.status === 'speaking'
+    });
+    if (result !== 'removed' && result !== 'cancelled') {
+      console.warn(`Entry ${btn.dataset.id}: ${result}`);
+    }
+  } catch (err) {
+    console.error('Failed to delete entry:', err);
+  }
+}
+
+// Copy one row's text, formatted like Copy Last Message
+async function copyEntry(event) {
+  const btn = event.target.closest('.copy-btn');
+  if (!btn) return;
+  try {
+    const result = await invoke('copy_last_message', { id: Number(btn.dataset.id) });
+    if (result === 'copied') {
+      btn.textContent = '✓';
+      setTimeout(() => { btn.textContent = '⧉'; }, 1000);
+    } else {
+      console.warn(`Entry ${btn.dataset.id}: ${result}`);
+    }
+  } catch (err) {
+    console.error('Failed to copy entry:', err);
+  }
+}
+
+// Queue a finished row again; the copy arrives as a voice://entry-queued event
+async function replayEntry(event) {
+  const btn = event.target.closest('.replay-btn');
+  if (!btn) return;
+  try {
+    await invoke('replay_entry', { id: Number(btn.dataset.id) });
+  } catch (err) {
+    // "not_found", "not_terminal" or "muted"
+    console.warn(`Entry ${btn.dataset.id}: ${err}`);
+    btn.title = err === 'muted' ? 'Muted' : 'Cannot speak again';
+  }
+}
+
+// Clear finished entries; with Shift also the queue and the message speaking now
+async function clearDone(event) {
+  const btn = document.getElementById('clear-btn');
   try {
-    await invoke('clear_timeline');
-    updateTimeline();
+    const scope = event.shiftKey ? 'all' : 'done';
+    const { removed, cancelled } = await invoke('clear_timeline', { scope });
+    const count = removed + cancelled;
+    btn.textContent = `Cleared ${count} ${count === 1 ? 'entry' : 'entries'}`;
+    setTimeout(() => { btn.textContent = 'Clear Done'; }, 2000);
   } catch (err) {
     console.error('Failed to clear timeline:', err);
   }
 }
 
-// Show settings view
-async function showSettings() {
-  // Stop polling when in settings
-  if (pollInterval) {
-    clearInterval(pollInterval);
-    pollInterval = null;
+// Save the timeline; the save dialog's file type picks CSV, JSON or Markdown
+async function exportTimeline() {
+  try {
+    const result = await invoke('export_timeline', {});
+    if (result.status === 'saved') console.log(`Timeline saved to ${result.path}`);
+  } catch (err) {
+    console.error('Failed to export timeline:', err);
   }
+}
 
+// Show settings view
+async function showSettings() {
   // Load current config
   try {
     const config = await invoke('get_mqtt_config');
+    mqttConfig = config;
     document.getElementById('broker').value = config.broker;
     document.getElementById('port').value = config.port;
     document.getElementById('topic-speak').value = config.topic_speak;
@@ -145,11 +354,8 @@ function showTimeline() {
   timelineView.classList.remove('hidden');
   settingsView.classList.add('hidden');
 
-  // Resume polling and immediately update
+  // Events kept arriving while the settings view was up; refresh anyway in case one was missed
   updateTimeline();
-  if (!pollInterval) {
-    pollInterval = setInterval(updateTimeline, 500);
-  }
 }
 
 // Save settings
@@ -178,6 +384,7 @@ async function saveSettings() {
   try {
     await invoke('save_mqtt_config', {
       config: {
+        ...mqttConfig,
         broker,
         port,
         topic_speak: topicSpeak,
@@ -209,15 +416,31 @@ window.addEventListener("DOMContentLoaded", () => {
   timelineView = document.getElementById('timeline-view');
   settingsView = document.getElementById('settings-view');
 
-  // Initial load
+  // Snapshot on load and whenever the popup is shown, then follow backend events
   updateTimeline();
-
-  // Poll for updates every 500ms
-  pollInterval = setInterval(updateTimeline, 500);
+  window.addEventListener('focus', updateTimeline);
+  window.addEventListener('focus', refreshAgents);
+  listen('voice://entry-queued', (event) => applyEntry(event.payload));
+  listen('voice://entry-status', (event) => applyEntry(event.payload));
+  listen('voice://status', (event) => renderStatus(event.payload));
+  listen('voice://show-timeline', showTimeline);
+  listen('voice://popup-pinned', (event) => renderPinned(event.payload));
+  listen('voice://agent-muted', refreshAgents);
+  // A message may come from an agent not listed yet
+  listen('voice://entry-queued', refreshAgents);
 
   // Button handlers - Timeline
   document.getElementById('test-btn').addEventListener('click', testVoice);
+  document.getElementById('speak-form').addEventListener('submit', speakText);
+  timelineEl.addEventListener('click', replayEntry);
+  timelineEl.addEventListener('click', copyEntry);
+  timelineEl.addEventListener('click', deleteEntry);
   document.getElementById('clear-btn').addEventListener('click', clearDone);
+  document.getElementById('export-btn').addEventListener('click', exportTimeline);
+  document.getElementById('pin-btn').addEventListener('click', togglePinned);
+  mqttStatusEl.addEventListener('click', toggleMqttInfo);
+  document.getElementById('agents-btn').addEventListener('click', toggleAgents);
+  document.getElementById('agents').addEventListener('click', toggleAgentMuted);
   document.getElementById('settings-btn').addEventListener('click', showSettings);
 
   // Button handlers - Settings
@@ -0,0 +1,291 @@
+const { invoke } = window.__TAURI__.core;
+const { getCurrentWindow } = window.__TAURI__.window;
+const { listen } = window.__TAURI__.event;
+
+// Form inputs (and selects) are named after the settings fields: "mqtt.port", "voice.default_rate", ...
+const TEXT_FIELDS = [
+  'mqtt.broker', 'mqtt.topic_speak', 'mqtt.topic_status', 'mqtt.username', 'mqtt.password',
+  'voice.default_voice', 'voice.test_voice_text', 'voice.language', 'ui.stop_hotkey', 'ui.mute_hotkey', 'ui.clipboard_hotkey',
+  'ui.copy_format', 'ui.icon_theme', 'log.level', 'http.bind', 'transcripts.dir', 'relay.url', 'relay.token', 'github.secret',
+  'ui.actions.left_click', 'ui.actions.double_click', 'ui.actions.middle_click'
+];
+const NUMBER_FIELDS = ['mqtt.port', 'http.port', 'voice.default_rate', 'files.max_bytes', 'transcripts.keep_days'];
+const CHECK_FIELDS = ['mqtt.enabled', 'mqtt.use_keyring', 'ui.muted', 'ui.show_queue_count', 'ui.start_at_login', 'ui.mute_during_focus', 'ui.focus_summary', 'voice.announce_voice_switch', 'http.advertise', 'pipe.enabled',
+  'transcripts.enabled', 'transcripts.include_suppressed', 'transcripts.include_failed', 'relay.enabled', 'github.enabled', 'mirror.include_suppressed'];
+// Lists edited as comma-separated text
+const LIST_FIELDS = ['voice.favorite_voices', 'files.allowed_dirs', 'github.events', 'github.workflow_conclusions', 'mirror.agents'];
+
+// Settings loaded from the backend; fields without an input are saved unchanged
+let settings;
+
+function input(field) {
+  return document.getElementById(field);
+}
+
+// Dotted paths may go deeper than a section: "ui.actions.left_click"
+function getField(field) {
+  return field.split('.').reduce((obj, key) => obj[key], settings);
+}
+
+function setField(field, value) {
+  const keys = field.split('.');
+  const last = keys.pop();
+  keys.reduce((obj, key) => obj[key], settings)[last] = value;
+}
+
+async function loadSettings() {
+  try {
+    settings = await invoke('get_config');
+    TEXT_FIELDS.forEach(f => { input(f).value = getField(f) ?? ''; });
+    NUMBER_FIELDS.forEach(f => { input(f).value = getField(f); });
+    CHECK_FIELDS.forEach(f => { input(f).checked = getField(f); });
+    LIST_FIELDS.forEach(f => { input(f).value = getField(f).join(', '); });
+    renderDiagnostics([]);
+    scheduleValidation();
+  } catch (err) {
+    showMessage('Failed to load settings: ' + err, 'error');
+  }
+}
+
+async function loadProfiles() {
+  try {
+    const { profiles, active } = await invoke('list_profiles');
+    const select = document.getElementById('profile');
+    select.innerHTML = '';
+    profiles.forEach(name => select.add(new Option(name, name, false, name === active)));
+    document.getElementById('delete-profile').disabled = active === 'default';
+  } catch (err) {
+    showMessage('Failed to load profiles: ' + err, 'error');
+  }
+}
+
+// Profile changes apply at once; the form then shows the new active profile
+async function profileAction(command, name, done) {
+  try {
+    const result = await invoke(command, { name });
+    await Promise.all([loadProfiles(), loadSettings()]);
+    showMessage(done(result), 'success');
+  } catch (err) {
+    showMessage(String(err), 'error');
+    loadProfiles();
+  }
+}
+
+// Suggestions for the voice field; it stays free text, so a failed listing only loses the suggestions
+async function loadVoices(refresh = false) {
+  const list = document.getElementById('voice-list');
+  try {
+    const voices = await invoke('get_voices', { refresh });
+    list.innerHTML = '';
+    voices.forEach(v => {
+      const option = document.createElement('option');
+      option.value = v.name;
+      option.label = [v.locale, v.gender].filter(Boolean).join(', ');
+      list.appendChild(option);
+    });
+  } catch (err) {
+    console.warn('Voice list unavailable:', err.message ?? err);
+    list.innerHTML = '';
+  }
+}
+
+function readForm() {
+  TEXT_FIELDS.forEach(f => setField(f, input(f).value.trim()));
+  // Empty credentials are stored as null, like the popup does; so is an empty transcripts folder
+  ['mqtt.username', 'mqtt.password', 'relay.token', 'github.secret', 'transcripts.dir'].forEach(f => setField(f, getField(f) || null));
+  NUMBER_FIELDS.forEach(f => setField(f, parseInt(input(f).value, 10) || 0));
+  CHECK_FIELDS.forEach(f => setField(f, input(f).checked));
+  LIST_FIELDS.forEach(f => setField(f, input(f).value.split(',').map(v => v.trim()).filter(Boolean)));
+}
+
+// Diagnostics are {field_path, severity, message}; each goes under its field, and those
+// without an input on the form (watcher fields, save failures) are returned
+function renderDiagnostics(diagnostics) {
+  document.querySelectorAll('.field-message').forEach(el => el.remove());
+  document.querySelectorAll('.form-group.invalid, .form-group.warning').forEach(el => el.classList.remove('invalid', 'warning'));
+  return diagnostics.filter(d => {
+    const group = input(d.field_path)?.closest('.form-group');
+    if (!group) return true;
+    group.classList.add(d.severity === 'error' ? 'invalid' : 'warning');
+    const note = document.createElement('div');
+    note.className = 'field-message ' + d.severity;
+    note.textContent = d.message;
+    group.appendChild(note);
+    return false;
+  });
+}
+
+let validateTimer;
+
+// Check the form as it is edited; the broker lookup makes this slow enough to debounce
+function scheduleValidation() {
+  clearTimeout(validateTimer);
+  validateTimer = setTimeout(async () => {
+    if (!settings) return;
+    readForm();
+    try {
+      renderDiagnostics(await invoke('validate_config', { settings }));
+    } catch (err) {
+      console.warn('Validation failed:', err);
+    }
+  }, 400);
+}
+
+async function saveSettings() {
+  clearTimeout(validateTimer);
+  readForm();
+  try {
+    // {message, restarted, warnings}: restarted lists the subsystems that picked up changes, e.g. ["mqtt"]
+    const { message, warnings } = await invoke('save_config', { settings });
+    const unplaced = renderDiagnostics(warnings);
+    showMessage([message, ...unplaced.map(d => d.message)].join(' '), 'success');
+  } catch (errors) {
+    const list = Array.isArray(errors) ? errors : [{ field_path: '', severity: 'error', message: String(errors) }];
+    const unplaced = renderDiagnostics(list).filter(d => d.severity === 'error');
+    const count = list.filter(d => d.severity === 'error').length;
+    const summary = unplaced.length ? unplaced.map(d => d.message).join('. ') : `Not saved: ${count} field${count === 1 ? '' : 's'} to fix.`;
+    showMessage(summary, 'error');
+  }
+}
+
+// Bundle being previewed: its path, so a mode change or Apply imports the same file
+let importPath = null;
+
+function formatValue(value) {
+  return value === null ? 'none' : JSON.stringify(value);
+}
+
+// outcome is {status, ...}: "preview" lists changes and diagnostics, "applied" and "rejected" end the import
+function renderImport(outcome) {
+  const panel = document.getElementById('import-preview');
+  if (outcome.status === 'cancelled') return;
+  if (outcome.status === 'preview') {
+    importPath = outcome.path;
+    const list = document.getElementById('import-changes');
+    const item = (text, className = '') => {
+      const li = document.createElement('li');
+      li.className = className;
+      li.textContent = text;
+      return li;
+    };
+    list.replaceChildren(
+      ...outcome.changes.map(c => item(`${c.field_path}: ${formatValue(c.current)} → ${formatValue(c.imported)}`)),
+      ...outcome.diagnostics.map(d => item(d.field_path ? `${d.field_path}: ${d.message}` : d.message, 'field-message ' + d.severity))
+    );
+    if (!outcome.changes.length) list.prepend(item('No changes.'));
+    const errors = outcome.diagnostics.some(d => d.severity === 'error');
+    document.getElementById('import-mode').value = outcome.mode;
+    document.getElementById('import-apply').disabled = errors;
+    panel.classList.remove('hidden');
+    panel.scrollIntoView({ block: 'nearest' });
+    return;
+  }
+  panel.classList.add('hidden');
+  importPath = null;
+  if (outcome.status === 'applied') {
+    const backup = outcome.backup ? ` Previous settings backed up to ${outcome.backup}.` : '';
+    showMessage(`Imported ${outcome.changes.length} setting${outcome.changes.length === 1 ? '' : 's'}. ${outcome.saved.message}${backup}`, 'success');
+    loadSettings();
+  } else {
+    showMessage(`Not imported: ${outcome.diagnostics.map(d => d.message).join('. ')}`, 'error');
+  }
+}
+
+async function importConfig(path, apply) {
+  try {
+    const mode = document.getElementById('import-mode').value;
+    renderImport(await invoke('import_config', { path, mode, apply }));
+  } catch (err) {
+    showMessage(String(err), 'error');
+  }
+}
+
+async function exportConfig() {
+  try {
+    const outcome = await invoke('export_config');
+    if (outcome.status === 'saved') showMessage(`Settings exported to ${outcome.path}.`, 'success');
+  } catch (err) {
+    showMessage(String(err), 'error');
+  }
+}
+
+// report is {at, outcome, checks: [{name, outcome: pass|warn|fail, message, hint}]}, or null before the startup run ends
+function renderReport(report) {
+  const list = document.getElementById('diagnostics-results');
+  if (!report) {
+    list.replaceChildren();
+    return;
+  }
+  const severity = { pass: '', warn: 'warning', fail: 'error' };
+  list.replaceChildren(...report.checks.map(c => {
+    const li = document.createElement('li');
+    li.className = 'field-message ' + severity[c.outcome];
+    li.textContent = `${c.name}: ${c.message}` + (c.hint ? ` — ${c.hint}` : '');
+    return li;
+  }));
+}
+
+async function runDiagnostics() {
+  const button = document.getElementById('run-diagnostics');
+  button.disabled = true;
+  button.textContent = 'Running…';
+  try {
+    renderReport(await invoke('run_diagnostics', { speak: input('diagnostics-speak').checked }));
+  } catch (err) {
+    showMessage('Diagnostics failed: ' + err, 'error');
+  } finally {
+    button.disabled = false;
+    button.textContent = 'Run';
+  }
+}
+
+function showMessage(text, type) {
+  const msgEl = document.getElementById('settings-message');
+  msgEl.textContent = text;
+  msgEl.className = 'settings-message ' + type;
+}
+
+window.addEventListener('DOMContentLoaded', () => {
+  loadSettings();
+  loadProfiles();
+  loadVoices();
+  invoke('get_diagnostics').then(renderReport).catch(err => console.warn('No diagnostics:', err));
+  // A profile switch from the tray, or an edit of config.json
+  listen('voice://config-changed', () => { loadProfiles(); loadSettings(); });
+  document.getElementById('profile').addEventListener('change', e => {
+    const name = e.target.value;
+    profileAction('switch_profile', name, () => `Switched to ${name}.`);
+  });
+  document.getElementById('create-profile').addEventListener('click', () => {
+    const field = document.getElementById('new-profile');
+    profileAction('create_profile', field.value, name => {
+      field.value = '';
+      return `Profile ${name} added.`;
+    });
+  });
+  document.getElementById('delete-profile').addEventListener('click', () => {
+    const name = document.getElementById('profile').value;
+    profileAction('delete_profile', name, () => `Profile ${name} deleted.`);
+  });
+  document.getElementById('refresh-voices').addEventListener('click', () => loadVoices(true));
+  document.getElementById('run-diagnostics').addEventListener('click', runDiagnostics);
+  document.getElementById('open-log-folder').addEventListener('click', () => {
+    invoke('open_log_folder').catch(e => showMessage(String(e), 'error'));
+  });
+  document.getElementById('reveal-config').addEventListener('click', () => {
+    invoke('reveal_config').catch(e => showMessage(String(e), 'error'));
+  });
+  document.getElementById('export-config').addEventListener('click', exportConfig);
+  document.getElementById('import-config').addEventListener('click', () => importConfig(null, false));
+  document.getElementById('import-mode').addEventListener('change', () => importConfig(importPath, false));
+  document.getElementById('import-apply').addEventListener('click', () => importConfig(importPath, true));
+  document.getElementById('import-cancel').addEventListener('click', () => {
+    document.getElementById('import-preview').classList.add('hidden');
+    importPath = null;
+  });
+  document.getElementById('save-btn').addEventListener('click', saveSettings);
+  document.querySelector('.settings-form').addEventListener('input', e => {
+    if (!['new-profile', 'profile', 'import-mode', 'diagnostics-speak'].includes(e.target.id)) scheduleValidation();
+  });
+  document.getElementById('cancel-btn').addEventListener('click', () => getCurrentWindow().close());
+});